@@ -1,18 +1,28 @@
 use clap::Parser;
 use clap_stdin::{FileOrStdin, Source};
 use modality_defmt_plugin::{
-    defmt_reader, tracing::try_init_tracing_subscriber, DefmtConfig, DefmtConfigEntry, DefmtOpts,
-    Interruptor, ReflectorOpts,
+    config_template, defmt_reader, tracing::try_init_tracing_subscriber, DefmtConfig,
+    DefmtConfigEntry, DefmtOpts, InputCompression, Interruptor, JLinkLogReader, LogicCapture,
+    LogicCaptureReader, PcapFilter, PcapReader, ReflectorOpts,
+};
+#[cfg(feature = "rtt-dump")]
+use modality_defmt_plugin::{rtt_dump, MemoryImage, RttDumpError};
+#[cfg(unix)]
+use std::os::unix::{
+    fs::FileTypeExt,
+    net::{UnixListener, UnixStream},
 };
 use std::{
     fs::File,
-    io::BufReader,
+    io::{self, BufRead, BufReader, Read},
     path::{Path, PathBuf},
     time::{Duration, Instant},
 };
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
 use tracing::{debug, error};
 
-/// Import defmt data from a file or stdin
+/// Import defmt data from a file, stdin, a Unix domain socket, or a Windows named pipe
 #[derive(Parser, Debug, Clone)]
 #[clap(version)]
 pub struct Opts {
@@ -22,6 +32,10 @@ pub struct Opts {
     #[clap(flatten)]
     pub defmt_opts: DefmtOpts,
 
+    /// Print an annotated configuration template listing every configuration key, then exit.
+    #[clap(long, name = "print-config-template")]
+    pub print_config_template: bool,
+
     /// The ELF file containing the defmt table and location information.
     #[clap(
         long,
@@ -38,6 +52,128 @@ pub struct Opts {
     #[clap(long, name = "open-timeout", help_heading = "COLLECTOR CONFIGURATION")]
     pub open_timeout: Option<humantime::Duration>,
 
+    /// Read from a Unix domain socket at the given path instead of a file.
+    #[clap(
+        long,
+        name = "unix-socket",
+        conflicts_with = "input",
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Listen on the Unix domain socket and read from the first accepted connection,
+    /// instead of connecting to it.
+    #[clap(
+        long,
+        name = "unix-socket-listen",
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub unix_socket_listen: bool,
+
+    /// Read from a Windows named pipe (e.g. \\.\pipe\defmt) instead of a file.
+    #[clap(
+        long,
+        name = "named-pipe",
+        conflicts_with_all = ["input", "unix-socket"],
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub named_pipe: Option<String>,
+
+    /// Create the named pipe and read from the first connected client,
+    /// instead of connecting to an existing pipe.
+    #[clap(
+        long,
+        name = "named-pipe-listen",
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub named_pipe_listen: bool,
+
+    /// The compression of the input.
+    /// Possible options: [auto, none, gzip, zstd].
+    ///
+    /// The default value is auto, which detects gzip and zstd from the magic bytes
+    /// or the file extension.
+    #[clap(long, name = "compression", help_heading = "IMPORTER CONFIGURATION")]
+    pub compression: Option<InputCompression>,
+
+    /// Treat the input as a pcap or pcapng capture and
+    /// extract the defmt data from the packets matching the filter.
+    /// Possible options: [udp:<PORT>, tcp:<PORT>].
+    ///
+    /// The port matches either the source or the destination port.
+    /// For TCP, the first direction of the first connection to carry data is followed.
+    #[clap(long, name = "pcap-filter", help_heading = "IMPORTER CONFIGURATION")]
+    pub pcap_filter: Option<PcapFilter>,
+
+    /// Treat the input as a logic analyzer export of decoded UART bytes.
+    /// Possible options: [binary, saleae, sigrok].
+    ///
+    /// The capture time of each byte is used for event timing.
+    #[clap(long, name = "logic-capture", help_heading = "IMPORTER CONFIGURATION")]
+    pub logic_capture: Option<LogicCapture>,
+
+    /// Sample rate (Hz) of the logic analyzer capture, used to convert the
+    /// sample numbers of sigrok annotations to time.
+    #[clap(
+        long,
+        name = "capture-sample-rate",
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub capture_sample_rate: Option<u64>,
+
+    /// Treat the input as a JLinkRTTLogger (or J-Link RTT Viewer) log file,
+    /// stripping the SEGGER log header and block marker lines.
+    #[clap(
+        long,
+        name = "jlink-rtt-logger",
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub jlink_rtt_logger: bool,
+
+    /// Treat the input as a post-mortem RAM dump or ELF core file
+    /// of the target, and import the residual data of an RTT up channel buffer.
+    ///
+    /// The RTT control block is located using the '_SEGGER_RTT' symbol of the firmware ELF file,
+    /// or by scanning the dump for it.
+    #[clap(long, name = "rtt-dump", help_heading = "IMPORTER CONFIGURATION")]
+    pub rtt_dump: bool,
+
+    /// Base address of a raw RAM dump.
+    /// Inferred from the '_SEGGER_RTT' symbol when not provided.
+    #[clap(
+        long,
+        name = "rtt-dump-address",
+        value_parser = parse_address,
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub rtt_dump_address: Option<u64>,
+
+    /// Use the provided RTT control block address instead of the '_SEGGER_RTT' symbol.
+    #[clap(
+        long,
+        name = "rtt-dump-control-block-address",
+        value_parser = parse_address,
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub rtt_dump_control_block_address: Option<u64>,
+
+    /// The RTT up channel of the memory dump to import (defaults to 0).
+    #[clap(
+        long,
+        name = "rtt-dump-up-channel",
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub rtt_dump_up_channel: Option<usize>,
+
+    /// Also import the data the host already read, which precedes the unread
+    /// data in the RTT buffer. The oldest data may be a partial defmt frame.
+    #[clap(
+        long,
+        name = "rtt-dump-history",
+        help_heading = "IMPORTER CONFIGURATION"
+    )]
+    pub rtt_dump_history: bool,
+
     /// Input file or stdin stream to read from ('-' for stdin)
     #[clap(name = "input", help_heading = "IMPORTER CONFIGURATION")]
     pub input: Option<FileOrStdin>,
@@ -62,6 +198,13 @@ async fn main() {
 async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
     let opts = Opts::parse();
 
+    if opts.print_config_template {
+        if let Some(template) = config_template(DefmtConfigEntry::Importer) {
+            print!("{template}");
+        }
+        return Ok(());
+    }
+
     try_init_tracing_subscriber()?;
 
     let intr = Interruptor::new();
@@ -94,41 +237,174 @@ async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
     if let Some(to) = opts.open_timeout {
         defmt_cfg.plugin.import.open_timeout = Some(to.into());
     }
+    if let Some(unix_socket) = opts.unix_socket {
+        defmt_cfg.plugin.import.unix_socket = Some(unix_socket);
+    }
+    if opts.unix_socket_listen {
+        defmt_cfg.plugin.import.unix_socket_listen = true;
+    }
+    if let Some(named_pipe) = opts.named_pipe {
+        defmt_cfg.plugin.import.named_pipe = Some(named_pipe);
+    }
+    if opts.named_pipe_listen {
+        defmt_cfg.plugin.import.named_pipe_listen = true;
+    }
+    if let Some(compression) = opts.compression {
+        defmt_cfg.plugin.import.compression = compression;
+    }
+    if let Some(pcap_filter) = opts.pcap_filter {
+        defmt_cfg.plugin.import.pcap_filter = Some(pcap_filter);
+    }
+    if let Some(logic_capture) = opts.logic_capture {
+        defmt_cfg.plugin.import.logic_capture = Some(logic_capture);
+    }
+    if let Some(rate) = opts.capture_sample_rate {
+        defmt_cfg.plugin.import.capture_sample_rate = Some(rate);
+    }
+    if opts.jlink_rtt_logger {
+        defmt_cfg.plugin.import.jlink_rtt_logger = true;
+    }
+    if opts.rtt_dump {
+        defmt_cfg.plugin.import.rtt_dump = true;
+    }
+    if let Some(addr) = opts.rtt_dump_address {
+        defmt_cfg.plugin.import.rtt_dump_address = Some(addr);
+    }
+    if let Some(addr) = opts.rtt_dump_control_block_address {
+        defmt_cfg.plugin.import.rtt_dump_control_block_address = Some(addr);
+    }
+    if let Some(channel) = opts.rtt_dump_up_channel {
+        defmt_cfg.plugin.import.rtt_dump_up_channel = channel;
+    }
+    if opts.rtt_dump_history {
+        defmt_cfg.plugin.import.rtt_dump_history = true;
+    }
+    let import_cfg = &defmt_cfg.plugin.import;
+    let input_formats = [
+        import_cfg.pcap_filter.is_some(),
+        import_cfg.logic_capture.is_some(),
+        import_cfg.jlink_rtt_logger,
+        import_cfg.rtt_dump,
+    ];
+    if input_formats.into_iter().filter(|f| *f).count() > 1 {
+        return Err(
+            "The pcap-filter, logic-capture, jlink-rtt-logger and rtt-dump options are mutually exclusive"
+                .into(),
+        );
+    }
 
     enum Input {
         Stdin,
-        File(File),
+        File(File, Option<PathBuf>),
+        #[cfg(unix)]
+        UnixSocketConnect(UnixStream),
+        #[cfg(unix)]
+        UnixSocketListen(UnixListener),
+        #[cfg(windows)]
+        NamedPipeListen(NamedPipeServer),
     }
 
     let input = if let Some(cli_input) = opts.input {
         debug!(source = ?cli_input.source, "Reading from input");
         match cli_input.source {
             Source::Stdin => Input::Stdin,
-            Source::Arg(f) => Input::File(match defmt_cfg.plugin.import.open_timeout {
-                Some(to) if !to.0.is_zero() => open_retry_loop(f, to.0)?,
-                _ => File::open(&f).map_err(|_| FileOpenError(f.into()))?,
-            }),
+            Source::Arg(f) => Input::File(
+                match defmt_cfg.plugin.import.open_timeout {
+                    Some(to) if !to.0.is_zero() => open_retry_loop(&f, to.0)?,
+                    _ => File::open(&f).map_err(|_| FileOpenError(f.clone().into()))?,
+                },
+                Some(f.into()),
+            ),
         }
+    } else if let Some(socket_path) = &defmt_cfg.plugin.import.unix_socket {
+        debug!(source = %socket_path.display(), listen = defmt_cfg.plugin.import.unix_socket_listen, "Reading from Unix domain socket");
+        #[cfg(unix)]
+        if defmt_cfg.plugin.import.unix_socket_listen {
+            Input::UnixSocketListen(bind_unix_socket(socket_path)?)
+        } else {
+            Input::UnixSocketConnect(match defmt_cfg.plugin.import.open_timeout {
+                Some(to) if !to.0.is_zero() => connect_retry_loop(socket_path, to.0)?,
+                _ => UnixStream::connect(socket_path)
+                    .map_err(|e| UnixSocketError::Connect(socket_path.clone(), e))?,
+            })
+        }
+        #[cfg(not(unix))]
+        return Err(UnixSocketError::Unsupported.into());
+    } else if let Some(pipe_name) = &defmt_cfg.plugin.import.named_pipe {
+        debug!(
+            source = pipe_name,
+            listen = defmt_cfg.plugin.import.named_pipe_listen,
+            "Reading from named pipe"
+        );
+        #[cfg(windows)]
+        if defmt_cfg.plugin.import.named_pipe_listen {
+            Input::NamedPipeListen(
+                ServerOptions::new()
+                    .first_pipe_instance(true)
+                    .create(pipe_name)
+                    .map_err(|e| NamedPipeError::Create(pipe_name.clone(), e))?,
+            )
+        } else {
+            // Opening a named pipe as a file connects to it as a client
+            Input::File(
+                match defmt_cfg.plugin.import.open_timeout {
+                    Some(to) if !to.0.is_zero() => open_retry_loop(pipe_name, to.0)?,
+                    _ => File::open(pipe_name).map_err(|_| FileOpenError(pipe_name.into()))?,
+                },
+                None,
+            )
+        }
+        #[cfg(not(windows))]
+        return Err(NamedPipeError::Unsupported.into());
     } else if let Some(input_file) = &defmt_cfg.plugin.import.file {
+        if defmt_cfg.plugin.save_raw.as_deref() == Some(input_file.as_path()) {
+            return Err("The raw capture file can't also be the import file".into());
+        }
         debug!(source = %input_file.display(), "Reading from input");
         let input = match defmt_cfg.plugin.import.open_timeout {
             Some(to) if !to.0.is_zero() => open_retry_loop(input_file, to.0)?,
             _ => File::open(input_file).map_err(|_| FileOpenError(input_file.into()))?,
         };
-        Input::File(input)
+        Input::File(input, Some(input_file.clone()))
     } else {
         return Err("Missing import file or input stream. Either supply it as a positional argument at the CLI or in a config file".into());
     };
 
+    let compression = defmt_cfg.plugin.import.compression;
     let mut join_handle = tokio::spawn(async move {
         match input {
             Input::Stdin => {
-                let mut r = std::io::stdin();
-                defmt_reader::run(&mut r, defmt_cfg, intr).await
+                let r = decompress(BufReader::new(io::stdin()), compression, None)?;
+                import(r, defmt_cfg, intr).await
+            }
+            Input::File(f, path) => {
+                let r = decompress(BufReader::new(f), compression, path.as_deref())?;
+                import(r, defmt_cfg, intr).await
+            }
+            #[cfg(unix)]
+            Input::UnixSocketConnect(stream) => {
+                let r = decompress(BufReader::new(stream), compression, None)?;
+                import(r, defmt_cfg, intr).await
             }
-            Input::File(f) => {
-                let mut r = BufReader::new(f);
-                defmt_reader::run(&mut r, defmt_cfg, intr).await
+            #[cfg(unix)]
+            Input::UnixSocketListen(listener) => {
+                debug!("Waiting for a connection");
+                let (stream, _) = listener.accept()?;
+                debug!("Accepted connection");
+                let r = decompress(BufReader::new(stream), compression, None)?;
+                import(r, defmt_cfg, intr).await
+            }
+            #[cfg(windows)]
+            Input::NamedPipeListen(pipe) => {
+                debug!("Waiting for a connection");
+                pipe.connect().await?;
+                debug!("Accepted connection");
+                let r = NamedPipeReader {
+                    interruptor: intr.clone(),
+                    pipe,
+                };
+                let r = decompress(BufReader::new(r), compression, None)?;
+                import(r, defmt_cfg, intr).await
             }
         }
     });
@@ -155,6 +431,211 @@ async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
 #[error("Failed to open input file '{0:?}'")]
 struct FileOpenError(PathBuf);
 
+/// Wraps the input in a decompressor when it's compressed
+fn decompress<R: BufRead + Send + 'static>(
+    mut r: R,
+    compression: InputCompression,
+    path: Option<&Path>,
+) -> io::Result<Box<dyn Read + Send>> {
+    // Peek at the magic bytes without consuming them
+    let compression = compression.detect(r.fill_buf()?, path);
+    debug!(%compression, "Resolved input compression");
+    match compression {
+        InputCompression::Auto | InputCompression::None => Ok(Box::new(r)),
+        #[cfg(feature = "compressed-input")]
+        InputCompression::Gzip => Ok(Box::new(flate2::bufread::MultiGzDecoder::new(r))),
+        #[cfg(feature = "compressed-input")]
+        InputCompression::Zstd => Ok(Box::new(zstd::stream::read::Decoder::with_buffer(r)?)),
+        #[cfg(not(feature = "compressed-input"))]
+        c => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "The input is {c} compressed, but the 'compressed-input' feature is not enabled"
+            ),
+        )),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum UnixSocketError {
+    #[cfg(not(unix))]
+    #[error("Unix domain sockets are not supported on this platform")]
+    Unsupported,
+
+    #[cfg(unix)]
+    #[error("Failed to connect to Unix domain socket '{0:?}'. {1}")]
+    Connect(PathBuf, #[source] std::io::Error),
+
+    #[cfg(unix)]
+    #[error("Failed to bind Unix domain socket '{0:?}'. {1}")]
+    Bind(PathBuf, #[source] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+enum NamedPipeError {
+    #[cfg(not(windows))]
+    #[error("Named pipes are only supported on Windows")]
+    Unsupported,
+
+    #[cfg(windows)]
+    #[error("Failed to create named pipe '{0}'. {1}")]
+    Create(String, #[source] std::io::Error),
+}
+
+/// Imports the input stream, extracting the defmt data from network or logic
+/// analyzer captures, J-Link RTT logger files or RTT memory dumps
+async fn import(
+    r: Box<dyn Read + Send>,
+    defmt_cfg: DefmtConfig,
+    intr: Interruptor,
+) -> Result<(), modality_defmt_plugin::Error> {
+    let import_cfg = defmt_cfg.plugin.import.clone();
+    if let Some(filter) = import_cfg.pcap_filter {
+        debug!(%filter, "Reading input as a network capture");
+        defmt_reader::run(PcapReader::new(r, filter), defmt_cfg, intr).await
+    } else if let Some(format) = import_cfg.logic_capture {
+        let r = LogicCaptureReader::new(BufReader::new(r), format, import_cfg.capture_sample_rate);
+        defmt_reader::run_logic_capture(r, defmt_cfg, intr).await
+    } else if import_cfg.rtt_dump {
+        let data = rtt_dump_data(r, &defmt_cfg)?;
+        defmt_reader::run(data.as_slice(), defmt_cfg, intr).await
+    } else if import_cfg.jlink_rtt_logger {
+        debug!("Reading input as a JLinkRTTLogger file");
+        defmt_reader::run(JLinkLogReader::new(r), defmt_cfg, intr).await
+    } else {
+        defmt_reader::run(r, defmt_cfg, intr).await
+    }
+}
+
+/// Extracts the RTT up channel data from a RAM dump or ELF core file
+#[cfg(feature = "rtt-dump")]
+fn rtt_dump_data(
+    mut r: Box<dyn Read + Send>,
+    defmt_cfg: &DefmtConfig,
+) -> Result<Vec<u8>, modality_defmt_plugin::Error> {
+    let import_cfg = &defmt_cfg.plugin.import;
+    let control_block_address = match import_cfg.rtt_dump_control_block_address {
+        Some(addr) => Some(addr),
+        None => match defmt_cfg.plugin.elf_file.as_ref() {
+            Some(elf_file) => {
+                let elf_contents = std::fs::read(elf_file)
+                    .map_err(|e| modality_defmt_plugin::Error::ElfFileRead(elf_file.clone(), e))?;
+                rtt_dump::rtt_symbol(&elf_contents)
+            }
+            None => None,
+        },
+    };
+
+    let mut dump = Vec::new();
+    r.read_to_end(&mut dump)?;
+    let mem = MemoryImage::from_dump(dump, import_cfg.rtt_dump_address, control_block_address)?;
+    let channel = rtt_dump::read_up_channels(&mem, control_block_address)?
+        .into_iter()
+        .find(|ch| ch.number == import_cfg.rtt_dump_up_channel)
+        .ok_or(RttDumpError::UpChannelInvalid(
+            import_cfg.rtt_dump_up_channel,
+        ))?;
+    debug!(
+        channel = channel.number,
+        name = channel.name.as_deref().unwrap_or("NA"),
+        buffer_size = channel.buffer.len(),
+        write_offset = channel.write_offset,
+        read_offset = channel.read_offset,
+        "Recovered RTT up channel"
+    );
+
+    Ok(if import_cfg.rtt_dump_history {
+        channel.history()
+    } else {
+        channel.unread()
+    })
+}
+
+#[cfg(not(feature = "rtt-dump"))]
+fn rtt_dump_data(
+    _r: Box<dyn Read + Send>,
+    _defmt_cfg: &DefmtConfig,
+) -> Result<Vec<u8>, modality_defmt_plugin::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Importing RTT memory dumps requires the 'rtt-dump' feature",
+    )
+    .into())
+}
+
+/// Parses a hex ('0x' prefixed) or decimal address
+fn parse_address(s: &str) -> Result<u64, String> {
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse::<u64>(),
+    };
+    res.map_err(|e| format!("Invalid address '{s}'. {e}"))
+}
+
+/// Reads from a connected named pipe server, polling so the interruptor is checked
+#[cfg(windows)]
+struct NamedPipeReader {
+    interruptor: Interruptor,
+    pipe: NamedPipeServer,
+}
+
+#[cfg(windows)]
+impl NamedPipeReader {
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+}
+
+#[cfg(windows)]
+impl std::io::Read for NamedPipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while !self.interruptor.is_set() {
+            match self.pipe.try_read(buf) {
+                Ok(bytes_read) => return Ok(bytes_read),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Self::POLL_INTERVAL);
+                }
+                // The client disconnected
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(0),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix_socket(p: &Path) -> Result<UnixListener, UnixSocketError> {
+    // Remove a stale socket left behind by a previous run
+    if let Ok(md) = std::fs::symlink_metadata(p) {
+        if md.file_type().is_socket() {
+            debug!(path = %p.display(), "Removing existing socket");
+            std::fs::remove_file(p).map_err(|e| UnixSocketError::Bind(p.into(), e))?;
+        }
+    }
+    UnixListener::bind(p).map_err(|e| UnixSocketError::Bind(p.into(), e))
+}
+
+#[cfg(unix)]
+fn connect_retry_loop(
+    p: &Path,
+    timeout: humantime::Duration,
+) -> Result<UnixStream, UnixSocketError> {
+    debug!(timeout = %timeout, "Starting Unix domain socket connect retry loop");
+    let timeout: Duration = timeout.into();
+    let start = Instant::now();
+    while Instant::now().duration_since(start) <= timeout {
+        match UnixStream::connect(p) {
+            Ok(s) => return Ok(s),
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+        }
+    }
+
+    // Timeout reached
+    UnixStream::connect(p).map_err(|e| UnixSocketError::Connect(p.into(), e))
+}
+
 fn open_retry_loop<P: AsRef<Path>>(
     p: P,
     timeout: humantime::Duration,
@@ -0,0 +1,432 @@
+use clap::Parser;
+use modality_defmt_plugin::{
+    defmt_reader, tracing::try_init_tracing_subscriber, DefmtConfig, DefmtConfigEntry, DefmtOpts,
+    Interruptor, OpenOcdCollectorConfig, ReflectorOpts,
+};
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+/// Collect defmt data from an OpenOCD RTT server
+#[derive(Parser, Debug, Clone)]
+#[clap(version)]
+struct Opts {
+    #[clap(flatten)]
+    pub rf_opts: ReflectorOpts,
+
+    #[clap(flatten)]
+    pub defmt_opts: DefmtOpts,
+
+    /// The ELF file containing the defmt table and location information.
+    #[clap(
+        long,
+        name = "elf-file",
+        verbatim_doc_comment,
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub elf_file: Option<PathBuf>,
+
+    /// Specify a connection timeout.
+    /// When provided, the plugin will continually attempt to set up and connect to the RTT server.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(
+        long,
+        name = "connect-timeout",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub connect_timeout: Option<humantime::Duration>,
+
+    /// The interval between reconnection attempts when the connection is dropped,
+    /// e.g. when OpenOCD is restarted.
+    ///
+    /// The default value is 1s.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(
+        long,
+        name = "reconnect-interval",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub reconnect_interval: Option<humantime::Duration>,
+
+    /// The RTT control block address, or the start of the memory region to search for it.
+    #[clap(
+        long,
+        name = "control-block-address",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub control_block_address: Option<u32>,
+
+    /// The size of the memory region to search for the RTT control block.
+    ///
+    /// The default value is 1024.
+    #[clap(
+        long,
+        name = "control-block-search-size",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub control_block_search_size: Option<u32>,
+
+    /// The RTT control block ID string.
+    ///
+    /// The default value is 'SEGGER RTT'.
+    #[clap(
+        long,
+        name = "control-block-id",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub control_block_id: Option<String>,
+
+    /// The RTT up (target to host) channel number to serve (defaults to 0).
+    #[clap(long, name = "up-channel", help_heading = "COLLECTOR CONFIGURATION")]
+    pub up_channel: Option<usize>,
+
+    /// Don't configure and start the RTT server through the OpenOCD Tcl port, just connect
+    /// to an RTT server that was already started (e.g. by an OpenOCD script).
+    #[clap(long, name = "disable-setup", help_heading = "COLLECTOR CONFIGURATION")]
+    pub disable_setup: bool,
+
+    /// The OpenOCD host.
+    ///
+    /// The default value is localhost.
+    #[clap(long, name = "host", help_heading = "OPENOCD CONFIGURATION")]
+    pub host: Option<String>,
+
+    /// The OpenOCD Tcl RPC server port.
+    ///
+    /// The default value is 6666.
+    #[clap(long, name = "tcl-port", help_heading = "OPENOCD CONFIGURATION")]
+    pub tcl_port: Option<u16>,
+
+    /// The OpenOCD RTT server port.
+    ///
+    /// The default value is 9090.
+    #[clap(long, name = "rtt-port", help_heading = "OPENOCD CONFIGURATION")]
+    pub rtt_port: Option<u16>,
+}
+
+#[tokio::main]
+async fn main() {
+    match do_main().await {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("{e}");
+            let mut cause = e.source();
+            while let Some(err) = cause {
+                eprintln!("Caused by: {err}");
+                cause = err.source();
+            }
+            std::process::exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
+    let opts = Opts::parse();
+
+    try_init_tracing_subscriber()?;
+
+    let intr = Interruptor::new();
+    let intr_clone = intr.clone();
+    ctrlc::set_handler(move || {
+        if intr_clone.is_set() {
+            let exit_code = if cfg!(target_family = "unix") {
+                // 128 (fatal error signal "n") + 2 (control-c is fatal error signal 2)
+                130
+            } else {
+                // Windows code 3221225786
+                // -1073741510 == C000013A
+                -1073741510
+            };
+            std::process::exit(exit_code);
+        }
+
+        debug!("Shutdown signal received");
+        intr_clone.set();
+    })?;
+
+    let mut defmt_cfg = DefmtConfig::load_merge_with_opts(
+        DefmtConfigEntry::OpenOcdCollector,
+        opts.rf_opts,
+        opts.defmt_opts,
+    )?;
+
+    if let Some(elf_file) = opts.elf_file.as_ref() {
+        defmt_cfg.plugin.elf_file = Some(elf_file.clone());
+    }
+    if let Some(to) = opts.connect_timeout {
+        defmt_cfg.plugin.openocd_collector.connect_timeout = Some(to.into());
+    }
+    if let Some(interval) = opts.reconnect_interval {
+        defmt_cfg.plugin.openocd_collector.reconnect_interval = Some(interval.into());
+    }
+    if let Some(addr) = opts.control_block_address {
+        defmt_cfg.plugin.openocd_collector.control_block_address = addr.into();
+    }
+    if let Some(size) = opts.control_block_search_size {
+        defmt_cfg.plugin.openocd_collector.control_block_search_size = size;
+    }
+    if let Some(id) = opts.control_block_id {
+        defmt_cfg.plugin.openocd_collector.control_block_id = id;
+    }
+    if let Some(up_channel) = opts.up_channel {
+        defmt_cfg.plugin.openocd_collector.up_channel = up_channel;
+    }
+    if opts.disable_setup {
+        defmt_cfg.plugin.openocd_collector.disable_setup = true;
+    }
+    if let Some(host) = opts.host {
+        defmt_cfg.plugin.openocd_collector.host = host;
+    }
+    if let Some(port) = opts.tcl_port {
+        defmt_cfg.plugin.openocd_collector.tcl_port = port;
+    }
+    if let Some(port) = opts.rtt_port {
+        defmt_cfg.plugin.openocd_collector.rtt_port = port;
+    }
+
+    let openocd = OpenOcd::new(&defmt_cfg.plugin.openocd_collector)?;
+
+    let stream = match defmt_cfg.plugin.openocd_collector.connect_timeout {
+        Some(to) if !to.0.is_zero() => openocd.connect_retry_loop(to.0)?,
+        _ => openocd.connect()?,
+    };
+
+    let reconnect_interval = defmt_cfg
+        .plugin
+        .openocd_collector
+        .reconnect_interval
+        .map(|i| i.0.into())
+        .unwrap_or(DefmtOpenOcdReader::DEFAULT_RECONNECT_INTERVAL);
+
+    let mut join_handle = tokio::spawn(async move {
+        let mut r = DefmtOpenOcdReader::new(intr.clone(), openocd, stream, reconnect_interval);
+        defmt_reader::run(&mut r, defmt_cfg, intr).await
+    });
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            debug!("User signaled shutdown");
+        }
+        res = &mut join_handle => {
+            match res? {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(error = %e, "Encountered and error during streaming");
+                    return Err(e.into())
+                }
+            }
+        }
+    };
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(
+        "Missing RTT control block address. Either supply it as a option at the CLI or a config file member 'control-block-address'"
+    )]
+    MissingControlBlockAddress,
+
+    #[error("Failed to communicate with the OpenOCD Tcl server at '{0}'. {1}")]
+    Tcl(String, #[source] io::Error),
+
+    #[error("Failed to connect to the OpenOCD RTT server at '{0}'. {1}")]
+    Connect(String, #[source] io::Error),
+}
+
+/// Tcl RPC commands used to configure and start the RTT server
+#[derive(Clone, Debug)]
+struct RttSetup {
+    commands: Vec<String>,
+}
+
+struct OpenOcd {
+    tcl_addr: String,
+    rtt_addr: String,
+    /// None when setup is disabled
+    setup: Option<RttSetup>,
+}
+
+impl OpenOcd {
+    /// Terminates Tcl RPC commands and responses
+    const TCL_TERMINATOR: u8 = 0x1A;
+    const TCL_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn new(cfg: &OpenOcdCollectorConfig) -> Result<Self, Error> {
+        let setup = if cfg.disable_setup {
+            None
+        } else {
+            let addr = cfg
+                .control_block_address
+                .ok_or(Error::MissingControlBlockAddress)?;
+            Some(RttSetup {
+                commands: vec![
+                    format!(
+                        "rtt setup 0x{addr:X} {} \"{}\"",
+                        cfg.control_block_search_size, cfg.control_block_id
+                    ),
+                    "rtt start".to_owned(),
+                    format!("rtt server start {} {}", cfg.rtt_port, cfg.up_channel),
+                ],
+            })
+        };
+        Ok(Self {
+            tcl_addr: format!("{}:{}", cfg.host, cfg.tcl_port),
+            rtt_addr: format!("{}:{}", cfg.host, cfg.rtt_port),
+            setup,
+        })
+    }
+
+    /// Configure and start the RTT server, if enabled, then connect to it
+    fn connect(&self) -> Result<TcpStream, Error> {
+        if let Some(setup) = &self.setup {
+            self.setup_rtt(setup)
+                .map_err(|e| Error::Tcl(self.tcl_addr.clone(), e))?;
+        }
+
+        debug!(rtt_addr = self.rtt_addr, "Connecting to RTT server");
+        let stream = TcpStream::connect(&self.rtt_addr)
+            .map_err(|e| Error::Connect(self.rtt_addr.clone(), e))?;
+        stream
+            .set_read_timeout(Some(DefmtOpenOcdReader::READ_TIMEOUT))
+            .map_err(|e| Error::Connect(self.rtt_addr.clone(), e))?;
+        Ok(stream)
+    }
+
+    fn connect_retry_loop(&self, timeout: humantime::Duration) -> Result<TcpStream, Error> {
+        debug!(timeout = %timeout, "Starting connect retry loop");
+        let timeout: Duration = timeout.into();
+        let start = Instant::now();
+        while Instant::now().duration_since(start) <= timeout {
+            match self.connect() {
+                Ok(s) => return Ok(s),
+                Err(_) => {
+                    std::thread::sleep(Duration::from_millis(50));
+                    continue;
+                }
+            }
+        }
+
+        // Timeout reached
+        self.connect()
+    }
+
+    fn setup_rtt(&self, setup: &RttSetup) -> io::Result<()> {
+        debug!(tcl_addr = self.tcl_addr, "Setting up RTT server");
+        let mut stream = TcpStream::connect(&self.tcl_addr)?;
+        stream.set_read_timeout(Some(Self::TCL_TIMEOUT))?;
+        for cmd in setup.commands.iter() {
+            let resp = Self::tcl_command(&mut stream, cmd)?;
+            debug!(cmd, resp, "Tcl command");
+        }
+        Ok(())
+    }
+
+    fn tcl_command(stream: &mut TcpStream, cmd: &str) -> io::Result<String> {
+        stream.write_all(cmd.as_bytes())?;
+        stream.write_all(&[Self::TCL_TERMINATOR])?;
+
+        let mut resp = Vec::new();
+        let mut buf = [0_u8; 256];
+        loop {
+            let bytes_read = stream.read(&mut buf)?;
+            if bytes_read == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if let Some(end) = buf[..bytes_read]
+                .iter()
+                .position(|b| *b == Self::TCL_TERMINATOR)
+            {
+                resp.extend_from_slice(&buf[..end]);
+                break;
+            }
+            resp.extend_from_slice(&buf[..bytes_read]);
+        }
+        Ok(String::from_utf8_lossy(&resp).into_owned())
+    }
+}
+
+struct DefmtOpenOcdReader {
+    interruptor: Interruptor,
+    openocd: OpenOcd,
+    stream: TcpStream,
+    reconnect_interval: Duration,
+}
+
+impl DefmtOpenOcdReader {
+    const DEFAULT_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+    /// How long a read blocks waiting for data before checking the interruptor
+    const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn new(
+        interruptor: Interruptor,
+        openocd: OpenOcd,
+        stream: TcpStream,
+        reconnect_interval: Duration,
+    ) -> Self {
+        Self {
+            interruptor,
+            openocd,
+            stream,
+            reconnect_interval,
+        }
+    }
+
+    /// Returns false if we were interrupted before the connection was re-established
+    fn reconnect(&mut self) -> bool {
+        while !self.interruptor.is_set() {
+            std::thread::sleep(self.reconnect_interval);
+            match self.openocd.connect() {
+                Ok(s) => {
+                    info!(rtt_addr = self.openocd.rtt_addr, "Reconnected");
+                    self.stream = s;
+                    return true;
+                }
+                Err(e) => {
+                    debug!(error = %e, "Reconnect attempt failed");
+                }
+            }
+        }
+        false
+    }
+}
+
+impl io::Read for DefmtOpenOcdReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.interruptor.is_set() {
+            match self.stream.read(buf) {
+                Ok(0) => {
+                    warn!(
+                        rtt_addr = self.openocd.rtt_addr,
+                        "RTT server connection closed, reconnecting"
+                    );
+                    if !self.reconnect() {
+                        return Ok(0);
+                    }
+                }
+                Ok(bytes_read) => return Ok(bytes_read),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::Interrupted =>
+                {
+                    continue
+                }
+                Err(e) => {
+                    warn!(rtt_addr = self.openocd.rtt_addr, error = %e, "RTT server connection lost, reconnecting");
+                    if !self.reconnect() {
+                        return Ok(0);
+                    }
+                }
+            }
+        }
+        Ok(0)
+    }
+}
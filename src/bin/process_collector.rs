@@ -0,0 +1,256 @@
+use clap::Parser;
+use modality_defmt_plugin::{
+    defmt_reader, tracing::try_init_tracing_subscriber, DefmtConfig, DefmtConfigEntry, DefmtOpts,
+    Interruptor, ProcessCollectorConfig, ProcessStream, ReflectorOpts,
+};
+use std::{
+    io,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+/// Collect defmt data from the output of a child process
+#[derive(Parser, Debug, Clone)]
+#[clap(version)]
+struct Opts {
+    #[clap(flatten)]
+    pub rf_opts: ReflectorOpts,
+
+    #[clap(flatten)]
+    pub defmt_opts: DefmtOpts,
+
+    /// The ELF file containing the defmt table and location information.
+    #[clap(
+        long,
+        name = "elf-file",
+        verbatim_doc_comment,
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub elf_file: Option<PathBuf>,
+
+    /// The working directory of the child process.
+    #[clap(
+        long,
+        name = "working-directory",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub working_directory: Option<PathBuf>,
+
+    /// The child process output carrying the defmt data.
+    /// Possible options: [stdout, stderr, fd:<N>].
+    ///
+    /// With 'fd:<N>', a pipe is provided to the child as file descriptor N (Unix platforms only).
+    ///
+    /// The default value is stdout.
+    #[clap(long, name = "stream", help_heading = "COLLECTOR CONFIGURATION")]
+    pub stream: Option<ProcessStream>,
+
+    /// The command, and its arguments, to spawn (e.g. -- qemu-system-arm -machine lm3s6965evb ...)
+    #[clap(
+        name = "command",
+        last = true,
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub command: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    match do_main().await {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("{e}");
+            let mut cause = e.source();
+            while let Some(err) = cause {
+                eprintln!("Caused by: {err}");
+                cause = err.source();
+            }
+            std::process::exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
+    let opts = Opts::parse();
+
+    try_init_tracing_subscriber()?;
+
+    let intr = Interruptor::new();
+    let intr_clone = intr.clone();
+    ctrlc::set_handler(move || {
+        if intr_clone.is_set() {
+            let exit_code = if cfg!(target_family = "unix") {
+                // 128 (fatal error signal "n") + 2 (control-c is fatal error signal 2)
+                130
+            } else {
+                // Windows code 3221225786
+                // -1073741510 == C000013A
+                -1073741510
+            };
+            std::process::exit(exit_code);
+        }
+
+        debug!("Shutdown signal received");
+        intr_clone.set();
+    })?;
+
+    let mut defmt_cfg = DefmtConfig::load_merge_with_opts(
+        DefmtConfigEntry::ProcessCollector,
+        opts.rf_opts,
+        opts.defmt_opts,
+    )?;
+
+    if let Some(elf_file) = opts.elf_file.as_ref() {
+        defmt_cfg.plugin.elf_file = Some(elf_file.clone());
+    }
+    if let Some(wd) = opts.working_directory {
+        defmt_cfg.plugin.process_collector.working_directory = Some(wd);
+    }
+    if let Some(stream) = opts.stream {
+        defmt_cfg.plugin.process_collector.stream = stream;
+    }
+    if let Some((cmd, args)) = opts.command.split_first() {
+        defmt_cfg.plugin.process_collector.command = Some(cmd.clone());
+        defmt_cfg.plugin.process_collector.args = args.to_vec();
+    }
+
+    let (child, reader) = spawn_child(&defmt_cfg.plugin.process_collector)?;
+    let child = Arc::new(Mutex::new(child));
+
+    // Tie the child's lifecycle to the interruptor
+    let watcher_intr = intr.clone();
+    let watcher_child = child.clone();
+    let watcher = thread::spawn(move || watch_child(watcher_intr, watcher_child));
+
+    let reader_intr = intr.clone();
+    let mut join_handle = tokio::spawn(async move {
+        let mut r = reader;
+        defmt_reader::run(&mut r, defmt_cfg, reader_intr).await
+    });
+
+    let res = tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            debug!("User signaled shutdown");
+            Ok(())
+        }
+        res = &mut join_handle => {
+            match res? {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    error!(error = %e, "Encountered and error during streaming");
+                    Err(e.into())
+                }
+            }
+        }
+    };
+
+    // Stop the child if it's still running
+    intr.set();
+    match watcher.join() {
+        Ok(Ok(Some(status))) if status.success() => info!(%status, "Child process exited"),
+        Ok(Ok(Some(status))) => warn!(%status, "Child process exited"),
+        Ok(Ok(None)) => debug!("Child process stopped"),
+        Ok(Err(e)) => warn!(error = %e, "Failed to wait on the child process"),
+        Err(_) => warn!("Child process watcher panicked"),
+    }
+
+    res
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(
+        "Missing command. Either supply it as trailing arguments at the CLI (after '--') or a config file member 'command'"
+    )]
+    MissingCommand,
+
+    #[error("Failed to spawn the child process '{0}'. {1}")]
+    Spawn(String, #[source] io::Error),
+
+    #[cfg(not(unix))]
+    #[error("File descriptor streams are only supported on Unix platforms")]
+    FdUnsupported,
+}
+
+type ChildReader = Box<dyn io::Read + Send>;
+
+fn spawn_child(cfg: &ProcessCollectorConfig) -> Result<(Child, ChildReader), Error> {
+    let program = cfg.command.as_ref().ok_or(Error::MissingCommand)?;
+    let spawn_err = |e| Error::Spawn(program.clone(), e);
+
+    let mut cmd = Command::new(program);
+    cmd.args(&cfg.args).stdin(Stdio::null());
+    if let Some(wd) = &cfg.working_directory {
+        cmd.current_dir(wd);
+    }
+
+    info!(command = program, args = ?cfg.args, stream = %cfg.stream, "Spawning child process");
+    match cfg.stream {
+        ProcessStream::Stdout => {
+            let mut child = cmd.stdout(Stdio::piped()).spawn().map_err(spawn_err)?;
+            // SAFETY: we requested a piped stdout
+            let out = child.stdout.take().unwrap();
+            Ok((child, Box::new(out)))
+        }
+        ProcessStream::Stderr => {
+            let mut child = cmd.stderr(Stdio::piped()).spawn().map_err(spawn_err)?;
+            // SAFETY: we requested a piped stderr
+            let out = child.stderr.take().unwrap();
+            Ok((child, Box::new(out)))
+        }
+        #[cfg(unix)]
+        ProcessStream::Fd(fd) => {
+            use std::os::{fd::AsRawFd, unix::process::CommandExt};
+
+            let (reader, writer) = io::pipe().map_err(spawn_err)?;
+            let writer_fd = writer.as_raw_fd();
+            // SAFETY: only async-signal-safe functions are called between fork and exec
+            unsafe {
+                cmd.pre_exec(move || {
+                    if writer_fd == fd {
+                        // dup2 is a no-op here, just clear close-on-exec
+                        if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    } else if libc::dup2(writer_fd, fd) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+            let child = cmd.spawn().map_err(spawn_err)?;
+            // Only the child holds the write end now, so we'll see EOF when it exits
+            drop(writer);
+            Ok((child, Box::new(reader)))
+        }
+        #[cfg(not(unix))]
+        ProcessStream::Fd(_) => Err(Error::FdUnsupported),
+    }
+}
+
+/// Waits for the child to exit, killing it once the interruptor is set.
+/// Returns None if the child was killed.
+fn watch_child(intr: Interruptor, child: Arc<Mutex<Child>>) -> io::Result<Option<ExitStatus>> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    loop {
+        {
+            let mut child = child.lock().unwrap();
+            // NOTE: the reader sees EOF once the child is gone and the pipe is drained
+            if let Some(status) = child.try_wait()? {
+                return Ok(Some(status));
+            }
+            if intr.is_set() {
+                debug!("Killing child process");
+                child.kill()?;
+                child.wait()?;
+                return Ok(None);
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
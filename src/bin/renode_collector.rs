@@ -0,0 +1,407 @@
+use auxon_sdk::reflector_config::{AttrKey, AttrKeyEqValuePair};
+use clap::Parser;
+use modality_defmt_plugin::{
+    defmt_reader, tracing::try_init_tracing_subscriber, DefmtConfig, DefmtConfigEntry, DefmtOpts,
+    Interruptor, ReflectorOpts, RenodeCollectorConfig,
+};
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info};
+
+/// Collect defmt data from a Renode UART, via a socket terminal or pty
+#[derive(Parser, Debug, Clone)]
+#[clap(version)]
+struct Opts {
+    #[clap(flatten)]
+    pub rf_opts: ReflectorOpts,
+
+    #[clap(flatten)]
+    pub defmt_opts: DefmtOpts,
+
+    /// The ELF file containing the defmt table and location information.
+    #[clap(
+        long,
+        name = "elf-file",
+        verbatim_doc_comment,
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub elf_file: Option<PathBuf>,
+
+    /// Specify a connection timeout.
+    /// When provided, the plugin will continually attempt to connect to the UART and monitor.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(
+        long,
+        name = "connect-timeout",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub connect_timeout: Option<humantime::Duration>,
+
+    /// The address of a Renode UART server socket terminal (e.g. localhost:3456).
+    ///
+    /// Created in Renode with 'emulation CreateServerSocketTerminal 3456 "term" false'
+    /// and 'connector Connect sysbus.uart term'.
+    #[clap(
+        long,
+        name = "uart-socket",
+        conflicts_with = "uart-pty",
+        help_heading = "RENODE CONFIGURATION"
+    )]
+    pub uart_socket: Option<String>,
+
+    /// The path of a Renode UART pty terminal (e.g. /tmp/uart).
+    ///
+    /// Created in Renode with 'emulation CreateUartPtyTerminal "term" "/tmp/uart"'
+    /// and 'connector Connect sysbus.uart term'.
+    #[clap(long, name = "uart-pty", help_heading = "RENODE CONFIGURATION")]
+    pub uart_pty: Option<PathBuf>,
+
+    /// The address of the Renode monitor (e.g. localhost:1234, from 'renode --port 1234').
+    ///
+    /// Monitor commands are only issued when this is provided.
+    #[clap(long, name = "monitor", help_heading = "RENODE CONFIGURATION")]
+    pub monitor: Option<String>,
+
+    /// A monitor command to issue once the UART is connected (e.g. start).
+    /// Can be provided multiple times, commands are issued in order.
+    #[clap(
+        long = "monitor-command",
+        name = "monitor-command",
+        help_heading = "RENODE CONFIGURATION"
+    )]
+    pub monitor_commands: Vec<String>,
+
+    /// The name of the Renode machine (e.g. atsamd).
+    ///
+    /// The machine is selected with 'mach set' before any monitor commands are issued,
+    /// and its name is added to the timeline attributes.
+    #[clap(long, name = "machine", help_heading = "RENODE CONFIGURATION")]
+    pub machine: Option<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    match do_main().await {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("{e}");
+            let mut cause = e.source();
+            while let Some(err) = cause {
+                eprintln!("Caused by: {err}");
+                cause = err.source();
+            }
+            std::process::exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
+    let opts = Opts::parse();
+
+    try_init_tracing_subscriber()?;
+
+    let intr = Interruptor::new();
+    let intr_clone = intr.clone();
+    ctrlc::set_handler(move || {
+        if intr_clone.is_set() {
+            let exit_code = if cfg!(target_family = "unix") {
+                // 128 (fatal error signal "n") + 2 (control-c is fatal error signal 2)
+                130
+            } else {
+                // Windows code 3221225786
+                // -1073741510 == C000013A
+                -1073741510
+            };
+            std::process::exit(exit_code);
+        }
+
+        debug!("Shutdown signal received");
+        intr_clone.set();
+    })?;
+
+    let mut defmt_cfg = DefmtConfig::load_merge_with_opts(
+        DefmtConfigEntry::RenodeCollector,
+        opts.rf_opts,
+        opts.defmt_opts,
+    )?;
+
+    if let Some(elf_file) = opts.elf_file.as_ref() {
+        defmt_cfg.plugin.elf_file = Some(elf_file.clone());
+    }
+    if let Some(to) = opts.connect_timeout {
+        defmt_cfg.plugin.renode_collector.connect_timeout = Some(to.into());
+    }
+    if let Some(socket) = opts.uart_socket {
+        defmt_cfg.plugin.renode_collector.uart_socket = Some(socket);
+        defmt_cfg.plugin.renode_collector.uart_pty = None;
+    }
+    if let Some(pty) = opts.uart_pty {
+        defmt_cfg.plugin.renode_collector.uart_pty = Some(pty);
+        defmt_cfg.plugin.renode_collector.uart_socket = None;
+    }
+    if let Some(monitor) = opts.monitor {
+        defmt_cfg.plugin.renode_collector.monitor = Some(monitor);
+    }
+    if !opts.monitor_commands.is_empty() {
+        defmt_cfg.plugin.renode_collector.monitor_commands = opts.monitor_commands;
+    }
+    if let Some(machine) = opts.machine {
+        defmt_cfg.plugin.renode_collector.machine = Some(machine);
+    }
+
+    if let Some(machine) = defmt_cfg.plugin.renode_collector.machine.as_ref() {
+        defmt_cfg
+            .ingest
+            .timeline_attributes
+            .additional_timeline_attributes
+            .push(AttrKeyEqValuePair(
+                AttrKey::new(MACHINE_ATTR_KEY.to_owned()),
+                machine.clone().into(),
+            ));
+    }
+
+    let connect_timeout = defmt_cfg
+        .plugin
+        .renode_collector
+        .connect_timeout
+        .map(|to| to.0)
+        .filter(|to| !to.is_zero());
+
+    // Connect to the UART first so nothing is missed once the emulation is started
+    let uart = open_uart(&defmt_cfg.plugin.renode_collector, connect_timeout)?;
+
+    if let Some(monitor) = defmt_cfg.plugin.renode_collector.monitor.as_ref() {
+        let mut m = Monitor::connect(monitor, connect_timeout)?;
+        if let Some(machine) = defmt_cfg.plugin.renode_collector.machine.as_ref() {
+            m.command(&format!("mach set \"{machine}\""))?;
+        }
+        for cmd in defmt_cfg.plugin.renode_collector.monitor_commands.iter() {
+            m.command(cmd)?;
+        }
+    } else if !defmt_cfg
+        .plugin
+        .renode_collector
+        .monitor_commands
+        .is_empty()
+    {
+        return Err(Error::MissingMonitor.into());
+    }
+
+    let mut join_handle = tokio::spawn(async move {
+        let mut r = DefmtRenodeReader::new(intr.clone(), uart);
+        defmt_reader::run(&mut r, defmt_cfg, intr).await
+    });
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            debug!("User signaled shutdown");
+        }
+        res = &mut join_handle => {
+            match res? {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(error = %e, "Encountered and error during streaming");
+                    return Err(e.into())
+                }
+            }
+        }
+    };
+
+    Ok(())
+}
+
+/// Timeline attribute recording the Renode machine name
+const MACHINE_ATTR_KEY: &str = "timeline.internal.defmt.renode.machine";
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(
+        "Missing UART. Either supply '--uart-socket' or '--uart-pty' at the CLI or a config file member 'uart-socket' or 'uart-pty'"
+    )]
+    MissingUart,
+
+    #[error("Only one of 'uart-socket' or 'uart-pty' can be provided")]
+    SocketAndPty,
+
+    #[error("Monitor commands were provided without a monitor address")]
+    MissingMonitor,
+
+    #[error("Failed to connect to '{0}'. {1}")]
+    Connect(String, #[source] io::Error),
+
+    #[error("Failed to open the pty '{0}'. {1}")]
+    OpenPty(PathBuf, #[source] serialport::Error),
+
+    #[error("Failed to issue the monitor command '{0}'. {1}")]
+    MonitorCommand(String, #[source] io::Error),
+}
+
+type Uart = Box<dyn Read + Send>;
+
+fn open_uart(
+    cfg: &RenodeCollectorConfig,
+    timeout: Option<humantime::Duration>,
+) -> Result<Uart, Error> {
+    match (cfg.uart_socket.as_ref(), cfg.uart_pty.as_ref()) {
+        (Some(_), Some(_)) => Err(Error::SocketAndPty),
+        (None, None) => Err(Error::MissingUart),
+        (Some(socket), None) => {
+            info!(socket, "Connecting to the UART socket");
+            let stream = retry_loop(timeout, || connect(socket))?;
+            Ok(Box::new(stream))
+        }
+        (None, Some(pty)) => {
+            info!(pty = %pty.display(), "Opening the UART pty");
+            let port = retry_loop(timeout, || open_pty(pty))?;
+            Ok(Box::new(port))
+        }
+    }
+}
+
+fn connect(addr: &str) -> Result<TcpStream, Error> {
+    debug!(addr, "Connecting");
+    let stream = TcpStream::connect(addr).map_err(|e| Error::Connect(addr.to_owned(), e))?;
+    stream
+        .set_read_timeout(Some(DefmtRenodeReader::READ_TIMEOUT))
+        .map_err(|e| Error::Connect(addr.to_owned(), e))?;
+    Ok(stream)
+}
+
+fn open_pty(path: &Path) -> Result<Box<dyn serialport::SerialPort>, Error> {
+    debug!(path = %path.display(), "Opening pty");
+    // The baud rate is meaningless for a pty, but opening it as a serial port
+    // puts it in raw mode
+    serialport::new(path.to_string_lossy(), 115_200)
+        .timeout(DefmtRenodeReader::READ_TIMEOUT)
+        .open()
+        .map_err(|e| Error::OpenPty(path.to_owned(), e))
+}
+
+/// Calls `f` until it succeeds or the timeout is reached, a single attempt is made
+/// when there's no timeout.
+fn retry_loop<T>(
+    timeout: Option<humantime::Duration>,
+    mut f: impl FnMut() -> Result<T, Error>,
+) -> Result<T, Error> {
+    if let Some(timeout) = timeout {
+        debug!(timeout = %timeout, "Starting connect retry loop");
+        let timeout: Duration = timeout.into();
+        let start = Instant::now();
+        while Instant::now().duration_since(start) <= timeout {
+            match f() {
+                Ok(t) => return Ok(t),
+                Err(_) => {
+                    std::thread::sleep(Duration::from_millis(50));
+                    continue;
+                }
+            }
+        }
+    }
+
+    // Timeout reached
+    f()
+}
+
+/// A connection to the Renode monitor
+struct Monitor {
+    addr: String,
+    stream: TcpStream,
+}
+
+impl Monitor {
+    /// How long to wait for the monitor to go quiet after a command
+    const RESPONSE_TIMEOUT: Duration = Duration::from_millis(250);
+
+    fn connect(addr: &str, timeout: Option<humantime::Duration>) -> Result<Self, Error> {
+        info!(monitor = addr, "Connecting to the Renode monitor");
+        let stream = retry_loop(timeout, || connect(addr))?;
+        stream
+            .set_read_timeout(Some(Self::RESPONSE_TIMEOUT))
+            .map_err(|e| Error::Connect(addr.to_owned(), e))?;
+        let mut m = Self {
+            addr: addr.to_owned(),
+            stream,
+        };
+        // Discard the banner and prompt
+        m.read_response()
+            .map_err(|e| Error::Connect(addr.to_owned(), e))?;
+        Ok(m)
+    }
+
+    fn command(&mut self, cmd: &str) -> Result<(), Error> {
+        info!(monitor = self.addr, cmd, "Issuing monitor command");
+        let err = |e| Error::MonitorCommand(cmd.to_owned(), e);
+        self.stream
+            .write_all(format!("{cmd}\n").as_bytes())
+            .map_err(err)?;
+        self.stream.flush().map_err(err)?;
+        let rsp = self.read_response().map_err(err)?;
+        debug!(cmd, response = rsp.trim(), "Monitor response");
+        Ok(())
+    }
+
+    /// Reads until the monitor stops responding for RESPONSE_TIMEOUT
+    fn read_response(&mut self) -> io::Result<String> {
+        let mut rsp = Vec::new();
+        let mut buf = [0_u8; 256];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => rsp.extend_from_slice(&buf[..n]),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    break
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        // Drop telnet negotiation (IAC) bytes and anything else that isn't text
+        rsp.retain(|b| b.is_ascii());
+        Ok(String::from_utf8_lossy(&rsp).into_owned())
+    }
+}
+
+struct DefmtRenodeReader {
+    interruptor: Interruptor,
+    uart: Uart,
+}
+
+impl DefmtRenodeReader {
+    /// How long a read blocks waiting for data before checking the interruptor
+    const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn new(interruptor: Interruptor, uart: Uart) -> Self {
+        Self { interruptor, uart }
+    }
+}
+
+impl io::Read for DefmtRenodeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.interruptor.is_set() {
+            match self.uart.read(buf) {
+                Ok(0) => {
+                    debug!("UART closed");
+                    return Ok(0);
+                }
+                Ok(bytes_read) => return Ok(bytes_read),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::Interrupted =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(0)
+    }
+}
@@ -1,24 +1,37 @@
+use auxon_sdk::reflector_config::{AttrKey, AttrKeyEqValuePair};
 use clap::Parser;
+use derive_more::Display;
 use human_bytes::human_bytes;
 use modality_defmt_plugin::{
-    defmt_reader, tracing::try_init_tracing_subscriber, DefmtConfig, DefmtConfigEntry, DefmtOpts,
-    Interruptor, ReflectorOpts,
+    config_template,
+    defmt_reader::{self, ReadOutcome},
+    raw_capture::source_capture_path,
+    tracing::try_init_tracing_subscriber,
+    CrashState, DefmtConfig, DefmtConfigEntry, DefmtOpts, EventRecord, FaultAction, FaultState,
+    Interruptor, MemoryDump, MemoryWindow, ProbeCommand, ReflectorOpts, RingBufferConfig,
+    RttChannelFormat, RttChannelMode, RttCollectorConfig, RttPollPolicy, RttUpChannel, SetupStage,
+    StagedMutation, StopTrigger, TargetArch, WatchSample,
 };
 use probe_rs::{
     config::MemoryRegion,
+    flashing::{download_file_with_options, DownloadOptions, Format},
     probe::{list::Lister, DebugProbeSelector, WireProtocol},
     rtt::{ChannelMode, Rtt, ScanRegion, UpChannel},
-    Core, CoreStatus, HaltReason, Permissions, RegisterValue, Session, VectorCatchCondition,
+    Core, CoreStatus, HaltReason, MemoryInterface, Permissions, RegisterValue, Session,
+    VectorCatchCondition,
 };
 use ratelimit::Ratelimiter;
 use simple_moving_average::{NoSumSMA, SMA};
 use std::{
+    cell::Cell,
+    collections::BTreeMap,
     fs, io,
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, PoisonError},
     time::{Duration, Instant},
 };
 use tracing::{debug, error, info, trace, warn};
+use uuid::Uuid;
 
 /// Collect defmt data from an on-device RTT buffer
 #[derive(Parser, Debug, Clone)]
@@ -30,6 +43,10 @@ struct Opts {
     #[clap(flatten)]
     pub defmt_opts: DefmtOpts,
 
+    /// Print an annotated configuration template listing every configuration key, then exit.
+    #[clap(long, name = "print-config-template")]
+    pub print_config_template: bool,
+
     /// Specify a target attach timeout.
     /// When provided, the plugin will continually attempt to attach and search
     /// for a valid RTT control block anywhere in the target RAM.
@@ -51,8 +68,63 @@ struct Opts {
     pub control_block_address: Option<u32>,
 
     /// The RTT up (target to host) channel number to poll on (defaults to 0).
+    ///
+    /// Can be provided multiple times to poll several channels concurrently, each
+    /// with its own decoder and timelines.
+    /// Use '--up-channel N=ELF_FILE' to decode a channel with the defmt table from a
+    /// different ELF file.
+    /// Channels can also be selected by their RTT channel name (e.g. '--up-channel defmt'),
+    /// resolved at attach time.
     #[clap(long, name = "up-channel", help_heading = "COLLECTOR CONFIGURATION")]
-    pub up_channel: Option<usize>,
+    pub up_channel: Vec<RttUpChannel>,
+
+    /// An RTT up channel carrying plain text (non-defmt) logs, e.g. a printf channel.
+    ///
+    /// Each line is ingested as an event on the channel's own timeline, named after
+    /// the RTT channel name.
+    /// Can be provided multiple times.
+    #[clap(
+        long,
+        name = "text-up-channel",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub text_up_channel: Vec<RttUpChannel>,
+
+    /// Poll a firmware-defined RAM ring buffer instead of RTT.
+    /// The symbol name or address of the buffer storage.
+    ///
+    /// Requires '--ring-buffer-head' and '--ring-buffer-tail'.
+    #[clap(long, name = "ring-buffer", help_heading = "COLLECTOR CONFIGURATION")]
+    pub ring_buffer: Option<String>,
+
+    /// Size of the ring buffer in bytes.
+    ///
+    /// Defaults to the size of the ring buffer symbol.
+    #[clap(
+        long,
+        name = "ring-buffer-size",
+        requires = "ring-buffer",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub ring_buffer_size: Option<u32>,
+
+    /// The symbol name or address of the ring buffer's u32 write offset, advanced by the target.
+    #[clap(
+        long,
+        name = "ring-buffer-head",
+        requires = "ring-buffer",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub ring_buffer_head: Option<String>,
+
+    /// The symbol name or address of the ring buffer's u32 read offset, advanced by the collector.
+    #[clap(
+        long,
+        name = "ring-buffer-tail",
+        requires = "ring-buffer",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub ring_buffer_tail: Option<String>,
 
     /// Set a breakpoint on the address of the given symbol used to signal
     /// when to enable RTT BlockIfFull channel mode and start reading.
@@ -65,15 +137,34 @@ struct Opts {
     )]
     pub setup_on_breakpoint: Option<String>,
 
+    /// A stage of a staged start, can be repeated.
+    /// The collector waits for each stage's breakpoint in turn, performs its actions
+    /// and resumes the target.
+    ///
+    /// Takes the form 'BREAKPOINT[,ACTION...]', where the breakpoint is a symbol name
+    /// or address and the actions are 'mode=CHANNEL_MODE', 'write=LOCATION=VALUE'
+    /// (a 32-bit word) and 'start' (attach to RTT and start reading at this stage).
+    /// Takes precedence over '--setup-on-breakpoint'.
+    #[arg(long, name = "setup-stage", help_heading = "COLLECTOR CONFIGURATION")]
+    pub setup_stage: Vec<SetupStage>,
+
     /// Assume thumb mode when resolving symbols from the ELF file
-    /// for breakpoint addresses.
-    #[arg(
-        long,
-        requires = "setup-on-breakpoint",
-        help_heading = "COLLECTOR CONFIGURATION"
-    )]
+    /// for breakpoint addresses. Only applies to Arm cores.
+    #[arg(long, help_heading = "COLLECTOR CONFIGURATION")]
     pub thumb: bool,
 
+    /// The core architecture (arm, riscv, xtensa), detected from the chip by default
+    #[arg(long, name = "arch", help_heading = "COLLECTOR CONFIGURATION")]
+    pub arch: Option<TargetArch>,
+
+    /// The RTT up channel mode to set (block-if-full, no-block-skip, no-block-trim).
+    ///
+    /// With '--setup-on-breakpoint', the mode is set once the breakpoint is hit and
+    /// defaults to block-if-full. Otherwise it's set on attach, and the channel mode
+    /// is left as is by default.
+    #[clap(long, name = "channel-mode", help_heading = "COLLECTOR CONFIGURATION")]
+    pub channel_mode: Option<RttChannelMode>,
+
     /// Select a specific probe instead of opening the first available one.
     ///
     /// Use '--probe VID:PID' or '--probe VID:PID:Serial' if you have more than one probe with the same VID:PID.
@@ -115,6 +206,20 @@ struct Opts {
     )]
     pub attach_under_reset: bool,
 
+    /// Flash the ELF file ('--elf-file') to the target before attaching,
+    /// then reset the target to run it.
+    #[clap(long, name = "download", help_heading = "PROBE CONFIGURATION")]
+    pub download: bool,
+
+    /// Verify the flash contents after downloading.
+    #[clap(
+        long,
+        name = "verify",
+        requires = "download",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub verify: bool,
+
     /// Chip description YAML file path.
     /// Provides custom target descriptions based on CMSIS Pack files.
     #[clap(
@@ -139,6 +244,7 @@ struct Opts {
     #[clap(
         long,
         name = "rtt-reader-buffer-size",
+        alias = "rtt-read-buffer-size",
         help_heading = "REFLECTOR CONFIGURATION"
     )]
     pub rtt_read_buffer_size: Option<usize>,
@@ -157,9 +263,205 @@ struct Opts {
     )]
     pub rtt_poll_interval: Option<humantime::Duration>,
 
-    /// Periodically log RTT metrics to stdout
+    /// The host-side RTT polling interval once polls stop returning data.
+    ///
+    /// The default value is 100ms.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(
+        long,
+        name = "rtt-idle-poll-interval",
+        help_heading = "REFLECTOR CONFIGURATION"
+    )]
+    pub rtt_idle_poll_interval: Option<humantime::Duration>,
+
+    /// How polls are paced: 'step' (the poll interval while there's data, the idle
+    /// interval otherwise), 'fixed' (always the poll interval) or 'adaptive' (poll again
+    /// right away while reads fill the read buffer, backing off to the idle interval).
+    ///
+    /// The default value is 'step'.
+    #[clap(
+        long,
+        name = "rtt-poll-policy",
+        help_heading = "REFLECTOR CONFIGURATION"
+    )]
+    pub rtt_poll_policy: Option<RttPollPolicy>,
+
+    /// Periodically log RTT metrics, and the decode and ingest throughput, to stdout
     #[clap(long, name = "metrics", help_heading = "REFLECTOR CONFIGURATION")]
     pub metrics: bool,
+
+    /// Release the probe after setup and only open it for each poll, so other
+    /// tools (e.g. 'probe-rs gdb' or a flasher) can use the same probe in between.
+    ///
+    /// Consider a longer '--rtt-poll-interval', since each poll re-attaches to the chip.
+    #[clap(long, name = "shared-probe", help_heading = "PROBE CONFIGURATION")]
+    pub shared_probe: bool,
+
+    /// Re-run the attach sequence (including any breakpoint setup) when the target resets
+    /// or the probe reports an error, instead of terminating.
+    #[clap(long, name = "reattach", help_heading = "COLLECTOR CONFIGURATION")]
+    pub reattach: bool,
+
+    /// The maximum number of re-attaches (unlimited by default).
+    #[clap(
+        long,
+        name = "reattach-attempts",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub reattach_attempts: Option<u32>,
+
+    /// The delay before re-attaching to the target.
+    ///
+    /// The default value is 500ms.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(
+        long,
+        name = "reattach-interval",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub reattach_interval: Option<humantime::Duration>,
+
+    /// End the session when no defmt data has arrived for this long, e.g. when the
+    /// firmware crashes before initializing RTT. Also bounds the wait for each
+    /// setup breakpoint.
+    ///
+    /// Accepts durations like "10s" or "1minute 2seconds".
+    #[clap(long, name = "exit-on-idle", help_heading = "COLLECTOR CONFIGURATION")]
+    pub exit_on_idle: Option<humantime::Duration>,
+
+    /// Guarantee the collector never halts, resets, sets breakpoints on or changes
+    /// the channel modes of the target. Options that would are rejected.
+    ///
+    /// For attaching to production units under test.
+    #[clap(long, name = "non-intrusive", help_heading = "COLLECTOR CONFIGURATION")]
+    pub non_intrusive: bool,
+
+    /// Halt the core when a stop trigger (--stop-on) ends the collection, preserving
+    /// the target state for inspection
+    #[clap(long, name = "halt-on-stop", help_heading = "COLLECTOR CONFIGURATION")]
+    pub halt_on_stop: bool,
+
+    /// Drain whatever is currently in the RTT buffers once, ingest it and exit.
+    ///
+    /// For post-mortem inspection of a halted or crashed target; the target
+    /// isn't reset, resumed or reconfigured.
+    #[clap(long, name = "snapshot", help_heading = "COLLECTOR CONFIGURATION")]
+    pub snapshot: bool,
+
+    /// Read the core registers and memory into a crash event when a panic or
+    /// HardFault event (or --crash-event) is observed.
+    ///
+    /// The core is briefly halted for the capture.
+    #[clap(long, name = "crash-capture", help_heading = "COLLECTOR CONFIGURATION")]
+    pub crash_capture: bool,
+
+    /// The events that trigger a crash capture, in the --stop-on syntax.
+    /// Can be provided multiple times.
+    #[clap(
+        long = "crash-event",
+        name = "crash-event",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub crash_events: Vec<StopTrigger>,
+
+    /// Bytes of stack read at the stack pointer into the crash event.
+    /// The default value is 256.
+    #[clap(
+        long,
+        name = "crash-stack-size",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub crash_stack_size: Option<u32>,
+
+    /// A memory window read into the crash event, as 'LOCATION:SIZE' where the
+    /// location is a symbol or address. Can be provided multiple times.
+    #[clap(
+        long = "crash-memory",
+        name = "crash-memory",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub crash_memory: Vec<MemoryWindow>,
+
+    /// Target memory sampled at a fixed interval (--watch-interval), as 'LOCATION:SIZE'
+    /// where the location is a symbol or address and the size is 1 to 8 bytes.
+    /// Can be provided multiple times.
+    ///
+    /// The little-endian values are ingested as events on the 'metrics' timeline,
+    /// e.g. heap high-water marks or counters the firmware doesn't log.
+    #[clap(
+        long = "watch",
+        name = "watch",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub watch: Vec<MemoryWindow>,
+
+    /// The --watch sampling interval. The default value is 1s.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds".
+    #[clap(
+        long,
+        name = "watch-interval",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub watch_interval: Option<humantime::Duration>,
+
+    /// Enable vector catch on HardFault exceptions (Arm cores). When the core halts on a
+    /// fault, a fault event with the decoded fault status registers (CFSR, HFSR, MMFAR, BFAR)
+    /// is added to the trace.
+    #[clap(long, name = "fault-catch", help_heading = "COLLECTOR CONFIGURATION")]
+    pub fault_catch: bool,
+
+    /// What to do with the core after a caught fault (--fault-catch).
+    ///
+    /// 'resume' runs the firmware's fault handler (the default), 'stop' leaves
+    /// the core halted for inspection and stops the collection.
+    #[clap(long, name = "fault-action", help_heading = "COLLECTOR CONFIGURATION")]
+    pub fault_action: Option<FaultAction>,
+
+    /// A command run on the core before attaching to RTT, for targets requiring a debug
+    /// unlock sequence or clock setup before RTT is reachable.
+    /// One of 'write=LOCATION=VALUE', 'reg=REGISTER=VALUE', 'delay=DURATION' or 'exec=COMMAND'.
+    /// Can be provided multiple times, the commands run in order.
+    #[clap(
+        long = "pre-attach",
+        name = "pre-attach",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub pre_attach: Vec<ProbeCommand>,
+
+    /// A command run on the core after attaching to RTT, before streaming, as with --pre-attach.
+    /// Can be provided multiple times, the commands run in order.
+    #[clap(
+        long = "post-attach",
+        name = "post-attach",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub post_attach: Vec<ProbeCommand>,
+
+    /// Stage a Deviant mutation, as 'MUTATOR_ID:MUTATION_ID', by writing the firmware's
+    /// DEVIANT_MUTATION_STAGED, DEVIANT_MUTATOR_ID and DEVIANT_MUTATION_ID statics
+    /// before the core runs.
+    ///
+    /// Requires '--reset' or '--attach-under-reset'.
+    #[clap(
+        long,
+        name = "staged-mutation",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub staged_mutation: Option<StagedMutation>,
+
+    /// Clear the firmware's DEVIANT_MUTATION_STAGED flag before the core runs, so a mutation
+    /// staged for a previous run (in uninitialized RAM) isn't injected again.
+    ///
+    /// Requires '--reset' or '--attach-under-reset'.
+    #[clap(
+        long,
+        name = "clear-staged-mutation",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub clear_staged_mutation: bool,
 }
 
 #[tokio::main]
@@ -181,6 +483,13 @@ async fn main() {
 async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
     let opts = Opts::parse();
 
+    if opts.print_config_template {
+        if let Some(template) = config_template(DefmtConfigEntry::RttCollector) {
+            print!("{template}");
+        }
+        return Ok(());
+    }
+
     try_init_tracing_subscriber()?;
 
     let intr = Interruptor::new();
@@ -217,15 +526,45 @@ async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
     if let Some(addr) = opts.control_block_address {
         defmt_cfg.plugin.rtt_collector.control_block_address = addr.into();
     }
-    if let Some(up_channel) = opts.up_channel {
-        defmt_cfg.plugin.rtt_collector.up_channel = up_channel;
+    if !opts.up_channel.is_empty() {
+        defmt_cfg.plugin.rtt_collector.up_channels = opts.up_channel;
+    }
+    if !opts.text_up_channel.is_empty() {
+        defmt_cfg.plugin.rtt_collector.text_up_channels = opts.text_up_channel;
+    }
+    if let Some(buffer) = opts.ring_buffer {
+        let rb = defmt_cfg
+            .plugin
+            .rtt_collector
+            .ring_buffer
+            .get_or_insert_with(Default::default);
+        rb.buffer = buffer;
+        if let Some(size) = opts.ring_buffer_size {
+            rb.size = Some(size);
+        }
+        if let Some(head) = opts.ring_buffer_head {
+            rb.head = head;
+        }
+        if let Some(tail) = opts.ring_buffer_tail {
+            rb.tail = tail;
+        }
+    }
+    if let Some(mode) = opts.channel_mode {
+        defmt_cfg.plugin.rtt_collector.channel_mode = Some(mode);
     }
     if let Some(setup_on_breakpoint) = &opts.setup_on_breakpoint {
         defmt_cfg.plugin.rtt_collector.setup_on_breakpoint = Some(setup_on_breakpoint.clone());
+        defmt_cfg.plugin.rtt_collector.setup_stages.clear();
+    }
+    if !opts.setup_stage.is_empty() {
+        defmt_cfg.plugin.rtt_collector.setup_stages = opts.setup_stage.clone();
     }
     if opts.thumb {
         defmt_cfg.plugin.rtt_collector.thumb = true;
     }
+    if let Some(arch) = opts.arch {
+        defmt_cfg.plugin.rtt_collector.arch = Some(arch);
+    }
     if let Some(ps) = &opts.probe_selector {
         defmt_cfg.plugin.rtt_collector.probe_selector = Some(ps.clone().into());
     }
@@ -247,6 +586,12 @@ async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
     if opts.attach_under_reset {
         defmt_cfg.plugin.rtt_collector.attach_under_reset = true;
     }
+    if opts.download {
+        defmt_cfg.plugin.rtt_collector.download = true;
+    }
+    if opts.verify {
+        defmt_cfg.plugin.rtt_collector.verify = true;
+    }
     if let Some(cd) = &opts.chip_description_path {
         defmt_cfg.plugin.rtt_collector.chip_description_path = Some(cd.clone());
     }
@@ -256,16 +601,84 @@ async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
     if let Some(rtt_poll_interval) = opts.rtt_poll_interval {
         defmt_cfg.plugin.rtt_collector.rtt_poll_interval = Some(rtt_poll_interval.into());
     }
+    if let Some(rtt_idle_poll_interval) = opts.rtt_idle_poll_interval {
+        defmt_cfg.plugin.rtt_collector.rtt_idle_poll_interval = Some(rtt_idle_poll_interval.into());
+    }
+    if let Some(policy) = opts.rtt_poll_policy {
+        defmt_cfg.plugin.rtt_collector.rtt_poll_policy = policy;
+    }
     if opts.metrics {
         defmt_cfg.plugin.rtt_collector.metrics = true;
     }
-
-    let chip = defmt_cfg
+    if defmt_cfg.plugin.rtt_collector.metrics && defmt_cfg.plugin.metrics_interval.is_none() {
+        // Report the decode and ingest throughput along with the RTT reads
+        defmt_cfg.plugin.metrics_interval =
+            Some(humantime::Duration::from(Metrics::WINDOW_DURATION).into());
+    }
+    if opts.shared_probe {
+        defmt_cfg.plugin.rtt_collector.shared_probe = true;
+    }
+    if opts.reattach {
+        defmt_cfg.plugin.rtt_collector.reattach = true;
+    }
+    if let Some(attempts) = opts.reattach_attempts {
+        defmt_cfg.plugin.rtt_collector.reattach_attempts = Some(attempts);
+    }
+    if let Some(interval) = opts.reattach_interval {
+        defmt_cfg.plugin.rtt_collector.reattach_interval = Some(interval.into());
+    }
+    if let Some(idle) = opts.exit_on_idle {
+        defmt_cfg.plugin.rtt_collector.exit_on_idle = Some(idle.into());
+    }
+    if opts.non_intrusive {
+        defmt_cfg.plugin.rtt_collector.non_intrusive = true;
+    }
+    if opts.halt_on_stop {
+        defmt_cfg.plugin.rtt_collector.halt_on_stop = true;
+    }
+    if opts.snapshot {
+        defmt_cfg.plugin.rtt_collector.snapshot = true;
+    }
+    if opts.crash_capture {
+        defmt_cfg.plugin.rtt_collector.crash_capture = true;
+    }
+    if !opts.crash_events.is_empty() {
+        defmt_cfg.plugin.rtt_collector.crash_events = opts.crash_events;
+    }
+    if let Some(size) = opts.crash_stack_size {
+        defmt_cfg.plugin.rtt_collector.crash_stack_size = Some(size);
+    }
+    defmt_cfg
         .plugin
         .rtt_collector
-        .chip
-        .clone()
-        .ok_or(Error::MissingChip)?;
+        .crash_memory
+        .extend(opts.crash_memory);
+    defmt_cfg.plugin.rtt_collector.watch.extend(opts.watch);
+    if let Some(interval) = opts.watch_interval {
+        defmt_cfg.plugin.rtt_collector.watch_interval = Some(interval.into());
+    }
+    if opts.fault_catch {
+        defmt_cfg.plugin.rtt_collector.fault_catch = true;
+    }
+    if let Some(action) = opts.fault_action {
+        defmt_cfg.plugin.rtt_collector.fault_action = action;
+    }
+    defmt_cfg
+        .plugin
+        .rtt_collector
+        .pre_attach
+        .extend(opts.pre_attach);
+    defmt_cfg
+        .plugin
+        .rtt_collector
+        .post_attach
+        .extend(opts.post_attach);
+    if let Some(mutation) = opts.staged_mutation {
+        defmt_cfg.plugin.rtt_collector.staged_mutation = Some(mutation);
+    }
+    if opts.clear_staged_mutation {
+        defmt_cfg.plugin.rtt_collector.clear_staged_mutation = true;
+    }
 
     if let Some(chip_desc) = &defmt_cfg.plugin.rtt_collector.chip_description_path {
         debug!(path = %chip_desc.display(), "Adding custom chip description");
@@ -273,33 +686,159 @@ async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
         probe_rs::config::add_target_from_yaml(f)?;
     }
 
-    let lister = Lister::new();
-    let mut probe = if let Some(probe_selector) = &defmt_cfg.plugin.rtt_collector.probe_selector {
-        debug!(probe_selector = %probe_selector.0, "Opening selected probe");
-        lister.open(probe_selector.0.clone())?
-    } else {
-        let probes = lister.list_all();
-        debug!(probes = probes.len(), "Opening first available probe");
-        if probes.is_empty() {
-            return Err(Error::NoProbesAvailable.into());
-        }
-        probes[0].open(&lister)?
+    check_mode_conflicts(&defmt_cfg.plugin.rtt_collector)?;
+    let rtt_cfg = &defmt_cfg.plugin.rtt_collector;
+    let stages_mutation = rtt_cfg.staged_mutation.is_some() || rtt_cfg.clear_staged_mutation;
+    if stages_mutation && !(rtt_cfg.reset || rtt_cfg.attach_under_reset) {
+        return Err(Error::MutationStagingWithoutReset.into());
+    }
+
+    if !defmt_cfg.plugin.rtt_collector.targets.is_empty() {
+        return supervise(defmt_cfg, intr).await;
+    }
+
+    if defmt_cfg.plugin.rtt_collector.reattach {
+        return Ok(collect_with_reattach(defmt_cfg, intr).await?);
+    }
+
+    Ok(collect(defmt_cfg, intr).await?)
+}
+
+/// Snapshot and non-intrusive modes leave the target as it is, reject anything that
+/// would change it (or in snapshot mode, keep the collector running)
+fn check_mode_conflicts(rtt_cfg: &RttCollectorConfig) -> Result<(), Error> {
+    let setup_stages = !rtt_cfg.selected_setup_stages().is_empty()
+        || rtt_cfg
+            .targets
+            .iter()
+            .any(|t| t.setup_on_breakpoint.is_some() || !t.setup_stages.is_empty());
+    let intrusive = [
+        ("reset", rtt_cfg.reset),
+        ("attach-under-reset", rtt_cfg.attach_under_reset),
+        ("download", rtt_cfg.download),
+        ("setup-on-breakpoint", setup_stages),
+        ("channel-mode", rtt_cfg.channel_mode.is_some()),
+        ("halt-on-stop", rtt_cfg.halt_on_stop),
+        ("crash-capture", rtt_cfg.crash_capture),
+        ("fault-catch", rtt_cfg.fault_catch),
+        ("pre-attach", !rtt_cfg.pre_attach.is_empty()),
+        ("post-attach", !rtt_cfg.post_attach.is_empty()),
+    ];
+    let continuous = [
+        ("reattach", rtt_cfg.reattach),
+        ("targets", !rtt_cfg.targets.is_empty()),
+    ];
+
+    let conflict = |mode: &'static str, options: &[(&'static str, bool)]| {
+        let option = options.iter().find(|(_, conflict)| *conflict);
+        option.map_or(Ok(()), |&(option, _)| {
+            Err(Error::ModeConflict(mode, option))
+        })
     };
+    if rtt_cfg.non_intrusive {
+        conflict("non-intrusive", &intrusive)?;
+    }
+    if rtt_cfg.snapshot {
+        conflict("snapshot", &intrusive)?;
+        conflict("snapshot", &continuous)?;
+    }
+    Ok(())
+}
 
-    debug!(protocol = %defmt_cfg.plugin.rtt_collector.protocol, speed = defmt_cfg.plugin.rtt_collector.speed, "Configuring probe");
-    probe.select_protocol(defmt_cfg.plugin.rtt_collector.protocol)?;
-    probe.set_speed(defmt_cfg.plugin.rtt_collector.speed)?;
+/// Like [`collect`], re-running the attach sequence after the target resets
+/// (e.g. a watchdog reset) or the probe reports an error
+async fn collect_with_reattach(mut defmt_cfg: DefmtConfig, intr: Interruptor) -> Result<(), Error> {
+    const DEFAULT_REATTACH_INTERVAL: Duration = Duration::from_millis(500);
 
-    debug!(
-        chip = chip,
-        core = defmt_cfg.plugin.rtt_collector.core,
-        "Attaching to chip"
-    );
+    let rtt_cfg = &defmt_cfg.plugin.rtt_collector;
+    let max_attempts = rtt_cfg.reattach_attempts;
+    let reattach_interval = rtt_cfg
+        .reattach_interval
+        .map(|d| d.0.into())
+        .unwrap_or(DEFAULT_REATTACH_INTERVAL);
 
-    let mut session = if defmt_cfg.plugin.rtt_collector.attach_under_reset {
-        probe.attach_under_reset(chip, Permissions::default())?
-    } else {
-        probe.attach(chip, Permissions::default())?
+    // Re-attaches continue the same run, unless segmenting runs by boot
+    defmt_cfg
+        .plugin
+        .run_id
+        .get_or_insert_with(|| Uuid::new_v4().to_string());
+    defmt_cfg
+        .plugin
+        .clock_id
+        .get_or_insert_with(|| Uuid::new_v4().to_string());
+
+    let mut reattaches: u32 = 0;
+    loop {
+        // Each attempt gets its own interruptor, since a failed attempt stops its readers
+        let attempt_intr = Interruptor::new();
+        let forward_intr = {
+            let intr = intr.clone();
+            let attempt_intr = attempt_intr.clone();
+            tokio::spawn(async move {
+                while !intr.is_set() {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                attempt_intr.set();
+            })
+        };
+        let res = collect(defmt_cfg.clone(), attempt_intr).await;
+        forward_intr.abort();
+        // Re-attaches run the firmware that's already flashed
+        defmt_cfg.plugin.rtt_collector.download = false;
+
+        match res {
+            Ok(()) => return Ok(()),
+            Err(_) if intr.is_set() => return Ok(()),
+            Err(e) if !e.is_reattachable() => return Err(e),
+            Err(e) => {
+                if max_attempts.map(|max| reattaches >= max).unwrap_or(false) {
+                    error!(reattaches, "Reached the maximum number of re-attaches");
+                    return Err(e);
+                }
+                reattaches += 1;
+                warn!(error = %e, reattaches, "Lost the target, re-attaching");
+                if defmt_cfg.plugin.segment_runs {
+                    let boot_index = defmt_cfg.plugin.boot_index.unwrap_or(0) + 1;
+                    info!(boot_index, "Starting a new run");
+                    defmt_cfg.plugin.boot_index = Some(boot_index);
+                    defmt_cfg.plugin.run_id = Some(Uuid::new_v4().to_string());
+                    defmt_cfg.plugin.clock_id = Some(Uuid::new_v4().to_string());
+                }
+            }
+        }
+
+        let start = Instant::now();
+        while !intr.is_set() && start.elapsed() < reattach_interval {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        if intr.is_set() {
+            return Ok(());
+        }
+    }
+}
+
+/// Attaches to the target and streams its RTT up channels until shutdown or an error occurs
+async fn collect(mut defmt_cfg: DefmtConfig, intr: Interruptor) -> Result<(), Error> {
+    let chip = defmt_cfg
+        .plugin
+        .rtt_collector
+        .chip
+        .clone()
+        .ok_or(Error::MissingChip)?;
+
+    let mut session = open_session(
+        &defmt_cfg.plugin.rtt_collector,
+        &chip,
+        defmt_cfg.plugin.rtt_collector.attach_under_reset,
+    )?;
+
+    if defmt_cfg.plugin.rtt_collector.download {
+        download(&mut session, &defmt_cfg)?;
+    }
+
+    let ring_buffer = match defmt_cfg.plugin.rtt_collector.ring_buffer.as_ref() {
+        Some(rb_cfg) => Some(RingBuffer::locate(rb_cfg, &defmt_cfg)?),
+        None => None,
     };
 
     let rtt_scan_regions = session.target().rtt_scan_regions.clone();
@@ -325,189 +864,1045 @@ async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut core = session.core(defmt_cfg.plugin.rtt_collector.core)?;
 
+    let arch = target_arch(&core, &defmt_cfg.plugin.rtt_collector);
+    let thumb = match arch {
+        TargetArch::Arm => defmt_cfg.plugin.rtt_collector.thumb,
+        _ if defmt_cfg.plugin.rtt_collector.thumb => {
+            warn!(%arch, "Thumb mode only applies to Arm cores, ignoring it");
+            false
+        }
+        _ => false,
+    };
+
     if defmt_cfg.plugin.rtt_collector.reset {
         debug!("Reset and halt core");
         core.reset_and_halt(Duration::from_millis(100))?;
     }
 
-    // Disable any previous vector catching (i.e. user just ran probe-rs run or a debugger)
-    core.disable_vector_catch(VectorCatchCondition::All)?;
-    core.clear_all_hw_breakpoints()?;
+    // Leave the debug state of a target we mustn't disturb as it is
+    let hands_off =
+        defmt_cfg.plugin.rtt_collector.non_intrusive || defmt_cfg.plugin.rtt_collector.snapshot;
+    if !hands_off {
+        // Disable any previous vector catching (i.e. user just ran probe-rs run or a debugger).
+        // RISC-V and Xtensa halt on exceptions through ebreak/debug exception settings that
+        // probe-rs leaves disabled, there's no vector catch to undo.
+        if arch == TargetArch::Arm {
+            core.disable_vector_catch(VectorCatchCondition::All)?;
+        }
+        core.clear_all_hw_breakpoints()?;
+    }
 
-    if let Some(bp_sym_or_addr) = &defmt_cfg.plugin.rtt_collector.setup_on_breakpoint {
+    let stages = defmt_cfg.plugin.rtt_collector.selected_setup_stages();
+    let mut stage_bp_addr = None;
+    if let Some(stage) = stages.first() {
         let num_bp = core.available_breakpoint_units()?;
-
-        let bp_addr = if let Some(bp_addr) = bp_sym_or_addr
-            .parse::<u64>()
-            .ok()
-            .or(u64::from_str_radix(bp_sym_or_addr.trim_start_matches("0x"), 16).ok())
-        {
-            bp_addr
-        } else {
-            let mut file = fs::File::open(
-                defmt_cfg
-                    .plugin
-                    .elf_file
-                    .as_ref()
-                    .ok_or(modality_defmt_plugin::Error::MissingElfFile)?,
-            )?;
-            let bp_addr = get_symbol(&mut file, bp_sym_or_addr)
-                .ok_or_else(|| Error::ElfSymbol(bp_sym_or_addr.to_owned()))?;
-            if defmt_cfg.plugin.rtt_collector.thumb {
-                bp_addr & !1
-            } else {
-                bp_addr
-            }
-        };
-
+        let bp_addr = resolve_address(&stage.breakpoint, &defmt_cfg, thumb)?;
         debug!(
             available_breakpoints = num_bp,
-            symbol_or_addr = bp_sym_or_addr,
+            symbol_or_addr = stage.breakpoint,
             addr = format_args!("0x{:X}", bp_addr),
             "Setting breakpoint to do RTT channel setup"
         );
         core.set_hw_breakpoint(bp_addr)?;
+        stage_bp_addr = Some(bp_addr);
     }
 
-    let mut rtt = match defmt_cfg.plugin.rtt_collector.attach_timeout {
-        Some(to) if !to.0.is_zero() => {
-            attach_retry_loop(&mut core, &memory_map, &rtt_scan_region, to.0)?
-        }
-        _ => {
-            debug!("Attaching to RTT");
-            Rtt::attach_region(&mut core, &memory_map, &rtt_scan_region)?
+    // The ring buffer replaces RTT entirely.
+    // A stage can defer the attach until the firmware has initialized RTT.
+    let mut up_channels = Vec::new();
+    if !stages.iter().any(|s| s.start) {
+        run_probe_commands(
+            &mut core,
+            "pre-attach",
+            &defmt_cfg.plugin.rtt_collector.pre_attach,
+            &defmt_cfg,
+        )?;
+        if ring_buffer.is_none() {
+            up_channels = open_up_channels(&mut core, &defmt_cfg, &memory_map, &rtt_scan_region)?;
+
+            // With a setup breakpoint, the mode is set once the firmware has initialized RTT
+            if let (Some(mode), true) = (
+                defmt_cfg.plugin.rtt_collector.channel_mode,
+                stages.is_empty(),
+            ) {
+                set_channel_mode(&mut core, &up_channels, mode.into())?;
+            }
         }
-    };
-
-    let up_channel = rtt
-        .up_channels()
-        .take(defmt_cfg.plugin.rtt_collector.up_channel)
-        .ok_or_else(|| Error::UpChannelInvalid(defmt_cfg.plugin.rtt_collector.up_channel))?;
-    let up_channel_mode = up_channel.mode(&mut core)?;
-    let up_channel_name = up_channel.name().unwrap_or("NA");
-    debug!(channel = up_channel.number(), name = up_channel_name, mode = ?up_channel_mode, buffer_size = up_channel.buffer_size(), "Opened up channel");
+        run_probe_commands(
+            &mut core,
+            "post-attach",
+            &defmt_cfg.plugin.rtt_collector.post_attach,
+            &defmt_cfg,
+        )?;
+    }
 
     if defmt_cfg.plugin.rtt_collector.reset || defmt_cfg.plugin.rtt_collector.attach_under_reset {
+        stage_mutation(&mut core, &defmt_cfg)?;
+
         let sp_reg = core.stack_pointer();
         let sp: RegisterValue = core.read_core_reg(sp_reg.id())?;
         let pc_reg = core.program_counter();
         let pc: RegisterValue = core.read_core_reg(pc_reg.id())?;
-        debug!(pc = %pc, sp = %sp, "Run core");
+        debug!(
+            pc = %pc,
+            sp = %sp,
+            pc_register = pc_reg.name(),
+            sp_register = sp_reg.name(),
+            "Run core"
+        );
         core.run()?;
     }
 
-    if defmt_cfg.plugin.rtt_collector.setup_on_breakpoint.is_some() {
-        debug!("Waiting for breakpoint");
-        'bp_loop: loop {
-            if intr.is_set() {
-                break;
-            }
-
-            match core.status()? {
-                CoreStatus::Running => (),
-                CoreStatus::Halted(halt_reason) => match halt_reason {
-                    HaltReason::Breakpoint(_) => break 'bp_loop,
-                    _ => {
-                        warn!(reason = ?halt_reason, "Unexpected halt reason");
-                        break 'bp_loop;
-                    }
-                },
-                state => {
-                    warn!(state = ?state, "Core is in an unexpected state");
-                    break 'bp_loop;
-                }
-            }
-
-            std::thread::sleep(Duration::from_millis(100));
+    for (idx, stage) in stages.iter().enumerate() {
+        debug!(
+            stage = idx,
+            breakpoint = stage.breakpoint,
+            "Waiting for breakpoint"
+        );
+        let idle_timeout = defmt_cfg
+            .plugin
+            .rtt_collector
+            .exit_on_idle
+            .map(|d| d.0.into());
+        if !wait_for_breakpoint(&mut core, &intr, idle_timeout)? {
+            break;
+        }
+        if let Some(bp_addr) = stage_bp_addr.take() {
+            core.clear_hw_breakpoint(bp_addr)?;
+        }
+
+        for write in stage.write.iter() {
+            let addr = resolve_address(&write.location, &defmt_cfg, false)?;
+            debug!(
+                location = write.location,
+                addr = format_args!("0x{:X}", addr),
+                value = write.value,
+                "Write memory"
+            );
+            core.write_word_32(addr, write.value)?;
+        }
+
+        if stage.start {
+            run_probe_commands(
+                &mut core,
+                "pre-attach",
+                &defmt_cfg.plugin.rtt_collector.pre_attach,
+                &defmt_cfg,
+            )?;
+            if ring_buffer.is_none() {
+                up_channels =
+                    open_up_channels(&mut core, &defmt_cfg, &memory_map, &rtt_scan_region)?;
+            }
+            run_probe_commands(
+                &mut core,
+                "post-attach",
+                &defmt_cfg.plugin.rtt_collector.post_attach,
+                &defmt_cfg,
+            )?;
+        }
+
+        // The last stage has the channels ready for streaming
+        let is_last = idx + 1 == stages.len();
+        let mode = stage
+            .channel_mode
+            .map(ChannelMode::from)
+            .or(is_last.then(|| {
+                defmt_cfg
+                    .plugin
+                    .rtt_collector
+                    .channel_mode
+                    .map(ChannelMode::from)
+                    .unwrap_or(ChannelMode::BlockIfFull)
+            }));
+        if let Some(mode) = mode {
+            if up_channels.is_empty() && ring_buffer.is_none() {
+                warn!(
+                    stage = idx,
+                    "RTT isn't attached yet, not setting the channel mode"
+                );
+            }
+            set_channel_mode(&mut core, &up_channels, mode)?;
         }
 
-        let mode = ChannelMode::BlockIfFull;
-        debug!(mode = ?mode, "Set channel mode");
-        up_channel.set_mode(&mut core, mode)?;
+        if let Some(next) = stages.get(idx + 1) {
+            let bp_addr = resolve_address(&next.breakpoint, &defmt_cfg, thumb)?;
+            debug!(
+                symbol_or_addr = next.breakpoint,
+                addr = format_args!("0x{:X}", bp_addr),
+                "Setting breakpoint for the next setup stage"
+            );
+            core.set_hw_breakpoint(bp_addr)?;
+            stage_bp_addr = Some(bp_addr);
+        }
 
-        debug!("Run core after breakpoint setup");
+        debug!(stage = idx, "Run core after breakpoint setup");
         core.run()?;
     }
 
+    // Enabled once the setup stages are done, their breakpoint waits would take a caught fault
+    // for the breakpoint
+    if defmt_cfg.plugin.rtt_collector.fault_catch {
+        if arch == TargetArch::Arm {
+            debug!("Enable fault vector catch");
+            core.enable_vector_catch(VectorCatchCondition::HardFault)?;
+        } else {
+            warn!(%arch, "Fault vector catch only applies to Arm cores, ignoring it");
+            defmt_cfg.plugin.rtt_collector.fault_catch = false;
+        }
+    }
+
     // Only hold onto the Core when we need to lock the debug probe driver (before each read/write)
     std::mem::drop(core);
 
-    let session = Arc::new(Mutex::new(session));
-    let up_channel = Arc::new(up_channel);
-    let session_clone = session.clone();
-    let up_channel_clone = up_channel.clone();
-    let defmt_cfg_clone = defmt_cfg.clone();
-    let mut join_handle: tokio::task::JoinHandle<Result<(), Error>> = tokio::spawn(async move {
-        let poll_interval = defmt_cfg_clone
+    let watch = if defmt_cfg.plugin.rtt_collector.watch.is_empty()
+        || defmt_cfg.plugin.rtt_collector.snapshot
+    {
+        None
+    } else {
+        Some(WatchSampler::new(&defmt_cfg)?)
+    };
+
+    // Channels share the run and clock, but each gets its own decoder and timelines
+    if up_channels.len() > 1 || watch.is_some() {
+        defmt_cfg
             .plugin
-            .rtt_collector
-            .rtt_poll_interval
-            .map(|d| d.0.into())
-            .unwrap_or(DefmtRttReader::DEFAULT_POLL_INTERVAL);
-        let metrics = if defmt_cfg_clone.plugin.rtt_collector.metrics {
-            Some(Metrics::new(
-                defmt_cfg_clone.plugin.rtt_collector.rtt_read_buffer_size,
-            ))
-        } else {
-            None
+            .run_id
+            .get_or_insert_with(|| Uuid::new_v4().to_string());
+        defmt_cfg
+            .plugin
+            .clock_id
+            .get_or_insert_with(|| Uuid::new_v4().to_string());
+    }
+
+    let probe = ProbeAccess::new(session, &defmt_cfg.plugin.rtt_collector, chip);
+    let mut readers = tokio::task::JoinSet::new();
+    for (idx, (selected, up_channel)) in up_channels.iter().enumerate() {
+        let mut channel_cfg = defmt_cfg.clone();
+        // The first reader reports the faults
+        if idx != 0 {
+            channel_cfg.plugin.rtt_collector.fault_catch = false;
+        }
+        if let Some(elf_file) = selected.elf_file.as_ref() {
+            channel_cfg.plugin.elf_file = Some(elf_file.clone());
+            channel_cfg.plugin.debug_file = None;
+        }
+        if let Some(core_id) = selected.core_id {
+            channel_cfg.plugin.core_id = Some(core_id);
+        }
+        if selected.format == RttChannelFormat::Text {
+            // Text channels get a single timeline, named after the channel
+            channel_cfg.plugin.init_task_name = Some(
+                up_channel
+                    .name()
+                    .map(|n| n.to_owned())
+                    .unwrap_or_else(|| format!("text:ch{}", up_channel.number())),
+            );
+        }
+        if up_channels.len() > 1 || ring_buffer.is_some() {
+            let source = format!("ch{}", up_channel.number());
+            let plugin = &mut channel_cfg.plugin;
+            if let Some(path) = defmt_cfg.plugin.save_raw.as_ref() {
+                plugin.save_raw = Some(source_capture_path(path, &source));
+            }
+            if let Some(path) = defmt_cfg.plugin.summary_file.as_ref() {
+                plugin.summary_file = Some(source_capture_path(path, &source));
+            }
+        }
+        if defmt_cfg.plugin.unknown_context_per_source && up_channels.len() > 1 {
+            channel_cfg.plugin.unknown_context_name = Some(format!(
+                "{}:ch{}",
+                defmt_cfg.plugin.unknown_context_name(),
+                up_channel.number()
+            ));
+        }
+        let channel_attrs = &mut channel_cfg
+            .ingest
+            .timeline_attributes
+            .additional_timeline_attributes;
+        channel_attrs.push(AttrKeyEqValuePair(
+            AttrKey::new(UP_CHANNEL_ATTR_KEY.to_owned()),
+            (up_channel.number() as i64).into(),
+        ));
+        if let Some(name) = up_channel.name() {
+            channel_attrs.push(AttrKeyEqValuePair(
+                AttrKey::new(UP_CHANNEL_NAME_ATTR_KEY.to_owned()),
+                name.to_owned().into(),
+            ));
+        }
+
+        let channel_intr = intr.clone();
+        let probe = probe.clone();
+        let source = ReadSource::Rtt(up_channel.clone());
+        let format = selected.format;
+        let rt = tokio::runtime::Handle::current();
+        readers.spawn_blocking(move || {
+            let channel = source.channel();
+            let res = run_reader(channel_intr, probe, source, format, channel_cfg, rt);
+            (channel, res)
+        });
+    }
+    if let Some(ring_buffer) = ring_buffer {
+        let mut rb_cfg = defmt_cfg.clone();
+        if let Some(path) = defmt_cfg.plugin.save_raw.as_ref() {
+            rb_cfg.plugin.save_raw = Some(source_capture_path(path, &ring_buffer.name));
+        }
+        if let Some(path) = defmt_cfg.plugin.summary_file.as_ref() {
+            rb_cfg.plugin.summary_file = Some(source_capture_path(path, &ring_buffer.name));
+        }
+        rb_cfg
+            .ingest
+            .timeline_attributes
+            .additional_timeline_attributes
+            .push(AttrKeyEqValuePair(
+                AttrKey::new(RING_BUFFER_ATTR_KEY.to_owned()),
+                ring_buffer.name.clone().into(),
+            ));
+        let rb_intr = intr.clone();
+        let probe = probe.clone();
+        let source = ReadSource::RingBuffer(ring_buffer);
+        let rt = tokio::runtime::Handle::current();
+        readers.spawn_blocking(move || {
+            let channel = source.channel();
+            let res = run_reader(rb_intr, probe, source, RttChannelFormat::Defmt, rb_cfg, rt);
+            (channel, res)
+        });
+    }
+
+    // Sampling runs until the readers are done
+    let watch_intr = Interruptor::new();
+    let watcher = watch.map(|watch| {
+        let watch_cfg = defmt_cfg.clone();
+        let watch_intr = watch_intr.clone();
+        let probe = probe.clone();
+        let rt = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || run_watch(watch_intr, probe, watch, watch_cfg, rt))
+    });
+
+    let mut result = Ok(());
+    let mut stopped = false;
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                debug!("User signaled shutdown");
+                // Wait for any on-going transfer to complete
+                probe.while_idle(|| {
+                    std::thread::sleep(Duration::from_millis(100));
+                    readers.abort_all();
+                });
+                break;
+            }
+            res = readers.join_next() => {
+                let Some(res) = res else {
+                    break;
+                };
+                match res? {
+                    (channel, Ok(ReadOutcome::Finished)) => debug!(channel, "Reader finished"),
+                    (channel, Ok(ReadOutcome::Stopped)) => {
+                        debug!(channel, "Reader stopped on a trigger");
+                        // Stop the other channels too
+                        stopped = true;
+                        intr.set();
+                    }
+                    (channel, Err(e)) => {
+                        error!(channel, error = %e, "Encountered and error during streaming");
+                        // Stop the other channels too
+                        intr.set();
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }
         };
-        let mut stream = DefmtRttReader::new(
-            intr.clone(),
-            session_clone,
-            up_channel_clone,
-            defmt_cfg_clone.plugin.rtt_collector.core,
-            poll_interval,
-            defmt_cfg_clone.plugin.rtt_collector.rtt_read_buffer_size,
-            metrics,
-        )?;
-        defmt_reader::run(&mut stream, defmt_cfg_clone, intr).await?;
+    }
+
+    if let Some(watcher) = watcher {
+        watch_intr.set();
+        if let Err(e) = watcher.await? {
+            warn!(error = %e, "Encountered an error during watch sampling");
+        }
+    }
+
+    if defmt_cfg.plugin.rtt_collector.snapshot {
+        info!("Snapshot complete");
+        return result;
+    }
+    if defmt_cfg.plugin.rtt_collector.non_intrusive {
+        return result;
+    }
+    if stopped && defmt_cfg.plugin.rtt_collector.halt_on_stop {
+        let halted = probe.try_with_core(defmt_cfg.plugin.rtt_collector.core, |core| {
+            core.halt(Duration::from_millis(100))?;
+            Ok(())
+        })?;
+        if halted.is_some() {
+            info!("Halted the core to preserve the target state");
+        } else {
+            warn!("The shared probe is in use, the core was not halted");
+        }
+        return result;
+    }
+
+    let mode = ChannelMode::NoBlockTrim;
+    let restored = probe.try_with_core(defmt_cfg.plugin.rtt_collector.core, |core| {
+        for (_, up_channel) in up_channels.iter() {
+            debug!(channel = up_channel.number(), mode = ?mode, "Set channel mode");
+            up_channel.set_mode(core, mode)?;
+        }
+        if defmt_cfg.plugin.rtt_collector.fault_catch {
+            core.disable_vector_catch(VectorCatchCondition::HardFault)?;
+        }
         Ok(())
-    });
+    })?;
+    if restored.is_none() {
+        warn!("The shared probe is in use, leaving the RTT channel modes as they are");
+    }
+
+    result
+}
+
+/// Firmware statics the Deviant mutation staging is written to, read at startup
+const DEVIANT_MUTATION_STAGED_SYMBOL: &str = "DEVIANT_MUTATION_STAGED";
+const DEVIANT_MUTATOR_ID_SYMBOL: &str = "DEVIANT_MUTATOR_ID";
+const DEVIANT_MUTATION_ID_SYMBOL: &str = "DEVIANT_MUTATION_ID";
+
+/// Writes (or clears) the staged Deviant mutation while the core is halted at reset,
+/// like the Renode `mc_write_staged_mutation` command does
+fn stage_mutation(core: &mut Core, defmt_cfg: &DefmtConfig) -> Result<(), Error> {
+    let rtt_cfg = &defmt_cfg.plugin.rtt_collector;
+    if let Some(mutation) = rtt_cfg.staged_mutation {
+        debug!(
+            mutator_id = %mutation.mutator_id,
+            mutation_id = %mutation.mutation_id,
+            "Stage mutation"
+        );
+        let mutator_id_addr = resolve_address(DEVIANT_MUTATOR_ID_SYMBOL, defmt_cfg, false)?;
+        core.write_8(mutator_id_addr, mutation.mutator_id.as_bytes())?;
+        let mutation_id_addr = resolve_address(DEVIANT_MUTATION_ID_SYMBOL, defmt_cfg, false)?;
+        core.write_8(mutation_id_addr, mutation.mutation_id.as_bytes())?;
+        let staged_addr = resolve_address(DEVIANT_MUTATION_STAGED_SYMBOL, defmt_cfg, false)?;
+        core.write_word_32(staged_addr, 1)?;
+    } else if rtt_cfg.clear_staged_mutation {
+        debug!("Clear staged mutation");
+        let staged_addr = resolve_address(DEVIANT_MUTATION_STAGED_SYMBOL, defmt_cfg, false)?;
+        core.write_word_32(staged_addr, 0)?;
+    }
+    Ok(())
+}
+
+/// Runs the commands of the pre-attach or post-attach hook on the core
+fn run_probe_commands(
+    core: &mut Core,
+    hook: &'static str,
+    commands: &[ProbeCommand],
+    defmt_cfg: &DefmtConfig,
+) -> Result<(), Error> {
+    for command in commands.iter() {
+        debug!(hook, ?command, "Run probe command");
+        match command {
+            ProbeCommand::Write(write) => {
+                let addr = resolve_address(&write.location, defmt_cfg, false)?;
+                core.write_word_32(addr, write.value)?;
+            }
+            ProbeCommand::WriteRegister { register, value } => {
+                let id = core
+                    .registers()
+                    .all_registers()
+                    .find(|reg| reg.name().eq_ignore_ascii_case(register))
+                    .map(|reg| reg.id())
+                    .ok_or_else(|| Error::UnknownRegister(register.clone()))?;
+                match u32::try_from(*value) {
+                    Ok(v) => core.write_core_reg(id, v)?,
+                    Err(_) => core.write_core_reg(id, *value)?,
+                }
+            }
+            ProbeCommand::Delay(delay) => std::thread::sleep(delay.0.into()),
+            ProbeCommand::Exec(cmd) => {
+                let status = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(cmd)
+                    .status()?;
+                if !status.success() {
+                    return Err(Error::HookCommand(hook, cmd.clone(), status.to_string()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The configured architecture, or the one probe-rs reports for the core
+fn target_arch(core: &Core, rtt_cfg: &RttCollectorConfig) -> TargetArch {
+    let detected = TargetArch::from(core.architecture());
+    match rtt_cfg.arch {
+        Some(arch) if arch != detected => {
+            warn!(%arch, %detected, "The configured architecture doesn't match the chip's core");
+            arch
+        }
+        Some(arch) => arch,
+        None => {
+            debug!(arch = %detected, "Detected core architecture");
+            detected
+        }
+    }
+}
+
+/// Attaches to RTT and opens the selected up channels
+fn open_up_channels(
+    core: &mut Core,
+    defmt_cfg: &DefmtConfig,
+    memory_map: &[MemoryRegion],
+    rtt_scan_region: &ScanRegion,
+) -> Result<Vec<(RttUpChannel, Arc<UpChannel>)>, Error> {
+    let mut rtt = match defmt_cfg.plugin.rtt_collector.attach_timeout {
+        Some(to) if !to.0.is_zero() => attach_retry_loop(core, memory_map, rtt_scan_region, to.0)?,
+        _ => {
+            debug!("Attaching to RTT");
+            Rtt::attach_region(core, memory_map, rtt_scan_region)?
+        }
+    };
+
+    let mut up_channels = Vec::new();
+    for selected in defmt_cfg.plugin.rtt_collector.selected_up_channels() {
+        // Firmware may reorder the channels, names are resolved at attach time
+        let number = match selected.name.as_deref() {
+            Some(name) => {
+                let number = rtt
+                    .up_channels()
+                    .iter()
+                    .find(|ch| ch.name() == Some(name))
+                    .map(|ch| ch.number())
+                    .ok_or_else(|| {
+                        let available = rtt
+                            .up_channels()
+                            .iter()
+                            .map(|ch| ch.name().unwrap_or("NA").to_owned())
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        Error::UpChannelName(name.to_owned(), available)
+                    })?;
+                debug!(name, channel = number, "Resolved up channel name");
+                number
+            }
+            None => selected.channel,
+        };
+        let up_channel = rtt
+            .up_channels()
+            .take(number)
+            .ok_or(Error::UpChannelInvalid(number))?;
+        let up_channel_mode = up_channel.mode(core)?;
+        let up_channel_name = up_channel.name().unwrap_or("NA");
+        debug!(channel = up_channel.number(), name = up_channel_name, mode = ?up_channel_mode, buffer_size = up_channel.buffer_size(), "Opened up channel");
+        up_channels.push((selected, Arc::new(up_channel)));
+    }
+    Ok(up_channels)
+}
+
+fn set_channel_mode(
+    core: &mut Core,
+    up_channels: &[(RttUpChannel, Arc<UpChannel>)],
+    mode: ChannelMode,
+) -> Result<(), Error> {
+    for (_, up_channel) in up_channels.iter() {
+        debug!(channel = up_channel.number(), mode = ?mode, "Set channel mode");
+        up_channel.set_mode(core, mode)?;
+    }
+    Ok(())
+}
+
+/// Waits for the core to halt on a breakpoint, returns false when interrupted.
+/// Giving up after the idle timeout ends the session.
+fn wait_for_breakpoint(
+    core: &mut Core,
+    intr: &Interruptor,
+    idle_timeout: Option<Duration>,
+) -> Result<bool, Error> {
+    let start = Instant::now();
+    loop {
+        if intr.is_set() {
+            return Ok(false);
+        }
+        if let Some(timeout) = idle_timeout.filter(|t| start.elapsed() >= *t) {
+            warn!(
+                timeout = %humantime::format_duration(timeout),
+                "The breakpoint wasn't hit before the idle timeout, ending the session"
+            );
+            intr.set();
+            return Ok(false);
+        }
 
-    tokio::select! {
-        _ = tokio::signal::ctrl_c() => {
-            debug!("User signaled shutdown");
-            // Wait for any on-going transfer to complete
-            let _session = session.lock().unwrap();
-            std::thread::sleep(Duration::from_millis(100));
-            join_handle.abort();
-        }
-        res = &mut join_handle => {
-            match res? {
-                Ok(_) => {},
-                Err(e) => {
-                    error!(error = %e, "Encountered and error during streaming");
-                    return Err(e.into())
+        match core.status()? {
+            CoreStatus::Running => (),
+            CoreStatus::Halted(halt_reason) => match halt_reason {
+                HaltReason::Breakpoint(_) => return Ok(true),
+                _ => {
+                    warn!(reason = ?halt_reason, "Unexpected halt reason");
+                    return Ok(true);
                 }
+            },
+            state => {
+                warn!(state = ?state, "Core is in an unexpected state");
+                return Ok(true);
             }
         }
+
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Flashes the ELF file, then resets the core so it runs the new firmware
+/// (unless it's reset and halted for the attach sequence later on)
+fn download(session: &mut Session, defmt_cfg: &DefmtConfig) -> Result<(), Error> {
+    let elf_file = defmt_cfg
+        .plugin
+        .elf_file
+        .as_ref()
+        .ok_or(modality_defmt_plugin::Error::MissingElfFile)?;
+    let rtt_cfg = &defmt_cfg.plugin.rtt_collector;
+
+    info!(elf_file = %elf_file.display(), "Downloading firmware");
+    let mut options = DownloadOptions::default();
+    options.verify = rtt_cfg.verify;
+    download_file_with_options(session, elf_file, Format::Elf, options)?;
+
+    if !rtt_cfg.reset {
+        debug!("Reset core after download");
+        session.core(rtt_cfg.core)?.reset()?;
+    }
+    Ok(())
+}
+
+/// Opens the configured probe and attaches to the chip
+fn open_session(
+    rtt_cfg: &RttCollectorConfig,
+    chip: &str,
+    attach_under_reset: bool,
+) -> Result<Session, Error> {
+    let lister = Lister::new();
+    let mut probe = if let Some(probe_selector) = &rtt_cfg.probe_selector {
+        debug!(probe_selector = %probe_selector.0, "Opening selected probe");
+        lister.open(probe_selector.0.clone())?
+    } else {
+        let probes = lister.list_all();
+        debug!(probes = probes.len(), "Opening first available probe");
+        if probes.is_empty() {
+            return Err(Error::NoProbesAvailable);
+        }
+        probes[0].open(&lister)?
     };
 
-    let mut session = match session.lock() {
-        Ok(s) => s,
-        // Reader thread is either shutdown or aborted
-        Err(s) => s.into_inner(),
+    debug!(protocol = %rtt_cfg.protocol, speed = rtt_cfg.speed, "Configuring probe");
+    probe.select_protocol(rtt_cfg.protocol)?;
+    probe.set_speed(rtt_cfg.speed)?;
+
+    debug!(chip = chip, core = rtt_cfg.core, "Attaching to chip");
+    Ok(if attach_under_reset {
+        probe.attach_under_reset(chip, Permissions::default())?
+    } else {
+        probe.attach(chip, Permissions::default())?
+    })
+}
+
+/// Opens the probe for each access and releases it in between,
+/// so other tools can use it concurrently
+struct SharedProbe {
+    rtt_cfg: RttCollectorConfig,
+    chip: String,
+}
+
+/// How the readers access the probe
+#[derive(Clone)]
+enum ProbeAccess {
+    /// The session is held for the lifetime of the collector
+    Exclusive(Arc<Mutex<Session>>),
+    Shared(Arc<Mutex<SharedProbe>>),
+}
+
+impl ProbeAccess {
+    fn new(session: Session, rtt_cfg: &RttCollectorConfig, chip: String) -> Self {
+        if rtt_cfg.shared_probe {
+            debug!("Releasing the probe, it will be opened for each poll");
+            ProbeAccess::Shared(Arc::new(Mutex::new(SharedProbe {
+                rtt_cfg: rtt_cfg.clone(),
+                chip,
+            })))
+        } else {
+            ProbeAccess::Exclusive(Arc::new(Mutex::new(session)))
+        }
+    }
+
+    /// Runs `f` with the core, or returns None when the shared probe can't be opened
+    /// because another tool is using it
+    fn try_with_core<T>(
+        &self,
+        core_index: usize,
+        f: impl FnOnce(&mut Core) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        match self {
+            ProbeAccess::Exclusive(session) => {
+                // Reader threads may have been shutdown or aborted
+                let mut session = session.lock().unwrap_or_else(PoisonError::into_inner);
+                let mut core = session.core(core_index)?;
+                f(&mut core).map(Some)
+            }
+            ProbeAccess::Shared(shared) => {
+                let shared = shared.lock().unwrap_or_else(PoisonError::into_inner);
+                let mut session = match open_session(&shared.rtt_cfg, &shared.chip, false) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        trace!(error = %e, "Shared probe is unavailable");
+                        return Ok(None);
+                    }
+                };
+                let mut core = session.core(core_index)?;
+                f(&mut core).map(Some)
+            }
+        }
+    }
+
+    /// Runs `f` while no reader is accessing the probe
+    fn while_idle(&self, f: impl FnOnce()) {
+        match self {
+            ProbeAccess::Exclusive(session) => {
+                let _session = session.lock().unwrap_or_else(PoisonError::into_inner);
+                f()
+            }
+            ProbeAccess::Shared(shared) => {
+                let _shared = shared.lock().unwrap_or_else(PoisonError::into_inner);
+                f()
+            }
+        }
+    }
+}
+
+/// Timeline attribute recording the RTT up channel number the timeline was read from
+const UP_CHANNEL_ATTR_KEY: &str = "timeline.internal.defmt.rtt.up_channel";
+
+/// Timeline attribute recording the RTT up channel name the timeline was read from
+const UP_CHANNEL_NAME_ATTR_KEY: &str = "timeline.internal.defmt.rtt.up_channel.name";
+
+/// Timeline attribute recording the ring buffer the timeline was read from
+const RING_BUFFER_ATTR_KEY: &str = "timeline.internal.defmt.ring_buffer";
+
+fn run_reader(
+    intr: Interruptor,
+    probe: ProbeAccess,
+    source: ReadSource,
+    format: RttChannelFormat,
+    defmt_cfg: DefmtConfig,
+    rt: tokio::runtime::Handle,
+) -> Result<ReadOutcome, Error> {
+    let channel = source.channel();
+    let metrics = if defmt_cfg.plugin.rtt_collector.metrics {
+        Some(Metrics::new(
+            channel,
+            defmt_cfg.plugin.rtt_collector.rtt_read_buffer_size,
+        ))
+    } else {
+        None
     };
-    let mut core = session.core(defmt_cfg.plugin.rtt_collector.core)?;
-    let mode = ChannelMode::NoBlockTrim;
-    debug!(mode = ?mode, "Set channel mode");
-    up_channel.set_mode(&mut core, mode)?;
+    let crash_capture =
+        if defmt_cfg.plugin.rtt_collector.crash_capture && format == RttChannelFormat::Defmt {
+            Some(CrashCapture::new(&defmt_cfg)?)
+        } else {
+            None
+        };
+    let mut stream = DefmtRttReader::new(
+        intr.clone(),
+        probe,
+        source,
+        &defmt_cfg.plugin.rtt_collector,
+        metrics,
+        crash_capture,
+    )?;
+    if format == RttChannelFormat::Text {
+        rt.block_on(defmt_reader::run_text(&mut stream, defmt_cfg, intr))?;
+        return Ok(ReadOutcome::Finished);
+    }
+    let outcome = rt.block_on(defmt_reader::run_with_overflows(
+        &mut stream,
+        defmt_cfg,
+        intr,
+        |r| r.overflows(),
+        |r, ev| r.crash_state(ev),
+        |r| r.fault(),
+    ))?;
+    Ok(outcome)
+}
+
+fn run_watch(
+    intr: Interruptor,
+    probe: ProbeAccess,
+    watch: WatchSampler,
+    defmt_cfg: DefmtConfig,
+    rt: tokio::runtime::Handle,
+) -> Result<(), Error> {
+    const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+    let interval = defmt_cfg
+        .plugin
+        .rtt_collector
+        .watch_interval
+        .map(|d| d.0.into())
+        .unwrap_or(DEFAULT_WATCH_INTERVAL);
+    let core_index = defmt_cfg.plugin.rtt_collector.core;
+    // Reads take the probe like the readers do, a shared probe that's in use skips the sample
+    let sample = || match probe.try_with_core(core_index, |core| watch.read(core)) {
+        Ok(samples) => samples,
+        Err(e) => {
+            warn!(error = %e, "Failed to sample the watched memory");
+            None
+        }
+    };
+    rt.block_on(defmt_reader::run_watch(defmt_cfg, intr, interval, sample))?;
+    Ok(())
+}
+
+/// Timeline attribute recording the name of a supervised target
+const TARGET_ATTR_KEY: &str = "timeline.internal.defmt.rtt.target";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display)]
+enum TargetState {
+    #[display(fmt = "running")]
+    Running,
+    #[display(fmt = "restarting")]
+    Restarting,
+    #[display(fmt = "stopped")]
+    Stopped,
+}
+
+#[derive(Clone, Debug)]
+struct TargetStatus {
+    state: TargetState,
+    restarts: u64,
+    last_error: Option<String>,
+}
+
+type TargetStatuses = Arc<Mutex<BTreeMap<String, TargetStatus>>>;
+
+/// Drives each of the configured targets from this process.
+/// Targets are independent, a failed target is restarted without affecting the others.
+async fn supervise(
+    defmt_cfg: DefmtConfig,
+    intr: Interruptor,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const DEFAULT_RESTART_INTERVAL: Duration = Duration::from_secs(1);
+    const DEFAULT_STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+    let rtt_cfg = &defmt_cfg.plugin.rtt_collector;
+    let restart_interval = rtt_cfg
+        .restart_interval
+        .map(|d| d.0.into())
+        .unwrap_or(DEFAULT_RESTART_INTERVAL);
+    let status_interval = rtt_cfg
+        .status_interval
+        .map(|d| d.0.into())
+        .unwrap_or(DEFAULT_STATUS_INTERVAL);
+
+    let statuses: TargetStatuses = Default::default();
+    let mut attempt_intrs = Vec::new();
+    let mut supervised = tokio::task::JoinSet::new();
+    for target in rtt_cfg.targets.iter() {
+        if target.name.trim().is_empty() {
+            return Err(Error::MissingTargetName.into());
+        }
+        let status = TargetStatus {
+            state: TargetState::Running,
+            restarts: 0,
+            last_error: None,
+        };
+        if statuses
+            .lock()
+            .unwrap()
+            .insert(target.name.clone(), status)
+            .is_some()
+        {
+            return Err(Error::DuplicateTargetName(target.name.clone()).into());
+        }
+
+        let mut target_cfg = defmt_cfg.clone();
+        target_cfg.plugin = defmt_cfg.plugin.for_rtt_target(target);
+        target_cfg
+            .ingest
+            .timeline_attributes
+            .additional_timeline_attributes
+            .push(AttrKeyEqValuePair(
+                AttrKey::new(TARGET_ATTR_KEY.to_owned()),
+                target.name.clone().into(),
+            ));
+
+        let attempt_intr = Arc::new(Mutex::new(Interruptor::new()));
+        attempt_intrs.push(attempt_intr.clone());
+        let name = target.name.clone();
+        let intr = intr.clone();
+        let statuses = statuses.clone();
+        let rt = tokio::runtime::Handle::current();
+        supervised.spawn_blocking(move || {
+            supervise_target(
+                name,
+                target_cfg,
+                intr,
+                attempt_intr,
+                restart_interval,
+                statuses,
+                rt,
+            )
+        });
+    }
+
+    info!(targets = attempt_intrs.len(), "Supervising targets");
+    let mut status_ticker = tokio::time::interval(status_interval);
+    status_ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                debug!("User signaled shutdown");
+                break;
+            }
+            _ = status_ticker.tick() => {
+                log_target_statuses(&statuses);
+            }
+            res = supervised.join_next() => {
+                match res {
+                    Some(res) => res?,
+                    None => break,
+                }
+            }
+        }
+    }
+
+    intr.set();
+    for attempt_intr in attempt_intrs.iter() {
+        attempt_intr.lock().unwrap().set();
+    }
+    while let Some(res) = supervised.join_next().await {
+        res?;
+    }
+    log_target_statuses(&statuses);
 
     Ok(())
 }
 
+/// Collects from a single target, restarting it on failure until shutdown
+fn supervise_target(
+    name: String,
+    mut defmt_cfg: DefmtConfig,
+    intr: Interruptor,
+    attempt_intr: Arc<Mutex<Interruptor>>,
+    restart_interval: Duration,
+    statuses: TargetStatuses,
+    rt: tokio::runtime::Handle,
+) {
+    let set_status = |f: &dyn Fn(&mut TargetStatus)| {
+        if let Some(status) = statuses.lock().unwrap().get_mut(&name) {
+            f(status);
+        }
+    };
+
+    loop {
+        // Each attempt gets its own interruptor so a failed target can stop
+        // its readers without affecting the others
+        let this_attempt = Interruptor::new();
+        *attempt_intr.lock().unwrap() = this_attempt.clone();
+        if intr.is_set() {
+            break;
+        }
+
+        info!(target_name = name, "Starting target");
+        set_status(&|s| s.state = TargetState::Running);
+        let res = rt.block_on(collect(defmt_cfg.clone(), this_attempt));
+        // Restarts run the firmware that's already flashed
+        defmt_cfg.plugin.rtt_collector.download = false;
+        match res {
+            Ok(()) => break,
+            Err(_) if intr.is_set() => break,
+            Err(e) => {
+                warn!(target_name = name, error = %e, "Target failed, restarting");
+                set_status(&|s| {
+                    s.state = TargetState::Restarting;
+                    s.restarts += 1;
+                    s.last_error = Some(e.to_string());
+                });
+            }
+        }
+
+        let start = Instant::now();
+        while !intr.is_set() && start.elapsed() < restart_interval {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    info!(target_name = name, "Target stopped");
+    set_status(&|s| s.state = TargetState::Stopped);
+}
+
+fn log_target_statuses(statuses: &TargetStatuses) {
+    let statuses = statuses.lock().unwrap();
+    let count = |state: TargetState| statuses.values().filter(|s| s.state == state).count();
+    info!(
+        targets = statuses.len(),
+        running = count(TargetState::Running),
+        restarting = count(TargetState::Restarting),
+        stopped = count(TargetState::Stopped),
+        "Supervisor status"
+    );
+    for (name, s) in statuses.iter() {
+        info!(
+            target_name = name,
+            state = %s.state,
+            restarts = s.restarts,
+            last_error = s.last_error.as_deref().unwrap_or(""),
+            "Target status"
+        );
+    }
+}
+
 fn get_rtt_symbol<T: io::Read + io::Seek>(file: &mut T) -> Option<u64> {
     get_symbol(file, "_SEGGER_RTT")
 }
 
 fn get_symbol<T: io::Read + io::Seek>(file: &mut T, symbol: &str) -> Option<u64> {
+    get_symbol_with_size(file, symbol).map(|(addr, _size)| addr)
+}
+
+/// Parses an absolute address, or resolves a symbol name from the ELF file
+fn resolve_address(sym_or_addr: &str, defmt_cfg: &DefmtConfig, thumb: bool) -> Result<u64, Error> {
+    let (addr, _) = resolve_address_with_size(sym_or_addr, defmt_cfg)?;
+    Ok(if thumb { addr & !1 } else { addr })
+}
+
+/// Resolves a decimal or hex address, or an ELF symbol along with its size
+fn resolve_address_with_size(
+    sym_or_addr: &str,
+    defmt_cfg: &DefmtConfig,
+) -> Result<(u64, Option<u64>), Error> {
+    if let Some(addr) = sym_or_addr.parse::<u64>().ok().or(u64::from_str_radix(
+        sym_or_addr.trim_start_matches("0x"),
+        16,
+    )
+    .ok())
+    {
+        return Ok((addr, None));
+    }
+    let mut file = fs::File::open(
+        defmt_cfg
+            .plugin
+            .elf_file
+            .as_ref()
+            .ok_or(modality_defmt_plugin::Error::MissingElfFile)?,
+    )?;
+    let (addr, size) = get_symbol_with_size(&mut file, sym_or_addr)
+        .ok_or_else(|| Error::ElfSymbol(sym_or_addr.to_owned()))?;
+    Ok((addr, Some(size)))
+}
+
+fn get_symbol_with_size<T: io::Read + io::Seek>(file: &mut T, symbol: &str) -> Option<(u64, u64)> {
     let mut buffer = Vec::new();
     if file.read_to_end(&mut buffer).is_ok() {
         if let Ok(binary) = goblin::elf::Elf::parse(buffer.as_slice()) {
             for sym in &binary.syms {
                 if let Some(name) = binary.strtab.get_at(sym.st_name) {
                     if name == symbol {
-                        return Some(sym.st_value);
+                        return Some((sym.st_value, sym.st_size));
                     }
                 }
             }
@@ -556,12 +1951,48 @@ enum Error {
     #[error("The RTT up channel ({0}) is invalid")]
     UpChannelInvalid(usize),
 
+    #[error("There's no RTT up channel named '{0}'. Available channels: {1}")]
+    UpChannelName(String, String),
+
     #[error("Could not locate the address of symbol '{0}' in the ELF file")]
     ElfSymbol(String),
 
+    #[error("The ring buffer requires a non-empty '{0}' symbol or address")]
+    MissingRingBufferLocation(&'static str),
+
+    #[error("The size of the ring buffer is unknown. Provide it with 'size'")]
+    RingBufferSize,
+
+    #[error("The {0} mode can't be combined with '{1}'")]
+    ModeConflict(&'static str, &'static str),
+
+    #[error("Staging a mutation requires 'reset' or 'attach-under-reset', the firmware reads it at startup")]
+    MutationStagingWithoutReset,
+
+    #[error("The core has no register named '{0}'")]
+    UnknownRegister(String),
+
+    #[error("The {0} command '{1}' failed ({2})")]
+    HookCommand(&'static str, String, String),
+
+    #[error("The watched value '{0}' must be 1 to 8 bytes")]
+    WatchSize(String),
+
+    #[error("Supervised targets require a non-empty 'name'")]
+    MissingTargetName,
+
+    #[error("The supervised target name '{0}' is used more than once")]
+    DuplicateTargetName(String),
+
     #[error("Encountered an error with the probe. {0}")]
     ProbeRs(#[from] probe_rs::Error),
 
+    #[error("Encountered an error with the debug probe. {0}")]
+    DebugProbe(#[from] probe_rs::probe::DebugProbeError),
+
+    #[error("Encountered an error downloading the firmware. {0}")]
+    Download(#[from] probe_rs::flashing::FileDownloadError),
+
     #[error("Encountered an error with the probe RTT instance. {0}")]
     ProbeRsRtt(#[from] probe_rs::rtt::Error),
 
@@ -570,107 +2001,513 @@ enum Error {
 
     #[error(transparent)]
     DefmtReader(#[from] modality_defmt_plugin::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Join(#[from] tokio::task::JoinError),
+}
+
+impl Error {
+    /// Whether the error could be caused by a target reset or a transient probe failure,
+    /// as opposed to a configuration error
+    fn is_reattachable(&self) -> bool {
+        matches!(
+            self,
+            Error::NoProbesAvailable
+                | Error::ProbeRs(_)
+                | Error::DebugProbe(_)
+                | Error::ProbeRsRtt(_)
+                | Error::DefmtReader(_)
+                | Error::Io(_)
+        )
+    }
+}
+
+/// A firmware-defined RAM ring buffer with u32 head (write) and tail (read) byte offsets
+struct RingBuffer {
+    name: String,
+    buffer: u64,
+    size: u32,
+    head: u64,
+    tail: u64,
+}
+
+impl RingBuffer {
+    fn locate(cfg: &RingBufferConfig, defmt_cfg: &DefmtConfig) -> Result<Self, Error> {
+        let required = |field: &'static str, sym_or_addr: &str| {
+            if sym_or_addr.is_empty() {
+                Err(Error::MissingRingBufferLocation(field))
+            } else {
+                Ok(())
+            }
+        };
+        required("buffer", &cfg.buffer)?;
+        required("head", &cfg.head)?;
+        required("tail", &cfg.tail)?;
+
+        let (buffer, buffer_sym_size) = resolve_address_with_size(&cfg.buffer, defmt_cfg)?;
+        let head = resolve_address(&cfg.head, defmt_cfg, false)?;
+        let tail = resolve_address(&cfg.tail, defmt_cfg, false)?;
+        let size = cfg
+            .size
+            .or(buffer_sym_size.and_then(|s| u32::try_from(s).ok()))
+            .filter(|s| *s != 0)
+            .ok_or(Error::RingBufferSize)?;
+        debug!(
+            buffer = format_args!("0x{:X}", buffer),
+            size,
+            head = format_args!("0x{:X}", head),
+            tail = format_args!("0x{:X}", tail),
+            "Located ring buffer"
+        );
+        Ok(Self {
+            name: cfg.buffer.clone(),
+            buffer,
+            size,
+            head,
+            tail,
+        })
+    }
+
+    /// Reads the contiguous data between the tail and the head, then advances the tail
+    fn read(&self, core: &mut Core, buf: &mut [u8]) -> Result<usize, probe_rs::Error> {
+        let head = core.read_word_32(self.head)?;
+        let tail = core.read_word_32(self.tail)?;
+        let Some((n, next_tail)) = ring_buffer_span(head, tail, self.size, buf) else {
+            warn!(head, tail, size = self.size, "Invalid ring buffer offsets");
+            return Ok(0);
+        };
+        if n == 0 {
+            return Ok(0);
+        }
+        core.read(self.buffer + u64::from(tail), &mut buf[..n])?;
+        core.write_word_32(self.tail, next_tail)?;
+        Ok(n)
+    }
+}
+
+/// The length of the contiguous unread data starting at the tail that fits in `buf`,
+/// and the tail after reading it. None when the offsets are out of bounds.
+fn ring_buffer_span(head: u32, tail: u32, size: u32, buf: &[u8]) -> Option<(usize, u32)> {
+    if head >= size || tail >= size {
+        return None;
+    }
+    let available = if head >= tail {
+        head - tail
+    } else {
+        // Up to the end of the buffer, the rest is read from the start next time
+        size - tail
+    };
+    let n = (available as usize).min(buf.len());
+    Some((n, (tail + n as u32) % size))
+}
+
+/// Where the reader gets its data from
+enum ReadSource {
+    Rtt(Arc<UpChannel>),
+    RingBuffer(RingBuffer),
+}
+
+impl ReadSource {
+    /// The RTT up channel number, ring buffers are reported as channel 0
+    fn channel(&self) -> usize {
+        match self {
+            ReadSource::Rtt(up_channel) => up_channel.number(),
+            ReadSource::RingBuffer(_) => 0,
+        }
+    }
+
+    fn read(&self, core: &mut Core, buf: &mut [u8]) -> Result<usize, Error> {
+        Ok(match self {
+            ReadSource::Rtt(up_channel) => up_channel.read(core, buf)?,
+            ReadSource::RingBuffer(rb) => rb.read(core, buf)?,
+        })
+    }
 }
 
 struct DefmtRttReader {
     interruptor: Interruptor,
-    session: Arc<Mutex<Session>>,
-    channel: Arc<UpChannel>,
+    probe: ProbeAccess,
+    source: ReadSource,
     core_index: usize,
-    last_poll_had_data: bool,
+    poll_policy: RttPollPolicy,
     poll_interval: Duration,
+    idle_poll_interval: Duration,
+    /// The delay before the next poll
+    interval: Duration,
     ratelimiter: Ratelimiter,
     metrics: Option<Metrics>,
+    /// Usable size of a lossy channel's buffer, a read of this many bytes means
+    /// the target found the buffer full and dropped data
+    overflow_capacity: Option<usize>,
+    overflows: u64,
+    /// Reads end at the first poll without data
+    snapshot: bool,
+    /// Reads end once there's been no data for this long
+    idle_timeout: Option<Duration>,
+    last_data: Instant,
+    crash_capture: Option<CrashCapture>,
+    /// Check for a caught fault at the polls without data
+    fault_action: Option<FaultAction>,
+    /// The caught fault, not yet reported
+    fault: Cell<Option<FaultState>>,
 }
 
 impl DefmtRttReader {
     const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(1);
-    const NO_DATA_POLL_INTERVAL: Duration = Duration::from_millis(100);
+    const DEFAULT_IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
     pub fn new(
         interruptor: Interruptor,
-        session: Arc<Mutex<Session>>,
-        channel: Arc<UpChannel>,
-        core_index: usize,
-        poll_interval: Duration,
-        rtt_buffer_size: usize,
+        probe: ProbeAccess,
+        source: ReadSource,
+        rtt_cfg: &RttCollectorConfig,
         metrics: Option<Metrics>,
+        crash_capture: Option<CrashCapture>,
     ) -> Result<Self, Error> {
-        debug!(rtt_buffer_size, data_poll_interval = ?poll_interval, no_data_poll_interval = ?Self::NO_DATA_POLL_INTERVAL, "Setup RTT reader");
+        let core_index = rtt_cfg.core;
+        let rtt_buffer_size = rtt_cfg.rtt_read_buffer_size;
+        let poll_policy = rtt_cfg.rtt_poll_policy;
+        let poll_interval = rtt_cfg
+            .rtt_poll_interval
+            .map(|d| d.0.into())
+            .unwrap_or(Self::DEFAULT_POLL_INTERVAL);
+        let idle_poll_interval = rtt_cfg
+            .rtt_idle_poll_interval
+            .map(|d| d.0.into())
+            .unwrap_or(Self::DEFAULT_IDLE_POLL_INTERVAL);
+        debug!(rtt_buffer_size, %poll_policy, data_poll_interval = ?poll_interval, idle_poll_interval = ?idle_poll_interval, "Setup RTT reader");
         let ratelimiter = Ratelimiter::builder(1, poll_interval)
             .initial_available(1)
             .build()?;
-        // Make sure we can safely unwrap on set_refill_interval in the Read impl
-        ratelimiter.set_refill_interval(Self::NO_DATA_POLL_INTERVAL)?;
+        // Make sure the intervals in between are valid too
+        ratelimiter.set_refill_interval(idle_poll_interval.max(poll_interval))?;
         ratelimiter.set_refill_interval(poll_interval)?;
+
+        let overflow_capacity = match &source {
+            ReadSource::Rtt(up_channel) => {
+                let mode = probe.try_with_core(core_index, |core| Ok(up_channel.mode(core)?))?;
+                // One byte of an RTT buffer is always left free
+                let capacity = up_channel.buffer_size().saturating_sub(1);
+                match mode {
+                    Some(ChannelMode::BlockIfFull) | None => None,
+                    Some(_) if rtt_buffer_size < capacity => {
+                        warn!(
+                            channel = up_channel.number(),
+                            rtt_buffer_size,
+                            capacity,
+                            "RTT read buffer is smaller than the channel buffer, overflows won't be detected"
+                        );
+                        None
+                    }
+                    Some(_) => Some(capacity),
+                }
+            }
+            ReadSource::RingBuffer(_) => None,
+        };
+
         Ok(Self {
             interruptor,
-            session,
-            channel,
+            probe,
+            source,
             core_index,
-            last_poll_had_data: true,
+            poll_policy,
             poll_interval,
+            idle_poll_interval,
+            interval: poll_interval,
             ratelimiter,
             metrics,
+            overflow_capacity,
+            overflows: 0,
+            snapshot: rtt_cfg.snapshot,
+            idle_timeout: rtt_cfg.exit_on_idle.map(|d| d.0.into()),
+            last_data: Instant::now(),
+            crash_capture,
+            fault_action: rtt_cfg.fault_catch.then_some(rtt_cfg.fault_action),
+            fault: Cell::new(None),
+        })
+    }
+
+    /// Number of reads that found a lossy channel's buffer full
+    fn overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    /// Takes the caught fault, if there is one
+    fn fault(&self) -> Option<FaultState> {
+        self.fault.take()
+    }
+
+    /// Reads the core state when the event triggers a crash capture
+    fn crash_state(&self, ev: &EventRecord) -> Option<CrashState> {
+        let capture = self.crash_capture.as_ref()?;
+        if !capture.events.iter().any(|trigger| trigger.matches(ev)) {
+            return None;
+        }
+        match self
+            .probe
+            .try_with_core(self.core_index, |core| capture.read(core))
+        {
+            Ok(Some(state)) => {
+                debug!(event_name = ev.event_name(), "Captured the crash state");
+                Some(state)
+            }
+            Ok(None) => {
+                warn!("The shared probe is in use, skipping the crash capture");
+                None
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to capture the crash state");
+                None
+            }
+        }
+    }
+}
+
+/// What to read from the target at a crash event
+struct CrashCapture {
+    events: Vec<StopTrigger>,
+    stack_size: u32,
+    /// The memory windows, with their resolved addresses
+    windows: Vec<(MemoryWindow, u64)>,
+}
+
+impl CrashCapture {
+    fn new(defmt_cfg: &DefmtConfig) -> Result<Self, Error> {
+        let rtt_cfg = &defmt_cfg.plugin.rtt_collector;
+        let windows = rtt_cfg
+            .crash_memory
+            .iter()
+            .map(|window| {
+                let address = resolve_address(&window.location, defmt_cfg, false)?;
+                Ok((window.clone(), address))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self {
+            events: rtt_cfg.selected_crash_events(),
+            stack_size: rtt_cfg
+                .crash_stack_size
+                .unwrap_or(RttCollectorConfig::DEFAULT_CRASH_STACK_SIZE),
+            windows,
         })
     }
+
+    /// Halts the core if it's running, reads its state and resumes it
+    fn read(&self, core: &mut Core) -> Result<CrashState, Error> {
+        let running = !core.core_halted()?;
+        if running {
+            core.halt(Duration::from_millis(100))?;
+        }
+        let res = self.read_halted(core);
+        if running {
+            core.run()?;
+        }
+        res
+    }
+
+    fn read_halted(&self, core: &mut Core) -> Result<CrashState, Error> {
+        let mut state = CrashState::default();
+        for reg in core.registers().core_registers() {
+            let value: RegisterValue = core.read_core_reg(reg.id())?;
+            state
+                .registers
+                .push((reg.name().to_owned(), register_u64(value)));
+        }
+
+        let read = |core: &mut Core, location: &str, address: u64, size: u32| {
+            let mut data = vec![0; size as usize];
+            core.read_8(address, &mut data)?;
+            Ok::<_, Error>(MemoryDump {
+                location: location.to_owned(),
+                address,
+                data,
+            })
+        };
+        if self.stack_size != 0 {
+            let sp_reg = core.stack_pointer();
+            let sp: RegisterValue = core.read_core_reg(sp_reg.id())?;
+            state.stack = Some(read(core, "SP", register_u64(sp), self.stack_size)?);
+        }
+        for (window, address) in self.windows.iter() {
+            state
+                .memory
+                .push(read(core, &window.location, *address, window.size)?);
+        }
+        Ok(state)
+    }
+}
+
+/// Reads the watched memory, for periodic sampling
+struct WatchSampler {
+    /// The watched values, with their resolved addresses
+    windows: Vec<(MemoryWindow, u64)>,
+}
+
+impl WatchSampler {
+    fn new(defmt_cfg: &DefmtConfig) -> Result<Self, Error> {
+        let windows = defmt_cfg
+            .plugin
+            .rtt_collector
+            .watch
+            .iter()
+            .map(|window| {
+                if window.size == 0 || window.size as usize > WatchSample::MAX_SIZE {
+                    return Err(Error::WatchSize(window.location.clone()));
+                }
+                let address = resolve_address(&window.location, defmt_cfg, false)?;
+                Ok((window.clone(), address))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self { windows })
+    }
+
+    fn read(&self, core: &mut Core) -> Result<Vec<WatchSample>, Error> {
+        let mut samples = Vec::with_capacity(self.windows.len());
+        for (window, address) in self.windows.iter() {
+            let mut data = vec![0; window.size as usize];
+            core.read_8(*address, &mut data)?;
+            samples.extend(WatchSample::from_le_bytes(
+                &window.location,
+                *address,
+                &data,
+            ));
+        }
+        Ok(samples)
+    }
+}
+
+/// Reads the fault status registers when the core halted on a fault exception vector catch,
+/// resuming it unless the action is to stop
+fn caught_fault(core: &mut Core, action: FaultAction) -> Result<Option<FaultState>, Error> {
+    if !matches!(core.status()?, CoreStatus::Halted(HaltReason::Exception)) {
+        return Ok(None);
+    }
+
+    // EXC_RETURN bit 2 selects the stack the exception frame was pushed to,
+    // the stacked PC is at offset 24
+    let lr: RegisterValue = core.read_core_reg(core.return_address().id())?;
+    let sp_reg = if register_u64(lr) & 0x4 != 0 {
+        core.registers().psp()
+    } else {
+        Some(core.stack_pointer())
+    };
+    let pc = match sp_reg.map(|reg| reg.id()) {
+        Some(id) => {
+            let sp: RegisterValue = core.read_core_reg(id)?;
+            Some(core.read_word_32(register_u64(sp) + 24)?.into())
+        }
+        None => None,
+    };
+
+    let fault = FaultState {
+        pc,
+        cfsr: core.read_word_32(FaultState::CFSR_ADDRESS)?,
+        hfsr: core.read_word_32(FaultState::HFSR_ADDRESS)?,
+        mmfar: core.read_word_32(FaultState::MMFAR_ADDRESS)?,
+        bfar: core.read_word_32(FaultState::BFAR_ADDRESS)?,
+        halted: action == FaultAction::Stop,
+    };
+    if !fault.halted {
+        core.run()?;
+    }
+    Ok(Some(fault))
+}
+
+fn register_u64(value: RegisterValue) -> u64 {
+    match value {
+        RegisterValue::U32(v) => v.into(),
+        RegisterValue::U64(v) => v,
+        RegisterValue::U128(v) => v as u64,
+    }
 }
 
 impl io::Read for DefmtRttReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         while !self.interruptor.is_set() {
-            let rtt_bytes_read = {
-                let mut session = self.session.lock().unwrap();
-                let mut core = session
-                    .core(self.core_index)
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-                self.channel
-                    .read(&mut core, buf)
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
-            };
+            // A busy shared probe reads as no data
+            let rtt_bytes_read = self
+                .probe
+                .try_with_core(self.core_index, |core| self.source.read(core, buf))
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+                .unwrap_or(0);
             trace!(rtt_bytes_read);
 
-            // NOTE: this is what probe-rs does
+            // NOTE: the step policy is what probe-rs does
             //
             // Poll RTT with a frequency of 10 Hz if we do not receive any new data.
             // Once we receive new data, we bump the frequency to 1kHz (default).
             //
             // If the polling frequency is too high, the USB connection to the probe
             // can become unstable. Hence we only pull as little as necessary.
-            //
-            // SAFETY: we check that both intervals are valid in the constructor
-            match ((rtt_bytes_read != 0), self.last_poll_had_data) {
-                (true, false) => {
-                    self.ratelimiter
-                        .set_refill_interval(self.poll_interval)
-                        .unwrap();
-                }
-                (false, true) => {
-                    self.ratelimiter
-                        .set_refill_interval(Self::NO_DATA_POLL_INTERVAL)
-                        .unwrap();
-                }
-                _ => (),
+            let interval = self.poll_policy.next_interval(
+                self.interval,
+                rtt_bytes_read,
+                buf.len(),
+                self.poll_interval,
+                self.idle_poll_interval,
+            );
+            if interval != self.interval && !interval.is_zero() {
+                // SAFETY: we check that the interval range is valid in the constructor
+                self.ratelimiter.set_refill_interval(interval).unwrap();
             }
-            self.last_poll_had_data = rtt_bytes_read != 0;
+            self.interval = interval;
 
-            if let Err(delay) = self.ratelimiter.try_wait() {
-                std::thread::sleep(delay);
+            // Reads filling the buffer are followed by another one right away,
+            // the target has more data
+            if !interval.is_zero() {
+                if let Err(delay) = self.ratelimiter.try_wait() {
+                    std::thread::sleep(delay);
+                }
             }
 
             if let Some(metrics) = self.metrics.as_mut() {
                 metrics.update(rtt_bytes_read);
             }
 
-            if rtt_bytes_read != 0 {
+            if let Some(capacity) = self.overflow_capacity {
+                if capacity != 0 && rtt_bytes_read >= capacity {
+                    self.overflows += 1;
+                    debug!(channel = self.source.channel(), "RTT buffer was full");
+                }
+            }
+
+            if rtt_bytes_read != 0 || self.snapshot {
+                self.last_data = Instant::now();
                 return Ok(rtt_bytes_read);
             }
+
+            // A faulted core stops writing, check for a caught fault when there's no data
+            if let Some(action) = self.fault_action {
+                let fault = self
+                    .probe
+                    .try_with_core(self.core_index, |core| caught_fault(core, action))
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+                    .flatten();
+                if let Some(fault) = fault {
+                    self.fault.set(Some(fault));
+                    if fault.halted {
+                        return Ok(0);
+                    }
+                }
+            }
+
+            if let Some(timeout) = self.idle_timeout.filter(|t| self.last_data.elapsed() >= *t) {
+                info!(
+                    channel = self.source.channel(),
+                    timeout = %humantime::format_duration(timeout),
+                    "No data before the idle timeout, ending the session"
+                );
+                return Ok(0);
+            }
         }
         Ok(0)
     }
 }
 
 struct Metrics {
+    channel: usize,
     rtt_buffer_size: u64,
     window_start: Instant,
     read_cnt: u64,
@@ -683,8 +2520,9 @@ struct Metrics {
 impl Metrics {
     const WINDOW_DURATION: Duration = Duration::from_secs(2);
 
-    fn new(rtt_buffer_size: usize) -> Self {
+    fn new(channel: usize, rtt_buffer_size: usize) -> Self {
         Self {
+            channel,
             rtt_buffer_size: rtt_buffer_size as u64,
             window_start: Instant::now(),
             read_cnt: 0,
@@ -722,6 +2560,7 @@ impl Metrics {
             let secs = dur.as_secs_f64();
 
             info!(
+                channel = self.channel,
                 transfer_rate = format!("{}/s", human_bytes(bytes / secs)),
                 cnt = self.read_cnt,
                 zero_cnt = self.read_zero_cnt,
@@ -733,3 +2572,29 @@ impl Metrics {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn ring_buffer_spans() {
+        let buf = [0_u8; 16];
+        // Empty
+        assert_eq!(ring_buffer_span(5, 5, 32, &buf), Some((0, 5)));
+        assert_eq!(ring_buffer_span(0, 0, 32, &buf), Some((0, 0)));
+        // Contiguous
+        assert_eq!(ring_buffer_span(12, 4, 32, &buf), Some((8, 12)));
+        assert_eq!(ring_buffer_span(30, 4, 32, &buf), Some((16, 20)));
+        // Wrapped, up to the end of the buffer first
+        assert_eq!(ring_buffer_span(3, 28, 32, &buf), Some((4, 0)));
+        assert_eq!(ring_buffer_span(3, 0, 32, &buf), Some((3, 3)));
+        assert_eq!(ring_buffer_span(3, 8, 32, &buf), Some((16, 24)));
+        // No room to read into
+        assert_eq!(ring_buffer_span(12, 4, 32, &[]), Some((0, 4)));
+        // Out of bounds
+        assert_eq!(ring_buffer_span(32, 4, 32, &buf), None);
+        assert_eq!(ring_buffer_span(4, 40, 32, &buf), None);
+    }
+}
@@ -0,0 +1,475 @@
+use clap::Parser;
+use modality_defmt_plugin::{
+    defmt_reader, esp, tracing::try_init_tracing_subscriber, DefmtConfig, DefmtConfigEntry,
+    DefmtOpts, EspBootLogReader, Framing, Interruptor, ReflectorOpts, SerialCollectorConfig,
+    SerialFlowControl, SerialParity, UsbDeviceSelector,
+};
+use serialport::{DataBits, SerialPort, SerialPortType, StopBits};
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+/// Collect defmt data from a serial device
+#[derive(Parser, Debug, Clone)]
+#[clap(version)]
+struct Opts {
+    #[clap(flatten)]
+    pub rf_opts: ReflectorOpts,
+
+    #[clap(flatten)]
+    pub defmt_opts: DefmtOpts,
+
+    /// The ELF file containing the defmt table and location information.
+    #[clap(
+        long,
+        name = "elf-file",
+        verbatim_doc_comment,
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub elf_file: Option<PathBuf>,
+
+    /// Specify an open device timeout.
+    /// When provided, the plugin will continually attempt to open the serial device.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(long, name = "open-timeout", help_heading = "COLLECTOR CONFIGURATION")]
+    pub open_timeout: Option<humantime::Duration>,
+
+    /// The baud rate.
+    ///
+    /// The default value is 115200.
+    #[clap(long, name = "baud-rate", help_heading = "COLLECTOR CONFIGURATION")]
+    pub baud_rate: Option<u32>,
+
+    /// The number of data bits per character.
+    /// Possible options: [5, 6, 7, 8].
+    ///
+    /// The default value is 8.
+    #[clap(long, name = "data-bits", help_heading = "COLLECTOR CONFIGURATION")]
+    pub data_bits: Option<u8>,
+
+    /// The parity checking mode.
+    /// Possible options: [none, odd, even].
+    ///
+    /// The default value is none.
+    #[clap(long, name = "parity", help_heading = "COLLECTOR CONFIGURATION")]
+    pub parity: Option<SerialParity>,
+
+    /// The number of stop bits.
+    /// Possible options: [1, 2].
+    ///
+    /// The default value is 1.
+    #[clap(long, name = "stop-bits", help_heading = "COLLECTOR CONFIGURATION")]
+    pub stop_bits: Option<u8>,
+
+    /// The flow control mode.
+    /// Possible options: [none, software, hardware].
+    ///
+    /// The default value is none.
+    #[clap(long, name = "flow-control", help_heading = "COLLECTOR CONFIGURATION")]
+    pub flow_control: Option<SerialFlowControl>,
+
+    /// Select a USB serial device (e.g. a CDC-ACM interface) instead of providing a path.
+    ///
+    /// Use '--usb-device VID:PID' or '--usb-device VID:PID:Serial' if you have more than one device with the same VID:PID.
+    #[clap(
+        long,
+        name = "usb-device",
+        conflicts_with = "path",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub usb_device: Option<UsbDeviceSelector>,
+
+    /// The interval between attempts to re-open the device when it disappears,
+    /// e.g. when the target is reset and the USB device re-enumerates.
+    ///
+    /// The default value is 500ms.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(
+        long,
+        name = "reopen-interval",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub reopen_interval: Option<humantime::Duration>,
+
+    /// Exit when the device disappears instead of waiting for it to come back.
+    #[clap(
+        long,
+        name = "disable-reopen",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub disable_reopen: bool,
+
+    /// The device is an Espressif chip's USB-Serial-JTAG console.
+    /// Selects the USB device 303a:1001 when no path or USB device is provided,
+    /// and strips the chip's reset and boot messages from the defmt data.
+    ///
+    /// Use '--framing espflash' when the firmware uses the esp-println 'defmt-espflash' framing.
+    #[clap(
+        long,
+        name = "esp-usb-serial-jtag",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub esp_usb_serial_jtag: bool,
+
+    /// Reset the Espressif chip after opening the device, so collection starts at boot.
+    #[clap(long, name = "esp-reset", help_heading = "COLLECTOR CONFIGURATION")]
+    pub esp_reset: bool,
+
+    /// The serial device to read from (e.g. /dev/ttyACM0 or COM3)
+    #[clap(name = "path", help_heading = "COLLECTOR CONFIGURATION")]
+    pub path: Option<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    match do_main().await {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("{e}");
+            let mut cause = e.source();
+            while let Some(err) = cause {
+                eprintln!("Caused by: {err}");
+                cause = err.source();
+            }
+            std::process::exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
+    let opts = Opts::parse();
+
+    try_init_tracing_subscriber()?;
+
+    let intr = Interruptor::new();
+    let intr_clone = intr.clone();
+    ctrlc::set_handler(move || {
+        if intr_clone.is_set() {
+            let exit_code = if cfg!(target_family = "unix") {
+                // 128 (fatal error signal "n") + 2 (control-c is fatal error signal 2)
+                130
+            } else {
+                // Windows code 3221225786
+                // -1073741510 == C000013A
+                -1073741510
+            };
+            std::process::exit(exit_code);
+        }
+
+        debug!("Shutdown signal received");
+        intr_clone.set();
+    })?;
+
+    let mut defmt_cfg = DefmtConfig::load_merge_with_opts(
+        DefmtConfigEntry::SerialCollector,
+        opts.rf_opts,
+        opts.defmt_opts,
+    )?;
+
+    if let Some(elf_file) = opts.elf_file.as_ref() {
+        defmt_cfg.plugin.elf_file = Some(elf_file.clone());
+    }
+    if let Some(to) = opts.open_timeout {
+        defmt_cfg.plugin.serial_collector.open_timeout = Some(to.into());
+    }
+    if let Some(path) = opts.path {
+        defmt_cfg.plugin.serial_collector.path = Some(path);
+        defmt_cfg.plugin.serial_collector.usb_device = None;
+    }
+    if let Some(usb_device) = opts.usb_device {
+        defmt_cfg.plugin.serial_collector.usb_device = Some(usb_device);
+        defmt_cfg.plugin.serial_collector.path = None;
+    }
+    if let Some(interval) = opts.reopen_interval {
+        defmt_cfg.plugin.serial_collector.reopen_interval = Some(interval.into());
+    }
+    if opts.disable_reopen {
+        defmt_cfg.plugin.serial_collector.disable_reopen = true;
+    }
+    if let Some(baud_rate) = opts.baud_rate {
+        defmt_cfg.plugin.serial_collector.baud_rate = baud_rate;
+    }
+    if let Some(data_bits) = opts.data_bits {
+        defmt_cfg.plugin.serial_collector.data_bits = data_bits;
+    }
+    if let Some(parity) = opts.parity {
+        defmt_cfg.plugin.serial_collector.parity = parity;
+    }
+    if let Some(stop_bits) = opts.stop_bits {
+        defmt_cfg.plugin.serial_collector.stop_bits = stop_bits;
+    }
+    if let Some(flow_control) = opts.flow_control {
+        defmt_cfg.plugin.serial_collector.flow_control = flow_control;
+    }
+    if opts.esp_usb_serial_jtag {
+        defmt_cfg.plugin.serial_collector.esp_usb_serial_jtag = true;
+    }
+    if opts.esp_reset {
+        defmt_cfg.plugin.serial_collector.esp_reset = true;
+    }
+
+    let mut port = match defmt_cfg.plugin.serial_collector.open_timeout {
+        Some(to) if !to.0.is_zero() => open_retry_loop(&defmt_cfg.plugin.serial_collector, to.0)?,
+        _ => open_port(&defmt_cfg.plugin.serial_collector)?,
+    };
+    if defmt_cfg.plugin.serial_collector.esp_reset {
+        esp_hard_reset(port.as_mut())?;
+    }
+
+    let reopen_interval = if defmt_cfg.plugin.serial_collector.disable_reopen {
+        None
+    } else {
+        Some(
+            defmt_cfg
+                .plugin
+                .serial_collector
+                .reopen_interval
+                .map(|i| i.0.into())
+                .unwrap_or(DefmtSerialReader::DEFAULT_REOPEN_INTERVAL),
+        )
+    };
+
+    // The espflash framing separates the console text from the defmt frames itself
+    let strip_boot_log = defmt_cfg.plugin.serial_collector.esp_usb_serial_jtag
+        && defmt_cfg.plugin.framing != Framing::Espflash;
+
+    let serial_cfg = defmt_cfg.plugin.serial_collector.clone();
+    let mut join_handle = tokio::spawn(async move {
+        let mut r = DefmtSerialReader::new(intr.clone(), serial_cfg, port, reopen_interval);
+        if strip_boot_log {
+            let mut r = EspBootLogReader::new(&mut r);
+            let res = defmt_reader::run(&mut r, defmt_cfg, intr).await;
+            debug!(resets = r.resets(), "Stripped Espressif boot messages");
+            res
+        } else {
+            defmt_reader::run(&mut r, defmt_cfg, intr).await
+        }
+    });
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            debug!("User signaled shutdown");
+        }
+        res = &mut join_handle => {
+            match res? {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(error = %e, "Encountered and error during streaming");
+                    return Err(e.into())
+                }
+            }
+        }
+    };
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(
+        "Missing serial device. Either supply a path or a USB device at the CLI, or a config file member 'path' or 'usb-device'"
+    )]
+    MissingPath,
+
+    #[error("Could not find a USB serial device matching '{0}'")]
+    UsbDeviceNotFound(UsbDeviceSelector),
+
+    #[error("Failed to enumerate serial devices. {0}")]
+    Enumerate(#[source] serialport::Error),
+
+    #[error("The number of data bits ({0}) is invalid")]
+    DataBitsInvalid(u8),
+
+    #[error("The number of stop bits ({0}) is invalid")]
+    StopBitsInvalid(u8),
+
+    #[error("Failed to open serial device '{0}'. {1}")]
+    SerialPortOpen(String, #[source] serialport::Error),
+
+    #[error("Failed to reset the Espressif chip. {0}")]
+    EspReset(#[source] serialport::Error),
+}
+
+/// Resolve the device path, looking up the USB device when one is selected
+fn port_path(cfg: &SerialCollectorConfig) -> Result<String, Error> {
+    let esp_selector = UsbDeviceSelector {
+        vendor_id: esp::USB_SERIAL_JTAG_VID,
+        product_id: esp::USB_SERIAL_JTAG_PID,
+        serial_number: None,
+    };
+    let selector = match (&cfg.usb_device, &cfg.path) {
+        (Some(selector), _) => selector,
+        (None, Some(path)) => return Ok(path.clone()),
+        (None, None) if cfg.esp_usb_serial_jtag => &esp_selector,
+        (None, None) => return Err(Error::MissingPath),
+    };
+
+    serialport::available_ports()
+        .map_err(Error::Enumerate)?
+        .into_iter()
+        .find_map(|p| match &p.port_type {
+            SerialPortType::UsbPort(info)
+                if selector.matches(info.vid, info.pid, info.serial_number.as_deref()) =>
+            {
+                Some(p.port_name)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| Error::UsbDeviceNotFound(selector.clone()))
+}
+
+fn open_port(cfg: &SerialCollectorConfig) -> Result<Box<dyn SerialPort>, Error> {
+    let path = &port_path(cfg)?;
+    let data_bits = match cfg.data_bits {
+        5 => DataBits::Five,
+        6 => DataBits::Six,
+        7 => DataBits::Seven,
+        8 => DataBits::Eight,
+        db => return Err(Error::DataBitsInvalid(db)),
+    };
+    let stop_bits = match cfg.stop_bits {
+        1 => StopBits::One,
+        2 => StopBits::Two,
+        sb => return Err(Error::StopBitsInvalid(sb)),
+    };
+
+    debug!(
+        path,
+        baud_rate = cfg.baud_rate,
+        data_bits = cfg.data_bits,
+        parity = %cfg.parity,
+        stop_bits = cfg.stop_bits,
+        flow_control = %cfg.flow_control,
+        "Opening serial device"
+    );
+    serialport::new(path, cfg.baud_rate)
+        .data_bits(data_bits)
+        .parity(cfg.parity.into())
+        .stop_bits(stop_bits)
+        .flow_control(cfg.flow_control.into())
+        .timeout(DefmtSerialReader::READ_TIMEOUT)
+        .open()
+        .map_err(|e| Error::SerialPortOpen(path.clone(), e))
+}
+
+fn open_retry_loop(
+    cfg: &SerialCollectorConfig,
+    timeout: humantime::Duration,
+) -> Result<Box<dyn SerialPort>, Error> {
+    debug!(timeout = %timeout, "Starting serial device open retry loop");
+    let timeout: Duration = timeout.into();
+    let start = Instant::now();
+    while Instant::now().duration_since(start) <= timeout {
+        match open_port(cfg) {
+            Ok(p) => return Ok(p),
+            Err(Error::SerialPortOpen(_, _)) | Err(Error::UsbDeviceNotFound(_)) => {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Timeout reached
+    open_port(cfg)
+}
+
+/// Resets the chip through the USB-Serial-JTAG (or USB-UART bridge) control lines,
+/// keeping the boot strapping pin (DTR) released so the firmware boots
+fn esp_hard_reset(port: &mut dyn SerialPort) -> Result<(), Error> {
+    debug!("Resetting the Espressif chip");
+    port.write_data_terminal_ready(false)
+        .map_err(Error::EspReset)?;
+    port.write_request_to_send(true).map_err(Error::EspReset)?;
+    std::thread::sleep(Duration::from_millis(200));
+    port.write_request_to_send(false).map_err(Error::EspReset)?;
+    std::thread::sleep(Duration::from_millis(200));
+    Ok(())
+}
+
+struct DefmtSerialReader {
+    interruptor: Interruptor,
+    cfg: SerialCollectorConfig,
+    port: Box<dyn SerialPort>,
+    /// None when re-opening is disabled
+    reopen_interval: Option<Duration>,
+}
+
+impl DefmtSerialReader {
+    const DEFAULT_REOPEN_INTERVAL: Duration = Duration::from_millis(500);
+    /// How long a read blocks waiting for data before checking the interruptor
+    const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn new(
+        interruptor: Interruptor,
+        cfg: SerialCollectorConfig,
+        port: Box<dyn SerialPort>,
+        reopen_interval: Option<Duration>,
+    ) -> Self {
+        Self {
+            interruptor,
+            cfg,
+            port,
+            reopen_interval,
+        }
+    }
+
+    /// Returns false if we were interrupted before the device was re-opened
+    fn reopen(&mut self, interval: Duration) -> bool {
+        while !self.interruptor.is_set() {
+            std::thread::sleep(interval);
+            match open_port(&self.cfg) {
+                Ok(p) => {
+                    info!(path = p.name(), "Re-opened serial device");
+                    self.port = p;
+                    return true;
+                }
+                Err(e) => {
+                    debug!(error = %e, "Re-open attempt failed");
+                }
+            }
+        }
+        false
+    }
+}
+
+impl io::Read for DefmtSerialReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.interruptor.is_set() {
+            match self.port.read(buf) {
+                Ok(0) => {
+                    let Some(interval) = self.reopen_interval else {
+                        debug!("Serial device closed");
+                        return Ok(0);
+                    };
+                    warn!("Serial device closed, waiting for it to re-appear");
+                    if !self.reopen(interval) {
+                        return Ok(0);
+                    }
+                }
+                Ok(bytes_read) => return Ok(bytes_read),
+                Err(e)
+                    if e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::Interrupted =>
+                {
+                    continue
+                }
+                Err(e) => {
+                    let Some(interval) = self.reopen_interval else {
+                        return Err(e);
+                    };
+                    warn!(error = %e, "Serial device lost, waiting for it to re-appear");
+                    if !self.reopen(interval) {
+                        return Ok(0);
+                    }
+                }
+            }
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,397 @@
+use auxon_sdk::reflector_config::{AttrKey, AttrKeyEqValuePair};
+use clap::Parser;
+use modality_defmt_plugin::{
+    defmt_reader, tracing::try_init_tracing_subscriber, DefmtConfig, DefmtConfigEntry, DefmtOpts,
+    Interruptor, ReflectorOpts,
+};
+use std::{
+    io,
+    net::{SocketAddr, TcpStream},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+/// Collect defmt data from a TCP server, or from TCP clients when listening
+#[derive(Parser, Debug, Clone)]
+#[clap(version)]
+struct Opts {
+    #[clap(flatten)]
+    pub rf_opts: ReflectorOpts,
+
+    #[clap(flatten)]
+    pub defmt_opts: DefmtOpts,
+
+    /// The ELF file containing the defmt table and location information.
+    #[clap(
+        long,
+        name = "elf-file",
+        verbatim_doc_comment,
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub elf_file: Option<PathBuf>,
+
+    /// Specify a connection timeout.
+    /// When provided, the plugin will continually attempt to connect to the remote.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(
+        long,
+        name = "connect-timeout",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub connect_timeout: Option<humantime::Duration>,
+
+    /// The interval between reconnection attempts when the connection is dropped.
+    ///
+    /// The default value is 1s.
+    ///
+    /// Accepts durations like "10ms" or "1minute 2seconds 22ms".
+    #[clap(
+        long,
+        name = "reconnect-interval",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub reconnect_interval: Option<humantime::Duration>,
+
+    /// Exit when the connection is dropped instead of reconnecting.
+    #[clap(
+        long,
+        name = "disable-reconnect",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub disable_reconnect: bool,
+
+    /// Listen for connections on the given address (e.g. 0.0.0.0:3456) instead of
+    /// connecting to a remote server.
+    /// Each accepted connection gets its own set of timelines and run ID.
+    #[clap(
+        long,
+        name = "listen",
+        conflicts_with = "remote",
+        help_heading = "COLLECTOR CONFIGURATION"
+    )]
+    pub listen: Option<String>,
+
+    /// The remote TCP server address to connect to (e.g. localhost:3456)
+    #[clap(name = "remote", help_heading = "COLLECTOR CONFIGURATION")]
+    pub remote: Option<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    match do_main().await {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("{e}");
+            let mut cause = e.source();
+            while let Some(err) = cause {
+                eprintln!("Caused by: {err}");
+                cause = err.source();
+            }
+            std::process::exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+async fn do_main() -> Result<(), Box<dyn std::error::Error>> {
+    let opts = Opts::parse();
+
+    try_init_tracing_subscriber()?;
+
+    let intr = Interruptor::new();
+    let intr_clone = intr.clone();
+    ctrlc::set_handler(move || {
+        if intr_clone.is_set() {
+            let exit_code = if cfg!(target_family = "unix") {
+                // 128 (fatal error signal "n") + 2 (control-c is fatal error signal 2)
+                130
+            } else {
+                // Windows code 3221225786
+                // -1073741510 == C000013A
+                -1073741510
+            };
+            std::process::exit(exit_code);
+        }
+
+        debug!("Shutdown signal received");
+        intr_clone.set();
+    })?;
+
+    let mut defmt_cfg = DefmtConfig::load_merge_with_opts(
+        DefmtConfigEntry::TcpCollector,
+        opts.rf_opts,
+        opts.defmt_opts,
+    )?;
+
+    if let Some(elf_file) = opts.elf_file.as_ref() {
+        defmt_cfg.plugin.elf_file = Some(elf_file.clone());
+    }
+    if let Some(to) = opts.connect_timeout {
+        defmt_cfg.plugin.tcp_collector.connect_timeout = Some(to.into());
+    }
+    if let Some(remote) = opts.remote {
+        defmt_cfg.plugin.tcp_collector.remote = Some(remote);
+        defmt_cfg.plugin.tcp_collector.listen = None;
+    }
+    if let Some(listen) = opts.listen {
+        defmt_cfg.plugin.tcp_collector.listen = Some(listen);
+        defmt_cfg.plugin.tcp_collector.remote = None;
+    }
+    if let Some(interval) = opts.reconnect_interval {
+        defmt_cfg.plugin.tcp_collector.reconnect_interval = Some(interval.into());
+    }
+    if opts.disable_reconnect {
+        defmt_cfg.plugin.tcp_collector.disable_reconnect = true;
+    }
+
+    if let Some(listen) = defmt_cfg.plugin.tcp_collector.listen.clone() {
+        if defmt_cfg.plugin.tcp_collector.remote.is_some() {
+            return Err(Error::RemoteAndListen.into());
+        }
+        return listen_loop(&listen, defmt_cfg, intr).await;
+    }
+
+    let remote = defmt_cfg
+        .plugin
+        .tcp_collector
+        .remote
+        .clone()
+        .ok_or(Error::MissingRemote)?;
+
+    let stream = match defmt_cfg.plugin.tcp_collector.connect_timeout {
+        Some(to) if !to.0.is_zero() => connect_retry_loop(&remote, to.0)?,
+        _ => connect(&remote)?,
+    };
+
+    let reconnect_interval = if defmt_cfg.plugin.tcp_collector.disable_reconnect {
+        None
+    } else {
+        Some(
+            defmt_cfg
+                .plugin
+                .tcp_collector
+                .reconnect_interval
+                .map(|i| i.0.into())
+                .unwrap_or(DefmtTcpReader::DEFAULT_RECONNECT_INTERVAL),
+        )
+    };
+
+    let mut join_handle = tokio::spawn(async move {
+        let mut r = DefmtTcpReader::new(intr.clone(), remote, stream, reconnect_interval);
+        defmt_reader::run(&mut r, defmt_cfg, intr).await
+    });
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            debug!("User signaled shutdown");
+        }
+        res = &mut join_handle => {
+            match res? {
+                Ok(_) => {},
+                Err(e) => {
+                    error!(error = %e, "Encountered and error during streaming");
+                    return Err(e.into())
+                }
+            }
+        }
+    };
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(
+        "Missing remote address. Either supply it as a positional argument at the CLI or a config file member 'remote'"
+    )]
+    MissingRemote,
+
+    #[error("Only one of 'remote' or 'listen' can be provided")]
+    RemoteAndListen,
+
+    #[error("Failed to listen on '{0}'. {1}")]
+    Listen(String, #[source] io::Error),
+
+    #[error("Failed to connect to '{0}'. {1}")]
+    Connect(String, #[source] io::Error),
+}
+
+/// Accepts connections until shutdown, each connection gets its own reader and
+/// ingest session.
+async fn listen_loop(
+    addr: &str,
+    defmt_cfg: DefmtConfig,
+    intr: Interruptor,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| Error::Listen(addr.to_owned(), e))?;
+    info!(addr = %listener.local_addr()?, "Listening for connections");
+
+    let mut connections = tokio::task::JoinSet::new();
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                debug!("User signaled shutdown");
+                break;
+            }
+            res = listener.accept() => {
+                let (stream, peer) = res?;
+                info!(peer = %peer, "Accepted connection");
+                let stream = stream.into_std()?;
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(DefmtTcpReader::READ_TIMEOUT))?;
+
+                let mut conn_cfg = defmt_cfg.clone();
+                conn_cfg
+                    .ingest
+                    .timeline_attributes
+                    .additional_timeline_attributes
+                    .push(AttrKeyEqValuePair(
+                        AttrKey::new(PEER_ADDRESS_ATTR_KEY.to_owned()),
+                        peer.to_string().into(),
+                    ));
+                let conn_intr = intr.clone();
+                let rt = tokio::runtime::Handle::current();
+                connections.spawn_blocking(move || {
+                    let mut r = DefmtTcpReader::new(conn_intr.clone(), peer.to_string(), stream, None);
+                    let res = rt.block_on(defmt_reader::run(&mut r, conn_cfg, conn_intr));
+                    (peer, res)
+                });
+            }
+            Some(res) = connections.join_next() => {
+                log_connection_result(res?);
+            }
+        }
+    }
+
+    intr.set();
+    while let Some(res) = connections.join_next().await {
+        log_connection_result(res?);
+    }
+
+    Ok(())
+}
+
+/// Timeline attribute recording the peer address of an accepted connection
+const PEER_ADDRESS_ATTR_KEY: &str = "timeline.internal.defmt.tcp.peer_address";
+
+fn log_connection_result(res: (SocketAddr, Result<(), modality_defmt_plugin::Error>)) {
+    match res {
+        (peer, Ok(())) => info!(peer = %peer, "Connection closed"),
+        (peer, Err(e)) => {
+            error!(peer = %peer, error = %e, "Encountered and error during streaming")
+        }
+    }
+}
+
+fn connect(remote: &str) -> Result<TcpStream, Error> {
+    debug!(remote, "Connecting");
+    let stream = TcpStream::connect(remote).map_err(|e| Error::Connect(remote.to_owned(), e))?;
+    stream
+        .set_read_timeout(Some(DefmtTcpReader::READ_TIMEOUT))
+        .map_err(|e| Error::Connect(remote.to_owned(), e))?;
+    Ok(stream)
+}
+
+fn connect_retry_loop(remote: &str, timeout: humantime::Duration) -> Result<TcpStream, Error> {
+    debug!(timeout = %timeout, "Starting connect retry loop");
+    let timeout: Duration = timeout.into();
+    let start = Instant::now();
+    while Instant::now().duration_since(start) <= timeout {
+        match connect(remote) {
+            Ok(s) => return Ok(s),
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+        }
+    }
+
+    // Timeout reached
+    connect(remote)
+}
+
+struct DefmtTcpReader {
+    interruptor: Interruptor,
+    remote: String,
+    stream: TcpStream,
+    /// None when reconnecting is disabled
+    reconnect_interval: Option<Duration>,
+}
+
+impl DefmtTcpReader {
+    const DEFAULT_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+    /// How long a read blocks waiting for data before checking the interruptor
+    const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn new(
+        interruptor: Interruptor,
+        remote: String,
+        stream: TcpStream,
+        reconnect_interval: Option<Duration>,
+    ) -> Self {
+        Self {
+            interruptor,
+            remote,
+            stream,
+            reconnect_interval,
+        }
+    }
+
+    /// Returns false if we were interrupted before the connection was re-established
+    fn reconnect(&mut self, interval: Duration) -> bool {
+        while !self.interruptor.is_set() {
+            std::thread::sleep(interval);
+            match connect(&self.remote) {
+                Ok(s) => {
+                    info!(remote = self.remote, "Reconnected");
+                    self.stream = s;
+                    return true;
+                }
+                Err(e) => {
+                    debug!(error = %e, "Reconnect attempt failed");
+                }
+            }
+        }
+        false
+    }
+}
+
+impl io::Read for DefmtTcpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.interruptor.is_set() {
+            match self.stream.read(buf) {
+                Ok(0) => {
+                    let Some(interval) = self.reconnect_interval else {
+                        debug!(remote = self.remote, "Connection closed");
+                        return Ok(0);
+                    };
+                    warn!(remote = self.remote, "Connection closed, reconnecting");
+                    if !self.reconnect(interval) {
+                        return Ok(0);
+                    }
+                }
+                Ok(bytes_read) => return Ok(bytes_read),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut
+                        || e.kind() == io::ErrorKind::Interrupted =>
+                {
+                    continue
+                }
+                Err(e) => {
+                    let Some(interval) = self.reconnect_interval else {
+                        return Err(e);
+                    };
+                    warn!(remote = self.remote, error = %e, "Connection lost, reconnecting");
+                    if !self.reconnect(interval) {
+                        return Ok(0);
+                    }
+                }
+            }
+        }
+        Ok(0)
+    }
+}
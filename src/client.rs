@@ -1,14 +1,17 @@
-use crate::Error;
+use crate::{AttrKey, AttrKeyLimitPolicy, Error};
 use auxon_sdk::{
     api::{AttrVal, TimelineId},
     ingest_client::{dynamic::DynamicIngestClient, IngestClient, ReadyState},
     ingest_protocol::InternedAttrKey,
 };
 use std::collections::BTreeMap;
+use tracing::{debug, warn};
 
 pub struct Client {
     timeline_keys: BTreeMap<String, InternedAttrKey>,
-    event_keys: BTreeMap<String, InternedAttrKey>,
+    /// Indexed by AttrKey::id
+    event_keys: Vec<Option<InternedAttrKey>>,
+    event_key_limit: Option<AttrKeyLimit>,
     pub(crate) inner: DynamicIngestClient,
 }
 
@@ -17,10 +20,25 @@ impl Client {
         Self {
             timeline_keys: Default::default(),
             event_keys: Default::default(),
+            event_key_limit: None,
             inner: client.into(),
         }
     }
 
+    /// Guard against exploding event attribute key cardinality, which slows down ingest
+    pub fn with_event_key_limit(mut self, max: Option<usize>, policy: AttrKeyLimitPolicy) -> Self {
+        self.event_key_limit = max.map(|max| AttrKeyLimit::new(max, policy));
+        self
+    }
+
+    /// The number of event attributes dropped by the event attribute key limit
+    pub fn dropped_event_attrs(&self) -> u64 {
+        self.event_key_limit
+            .as_ref()
+            .map(|l| l.dropped)
+            .unwrap_or(0)
+    }
+
     pub async fn switch_timeline(
         &mut self,
         id: TimelineId,
@@ -48,23 +66,107 @@ impl Client {
     pub async fn send_event(
         &mut self,
         ordering: u128,
-        attrs: impl IntoIterator<Item = (&String, &AttrVal)>,
+        attrs: impl IntoIterator<Item = (&AttrKey, &AttrVal)>,
+        synthetic: bool,
     ) -> Result<(), Error> {
         let mut interned_attrs = Vec::new();
         for (k, v) in attrs.into_iter() {
-            let key = normalize_event_key(k);
-            let int_key = if let Some(ik) = self.event_keys.get(&key) {
-                *ik
-            } else {
-                let ik = self.inner.declare_attr_key(key.clone()).await?;
-                self.event_keys.insert(key, ik);
-                ik
-            };
-            interned_attrs.push((int_key, v.clone()));
+            if let Some(int_key) = self.event_key(k, synthetic).await? {
+                interned_attrs.push((int_key, v.clone()));
+            }
         }
         self.inner.event(ordering, interned_attrs).await?;
         Ok(())
     }
+
+    /// The interned key, None if it was dropped by the key limit.
+    /// The plugin's own keys, including all of the keys of synthetic events, are exempt
+    /// from the limit.
+    async fn event_key(
+        &mut self,
+        key: &AttrKey,
+        synthetic: bool,
+    ) -> Result<Option<InternedAttrKey>, Error> {
+        let idx = key.id() as usize;
+        if let Some(Some(ik)) = self.event_keys.get(idx) {
+            return Ok(Some(*ik));
+        }
+        if let Some(limit) = self.event_key_limit.as_mut() {
+            if !limit.admit(key.as_str(), synthetic) {
+                return Ok(None);
+            }
+        }
+        let ik = self
+            .inner
+            .declare_attr_key(normalize_event_key(key.as_str()))
+            .await?;
+        if idx >= self.event_keys.len() {
+            self.event_keys.resize(idx + 1, None);
+        }
+        self.event_keys[idx] = Some(ik);
+        Ok(Some(ik))
+    }
+}
+
+/// Counts the distinct keys declared to the ingest client
+#[derive(Debug)]
+struct AttrKeyLimit {
+    max: usize,
+    policy: AttrKeyLimitPolicy,
+    declared: usize,
+    warned: bool,
+    dropped: u64,
+}
+
+impl AttrKeyLimit {
+    fn new(max: usize, policy: AttrKeyLimitPolicy) -> Self {
+        Self {
+            max,
+            policy,
+            declared: 0,
+            warned: false,
+            dropped: 0,
+        }
+    }
+
+    /// Whether a new key can be declared. The plugin's own keys don't count towards
+    /// the limit.
+    fn admit(&mut self, key: &str, synthetic: bool) -> bool {
+        if synthetic || is_plugin_event_key(key) {
+            return true;
+        }
+        if self.declared < self.max {
+            self.declared += 1;
+            return true;
+        }
+        if !self.warned {
+            self.warned = true;
+            warn!(
+                max = self.max,
+                policy = %self.policy,
+                "Reached the event attribute key limit, check the firmware for attribute keys derived from variable data"
+            );
+        }
+        match self.policy {
+            AttrKeyLimitPolicy::Warn => {
+                self.declared += 1;
+                true
+            }
+            AttrKeyLimitPolicy::Drop => {
+                debug!(attr_key = key, "Dropping attribute, over the key limit");
+                self.dropped += 1;
+                false
+            }
+        }
+    }
+}
+
+/// Keys the plugin adds to firmware events itself, a fixed set that would otherwise
+/// be dropped when they first appear after the key limit is reached
+fn is_plugin_event_key(key: &str) -> bool {
+    const PREFIXES: &[&str] = &["event.internal.", "event.interaction."];
+    const KEYS: &[&str] = &["event.name", "event.timestamp", "event.timestamp_gap"];
+    PREFIXES.iter().any(|p| key.starts_with(p)) || KEYS.contains(&key)
 }
 
 fn normalize_timeline_key(s: &str) -> String {
@@ -82,3 +184,52 @@ fn normalize_event_key(s: &str) -> String {
         format!("event.{s}")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attr_key_limit() {
+        let mut limit = AttrKeyLimit::new(2, AttrKeyLimitPolicy::Warn);
+        assert!(limit.admit("event.a", false));
+        assert!(limit.admit("event.b", false));
+        assert!(limit.admit("event.c", false));
+        assert_eq!(limit.declared, 3);
+        assert_eq!(limit.dropped, 0);
+
+        let mut limit = AttrKeyLimit::new(2, AttrKeyLimitPolicy::Drop);
+        assert!(limit.admit("event.a", false));
+        assert!(limit.admit("event.b", false));
+        assert!(!limit.admit("event.c", false));
+        assert!(!limit.admit("event.c", false));
+        assert_eq!(limit.declared, 2);
+        assert_eq!(limit.dropped, 2);
+    }
+
+    #[test]
+    fn attr_key_limit_exempts_plugin_keys() {
+        let mut limit = AttrKeyLimit::new(1, AttrKeyLimitPolicy::Drop);
+        assert!(limit.admit("event.a", false));
+        assert!(!limit.admit("event.b", false));
+        assert!(limit.admit("event.interaction.remote_timeline_id", false));
+        assert!(limit.admit("event.interaction.remote_nonce", false));
+        assert!(limit.admit("event.timestamp_gap", false));
+        assert!(limit.admit("event.fault.kind", true));
+        assert!(limit.admit("event.crash.register.pc", true));
+        assert!(!limit.admit("event.fault.kind", false));
+        assert_eq!(limit.declared, 1);
+        assert_eq!(limit.dropped, 2);
+    }
+
+    #[test]
+    fn plugin_event_keys() {
+        assert!(is_plugin_event_key("event.interaction.remote_timeline_id"));
+        assert!(is_plugin_event_key("event.interaction.remote_nonce"));
+        assert!(is_plugin_event_key("event.internal.defmt.nonce"));
+        assert!(is_plugin_event_key("event.timestamp_gap"));
+        assert!(!is_plugin_event_key("event.interaction"));
+        assert!(!is_plugin_event_key("event.timestamp_gap_ms"));
+        assert!(!is_plugin_event_key("event.sensor_3"));
+    }
+}
@@ -1,18 +1,38 @@
 use crate::{
-    opts::{DefmtOpts, ReflectorOpts, RtosMode},
+    event_record::EventRecord,
+    framing::{FrameCrc, Framing},
+    glob::Glob,
+    logic_capture::LogicCapture,
+    opts::{
+        AttrKeyLimitPolicy, BackwardsTimestampPolicy, ByteEncoding, PreStartPolicy, RtosMode,
+        TimestampComposition, UnnamedEventNames,
+    },
+    pcap::PcapFilter,
     time::Rate,
 };
-use auxon_sdk::{
-    auth_token::AuthToken,
-    reflector_config::{Config, TomlValue, TopLevelIngest, CONFIG_ENV_VAR},
-};
-use derive_more::{Deref, From, Into};
+use auxon_sdk::api::AttrVal;
+use derive_more::{Deref, Display, From, Into};
 use serde::Deserialize;
-use std::env;
-use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+#[cfg(any(feature = "serial-collector", feature = "process-collector"))]
+use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
-use url::Url;
+#[cfg(feature = "rtt-collector")]
+use std::time::Duration;
+#[cfg(feature = "ingest")]
+use {
+    crate::opts::{DefmtOpts, ReflectorOpts},
+    auxon_sdk::{
+        auth_token::AuthToken,
+        reflector_config::{AttrKeyEqValuePair, Config, TomlValue, TopLevelIngest, CONFIG_ENV_VAR},
+    },
+    std::env,
+    std::path::Path,
+    url::Url,
+};
 
+#[cfg(feature = "ingest")]
 #[derive(Debug, thiserror::Error)]
 pub enum AuthTokenError {
     #[error(transparent)]
@@ -22,13 +42,52 @@ pub enum AuthTokenError {
     LoadAuthTokenError(#[from] auxon_sdk::auth_token::LoadAuthTokenError),
 }
 
+#[cfg(feature = "ingest")]
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EnvVarExpansionError {
+    #[error("The environment variable '{0}' referenced in the configuration is not set")]
+    NotSet(String),
+
+    #[error("Unterminated environment variable reference in the configuration value '{0}'")]
+    Unterminated(String),
+}
+
+#[cfg(feature = "ingest")]
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ConfigLayerError {
+    #[error("The configuration file '{0}' includes itself")]
+    IncludeCycle(PathBuf),
+
+    #[error("The '{0}' configuration field must be a string or an array of strings")]
+    InvalidStringList(&'static str),
+
+    #[error("The '{0}' configuration field must be a table of profile tables")]
+    InvalidProfiles(&'static str),
+
+    #[error("The configuration profile '{0}' doesn't exist")]
+    UnknownProfile(String),
+}
+
+#[cfg(feature = "ingest")]
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
 pub enum DefmtConfigEntry {
     #[default]
     Importer,
+    #[cfg(feature = "rtt-collector")]
     RttCollector,
+    #[cfg(feature = "serial-collector")]
+    SerialCollector,
+    #[cfg(feature = "tcp-collector")]
+    TcpCollector,
+    #[cfg(feature = "openocd-collector")]
+    OpenOcdCollector,
+    #[cfg(feature = "process-collector")]
+    ProcessCollector,
+    #[cfg(feature = "renode-collector")]
+    RenodeCollector,
 }
 
+#[cfg(feature = "ingest")]
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct DefmtConfig {
     pub auth_token: Option<String>,
@@ -43,12 +102,242 @@ pub struct PluginConfig {
     pub clock_id: Option<String>,
     pub init_task_name: Option<String>,
     pub disable_interactions: bool,
+    pub disable_linear_causality: bool,
     pub clock_rate: Option<Rate>,
     pub rtos_mode: RtosMode,
+    pub pre_start_policy: PreStartPolicy,
+    pub rtos_markers: RtosMarkers,
+    pub fold_isrs: Vec<Glob>,
     pub elf_file: Option<PathBuf>,
+    pub elf_reload: bool,
+    pub debug_file: Option<PathBuf>,
+    pub outcome_rules: Vec<OutcomeRule>,
+    pub message_correlations: Vec<MessageCorrelation>,
+    pub boot_index: Option<u64>,
+    pub segment_runs: bool,
+    pub reboot_event: Option<String>,
+    pub reboot_timestamp_threshold: Option<u64>,
+    pub core_id: Option<u64>,
+    pub context_stats: bool,
+    pub max_context_depth: Option<usize>,
+    pub unknown_context_name: Option<String>,
+    pub unknown_context_per_source: bool,
+    pub phase_marker_event: Option<String>,
+    pub promote_internal_attributes: Vec<Glob>,
+    pub framing: Framing,
+    pub frame_crc: FrameCrc,
+    pub max_array_elements: Option<usize>,
+    pub byte_encoding: ByteEncoding,
+    pub display_hints: bool,
+    pub println_level: Option<String>,
+    pub timestamp_composition: TimestampComposition,
+    pub timestamp_width: Option<u32>,
+    pub host_time_fallback: bool,
+    pub backwards_timestamp_policy: BackwardsTimestampPolicy,
+    pub timestamp_gap_threshold: Option<HumanTime>,
+    pub event_name_delimiter: Option<String>,
+    pub attr_separator: Option<char>,
+    pub literal_event_names: bool,
+    pub unnamed_event_names: UnnamedEventNames,
+    pub normalize_event_names: bool,
+    pub max_event_name_length: Option<usize>,
+    pub max_attribute_keys: Option<usize>,
+    pub attribute_key_limit_policy: AttrKeyLimitPolicy,
+    pub infer_units: bool,
+    pub attribute_renames: BTreeMap<String, String>,
+    pub attribute_coercions: Vec<AttrCoercion>,
+    pub event_filter: EventFilter,
+    /// Stops collection at the first event matching any of the triggers
+    pub stop_on: Vec<StopTrigger>,
+    pub exclude_table_indices: Vec<u64>,
+    pub exclude_format_strings: Vec<Glob>,
+    pub omit_internal_attributes: bool,
+    pub omit_formatted_string: bool,
+    pub debug_attributes: bool,
+    pub message_attribute: bool,
+    pub raw_frame_attributes: bool,
+    /// Periodically log the decode and ingest throughput at this interval
+    pub metrics_interval: Option<HumanTime>,
+    /// Also ingest the periodic metrics as events
+    pub metric_events: bool,
+    /// Write a copy of the raw defmt byte stream to this file while ingesting
+    pub save_raw: Option<PathBuf>,
+    /// Rotate the raw capture file once it reaches this many bytes
+    pub save_raw_rotate_size: Option<u64>,
+    /// The number of rotated raw capture files to keep
+    pub save_raw_rotate_count: Option<usize>,
+    /// Write a JSON summary of the run to this file on exit
+    pub summary_file: Option<PathBuf>,
+    pub source_path_remaps: Vec<SourcePathRemap>,
+    pub source_urls: Vec<SourceUrlMapping>,
+    pub source_commit: Option<String>,
+    #[cfg(feature = "ingest")]
+    pub additional_event_attributes: Vec<AttrKeyEqValuePair>,
 
     pub import: ImportConfig,
+    #[cfg(feature = "rtt-collector")]
     pub rtt_collector: RttCollectorConfig,
+    #[cfg(feature = "serial-collector")]
+    pub serial_collector: SerialCollectorConfig,
+    #[cfg(feature = "tcp-collector")]
+    pub tcp_collector: TcpCollectorConfig,
+    #[cfg(feature = "openocd-collector")]
+    pub openocd_collector: OpenOcdCollectorConfig,
+    #[cfg(feature = "process-collector")]
+    pub process_collector: ProcessCollectorConfig,
+    #[cfg(feature = "renode-collector")]
+    pub renode_collector: RenodeCollectorConfig,
+}
+
+impl PluginConfig {
+    const DEFAULT_READ_BUFFER_SIZE: usize = 1024;
+    pub(crate) const DEFAULT_UNKNOWN_CONTEXT_NAME: &'static str = "UNKNOWN_CONTEXT";
+    pub(crate) const DEFAULT_MAX_ARRAY_ELEMENTS: usize = 32;
+    pub(crate) const DEFAULT_PRINTLN_LEVEL: &'static str = "println";
+    pub(crate) const DEFAULT_EVENT_NAME_DELIMITER: &'static str = "::";
+    pub(crate) const DEFAULT_ATTR_SEPARATOR: char = ',';
+
+    /// The name of the context of events whose context is unknown
+    pub fn unknown_context_name(&self) -> &str {
+        self.unknown_context_name
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_UNKNOWN_CONTEXT_NAME)
+    }
+
+    /// The maximum number of array or slice argument elements recorded as indexed attributes
+    pub fn max_array_elements(&self) -> usize {
+        self.max_array_elements
+            .unwrap_or(Self::DEFAULT_MAX_ARRAY_ELEMENTS)
+    }
+
+    /// The level of frames that don't have one (`defmt::println!`)
+    pub fn println_level(&self) -> &str {
+        self.println_level
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_PRINTLN_LEVEL)
+    }
+
+    /// The delimiter between the event name and the rest of the format string
+    pub fn event_name_delimiter(&self) -> &str {
+        self.event_name_delimiter
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_EVENT_NAME_DELIMITER)
+    }
+
+    /// The separator between key/value pairs in the format string
+    pub fn attr_separator(&self) -> char {
+        self.attr_separator.unwrap_or(Self::DEFAULT_ATTR_SEPARATOR)
+    }
+
+    /// Whether the internal event attributes (`event.internal.defmt.*`) are omitted from ingest.
+    /// Promoted attributes are kept.
+    pub fn omits_internal_attributes(&self) -> bool {
+        self.omit_internal_attributes && !self.debug_attributes
+    }
+
+    /// Whether the formatted string event attribute is omitted from ingest
+    pub fn omits_formatted_string(&self) -> bool {
+        (self.omit_formatted_string || self.omit_internal_attributes) && !self.debug_attributes
+    }
+
+    /// The source file path with the first matching prefix remapped
+    pub fn remap_source_path(&self, path: &str) -> String {
+        self.source_path_remaps
+            .iter()
+            .find_map(|r| r.remap(path))
+            .unwrap_or_else(|| path.to_owned())
+    }
+
+    /// The `event.source.uri` of a (remapped) source location, the first matching
+    /// source URL mapping or a `file://` URI
+    pub fn source_uri(&self, file: &str, line: u64) -> String {
+        self.source_urls
+            .iter()
+            .find_map(|m| m.url(file, line, self.source_commit.as_deref()))
+            .unwrap_or_else(|| format!("file://{file}:{line}"))
+    }
+
+    /// Whether frames of the table entry are skipped right after decoding, by table index
+    /// or format string
+    pub fn excludes_frame(&self, table_index: u64, format: &str) -> bool {
+        self.exclude_table_indices.contains(&table_index)
+            || self
+                .exclude_format_strings
+                .iter()
+                .any(|p| p.is_match(format))
+    }
+
+    /// The size of the buffer used when reading from the input
+    #[cfg(feature = "rtt-collector")]
+    pub fn read_buffer_size(&self) -> usize {
+        self.rtt_collector.rtt_read_buffer_size
+    }
+
+    /// The size of the buffer used when reading from the input
+    #[cfg(not(feature = "rtt-collector"))]
+    pub fn read_buffer_size(&self) -> usize {
+        Self::DEFAULT_READ_BUFFER_SIZE
+    }
+
+    /// The configuration of a single supervised RTT target, the target's
+    /// members override the top-level ones
+    #[cfg(feature = "rtt-collector")]
+    pub fn for_rtt_target(&self, target: &RttTargetConfig) -> PluginConfig {
+        let mut c = self.clone();
+        c.rtt_collector.targets.clear();
+        if let Some(run_id) = &target.run_id {
+            c.run_id = Some(run_id.clone());
+        }
+        if let Some(elf_file) = &target.elf_file {
+            c.elf_file = Some(elf_file.clone());
+            // The debug file belongs to the top-level ELF file
+            c.debug_file = None;
+        }
+        if let Some(path) = &c.save_raw {
+            c.save_raw = Some(crate::raw_capture::source_capture_path(path, &target.name));
+        }
+        if let Some(path) = &c.summary_file {
+            c.summary_file = Some(crate::raw_capture::source_capture_path(path, &target.name));
+        }
+        let rtt = &mut c.rtt_collector;
+        if let Some(ps) = &target.probe_selector {
+            rtt.probe_selector = Some(ps.clone());
+        }
+        if let Some(chip) = &target.chip {
+            rtt.chip = Some(chip.clone());
+        }
+        if let Some(protocol) = target.protocol {
+            rtt.protocol = protocol;
+        }
+        if let Some(speed) = target.speed {
+            rtt.speed = speed;
+        }
+        if let Some(core) = target.core {
+            rtt.core = core;
+        }
+        if let Some(addr) = target.control_block_address {
+            rtt.control_block_address = Some(addr);
+        }
+        if let Some(up_channel) = target.up_channel {
+            rtt.up_channel = up_channel;
+            rtt.up_channel_name = None;
+            rtt.up_channels.clear();
+        }
+        if !target.up_channels.is_empty() {
+            rtt.up_channels = target.up_channels.clone();
+        }
+        if let Some(bp) = &target.setup_on_breakpoint {
+            rtt.setup_on_breakpoint = Some(bp.clone());
+            rtt.setup_stages.clear();
+        }
+        if !target.setup_stages.is_empty() {
+            rtt.setup_stages = target.setup_stages.clone();
+        }
+        if let Some(ring_buffer) = &target.ring_buffer {
+            rtt.ring_buffer = Some(ring_buffer.clone());
+        }
+        c
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
@@ -56,14 +345,121 @@ pub struct PluginConfig {
 pub struct ImportConfig {
     pub open_timeout: Option<HumanTime>,
     pub file: Option<PathBuf>,
+    pub unix_socket: Option<PathBuf>,
+    pub unix_socket_listen: bool,
+    pub named_pipe: Option<String>,
+    pub named_pipe_listen: bool,
+    pub compression: InputCompression,
+    /// Treat the input as a pcap or pcapng capture, extracting the defmt data
+    /// from the packets matching the filter
+    pub pcap_filter: Option<PcapFilter>,
+    /// Treat the input as a logic analyzer export of decoded UART bytes
+    pub logic_capture: Option<LogicCapture>,
+    /// Sample rate (Hz) of the logic analyzer capture, used to convert
+    /// sample numbers to time
+    pub capture_sample_rate: Option<u64>,
+    /// Treat the input as a `JLinkRTTLogger` file, stripping the SEGGER
+    /// log header and block marker lines
+    pub jlink_rtt_logger: bool,
+    /// Treat the input as a post-mortem RAM dump or ELF core file, and import
+    /// the residual data of an RTT up channel buffer
+    pub rtt_dump: bool,
+    /// Base address of a raw RAM dump
+    pub rtt_dump_address: Option<u64>,
+    /// RTT control block address, instead of the firmware's `_SEGGER_RTT` symbol
+    pub rtt_dump_control_block_address: Option<u64>,
+    pub rtt_dump_up_channel: usize,
+    /// Also import the data the host already read, which precedes the unread data
+    /// in the ring buffer
+    pub rtt_dump_history: bool,
+}
+
+/// The compression of the importer's input file or stream
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    Default,
+    Display,
+    serde_with::DeserializeFromStr,
+)]
+pub enum InputCompression {
+    /// Detected from the magic bytes, or the file extension
+    #[default]
+    #[display(fmt = "auto")]
+    Auto,
+    #[display(fmt = "none")]
+    None,
+    #[display(fmt = "gzip")]
+    Gzip,
+    #[display(fmt = "zstd")]
+    Zstd,
+}
+
+impl InputCompression {
+    const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+    const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+    /// Resolves `Auto` given the start of the input and its path, if any.
+    /// The magic bytes take precedence over the file extension.
+    pub fn detect(self, header: &[u8], path: Option<&std::path::Path>) -> Self {
+        if self != InputCompression::Auto {
+            return self;
+        }
+        if header.starts_with(&Self::GZIP_MAGIC) {
+            return InputCompression::Gzip;
+        }
+        if header.starts_with(&Self::ZSTD_MAGIC) {
+            return InputCompression::Zstd;
+        }
+        // Only trust the extension when there isn't enough data to tell
+        if header.len() < Self::ZSTD_MAGIC.len() {
+            match path
+                .and_then(|p| p.extension())
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_lowercase())
+                .as_deref()
+            {
+                Some("gz" | "gzip") => return InputCompression::Gzip,
+                Some("zst" | "zstd") => return InputCompression::Zstd,
+                _ => (),
+            }
+        }
+        InputCompression::None
+    }
+}
+
+impl FromStr for InputCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "auto" => InputCompression::Auto,
+            "none" => InputCompression::None,
+            "gzip" | "gz" => InputCompression::Gzip,
+            "zstd" | "zst" => InputCompression::Zstd,
+            _ => return Err(format!("Unsupported input compression '{s}'")),
+        })
+    }
 }
 
+#[cfg(feature = "rtt-collector")]
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "kebab-case", default)]
 pub struct RttCollectorConfig {
     pub attach_timeout: Option<HumanTime>,
     pub control_block_address: Option<u32>,
     pub up_channel: usize,
+    /// Selects the up channel by name instead of `up_channel`
+    pub up_channel_name: Option<String>,
+    pub up_channels: Vec<RttUpChannel>,
+    /// Up channels carrying plain text logs, each ingested on its own timeline
+    pub text_up_channels: Vec<RttUpChannel>,
     pub probe_selector: Option<ProbeSelector>,
     pub chip: Option<String>,
     pub protocol: probe_rs::probe::WireProtocol,
@@ -71,28 +467,178 @@ pub struct RttCollectorConfig {
     pub core: usize,
     pub reset: bool,
     pub attach_under_reset: bool,
+    /// Flash the ELF file before attaching
+    pub download: bool,
+    pub verify: bool,
     pub chip_description_path: Option<PathBuf>,
+    /// The core architecture, detected from the chip by default
+    pub arch: Option<TargetArch>,
+    /// Only applies to Arm cores
     pub thumb: bool,
     pub setup_on_breakpoint: Option<String>,
+    /// Staged start, takes precedence over `setup_on_breakpoint`
+    pub setup_stages: Vec<SetupStage>,
+    /// The up channel mode to set, by default only BlockIfFull after the breakpoint setup
+    pub channel_mode: Option<RttChannelMode>,
     pub rtt_read_buffer_size: usize,
     pub rtt_poll_interval: Option<HumanTime>,
+    /// The polling interval once polls stop returning data
+    pub rtt_idle_poll_interval: Option<HumanTime>,
+    pub rtt_poll_policy: RttPollPolicy,
     pub metrics: bool,
+    pub targets: Vec<RttTargetConfig>,
+    pub restart_interval: Option<HumanTime>,
+    pub status_interval: Option<HumanTime>,
+    pub ring_buffer: Option<RingBufferConfig>,
+    /// Release the probe between polls so other tools can use it
+    pub shared_probe: bool,
+    /// Re-run the attach sequence after the target resets, instead of terminating
+    pub reattach: bool,
+    /// The maximum number of re-attaches, unlimited by default
+    pub reattach_attempts: Option<u32>,
+    pub reattach_interval: Option<HumanTime>,
+    /// End the session when no data has arrived for this long
+    pub exit_on_idle: Option<HumanTime>,
+    /// Never halt, reset, set breakpoints on or change the channel modes of the target
+    pub non_intrusive: bool,
+    /// Halt the core when a stop trigger ends the collection
+    pub halt_on_stop: bool,
+    /// Drain the RTT buffers once and exit, leaving the target as it is
+    pub snapshot: bool,
+    /// Read the core registers and memory into a crash event when a crash event is observed
+    pub crash_capture: bool,
+    /// The events that trigger a crash capture, panic and HardFault events by default
+    pub crash_events: Vec<StopTrigger>,
+    /// Bytes of stack read at the stack pointer
+    pub crash_stack_size: Option<u32>,
+    /// Additional memory windows read into the crash event
+    pub crash_memory: Vec<MemoryWindow>,
+    /// Memory sampled at `watch-interval` onto the metrics timeline, values of up to 8 bytes
+    pub watch: Vec<MemoryWindow>,
+    pub watch_interval: Option<HumanTime>,
+    /// Enable vector catch on fault exceptions, synthesizing a fault event when the core halts
+    pub fault_catch: bool,
+    /// What to do with the core after a caught fault
+    pub fault_action: FaultAction,
+    /// Commands run on the core before attaching to RTT, e.g. clock setup
+    pub pre_attach: Vec<ProbeCommand>,
+    /// Commands run on the core after attaching to RTT, before streaming
+    pub post_attach: Vec<ProbeCommand>,
+    /// A Deviant mutation written to the firmware's staging statics before the core runs
+    pub staged_mutation: Option<StagedMutation>,
+    /// Clear the firmware's staged mutation flag before the core runs
+    pub clear_staged_mutation: bool,
+}
+
+/// A firmware-defined RAM ring buffer, polled through the debug probe instead of RTT.
+///
+/// The locations are symbol names from the ELF file or addresses.
+/// The head and tail are `u32` byte offsets into the buffer.
+#[cfg(feature = "rtt-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RingBufferConfig {
+    /// The buffer storage
+    pub buffer: String,
+    /// Size of the buffer in bytes, defaults to the size of the buffer symbol
+    pub size: Option<u32>,
+    /// The write offset, advanced by the target
+    pub head: String,
+    /// The read offset, advanced by the collector
+    pub tail: String,
+}
+
+/// A target driven by the RTT collector supervisor mode
+#[cfg(feature = "rtt-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RttTargetConfig {
+    pub name: String,
+    pub run_id: Option<String>,
+    pub elf_file: Option<PathBuf>,
+    pub probe_selector: Option<ProbeSelector>,
+    pub chip: Option<String>,
+    pub protocol: Option<probe_rs::probe::WireProtocol>,
+    pub speed: Option<u32>,
+    pub core: Option<usize>,
+    pub control_block_address: Option<u32>,
+    pub up_channel: Option<usize>,
+    pub up_channels: Vec<RttUpChannel>,
+    pub setup_on_breakpoint: Option<String>,
+    pub setup_stages: Vec<SetupStage>,
+    pub ring_buffer: Option<RingBufferConfig>,
 }
 
+#[cfg(feature = "rtt-collector")]
 impl RttCollectorConfig {
     pub const DEFAULT_UP_CHANNEL: usize = 0;
     pub const DEFAULT_PROTOCOL: probe_rs::probe::WireProtocol = probe_rs::probe::WireProtocol::Swd;
     pub const DEFAULT_SPEED: u32 = 4000;
     pub const DEFAULT_CORE: usize = 0;
-    const DEFAULT_RTT_BUFFER_SIZE: usize = 1024;
+    pub const DEFAULT_CRASH_STACK_SIZE: u32 = 256;
+    const DEFAULT_CRASH_EVENTS: [&'static str; 2] = ["panic*", "*HardFault*"];
+    const DEFAULT_RTT_BUFFER_SIZE: usize = PluginConfig::DEFAULT_READ_BUFFER_SIZE;
+
+    /// The up channels to collect from, followed by the plain text channels.
+    /// `up-channels` takes precedence over the single `up-channel`.
+    pub fn selected_up_channels(&self) -> Vec<RttUpChannel> {
+        let text_channels = self.text_up_channels.iter().map(|ch| RttUpChannel {
+            format: RttChannelFormat::Text,
+            ..ch.clone()
+        });
+        let channels = if self.up_channels.is_empty() {
+            vec![RttUpChannel {
+                channel: self.up_channel,
+                name: self.up_channel_name.clone(),
+                elf_file: None,
+                core_id: None,
+                format: RttChannelFormat::Defmt,
+            }]
+        } else {
+            self.up_channels.clone()
+        };
+        channels.into_iter().chain(text_channels).collect()
+    }
+
+    /// The staged start sequence.
+    /// `setup-stages` takes precedence over the single-stage `setup-on-breakpoint`.
+    pub fn selected_setup_stages(&self) -> Vec<SetupStage> {
+        match self.setup_on_breakpoint.as_ref() {
+            Some(bp) if self.setup_stages.is_empty() => vec![SetupStage {
+                breakpoint: bp.clone(),
+                ..Default::default()
+            }],
+            _ => self.setup_stages.clone(),
+        }
+    }
+
+    /// The events that trigger a crash capture, panic and HardFault events
+    /// unless `crash-events` is provided
+    pub fn selected_crash_events(&self) -> Vec<StopTrigger> {
+        if self.crash_events.is_empty() {
+            Self::DEFAULT_CRASH_EVENTS
+                .into_iter()
+                .map(|name| StopTrigger {
+                    name: Some(Glob::new(name)),
+                    ..Default::default()
+                })
+                .collect()
+        } else {
+            self.crash_events.clone()
+        }
+    }
 }
 
+#[cfg(feature = "rtt-collector")]
 impl Default for RttCollectorConfig {
     fn default() -> Self {
         Self {
             attach_timeout: None,
             control_block_address: None,
             up_channel: Self::DEFAULT_UP_CHANNEL,
+            up_channel_name: None,
+            up_channels: Vec::new(),
+            text_up_channels: Vec::new(),
             probe_selector: None,
             chip: None,
             protocol: Self::DEFAULT_PROTOCOL,
@@ -100,312 +646,3794 @@ impl Default for RttCollectorConfig {
             core: Self::DEFAULT_CORE,
             reset: false,
             attach_under_reset: false,
+            download: false,
+            verify: false,
             chip_description_path: None,
+            arch: None,
             thumb: false,
             setup_on_breakpoint: None,
+            setup_stages: Vec::new(),
+            channel_mode: None,
             rtt_read_buffer_size: Self::DEFAULT_RTT_BUFFER_SIZE,
             rtt_poll_interval: None,
+            rtt_idle_poll_interval: None,
+            rtt_poll_policy: Default::default(),
             metrics: false,
+            targets: Vec::new(),
+            restart_interval: None,
+            status_interval: None,
+            ring_buffer: None,
+            shared_probe: false,
+            reattach: false,
+            reattach_attempts: None,
+            reattach_interval: None,
+            exit_on_idle: None,
+            non_intrusive: false,
+            halt_on_stop: false,
+            snapshot: false,
+            crash_capture: false,
+            crash_events: Vec::new(),
+            crash_stack_size: None,
+            crash_memory: Vec::new(),
+            watch: Vec::new(),
+            watch_interval: None,
+            fault_catch: false,
+            fault_action: Default::default(),
+            pre_attach: Vec::new(),
+            post_attach: Vec::new(),
+            staged_mutation: None,
+            clear_staged_mutation: false,
         }
     }
 }
 
-#[derive(Clone, Debug, From, Into, Deref, serde_with::DeserializeFromStr)]
-pub struct ProbeSelector(pub probe_rs::probe::DebugProbeSelector);
-
-impl PartialEq for ProbeSelector {
-    fn eq(&self, other: &Self) -> bool {
-        self.0.vendor_id == other.0.vendor_id
-            && self.0.product_id == other.0.product_id
-            && self.0.serial_number == other.0.serial_number
-    }
+#[cfg(feature = "serial-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SerialCollectorConfig {
+    pub open_timeout: Option<HumanTime>,
+    pub path: Option<String>,
+    pub usb_device: Option<UsbDeviceSelector>,
+    pub baud_rate: u32,
+    pub data_bits: u8,
+    pub parity: SerialParity,
+    pub stop_bits: u8,
+    pub flow_control: SerialFlowControl,
+    pub reopen_interval: Option<HumanTime>,
+    pub disable_reopen: bool,
+    pub esp_usb_serial_jtag: bool,
+    pub esp_reset: bool,
 }
 
-impl Eq for ProbeSelector {}
+#[cfg(feature = "serial-collector")]
+impl SerialCollectorConfig {
+    pub const DEFAULT_BAUD_RATE: u32 = 115200;
+    pub const DEFAULT_DATA_BITS: u8 = 8;
+    pub const DEFAULT_STOP_BITS: u8 = 1;
+}
 
-impl FromStr for ProbeSelector {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(
-            probe_rs::probe::DebugProbeSelector::from_str(s).map_err(|e| e.to_string())?,
-        ))
+#[cfg(feature = "serial-collector")]
+impl Default for SerialCollectorConfig {
+    fn default() -> Self {
+        Self {
+            open_timeout: None,
+            path: None,
+            usb_device: None,
+            baud_rate: Self::DEFAULT_BAUD_RATE,
+            data_bits: Self::DEFAULT_DATA_BITS,
+            parity: Default::default(),
+            stop_bits: Self::DEFAULT_STOP_BITS,
+            flow_control: Default::default(),
+            reopen_interval: None,
+            disable_reopen: false,
+            esp_usb_serial_jtag: false,
+            esp_reset: false,
+        }
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, From, Into, Deref, serde_with::DeserializeFromStr)]
-pub struct HumanTime(pub humantime::Duration);
+/// Selects a USB serial device (e.g. a CDC-ACM interface) by 'VID:PID[:SERIAL]'
+#[cfg(feature = "serial-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, serde_with::DeserializeFromStr)]
+pub struct UsbDeviceSelector {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial_number: Option<String>,
+}
 
-impl FromStr for HumanTime {
-    type Err = humantime::DurationError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(humantime::Duration::from_str(s)?))
+#[cfg(feature = "serial-collector")]
+impl UsbDeviceSelector {
+    pub fn matches(&self, vendor_id: u16, product_id: u16, serial_number: Option<&str>) -> bool {
+        self.vendor_id == vendor_id
+            && self.product_id == product_id
+            && match self.serial_number.as_deref() {
+                Some(sn) => serial_number == Some(sn),
+                None => true,
+            }
     }
 }
 
-impl DefmtConfig {
-    pub fn load_merge_with_opts(
-        entry: DefmtConfigEntry,
-        rf_opts: ReflectorOpts,
-        defmt_opts: DefmtOpts,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let cfg = if let Some(cfg_path) = &rf_opts.config_file {
-            auxon_sdk::reflector_config::try_from_file(cfg_path)?
-        } else if let Ok(env_path) = env::var(CONFIG_ENV_VAR) {
-            auxon_sdk::reflector_config::try_from_file(Path::new(&env_path))?
-        } else {
-            Config::default()
-        };
-
-        let mut ingest = cfg.ingest.clone().unwrap_or_default();
-        if let Some(url) = &rf_opts.protocol_parent_url {
-            ingest.protocol_parent_url = Some(url.clone());
-        }
-        if rf_opts.allow_insecure_tls {
-            ingest.allow_insecure_tls = true;
-        }
+#[cfg(feature = "serial-collector")]
+impl FromStr for UsbDeviceSelector {
+    type Err = String;
 
-        let cfg_plugin = PluginConfig::from_metadata(&cfg, entry)?;
-        let plugin = PluginConfig {
-            client_timeout: rf_opts
-                .client_timeout
-                .map(|t| t.into())
-                .or(cfg_plugin.client_timeout),
-            run_id: rf_opts.run_id.or(cfg_plugin.run_id),
-            clock_id: rf_opts.clock_id.or(cfg_plugin.clock_id),
-            init_task_name: defmt_opts.init_task_name.or(cfg_plugin.init_task_name),
-            disable_interactions: if defmt_opts.disable_interactions {
-                true
-            } else {
-                cfg_plugin.disable_interactions
-            },
-            clock_rate: defmt_opts.clock_rate.or(cfg_plugin.clock_rate),
-            rtos_mode: defmt_opts.rtos_mode.unwrap_or(cfg_plugin.rtos_mode),
-            elf_file: cfg_plugin.elf_file, // NOTE: plugin opts handling may override this
-            import: cfg_plugin.import,
-            rtt_collector: cfg_plugin.rtt_collector,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_id = |id: Option<&str>| {
+            id.map(|id| id.trim().trim_start_matches("0x"))
+                .and_then(|id| u16::from_str_radix(id, 16).ok())
+                .ok_or_else(|| {
+                    format!("Invalid USB device selector '{s}', expected 'VID:PID[:SERIAL]'")
+                })
         };
-
+        let mut parts = s.splitn(3, ':');
+        let vendor_id = parse_id(parts.next())?;
+        let product_id = parse_id(parts.next())?;
+        let serial_number = parts
+            .next()
+            .map(|sn| sn.trim().to_owned())
+            .filter(|sn| !sn.is_empty());
         Ok(Self {
-            auth_token: rf_opts.auth_token,
-            ingest,
-            plugin,
+            vendor_id,
+            product_id,
+            serial_number,
         })
     }
+}
 
-    pub fn protocol_parent_url(&self) -> Result<Url, url::ParseError> {
-        if let Some(url) = &self.ingest.protocol_parent_url {
-            Ok(url.clone())
-        } else {
-            let url = Url::parse("modality-ingest://127.0.0.1:14188")?;
-            Ok(url)
+#[cfg(feature = "serial-collector")]
+impl fmt::Display for UsbDeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}:{:04x}", self.vendor_id, self.product_id)?;
+        if let Some(sn) = &self.serial_number {
+            write!(f, ":{sn}")?;
         }
+        Ok(())
     }
+}
 
-    pub fn resolve_auth(&self) -> Result<AuthToken, AuthTokenError> {
-        if let Some(auth_token_hex) = self.auth_token.as_deref() {
-            Ok(auxon_sdk::auth_token::decode_auth_token_hex(
-                auth_token_hex,
-            )?)
-        } else {
-            Ok(AuthToken::load()?)
-        }
-    }
+#[cfg(feature = "serial-collector")]
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    Default,
+    Display,
+    serde_with::DeserializeFromStr,
+)]
+pub enum SerialParity {
+    #[default]
+    #[display(fmt = "none")]
+    None,
+    #[display(fmt = "odd")]
+    Odd,
+    #[display(fmt = "even")]
+    Even,
 }
 
-mod internal {
-    use super::*;
+#[cfg(feature = "serial-collector")]
+impl FromStr for SerialParity {
+    type Err = String;
 
-    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
-    #[serde(rename_all = "kebab-case", default)]
-    pub struct CommonPluginConfig {
-        pub client_timeout: Option<HumanTime>,
-        pub run_id: Option<String>,
-        pub clock_id: Option<String>,
-        pub init_task_name: Option<String>,
-        pub disable_interactions: bool,
-        pub clock_rate: Option<Rate>,
-        pub rtos_mode: RtosMode,
-        pub elf_file: Option<PathBuf>,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "none" => SerialParity::None,
+            "odd" => SerialParity::Odd,
+            "even" => SerialParity::Even,
+            _ => return Err(format!("Unsupported parity '{s}'")),
+        })
     }
+}
 
-    impl From<CommonPluginConfig> for PluginConfig {
-        fn from(c: CommonPluginConfig) -> Self {
-            Self {
-                client_timeout: c.client_timeout,
-                run_id: c.run_id,
-                clock_id: c.clock_id,
-                init_task_name: c.init_task_name,
-                disable_interactions: c.disable_interactions,
-                clock_rate: c.clock_rate,
-                rtos_mode: c.rtos_mode,
-                elf_file: c.elf_file,
-                import: Default::default(),
-                rtt_collector: Default::default(),
-            }
+#[cfg(feature = "serial-collector")]
+impl From<SerialParity> for serialport::Parity {
+    fn from(p: SerialParity) -> Self {
+        match p {
+            SerialParity::None => serialport::Parity::None,
+            SerialParity::Odd => serialport::Parity::Odd,
+            SerialParity::Even => serialport::Parity::Even,
         }
     }
+}
 
-    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
-    #[serde(rename_all = "kebab-case", default)]
-    pub struct ImportPluginConfig {
-        #[serde(flatten)]
-        pub common: CommonPluginConfig,
-        #[serde(flatten)]
-        pub import: ImportConfig,
+#[cfg(feature = "serial-collector")]
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    Default,
+    Display,
+    serde_with::DeserializeFromStr,
+)]
+pub enum SerialFlowControl {
+    #[default]
+    #[display(fmt = "none")]
+    None,
+    #[display(fmt = "software")]
+    Software,
+    #[display(fmt = "hardware")]
+    Hardware,
+}
+
+#[cfg(feature = "serial-collector")]
+impl FromStr for SerialFlowControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "none" => SerialFlowControl::None,
+            "software" | "xon-xoff" => SerialFlowControl::Software,
+            "hardware" | "rts-cts" => SerialFlowControl::Hardware,
+            _ => return Err(format!("Unsupported flow control '{s}'")),
+        })
     }
+}
 
-    impl From<ImportPluginConfig> for PluginConfig {
-        fn from(pc: ImportPluginConfig) -> Self {
-            let ImportPluginConfig { common, import } = pc;
-            let mut c = PluginConfig::from(common);
-            c.import = import;
-            c
+#[cfg(feature = "serial-collector")]
+impl From<SerialFlowControl> for serialport::FlowControl {
+    fn from(f: SerialFlowControl) -> Self {
+        match f {
+            SerialFlowControl::None => serialport::FlowControl::None,
+            SerialFlowControl::Software => serialport::FlowControl::Software,
+            SerialFlowControl::Hardware => serialport::FlowControl::Hardware,
         }
     }
+}
 
-    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
-    #[serde(rename_all = "kebab-case", default)]
-    pub struct RttCollectorPluginConfig {
-        #[serde(flatten)]
-        pub common: CommonPluginConfig,
-        #[serde(flatten)]
-        pub rtt_collector: RttCollectorConfig,
-    }
+#[cfg(feature = "tcp-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct TcpCollectorConfig {
+    pub connect_timeout: Option<HumanTime>,
+    pub remote: Option<String>,
+    pub listen: Option<String>,
+    pub reconnect_interval: Option<HumanTime>,
+    pub disable_reconnect: bool,
+}
 
-    impl From<RttCollectorPluginConfig> for PluginConfig {
-        fn from(pc: RttCollectorPluginConfig) -> Self {
-            let RttCollectorPluginConfig {
-                common,
-                rtt_collector,
-            } = pc;
-            let mut c = PluginConfig::from(common);
-            c.rtt_collector = rtt_collector;
-            c
+#[cfg(feature = "openocd-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct OpenOcdCollectorConfig {
+    pub connect_timeout: Option<HumanTime>,
+    pub host: String,
+    pub tcl_port: u16,
+    pub rtt_port: u16,
+    pub up_channel: usize,
+    pub control_block_address: Option<u32>,
+    pub control_block_search_size: u32,
+    pub control_block_id: String,
+    pub disable_setup: bool,
+    pub reconnect_interval: Option<HumanTime>,
+}
+
+#[cfg(feature = "openocd-collector")]
+impl OpenOcdCollectorConfig {
+    pub const DEFAULT_HOST: &'static str = "localhost";
+    pub const DEFAULT_TCL_PORT: u16 = 6666;
+    pub const DEFAULT_RTT_PORT: u16 = 9090;
+    pub const DEFAULT_UP_CHANNEL: usize = 0;
+    pub const DEFAULT_CONTROL_BLOCK_SEARCH_SIZE: u32 = 1024;
+    pub const DEFAULT_CONTROL_BLOCK_ID: &'static str = "SEGGER RTT";
+}
+
+#[cfg(feature = "openocd-collector")]
+impl Default for OpenOcdCollectorConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: None,
+            host: Self::DEFAULT_HOST.to_owned(),
+            tcl_port: Self::DEFAULT_TCL_PORT,
+            rtt_port: Self::DEFAULT_RTT_PORT,
+            up_channel: Self::DEFAULT_UP_CHANNEL,
+            control_block_address: None,
+            control_block_search_size: Self::DEFAULT_CONTROL_BLOCK_SEARCH_SIZE,
+            control_block_id: Self::DEFAULT_CONTROL_BLOCK_ID.to_owned(),
+            disable_setup: false,
+            reconnect_interval: None,
         }
     }
 }
 
-impl PluginConfig {
-    pub(crate) fn from_metadata(
-        cfg: &Config,
-        entry: DefmtConfigEntry,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        use internal::{ImportPluginConfig, RttCollectorPluginConfig};
-        match entry {
-            DefmtConfigEntry::Importer => {
-                Self::from_cfg_metadata::<ImportPluginConfig>(cfg).map(|c| c.into())
+#[cfg(feature = "process-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct ProcessCollectorConfig {
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub working_directory: Option<PathBuf>,
+    pub stream: ProcessStream,
+}
+
+/// The child process output carrying the defmt data
+#[cfg(feature = "process-collector")]
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    Default,
+    serde_with::DeserializeFromStr,
+)]
+pub enum ProcessStream {
+    #[default]
+    Stdout,
+    Stderr,
+    /// A pipe provided to the child as the given file descriptor (Unix platforms only)
+    Fd(i32),
+}
+
+#[cfg(feature = "process-collector")]
+impl FromStr for ProcessStream {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        Ok(match s.as_ref() {
+            "stdout" => ProcessStream::Stdout,
+            "stderr" => ProcessStream::Stderr,
+            _ => match s.strip_prefix("fd:").map(|fd| fd.trim().parse::<i32>()) {
+                Some(Ok(fd)) if fd > 2 => ProcessStream::Fd(fd),
+                _ => {
+                    return Err(format!(
+                        "Unsupported process stream '{s}', expected 'stdout', 'stderr' or 'fd:<N>' (N > 2)"
+                    ))
+                }
+            },
+        })
+    }
+}
+
+#[cfg(feature = "process-collector")]
+impl fmt::Display for ProcessStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessStream::Stdout => f.write_str("stdout"),
+            ProcessStream::Stderr => f.write_str("stderr"),
+            ProcessStream::Fd(fd) => write!(f, "fd:{fd}"),
+        }
+    }
+}
+
+#[cfg(feature = "renode-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RenodeCollectorConfig {
+    pub connect_timeout: Option<HumanTime>,
+    pub uart_socket: Option<String>,
+    pub uart_pty: Option<PathBuf>,
+    pub monitor: Option<String>,
+    pub monitor_commands: Vec<String>,
+    pub machine: Option<String>,
+}
+
+/// Maps the event names and name parameters of in-house RTOS instrumentation to the
+/// context manager roles, used by the `custom` RTOS mode.
+/// Unset members use the `rtic1` conventions.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RtosMarkers {
+    pub trace_start: Option<String>,
+    pub task_enter: Option<String>,
+    pub task_exit: Option<String>,
+    pub isr_enter: Option<String>,
+    pub isr_exit: Option<String>,
+    /// The parameter carrying the task name of the start and task enter events
+    pub task_name_key: Option<String>,
+    /// The parameter carrying the ISR name of the ISR enter events
+    pub isr_name_key: Option<String>,
+    /// The parameter carrying the start event's integration version.
+    /// When not set, the version is optional.
+    pub version_key: Option<String>,
+}
+
+/// Pairs the events sending a message with the events receiving it, typically
+/// on another context, by the value of a parameter identifying the message.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct MessageCorrelation {
+    /// Send event name pattern
+    pub send: Option<Glob>,
+    /// Receive event name pattern
+    pub receive: Option<Glob>,
+    /// The parameter identifying the message, present on both events
+    pub key: Option<String>,
+}
+
+/// Tags matching events with conventional outcome/severity attributes.
+/// A rule matches when all of its provided criteria match, the first matching rule wins.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct OutcomeRule {
+    /// Event name pattern
+    pub name: Option<Glob>,
+    /// Event level (trace, debug, info, warn, error)
+    pub level: Option<String>,
+    /// Sets `event.outcome`
+    pub outcome: Option<Outcome>,
+    /// Sets `event.severity`
+    pub severity: Option<String>,
+}
+
+impl OutcomeRule {
+    pub fn matches(&self, event_name: Option<&str>, level: Option<&str>) -> bool {
+        let name_matches = match (&self.name, event_name) {
+            (Some(pattern), Some(n)) => pattern.is_match(n),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let level_matches = match (&self.level, level) {
+            (Some(l), Some(ev_l)) => l.eq_ignore_ascii_case(ev_l),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        name_matches && level_matches
+    }
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    Display,
+    serde_with::DeserializeFromStr,
+)]
+pub enum Outcome {
+    #[display(fmt = "success")]
+    Success,
+    #[display(fmt = "failure")]
+    Failure,
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "success" | "pass" => Outcome::Success,
+            "failure" | "fail" => Outcome::Failure,
+            _ => return Err(format!("Unsupported outcome '{s}'")),
+        })
+    }
+}
+
+/// Include/exclude filters on the event level, source module path and name, applied before ingest.
+/// An event is kept when it matches at least one of the include patterns of each provided
+/// include list, and none of the exclude patterns.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct EventFilter {
+    pub include_levels: Vec<Glob>,
+    pub exclude_levels: Vec<Glob>,
+    /// Events without location info aren't filtered by module
+    pub include_modules: Vec<Glob>,
+    pub exclude_modules: Vec<Glob>,
+    pub include_names: Vec<Glob>,
+    pub exclude_names: Vec<Glob>,
+}
+
+impl EventFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include_levels.is_empty()
+            && self.exclude_levels.is_empty()
+            && self.include_modules.is_empty()
+            && self.exclude_modules.is_empty()
+            && self.include_names.is_empty()
+            && self.exclude_names.is_empty()
+    }
+
+    pub fn allows(
+        &self,
+        event_name: Option<&str>,
+        level: Option<&str>,
+        module: Option<&str>,
+    ) -> bool {
+        fn allowed(include: &[Glob], exclude: &[Glob], s: Option<&str>, unknown: bool) -> bool {
+            match s {
+                Some(s) => {
+                    (include.is_empty() || include.iter().any(|p| p.is_match(s)))
+                        && !exclude.iter().any(|p| p.is_match(s))
+                }
+                None => unknown || include.is_empty(),
             }
-            DefmtConfigEntry::RttCollector => {
-                Self::from_cfg_metadata::<RttCollectorPluginConfig>(cfg).map(|c| c.into())
+        }
+        allowed(&self.include_levels, &self.exclude_levels, level, false)
+            && allowed(&self.include_modules, &self.exclude_modules, module, true)
+            && allowed(&self.include_names, &self.exclude_names, event_name, false)
+    }
+}
+
+/// Stops collection at the first event matching all of the trigger's provided criteria.
+///
+/// Parsed from 'NAME' or comma-separated 'KEY=PATTERN' criteria at the CLI, where the keys are
+/// 'name', 'level', 'module' or an attribute key (e.g. 'name=panic*,event.code=42').
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct StopTrigger {
+    /// Event name pattern
+    pub name: Option<Glob>,
+    /// Event level (trace, debug, info, warn, error)
+    pub level: Option<String>,
+    /// Source module path pattern
+    pub module: Option<Glob>,
+    /// Attribute value patterns, by attribute key
+    pub attributes: BTreeMap<String, Glob>,
+}
+
+impl StopTrigger {
+    pub fn matches(&self, ev: &EventRecord) -> bool {
+        fn glob_matches(pattern: &Option<Glob>, s: Option<&str>) -> bool {
+            match (pattern, s) {
+                (Some(pattern), Some(s)) => pattern.is_match(s),
+                (Some(_), None) => false,
+                (None, _) => true,
             }
         }
+        let level_matches = match (&self.level, ev.level()) {
+            (Some(l), Some(ev_l)) => l.eq_ignore_ascii_case(ev_l),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let attrs_match = self.attributes.iter().all(|(k, pattern)| {
+            let k = k.strip_prefix("event.").unwrap_or(k);
+            let v = match ev.attributes().get(EventRecord::attr_key(k).as_str()) {
+                Some(AttrVal::String(s)) => s.to_string(),
+                Some(AttrVal::Integer(n)) => n.to_string(),
+                Some(AttrVal::Bool(b)) => b.to_string(),
+                Some(AttrVal::Float(f)) => f.0.to_string(),
+                _ => return false,
+            };
+            pattern.is_match(&v)
+        });
+        glob_matches(&self.name, ev.event_name())
+            && level_matches
+            && glob_matches(&self.module, ev.str_param("source.module"))
+            && attrs_match
     }
+}
 
-    fn from_cfg_metadata<'a, T: Deserialize<'a>>(
-        cfg: &Config,
-    ) -> Result<T, Box<dyn std::error::Error>> {
-        let cfg = TomlValue::Table(cfg.metadata.clone().into_iter().collect()).try_into()?;
-        Ok(cfg)
+impl FromStr for StopTrigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut trigger = StopTrigger::default();
+        for criterion in s.split(',').map(str::trim) {
+            match criterion.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                None if !criterion.is_empty() => trigger.name = Some(Glob::new(criterion)),
+                Some(("name", v)) => trigger.name = Some(Glob::new(v)),
+                Some(("level", v)) => trigger.level = Some(v.to_owned()),
+                Some(("module", v)) => trigger.module = Some(Glob::new(v)),
+                Some((k, v)) if !k.is_empty() => {
+                    trigger.attributes.insert(k.to_owned(), Glob::new(v));
+                }
+                _ => return Err(format!("Invalid stop trigger criterion in '{s}'")),
+            }
+        }
+        if trigger == StopTrigger::default() {
+            return Err(format!("Stop trigger '{s}' has no criteria"));
+        }
+        Ok(trigger)
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use auxon_sdk::reflector_config::{AttrKeyEqValuePair, TimelineAttributes};
-    use pretty_assertions::assert_eq;
-    use std::{env, fs::File, io::Write};
+/// Replaces a source file path prefix, e.g. a CI builder's workspace directory.
+///
+/// Parsed from 'FROM=TO' at the CLI, an empty 'TO' strips the prefix.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SourcePathRemap {
+    pub from: String,
+    pub to: String,
+}
 
-    const IMPORTER_CONFIG: &str = r#"[ingest]
-protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
-additional-timeline-attributes = [
-    "ci_run=1",
-    "platform='RTICv1'",
-    "defmt-mode='rtt'",
-]
+impl SourcePathRemap {
+    /// The remapped path, or None if the prefix doesn't match
+    pub fn remap(&self, path: &str) -> Option<String> {
+        if self.from.is_empty() {
+            return None;
+        }
+        path.strip_prefix(self.from.as_str())
+            .map(|rest| format!("{}{rest}", self.to))
+    }
+}
 
-[metadata]
-client-timeout = "1s"
-run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
-clock-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
-init-task-name = 'main'
-disable-interactions = true
-rtos-mode = "rtic1"
-clock-rate = "1/1000000"
-elf-file = "fw.elf"
-open-timeout = "100ms"
-file = "rtt_log.bin"
-"#;
+impl FromStr for SourcePathRemap {
+    type Err = String;
 
-    const RTT_COLLECTOR_CONFIG: &str = r#"[ingest]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((from, to)) if !from.trim().is_empty() => Ok(Self {
+                from: from.trim().to_owned(),
+                to: to.trim().to_owned(),
+            }),
+            _ => Err(format!(
+                "Invalid source path remap '{s}', expected 'FROM_PREFIX=TO_PREFIX'"
+            )),
+        }
+    }
+}
+
+/// Maps the source files under a local path prefix to web URLs, e.g. GitHub or GitLab
+/// permalinks, recorded as `event.source.uri`.
+///
+/// The URL template supports the `{path}` (the path after the prefix), `{line}` and
+/// `{commit}` placeholders.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SourceUrlMapping {
+    /// Local path prefix, matched against the remapped source file path
+    pub prefix: String,
+    /// e.g. `https://github.com/acme/fw/blob/{commit}/{path}#L{line}`
+    pub url: String,
+    pub commit: Option<String>,
+}
+
+impl SourceUrlMapping {
+    /// The URL of the source location, or None if the prefix doesn't match
+    pub fn url(&self, file: &str, line: u64, commit: Option<&str>) -> Option<String> {
+        let path = file.strip_prefix(self.prefix.as_str())?;
+        let commit = commit.or(self.commit.as_deref()).unwrap_or("HEAD");
+        Some(
+            self.url
+                .replace("{path}", path.trim_start_matches('/'))
+                .replace("{line}", &line.to_string())
+                .replace("{commit}", commit),
+        )
+    }
+}
+
+/// Coerces the values of matching attributes, for firmware that logs a type that
+/// doesn't match how it's queried. The value is converted first, then scaled.
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct AttrCoercion {
+    /// Attribute key pattern, e.g. `event.status`
+    pub key: Option<Glob>,
+    /// The type to convert the value to
+    pub to: Option<AttrType>,
+    /// Multiplies numeric values, producing a float
+    pub scale: Option<ScaleFactor>,
+}
+
+impl AttrCoercion {
+    pub fn matches(&self, key: &str) -> bool {
+        self.key.as_ref().map(|k| k.is_match(key)).unwrap_or(false)
+    }
+}
+
+/// The target type of an attribute coercion
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    Display,
+    serde_with::DeserializeFromStr,
+)]
+pub enum AttrType {
+    #[display(fmt = "int")]
+    Int,
+    /// An integer from a hexadecimal string, with or without the `0x` prefix
+    #[display(fmt = "hex-int")]
+    HexInt,
+    #[display(fmt = "float")]
+    Float,
+    #[display(fmt = "bool")]
+    Bool,
+    #[display(fmt = "string")]
+    String,
+}
+
+impl FromStr for AttrType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "int" | "integer" => AttrType::Int,
+            "hex-int" | "hex" => AttrType::HexInt,
+            "float" => AttrType::Float,
+            "bool" | "boolean" => AttrType::Bool,
+            "string" | "str" => AttrType::String,
+            _ => return Err(format!("Unsupported attribute type '{s}'")),
+        })
+    }
+}
+
+/// A floating point scale factor
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(transparent)]
+pub struct ScaleFactor(pub f64);
+
+impl PartialEq for ScaleFactor {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for ScaleFactor {}
+
+#[cfg(feature = "rtt-collector")]
+#[derive(Clone, Debug, From, Into, Deref, serde_with::DeserializeFromStr)]
+pub struct ProbeSelector(pub probe_rs::probe::DebugProbeSelector);
+
+#[cfg(feature = "rtt-collector")]
+impl PartialEq for ProbeSelector {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.vendor_id == other.0.vendor_id
+            && self.0.product_id == other.0.product_id
+            && self.0.serial_number == other.0.serial_number
+    }
+}
+
+#[cfg(feature = "rtt-collector")]
+impl Eq for ProbeSelector {}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for ProbeSelector {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            probe_rs::probe::DebugProbeSelector::from_str(s).map_err(|e| e.to_string())?,
+        ))
+    }
+}
+
+/// What the RTT collector does with the core after catching a fault exception
+#[cfg(feature = "rtt-collector")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Display, serde_with::DeserializeFromStr)]
+pub enum FaultAction {
+    /// Resume the core, running the firmware's fault handler
+    #[default]
+    #[display(fmt = "resume")]
+    Resume,
+    /// Leave the core halted for inspection and stop the collection
+    #[display(fmt = "stop")]
+    Stop,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for FaultAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "resume" => FaultAction::Resume,
+            "stop" => FaultAction::Stop,
+            _ => return Err(format!("Unsupported fault action '{s}'")),
+        })
+    }
+}
+
+/// The RTT up channel mode, what the target does when the channel buffer is full
+#[cfg(feature = "rtt-collector")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display, serde_with::DeserializeFromStr)]
+pub enum RttChannelMode {
+    /// Block until the host reads, lossless but stalls the target
+    #[display(fmt = "block-if-full")]
+    BlockIfFull,
+    /// Drop the write if it doesn't fit entirely
+    #[display(fmt = "no-block-skip")]
+    NoBlockSkip,
+    /// Write as much as fits, dropping the rest
+    #[display(fmt = "no-block-trim")]
+    NoBlockTrim,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for RttChannelMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().replace('_', "-").as_ref() {
+            "block-if-full" | "blockiffull" => RttChannelMode::BlockIfFull,
+            "no-block-skip" | "noblockskip" => RttChannelMode::NoBlockSkip,
+            "no-block-trim" | "noblocktrim" => RttChannelMode::NoBlockTrim,
+            _ => return Err(format!("Unsupported RTT channel mode '{s}'")),
+        })
+    }
+}
+
+#[cfg(feature = "rtt-collector")]
+impl From<RttChannelMode> for probe_rs::rtt::ChannelMode {
+    fn from(m: RttChannelMode) -> Self {
+        match m {
+            RttChannelMode::BlockIfFull => probe_rs::rtt::ChannelMode::BlockIfFull,
+            RttChannelMode::NoBlockSkip => probe_rs::rtt::ChannelMode::NoBlockSkip,
+            RttChannelMode::NoBlockTrim => probe_rs::rtt::ChannelMode::NoBlockTrim,
+        }
+    }
+}
+
+/// The target core architecture
+#[cfg(feature = "rtt-collector")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display, serde_with::DeserializeFromStr)]
+pub enum TargetArch {
+    #[display(fmt = "arm")]
+    Arm,
+    #[display(fmt = "riscv")]
+    RiscV,
+    #[display(fmt = "xtensa")]
+    Xtensa,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for TargetArch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(
+            match s.trim().to_lowercase().replace(['-', '_'], "").as_ref() {
+                "arm" | "cortexm" => TargetArch::Arm,
+                "riscv" => TargetArch::RiscV,
+                "xtensa" => TargetArch::Xtensa,
+                _ => return Err(format!("Unsupported target architecture '{s}'")),
+            },
+        )
+    }
+}
+
+#[cfg(feature = "rtt-collector")]
+impl From<probe_rs::Architecture> for TargetArch {
+    fn from(a: probe_rs::Architecture) -> Self {
+        match a {
+            probe_rs::Architecture::Arm => TargetArch::Arm,
+            probe_rs::Architecture::Riscv => TargetArch::RiscV,
+            probe_rs::Architecture::Xtensa => TargetArch::Xtensa,
+        }
+    }
+}
+
+/// How the RTT collector paces its polls of the target
+#[cfg(feature = "rtt-collector")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Display, serde_with::DeserializeFromStr)]
+pub enum RttPollPolicy {
+    /// Poll at the poll interval while there's data, at the idle interval otherwise
+    #[default]
+    #[display(fmt = "step")]
+    Step,
+    /// Always poll at the poll interval
+    #[display(fmt = "fixed")]
+    Fixed,
+    /// Poll again right away while reads fill the read buffer, backing off
+    /// exponentially to the idle interval while there's no data
+    #[display(fmt = "adaptive")]
+    Adaptive,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl RttPollPolicy {
+    /// The delay before the next poll, given the delay before the last one and the number
+    /// of bytes it read into a `buffer_size` read buffer. Zero polls again right away.
+    pub fn next_interval(
+        self,
+        last: Duration,
+        bytes_read: usize,
+        buffer_size: usize,
+        poll_interval: Duration,
+        idle_interval: Duration,
+    ) -> Duration {
+        match self {
+            RttPollPolicy::Step if bytes_read == 0 => idle_interval,
+            RttPollPolicy::Step | RttPollPolicy::Fixed => poll_interval,
+            RttPollPolicy::Adaptive if bytes_read == 0 => {
+                last.saturating_mul(2).min(idle_interval).max(poll_interval)
+            }
+            RttPollPolicy::Adaptive if bytes_read >= buffer_size => Duration::ZERO,
+            RttPollPolicy::Adaptive => poll_interval,
+        }
+    }
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for RttPollPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "step" => RttPollPolicy::Step,
+            "fixed" => RttPollPolicy::Fixed,
+            "adaptive" => RttPollPolicy::Adaptive,
+            _ => return Err(format!("Unsupported RTT poll policy '{s}'")),
+        })
+    }
+}
+
+/// An RTT up channel to collect from, with an optional channel-specific ELF file
+/// containing its defmt table.
+///
+/// The channel is selected by number, or by name (resolved at attach time).
+/// Parsed from 'N', 'NAME', 'N=ELF_FILE' or 'NAME=ELF_FILE' at the CLI.
+#[cfg(feature = "rtt-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RttUpChannel {
+    #[serde(default)]
+    pub channel: usize,
+    /// The RTT channel name, takes precedence over the number
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub elf_file: Option<PathBuf>,
+    /// The core writing to the channel, overrides the plugin's `core-id`
+    #[serde(default)]
+    pub core_id: Option<u64>,
+    /// What the channel carries, plain text channels are ingested line by line
+    #[serde(default)]
+    pub format: RttChannelFormat,
+}
+
+#[cfg(feature = "rtt-collector")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Display, serde_with::DeserializeFromStr)]
+pub enum RttChannelFormat {
+    #[default]
+    #[display(fmt = "defmt")]
+    Defmt,
+    #[display(fmt = "text")]
+    Text,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for RttChannelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "defmt" => RttChannelFormat::Defmt,
+            "text" => RttChannelFormat::Text,
+            _ => return Err(format!("Unsupported RTT channel format '{s}'")),
+        })
+    }
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for RttUpChannel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (channel, elf_file) = match s.trim().split_once('=') {
+            Some((ch, elf)) if !elf.trim().is_empty() => (ch, Some(PathBuf::from(elf.trim()))),
+            Some(_) => return Err(format!("Missing ELF file in RTT up channel '{s}'")),
+            None => (s, None),
+        };
+        let channel = channel.trim();
+        if channel.is_empty() {
+            return Err(format!("Missing RTT up channel in '{s}'"));
+        }
+        let (channel, name) = match channel.parse::<usize>() {
+            Ok(n) => (n, None),
+            Err(_) => (0, Some(channel.to_owned())),
+        };
+        Ok(Self {
+            channel,
+            name,
+            elf_file,
+            core_id: None,
+            format: RttChannelFormat::Defmt,
+        })
+    }
+}
+
+/// A stage of the staged start: the collector waits for the target to hit the stage's
+/// breakpoint, performs the stage's actions and resumes the target.
+///
+/// Parsed from 'BREAKPOINT[,ACTION...]' at the CLI, where the actions are
+/// 'mode=CHANNEL_MODE', 'write=LOCATION=VALUE' and 'start'.
+#[cfg(feature = "rtt-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SetupStage {
+    /// Symbol name or address to break on
+    pub breakpoint: String,
+    /// The up channel mode to set, the last stage defaults to the configured
+    /// `channel-mode` or BlockIfFull
+    pub channel_mode: Option<RttChannelMode>,
+    /// Memory words to write
+    pub write: Vec<MemoryWrite>,
+    /// Attach to RTT and start reading at this stage, instead of before the first one
+    pub start: bool,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for SetupStage {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(str::trim);
+        let breakpoint = parts.next().unwrap_or_default();
+        if breakpoint.is_empty() {
+            return Err(format!("Missing breakpoint in setup stage '{s}'"));
+        }
+        let mut stage = SetupStage {
+            breakpoint: breakpoint.to_owned(),
+            ..Default::default()
+        };
+        for action in parts {
+            match action.split_once('=') {
+                Some(("mode", mode)) => stage.channel_mode = Some(mode.parse()?),
+                Some(("write", write)) => stage.write.push(write.parse()?),
+                None if action == "start" => stage.start = true,
+                _ => return Err(format!("Unsupported setup stage action '{action}'")),
+            }
+        }
+        Ok(stage)
+    }
+}
+
+/// A 32-bit word to write to a symbol or address, parsed from 'LOCATION=VALUE'
+#[cfg(feature = "rtt-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, serde_with::DeserializeFromStr)]
+pub struct MemoryWrite {
+    pub location: String,
+    pub value: u32,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for MemoryWrite {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (location, value) = s
+            .split_once('=')
+            .map(|(l, v)| (l.trim(), v.trim()))
+            .filter(|(l, _)| !l.is_empty())
+            .ok_or_else(|| format!("Memory write '{s}' must be of the form 'LOCATION=VALUE'"))?;
+        let value = match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => value.parse::<u32>(),
+        }
+        .map_err(|e| format!("Invalid memory write value in '{s}'. {e}"))?;
+        Ok(Self {
+            location: location.to_owned(),
+            value,
+        })
+    }
+}
+
+/// A step of a pre-attach or post-attach hook, parsed from 'write=LOCATION=VALUE',
+/// 'reg=REGISTER=VALUE', 'delay=DURATION' or 'exec=COMMAND'
+#[cfg(feature = "rtt-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, serde_with::DeserializeFromStr)]
+pub enum ProbeCommand {
+    /// Write a 32-bit word to a symbol or address
+    Write(MemoryWrite),
+    /// Write a core register, by name
+    WriteRegister {
+        register: String,
+        value: u64,
+    },
+    Delay(HumanTime),
+    /// Run a shell command, e.g. a debug unlock script
+    Exec(String),
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for ProbeCommand {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (command, arg) = s
+            .split_once('=')
+            .map(|(c, a)| (c.trim(), a.trim()))
+            .filter(|(_, a)| !a.is_empty())
+            .ok_or_else(|| format!("Probe command '{s}' must be of the form 'COMMAND=ARGS'"))?;
+        Ok(match command {
+            "write" => ProbeCommand::Write(arg.parse()?),
+            "reg" => {
+                let (register, value) = arg
+                    .split_once('=')
+                    .map(|(r, v)| (r.trim(), v.trim()))
+                    .filter(|(r, _)| !r.is_empty())
+                    .ok_or_else(|| {
+                        format!("Register write '{s}' must be of the form 'reg=REGISTER=VALUE'")
+                    })?;
+                let value = match value.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => value.parse::<u64>(),
+                }
+                .map_err(|e| format!("Invalid register write value in '{s}'. {e}"))?;
+                ProbeCommand::WriteRegister {
+                    register: register.to_owned(),
+                    value,
+                }
+            }
+            "delay" => ProbeCommand::Delay(
+                arg.parse()
+                    .map_err(|e| format!("Invalid delay in '{s}'. {e}"))?,
+            ),
+            "exec" => ProbeCommand::Exec(arg.to_owned()),
+            _ => return Err(format!("Unsupported probe command '{command}'")),
+        })
+    }
+}
+
+/// A Deviant mutation staged in the firmware before it runs, parsed from
+/// 'MUTATOR_ID:MUTATION_ID' at the CLI
+#[cfg(feature = "rtt-collector")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StagedMutation {
+    pub mutator_id: uuid::Uuid,
+    pub mutation_id: uuid::Uuid,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for StagedMutation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mutator_id, mutation_id) = s.split_once(':').ok_or_else(|| {
+            format!("Staged mutation '{s}' must be of the form 'MUTATOR_ID:MUTATION_ID'")
+        })?;
+        let parse = |id: &str| {
+            uuid::Uuid::parse_str(id.trim())
+                .map_err(|e| format!("Invalid UUID '{id}' in staged mutation '{s}'. {e}"))
+        };
+        Ok(Self {
+            mutator_id: parse(mutator_id)?,
+            mutation_id: parse(mutation_id)?,
+        })
+    }
+}
+
+/// A window of target memory at a symbol or address, parsed from 'LOCATION:SIZE'
+#[cfg(feature = "rtt-collector")]
+#[derive(Clone, Debug, PartialEq, Eq, serde_with::DeserializeFromStr)]
+pub struct MemoryWindow {
+    pub location: String,
+    /// Size in bytes
+    pub size: u32,
+}
+
+#[cfg(feature = "rtt-collector")]
+impl FromStr for MemoryWindow {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (location, size) = s
+            .rsplit_once(':')
+            .map(|(l, v)| (l.trim(), v.trim()))
+            .filter(|(l, _)| !l.is_empty())
+            .ok_or_else(|| format!("Memory window '{s}' must be of the form 'LOCATION:SIZE'"))?;
+        let size = match size.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => size.parse::<u32>(),
+        }
+        .map_err(|e| format!("Invalid memory window size in '{s}'. {e}"))?;
+        Ok(Self {
+            location: location.to_owned(),
+            size,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, From, Into, Deref, serde_with::DeserializeFromStr)]
+pub struct HumanTime(pub humantime::Duration);
+
+impl FromStr for HumanTime {
+    type Err = humantime::DurationError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(humantime::Duration::from_str(s)?))
+    }
+}
+
+#[cfg(feature = "ingest")]
+impl DefmtConfig {
+    pub fn load_merge_with_opts(
+        entry: DefmtConfigEntry,
+        rf_opts: ReflectorOpts,
+        defmt_opts: DefmtOpts,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let cfg_path = rf_opts
+            .config_file
+            .clone()
+            .or_else(|| env::var(CONFIG_ENV_VAR).ok().map(PathBuf::from));
+        let mut cfg = if let Some(cfg_path) = &cfg_path {
+            auxon_sdk::reflector_config::try_from_file(cfg_path)?
+        } else {
+            Config::default()
+        };
+        resolve_config_layers(&mut cfg, cfg_path.as_deref(), &rf_opts.profiles)?;
+        expand_config_env_vars(&mut cfg)?;
+
+        let mut ingest = cfg.ingest.clone().unwrap_or_default();
+        if let Some(url) = &rf_opts.protocol_parent_url {
+            ingest.protocol_parent_url = Some(url.clone());
+        }
+        if rf_opts.allow_insecure_tls {
+            ingest.allow_insecure_tls = true;
+        }
+        // The command line attributes are added last, so they take precedence
+        ingest
+            .timeline_attributes
+            .additional_timeline_attributes
+            .extend(rf_opts.additional_timeline_attributes);
+        ingest
+            .timeline_attributes
+            .override_timeline_attributes
+            .extend(rf_opts.override_timeline_attributes);
+
+        let cfg_plugin = PluginConfig::from_metadata(&cfg, entry)?;
+        let plugin = PluginConfig {
+            client_timeout: rf_opts
+                .client_timeout
+                .map(|t| t.into())
+                .or(cfg_plugin.client_timeout),
+            run_id: rf_opts.run_id.or(cfg_plugin.run_id),
+            clock_id: rf_opts.clock_id.or(cfg_plugin.clock_id),
+            init_task_name: defmt_opts.init_task_name.or(cfg_plugin.init_task_name),
+            disable_interactions: if defmt_opts.disable_interactions {
+                true
+            } else {
+                cfg_plugin.disable_interactions
+            },
+            disable_linear_causality: if defmt_opts.disable_linear_causality {
+                true
+            } else {
+                cfg_plugin.disable_linear_causality
+            },
+            clock_rate: defmt_opts.clock_rate.or(cfg_plugin.clock_rate),
+            rtos_mode: defmt_opts.rtos_mode.unwrap_or(cfg_plugin.rtos_mode),
+            pre_start_policy: defmt_opts
+                .pre_start_policy
+                .unwrap_or(cfg_plugin.pre_start_policy),
+            rtos_markers: cfg_plugin.rtos_markers,
+            fold_isrs: {
+                let mut isrs = cfg_plugin.fold_isrs;
+                isrs.extend(defmt_opts.fold_isrs);
+                isrs
+            },
+            elf_file: cfg_plugin.elf_file, // NOTE: plugin opts handling may override this
+            elf_reload: if defmt_opts.elf_reload {
+                true
+            } else {
+                cfg_plugin.elf_reload
+            },
+            debug_file: defmt_opts.debug_file.or(cfg_plugin.debug_file),
+            outcome_rules: cfg_plugin.outcome_rules,
+            message_correlations: cfg_plugin.message_correlations,
+            boot_index: defmt_opts.boot_index.or(cfg_plugin.boot_index),
+            segment_runs: if defmt_opts.segment_runs {
+                true
+            } else {
+                cfg_plugin.segment_runs
+            },
+            reboot_event: defmt_opts.reboot_event.or(cfg_plugin.reboot_event),
+            reboot_timestamp_threshold: defmt_opts
+                .reboot_timestamp_threshold
+                .or(cfg_plugin.reboot_timestamp_threshold),
+            core_id: defmt_opts.core_id.or(cfg_plugin.core_id),
+            context_stats: if defmt_opts.context_stats {
+                true
+            } else {
+                cfg_plugin.context_stats
+            },
+            max_context_depth: defmt_opts
+                .max_context_depth
+                .or(cfg_plugin.max_context_depth),
+            unknown_context_name: defmt_opts
+                .unknown_context_name
+                .or(cfg_plugin.unknown_context_name),
+            unknown_context_per_source: if defmt_opts.unknown_context_per_source {
+                true
+            } else {
+                cfg_plugin.unknown_context_per_source
+            },
+            phase_marker_event: defmt_opts
+                .phase_marker_event
+                .or(cfg_plugin.phase_marker_event),
+            promote_internal_attributes: {
+                let mut keys = cfg_plugin.promote_internal_attributes;
+                keys.extend(defmt_opts.promote_internal_attributes);
+                keys
+            },
+            framing: defmt_opts.framing.unwrap_or(cfg_plugin.framing),
+            frame_crc: defmt_opts.frame_crc.unwrap_or(cfg_plugin.frame_crc),
+            max_array_elements: defmt_opts
+                .max_array_elements
+                .or(cfg_plugin.max_array_elements),
+            byte_encoding: defmt_opts.byte_encoding.unwrap_or(cfg_plugin.byte_encoding),
+            display_hints: if defmt_opts.display_hints {
+                true
+            } else {
+                cfg_plugin.display_hints
+            },
+            println_level: defmt_opts.println_level.or(cfg_plugin.println_level),
+            timestamp_composition: defmt_opts
+                .timestamp_composition
+                .unwrap_or(cfg_plugin.timestamp_composition),
+            timestamp_width: defmt_opts.timestamp_width.or(cfg_plugin.timestamp_width),
+            host_time_fallback: if defmt_opts.host_time_fallback {
+                true
+            } else {
+                cfg_plugin.host_time_fallback
+            },
+            backwards_timestamp_policy: defmt_opts
+                .backwards_timestamp_policy
+                .unwrap_or(cfg_plugin.backwards_timestamp_policy),
+            timestamp_gap_threshold: defmt_opts
+                .timestamp_gap_threshold
+                .map(|t| t.into())
+                .or(cfg_plugin.timestamp_gap_threshold),
+            event_name_delimiter: defmt_opts
+                .event_name_delimiter
+                .or(cfg_plugin.event_name_delimiter),
+            attr_separator: defmt_opts.attr_separator.or(cfg_plugin.attr_separator),
+            literal_event_names: if defmt_opts.literal_event_names {
+                true
+            } else {
+                cfg_plugin.literal_event_names
+            },
+            unnamed_event_names: defmt_opts
+                .unnamed_event_names
+                .unwrap_or(cfg_plugin.unnamed_event_names),
+            normalize_event_names: if defmt_opts.normalize_event_names {
+                true
+            } else {
+                cfg_plugin.normalize_event_names
+            },
+            max_event_name_length: defmt_opts
+                .max_event_name_length
+                .or(cfg_plugin.max_event_name_length),
+            max_attribute_keys: defmt_opts
+                .max_attribute_keys
+                .or(cfg_plugin.max_attribute_keys),
+            attribute_key_limit_policy: defmt_opts
+                .attribute_key_limit_policy
+                .unwrap_or(cfg_plugin.attribute_key_limit_policy),
+            infer_units: if defmt_opts.infer_units {
+                true
+            } else {
+                cfg_plugin.infer_units
+            },
+            attribute_renames: {
+                let mut renames = cfg_plugin.attribute_renames;
+                renames.extend(
+                    defmt_opts
+                        .rename_attributes
+                        .into_iter()
+                        .map(|r| (r.from, r.to)),
+                );
+                renames
+            },
+            attribute_coercions: cfg_plugin.attribute_coercions,
+            event_filter: {
+                let mut filter = cfg_plugin.event_filter;
+                filter.include_levels.extend(defmt_opts.include_levels);
+                filter.exclude_levels.extend(defmt_opts.exclude_levels);
+                filter.include_modules.extend(defmt_opts.include_modules);
+                filter.exclude_modules.extend(defmt_opts.exclude_modules);
+                filter.include_names.extend(defmt_opts.include_events);
+                filter.exclude_names.extend(defmt_opts.exclude_events);
+                filter
+            },
+            stop_on: {
+                let mut triggers = cfg_plugin.stop_on;
+                triggers.extend(defmt_opts.stop_on);
+                triggers
+            },
+            exclude_table_indices: {
+                let mut indices = cfg_plugin.exclude_table_indices;
+                indices.extend(defmt_opts.exclude_table_indices);
+                indices
+            },
+            exclude_format_strings: {
+                let mut formats = cfg_plugin.exclude_format_strings;
+                formats.extend(defmt_opts.exclude_format_strings);
+                formats
+            },
+            omit_internal_attributes: if defmt_opts.omit_internal_attributes {
+                true
+            } else {
+                cfg_plugin.omit_internal_attributes
+            },
+            omit_formatted_string: if defmt_opts.omit_formatted_string {
+                true
+            } else {
+                cfg_plugin.omit_formatted_string
+            },
+            debug_attributes: if defmt_opts.debug_attributes {
+                true
+            } else {
+                cfg_plugin.debug_attributes
+            },
+            message_attribute: if defmt_opts.message_attribute {
+                true
+            } else {
+                cfg_plugin.message_attribute
+            },
+            raw_frame_attributes: if defmt_opts.raw_frame_attributes {
+                true
+            } else {
+                cfg_plugin.raw_frame_attributes
+            },
+            metrics_interval: defmt_opts
+                .metrics_interval
+                .map(|t| t.into())
+                .or(cfg_plugin.metrics_interval),
+            metric_events: if defmt_opts.metric_events {
+                true
+            } else {
+                cfg_plugin.metric_events
+            },
+            save_raw: defmt_opts.save_raw.or(cfg_plugin.save_raw),
+            save_raw_rotate_size: defmt_opts
+                .save_raw_rotate_size
+                .or(cfg_plugin.save_raw_rotate_size),
+            save_raw_rotate_count: defmt_opts
+                .save_raw_rotate_count
+                .or(cfg_plugin.save_raw_rotate_count),
+            summary_file: defmt_opts.summary_file.or(cfg_plugin.summary_file),
+            source_path_remaps: {
+                // The command line remaps take precedence
+                let mut remaps = defmt_opts.source_path_remaps;
+                remaps.extend(cfg_plugin.source_path_remaps);
+                remaps
+            },
+            source_urls: cfg_plugin.source_urls,
+            source_commit: defmt_opts.source_commit.or(cfg_plugin.source_commit),
+            additional_event_attributes: {
+                // The CLI attributes come last, so they take precedence
+                let mut attrs = cfg_plugin.additional_event_attributes;
+                attrs.extend(defmt_opts.additional_event_attributes);
+                attrs
+            },
+            import: cfg_plugin.import,
+            #[cfg(feature = "rtt-collector")]
+            rtt_collector: cfg_plugin.rtt_collector,
+            #[cfg(feature = "serial-collector")]
+            serial_collector: cfg_plugin.serial_collector,
+            #[cfg(feature = "tcp-collector")]
+            tcp_collector: cfg_plugin.tcp_collector,
+            #[cfg(feature = "openocd-collector")]
+            openocd_collector: cfg_plugin.openocd_collector,
+            #[cfg(feature = "process-collector")]
+            process_collector: cfg_plugin.process_collector,
+            #[cfg(feature = "renode-collector")]
+            renode_collector: cfg_plugin.renode_collector,
+        };
+
+        Ok(Self {
+            auth_token: rf_opts.auth_token,
+            ingest,
+            plugin,
+        })
+    }
+
+    pub fn protocol_parent_url(&self) -> Result<Url, url::ParseError> {
+        if let Some(url) = &self.ingest.protocol_parent_url {
+            Ok(url.clone())
+        } else {
+            let url = Url::parse("modality-ingest://127.0.0.1:14188")?;
+            Ok(url)
+        }
+    }
+
+    pub fn resolve_auth(&self) -> Result<AuthToken, AuthTokenError> {
+        if let Some(auth_token_hex) = self.auth_token.as_deref() {
+            Ok(auxon_sdk::auth_token::decode_auth_token_hex(
+                auth_token_hex,
+            )?)
+        } else {
+            Ok(AuthToken::load()?)
+        }
+    }
+}
+
+/// Layers the configuration file over the base files it includes, then applies the
+/// selected profiles (the command line ones take precedence over 'active-profiles')
+#[cfg(feature = "ingest")]
+fn resolve_config_layers(
+    cfg: &mut Config,
+    cfg_path: Option<&Path>,
+    profiles: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    const PROFILE_KEY: &str = "profile";
+    const ACTIVE_PROFILES_KEY: &str = "active-profiles";
+
+    let mut includers: Vec<PathBuf> = cfg_path.map(canonical_path).into_iter().collect();
+    resolve_includes(cfg, cfg_path, &mut includers)?;
+
+    let active_profiles = take_string_list(&mut cfg.metadata, ACTIVE_PROFILES_KEY)?;
+    let selected = if profiles.is_empty() {
+        active_profiles
+    } else {
+        profiles.to_vec()
+    };
+    let mut available = match cfg.metadata.remove(PROFILE_KEY) {
+        Some(TomlValue::Table(t)) => t,
+        Some(_) => return Err(ConfigLayerError::InvalidProfiles(PROFILE_KEY).into()),
+        None => Default::default(),
+    };
+    for name in selected {
+        match available.remove(&name) {
+            Some(TomlValue::Table(profile)) => {
+                merge_metadata(&mut cfg.metadata, profile);
+            }
+            Some(_) => return Err(ConfigLayerError::InvalidProfiles(PROFILE_KEY).into()),
+            None => return Err(ConfigLayerError::UnknownProfile(name).into()),
+        }
+    }
+    Ok(())
+}
+
+/// Merges the 'include' base files under the configuration, in order, so later files
+/// and the including file take precedence
+#[cfg(feature = "ingest")]
+fn resolve_includes(
+    cfg: &mut Config,
+    cfg_path: Option<&Path>,
+    includers: &mut Vec<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const INCLUDE_KEY: &str = "include";
+
+    let includes = take_string_list(&mut cfg.metadata, INCLUDE_KEY)?;
+    if includes.is_empty() {
+        return Ok(());
+    }
+    // Included paths are relative to the including file
+    let dir = cfg_path.and_then(Path::parent).unwrap_or(Path::new(""));
+    let mut metadata = BTreeMap::new();
+    let mut ingest = None;
+    for include in includes {
+        let path = canonical_path(&dir.join(expand_env_vars(&include)?));
+        if includers.contains(&path) {
+            return Err(ConfigLayerError::IncludeCycle(path).into());
+        }
+        let mut base = auxon_sdk::reflector_config::try_from_file(&path)?;
+        includers.push(path.clone());
+        resolve_includes(&mut base, Some(&path), includers)?;
+        includers.pop();
+        merge_metadata(&mut metadata, base.metadata);
+        ingest = merge_ingest(ingest, base.ingest);
+    }
+    merge_metadata(&mut metadata, std::mem::take(&mut cfg.metadata));
+    cfg.metadata = metadata;
+    cfg.ingest = merge_ingest(ingest, cfg.ingest.take());
+    Ok(())
+}
+
+#[cfg(feature = "ingest")]
+fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+/// Removes a string or array of strings field from the metadata
+#[cfg(feature = "ingest")]
+fn take_string_list(
+    metadata: &mut BTreeMap<String, TomlValue>,
+    key: &'static str,
+) -> Result<Vec<String>, ConfigLayerError> {
+    match metadata.remove(key) {
+        None => Ok(Vec::new()),
+        Some(TomlValue::String(s)) => Ok(vec![s]),
+        Some(TomlValue::Array(vals)) => vals
+            .into_iter()
+            .map(|v| match v {
+                TomlValue::String(s) => Ok(s),
+                _ => Err(ConfigLayerError::InvalidStringList(key)),
+            })
+            .collect(),
+        Some(_) => Err(ConfigLayerError::InvalidStringList(key)),
+    }
+}
+
+/// Tables are merged recursively, any other overlay value replaces the base value
+#[cfg(feature = "ingest")]
+fn merge_metadata<I: IntoIterator<Item = (String, TomlValue)>>(
+    base: &mut BTreeMap<String, TomlValue>,
+    overlay: I,
+) {
+    fn merge_value(base: &mut TomlValue, overlay: TomlValue) {
+        match (base, overlay) {
+            (TomlValue::Table(base), TomlValue::Table(overlay)) => {
+                for (k, v) in overlay {
+                    match base.get_mut(&k) {
+                        Some(b) => merge_value(b, v),
+                        None => {
+                            base.insert(k, v);
+                        }
+                    }
+                }
+            }
+            (base, overlay) => *base = overlay,
+        }
+    }
+
+    for (k, v) in overlay {
+        match base.get_mut(&k) {
+            Some(b) => merge_value(b, v),
+            None => {
+                base.insert(k, v);
+            }
+        }
+    }
+}
+
+/// The overlay's fields take precedence, the timeline attributes are appended to the base ones
+#[cfg(feature = "ingest")]
+fn merge_ingest(
+    base: Option<TopLevelIngest>,
+    overlay: Option<TopLevelIngest>,
+) -> Option<TopLevelIngest> {
+    match (base, overlay) {
+        (Some(base), Some(mut ingest)) => {
+            ingest.protocol_parent_url = ingest.protocol_parent_url.or(base.protocol_parent_url);
+            ingest.allow_insecure_tls |= base.allow_insecure_tls;
+            ingest.protocol_child_port = ingest.protocol_child_port.or(base.protocol_child_port);
+            ingest.max_write_batch_staleness = ingest
+                .max_write_batch_staleness
+                .or(base.max_write_batch_staleness);
+            let attrs = &mut ingest.timeline_attributes;
+            let mut additional = base.timeline_attributes.additional_timeline_attributes;
+            additional.append(&mut attrs.additional_timeline_attributes);
+            attrs.additional_timeline_attributes = additional;
+            let mut overrides = base.timeline_attributes.override_timeline_attributes;
+            overrides.append(&mut attrs.override_timeline_attributes);
+            attrs.override_timeline_attributes = overrides;
+            Some(ingest)
+        }
+        (base, ingest) => ingest.or(base),
+    }
+}
+
+/// Expands the `${VAR}` environment variable references in the string values of the
+/// plugin metadata and the timeline attributes
+#[cfg(feature = "ingest")]
+fn expand_config_env_vars(cfg: &mut Config) -> Result<(), EnvVarExpansionError> {
+    fn expand_toml(v: &mut TomlValue) -> Result<(), EnvVarExpansionError> {
+        match v {
+            TomlValue::String(s) => *s = expand_env_vars(s)?,
+            TomlValue::Array(vals) => vals.iter_mut().try_for_each(expand_toml)?,
+            TomlValue::Table(t) => t.iter_mut().try_for_each(|(_, v)| expand_toml(v))?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    cfg.metadata.values_mut().try_for_each(expand_toml)?;
+    if let Some(ingest) = cfg.ingest.as_mut() {
+        let attrs = &mut ingest.timeline_attributes;
+        for kv in attrs
+            .additional_timeline_attributes
+            .iter_mut()
+            .chain(attrs.override_timeline_attributes.iter_mut())
+        {
+            if let AttrVal::String(s) = &kv.1 {
+                if s.as_ref().contains("${") {
+                    kv.1 = expand_env_vars(s.as_ref())?.into();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Expands the `${VAR}` environment variable references in a configuration value,
+/// `$${` is a literal `${`
+#[cfg(feature = "ingest")]
+fn expand_env_vars(s: &str) -> Result<String, EnvVarExpansionError> {
+    expand_env_vars_with(s, |name| env::var(name).ok())
+}
+
+#[cfg(feature = "ingest")]
+fn expand_env_vars_with<F: Fn(&str) -> Option<String>>(
+    s: &str,
+    lookup: F,
+) -> Result<String, EnvVarExpansionError> {
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            expanded.push_str(&rest[..start - 1]);
+            expanded.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        expanded.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let end = reference
+            .find('}')
+            .ok_or_else(|| EnvVarExpansionError::Unterminated(s.to_owned()))?;
+        let name = &reference[..end];
+        let value = lookup(name).ok_or_else(|| EnvVarExpansionError::NotSet(name.to_owned()))?;
+        expanded.push_str(&value);
+        rest = &reference[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(feature = "ingest")]
+pub(crate) mod internal {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct CommonPluginConfig {
+        pub client_timeout: Option<HumanTime>,
+        pub run_id: Option<String>,
+        pub clock_id: Option<String>,
+        pub init_task_name: Option<String>,
+        pub disable_interactions: bool,
+        pub disable_linear_causality: bool,
+        pub clock_rate: Option<Rate>,
+        pub rtos_mode: RtosMode,
+        pub pre_start_policy: PreStartPolicy,
+        pub rtos_markers: RtosMarkers,
+        pub fold_isrs: Vec<Glob>,
+        pub elf_file: Option<PathBuf>,
+        pub elf_reload: bool,
+        pub debug_file: Option<PathBuf>,
+        pub outcome_rules: Vec<OutcomeRule>,
+        pub message_correlations: Vec<MessageCorrelation>,
+        pub boot_index: Option<u64>,
+        pub segment_runs: bool,
+        pub reboot_event: Option<String>,
+        pub reboot_timestamp_threshold: Option<u64>,
+        pub core_id: Option<u64>,
+        pub context_stats: bool,
+        pub max_context_depth: Option<usize>,
+        pub unknown_context_name: Option<String>,
+        pub unknown_context_per_source: bool,
+        pub phase_marker_event: Option<String>,
+        pub promote_internal_attributes: Vec<Glob>,
+        pub framing: Framing,
+        pub frame_crc: FrameCrc,
+        pub max_array_elements: Option<usize>,
+        pub byte_encoding: ByteEncoding,
+        pub display_hints: bool,
+        pub println_level: Option<String>,
+        pub timestamp_composition: TimestampComposition,
+        pub timestamp_width: Option<u32>,
+        pub host_time_fallback: bool,
+        pub backwards_timestamp_policy: BackwardsTimestampPolicy,
+        pub timestamp_gap_threshold: Option<HumanTime>,
+        pub event_name_delimiter: Option<String>,
+        pub attr_separator: Option<char>,
+        pub literal_event_names: bool,
+        pub unnamed_event_names: UnnamedEventNames,
+        pub normalize_event_names: bool,
+        pub max_event_name_length: Option<usize>,
+        pub max_attribute_keys: Option<usize>,
+        pub attribute_key_limit_policy: AttrKeyLimitPolicy,
+        pub infer_units: bool,
+        pub attribute_renames: BTreeMap<String, String>,
+        pub attribute_coercions: Vec<AttrCoercion>,
+        pub event_filter: EventFilter,
+        pub stop_on: Vec<StopTrigger>,
+        pub exclude_table_indices: Vec<u64>,
+        pub exclude_format_strings: Vec<Glob>,
+        pub omit_internal_attributes: bool,
+        pub omit_formatted_string: bool,
+        pub debug_attributes: bool,
+        pub message_attribute: bool,
+        pub raw_frame_attributes: bool,
+        pub metrics_interval: Option<HumanTime>,
+        pub metric_events: bool,
+        pub save_raw: Option<PathBuf>,
+        pub save_raw_rotate_size: Option<u64>,
+        pub save_raw_rotate_count: Option<usize>,
+        pub summary_file: Option<PathBuf>,
+        pub source_path_remaps: Vec<SourcePathRemap>,
+        pub source_urls: Vec<SourceUrlMapping>,
+        pub source_commit: Option<String>,
+        pub additional_event_attributes: Vec<AttrKeyEqValuePair>,
+    }
+
+    impl From<CommonPluginConfig> for PluginConfig {
+        fn from(c: CommonPluginConfig) -> Self {
+            Self {
+                client_timeout: c.client_timeout,
+                run_id: c.run_id,
+                clock_id: c.clock_id,
+                init_task_name: c.init_task_name,
+                disable_interactions: c.disable_interactions,
+                disable_linear_causality: c.disable_linear_causality,
+                clock_rate: c.clock_rate,
+                rtos_mode: c.rtos_mode,
+                pre_start_policy: c.pre_start_policy,
+                rtos_markers: c.rtos_markers,
+                fold_isrs: c.fold_isrs,
+                elf_file: c.elf_file,
+                elf_reload: c.elf_reload,
+                debug_file: c.debug_file,
+                outcome_rules: c.outcome_rules,
+                message_correlations: c.message_correlations,
+                boot_index: c.boot_index,
+                segment_runs: c.segment_runs,
+                reboot_event: c.reboot_event,
+                reboot_timestamp_threshold: c.reboot_timestamp_threshold,
+                core_id: c.core_id,
+                context_stats: c.context_stats,
+                max_context_depth: c.max_context_depth,
+                unknown_context_name: c.unknown_context_name,
+                unknown_context_per_source: c.unknown_context_per_source,
+                phase_marker_event: c.phase_marker_event,
+                promote_internal_attributes: c.promote_internal_attributes,
+                framing: c.framing,
+                frame_crc: c.frame_crc,
+                max_array_elements: c.max_array_elements,
+                byte_encoding: c.byte_encoding,
+                display_hints: c.display_hints,
+                println_level: c.println_level,
+                timestamp_composition: c.timestamp_composition,
+                timestamp_width: c.timestamp_width,
+                host_time_fallback: c.host_time_fallback,
+                backwards_timestamp_policy: c.backwards_timestamp_policy,
+                timestamp_gap_threshold: c.timestamp_gap_threshold,
+                event_name_delimiter: c.event_name_delimiter,
+                attr_separator: c.attr_separator,
+                literal_event_names: c.literal_event_names,
+                unnamed_event_names: c.unnamed_event_names,
+                normalize_event_names: c.normalize_event_names,
+                max_event_name_length: c.max_event_name_length,
+                max_attribute_keys: c.max_attribute_keys,
+                attribute_key_limit_policy: c.attribute_key_limit_policy,
+                infer_units: c.infer_units,
+                attribute_renames: c.attribute_renames,
+                attribute_coercions: c.attribute_coercions,
+                event_filter: c.event_filter,
+                stop_on: c.stop_on,
+                exclude_table_indices: c.exclude_table_indices,
+                exclude_format_strings: c.exclude_format_strings,
+                omit_internal_attributes: c.omit_internal_attributes,
+                omit_formatted_string: c.omit_formatted_string,
+                debug_attributes: c.debug_attributes,
+                message_attribute: c.message_attribute,
+                raw_frame_attributes: c.raw_frame_attributes,
+                metrics_interval: c.metrics_interval,
+                metric_events: c.metric_events,
+                save_raw: c.save_raw,
+                save_raw_rotate_size: c.save_raw_rotate_size,
+                save_raw_rotate_count: c.save_raw_rotate_count,
+                summary_file: c.summary_file,
+                source_path_remaps: c.source_path_remaps,
+                source_urls: c.source_urls,
+                source_commit: c.source_commit,
+                additional_event_attributes: c.additional_event_attributes,
+                import: Default::default(),
+                #[cfg(feature = "rtt-collector")]
+                rtt_collector: Default::default(),
+                #[cfg(feature = "serial-collector")]
+                serial_collector: Default::default(),
+                #[cfg(feature = "tcp-collector")]
+                tcp_collector: Default::default(),
+                #[cfg(feature = "openocd-collector")]
+                openocd_collector: Default::default(),
+                #[cfg(feature = "process-collector")]
+                process_collector: Default::default(),
+                #[cfg(feature = "renode-collector")]
+                renode_collector: Default::default(),
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct ImportPluginConfig {
+        #[serde(flatten)]
+        pub common: CommonPluginConfig,
+        #[serde(flatten)]
+        pub import: ImportConfig,
+    }
+
+    impl From<ImportPluginConfig> for PluginConfig {
+        fn from(pc: ImportPluginConfig) -> Self {
+            let ImportPluginConfig { common, import } = pc;
+            let mut c = PluginConfig::from(common);
+            c.import = import;
+            c
+        }
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct RttCollectorPluginConfig {
+        #[serde(flatten)]
+        pub common: CommonPluginConfig,
+        #[serde(flatten)]
+        pub rtt_collector: RttCollectorConfig,
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    impl From<RttCollectorPluginConfig> for PluginConfig {
+        fn from(pc: RttCollectorPluginConfig) -> Self {
+            let RttCollectorPluginConfig {
+                common,
+                rtt_collector,
+            } = pc;
+            let mut c = PluginConfig::from(common);
+            c.rtt_collector = rtt_collector;
+            c
+        }
+    }
+
+    #[cfg(feature = "serial-collector")]
+    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct SerialCollectorPluginConfig {
+        #[serde(flatten)]
+        pub common: CommonPluginConfig,
+        #[serde(flatten)]
+        pub serial_collector: SerialCollectorConfig,
+    }
+
+    #[cfg(feature = "serial-collector")]
+    impl From<SerialCollectorPluginConfig> for PluginConfig {
+        fn from(pc: SerialCollectorPluginConfig) -> Self {
+            let SerialCollectorPluginConfig {
+                common,
+                serial_collector,
+            } = pc;
+            let mut c = PluginConfig::from(common);
+            c.serial_collector = serial_collector;
+            c
+        }
+    }
+
+    #[cfg(feature = "tcp-collector")]
+    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct TcpCollectorPluginConfig {
+        #[serde(flatten)]
+        pub common: CommonPluginConfig,
+        #[serde(flatten)]
+        pub tcp_collector: TcpCollectorConfig,
+    }
+
+    #[cfg(feature = "tcp-collector")]
+    impl From<TcpCollectorPluginConfig> for PluginConfig {
+        fn from(pc: TcpCollectorPluginConfig) -> Self {
+            let TcpCollectorPluginConfig {
+                common,
+                tcp_collector,
+            } = pc;
+            let mut c = PluginConfig::from(common);
+            c.tcp_collector = tcp_collector;
+            c
+        }
+    }
+
+    #[cfg(feature = "openocd-collector")]
+    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct OpenOcdCollectorPluginConfig {
+        #[serde(flatten)]
+        pub common: CommonPluginConfig,
+        #[serde(flatten)]
+        pub openocd_collector: OpenOcdCollectorConfig,
+    }
+
+    #[cfg(feature = "openocd-collector")]
+    impl From<OpenOcdCollectorPluginConfig> for PluginConfig {
+        fn from(pc: OpenOcdCollectorPluginConfig) -> Self {
+            let OpenOcdCollectorPluginConfig {
+                common,
+                openocd_collector,
+            } = pc;
+            let mut c = PluginConfig::from(common);
+            c.openocd_collector = openocd_collector;
+            c
+        }
+    }
+
+    #[cfg(feature = "process-collector")]
+    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct ProcessCollectorPluginConfig {
+        #[serde(flatten)]
+        pub common: CommonPluginConfig,
+        #[serde(flatten)]
+        pub process_collector: ProcessCollectorConfig,
+    }
+
+    #[cfg(feature = "process-collector")]
+    impl From<ProcessCollectorPluginConfig> for PluginConfig {
+        fn from(pc: ProcessCollectorPluginConfig) -> Self {
+            let ProcessCollectorPluginConfig {
+                common,
+                process_collector,
+            } = pc;
+            let mut c = PluginConfig::from(common);
+            c.process_collector = process_collector;
+            c
+        }
+    }
+
+    #[cfg(feature = "renode-collector")]
+    #[derive(Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case", default)]
+    pub struct RenodeCollectorPluginConfig {
+        #[serde(flatten)]
+        pub common: CommonPluginConfig,
+        #[serde(flatten)]
+        pub renode_collector: RenodeCollectorConfig,
+    }
+
+    #[cfg(feature = "renode-collector")]
+    impl From<RenodeCollectorPluginConfig> for PluginConfig {
+        fn from(pc: RenodeCollectorPluginConfig) -> Self {
+            let RenodeCollectorPluginConfig {
+                common,
+                renode_collector,
+            } = pc;
+            let mut c = PluginConfig::from(common);
+            c.renode_collector = renode_collector;
+            c
+        }
+    }
+}
+
+#[cfg(feature = "ingest")]
+impl PluginConfig {
+    pub(crate) fn from_metadata(
+        cfg: &Config,
+        entry: DefmtConfigEntry,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        match entry {
+            DefmtConfigEntry::Importer => {
+                Self::from_cfg_metadata::<internal::ImportPluginConfig>(cfg).map(|c| c.into())
+            }
+            #[cfg(feature = "rtt-collector")]
+            DefmtConfigEntry::RttCollector => {
+                Self::from_cfg_metadata::<internal::RttCollectorPluginConfig>(cfg).map(|c| c.into())
+            }
+            #[cfg(feature = "serial-collector")]
+            DefmtConfigEntry::SerialCollector => {
+                Self::from_cfg_metadata::<internal::SerialCollectorPluginConfig>(cfg)
+                    .map(|c| c.into())
+            }
+            #[cfg(feature = "tcp-collector")]
+            DefmtConfigEntry::TcpCollector => {
+                Self::from_cfg_metadata::<internal::TcpCollectorPluginConfig>(cfg).map(|c| c.into())
+            }
+            #[cfg(feature = "openocd-collector")]
+            DefmtConfigEntry::OpenOcdCollector => {
+                Self::from_cfg_metadata::<internal::OpenOcdCollectorPluginConfig>(cfg)
+                    .map(|c| c.into())
+            }
+            #[cfg(feature = "process-collector")]
+            DefmtConfigEntry::ProcessCollector => {
+                Self::from_cfg_metadata::<internal::ProcessCollectorPluginConfig>(cfg)
+                    .map(|c| c.into())
+            }
+            #[cfg(feature = "renode-collector")]
+            DefmtConfigEntry::RenodeCollector => {
+                Self::from_cfg_metadata::<internal::RenodeCollectorPluginConfig>(cfg)
+                    .map(|c| c.into())
+            }
+        }
+    }
+
+    fn from_cfg_metadata<'a, T: Deserialize<'a>>(
+        cfg: &Config,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let cfg = TomlValue::Table(cfg.metadata.clone().into_iter().collect()).try_into()?;
+        Ok(cfg)
+    }
+}
+
+#[cfg(all(test, feature = "rtt-collector"))]
+mod test {
+    use super::*;
+    use auxon_sdk::reflector_config::{AttrKeyEqValuePair, TimelineAttributes};
+    use pretty_assertions::assert_eq;
+    use std::{env, fs::File, io::Write};
+
+    const IMPORTER_CONFIG: &str = r#"[ingest]
+protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
+additional-timeline-attributes = [
+    "ci_run=1",
+    "platform='RTICv1'",
+    "defmt-mode='rtt'",
+]
+
+[metadata]
+client-timeout = "1s"
+run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+clock-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+init-task-name = 'main'
+disable-interactions = true
+disable-linear-causality = true
+rtos-mode = "rtic1"
+pre-start-policy = "hold"
+fold-isrs = ["SysTick", "UART*"]
+clock-rate = "1/1000000"
+elf-file = "fw.elf"
+elf-reload = true
+debug-file = "fw.debug"
+boot-index = 2
+segment-runs = true
+reboot-event = "booted"
+reboot-timestamp-threshold = 1000
+core-id = 1
+context-stats = true
+max-context-depth = 8
+unknown-context-name = "ORPHANS"
+unknown-context-per-source = true
+phase-marker-event = "TEST_PHASE"
+promote-internal-attributes = ["event.internal.defmt.table_index", "timeline.internal.defmt.*"]
+framing = "cobs"
+frame-crc = "crc32"
+max-array-elements = 16
+byte-encoding = "base64"
+display-hints = true
+println-level = "info"
+timestamp-composition = "high-low"
+timestamp-width = 24
+host-time-fallback = true
+backwards-timestamp-policy = "rollover"
+timestamp-gap-threshold = "10ms"
+event-name-delimiter = "@@"
+attr-separator = "|"
+literal-event-names = true
+unnamed-event-names = "format-string"
+normalize-event-names = true
+max-event-name-length = 64
+max-attribute-keys = 512
+attribute-key-limit-policy = "drop"
+infer-units = true
+exclude-table-indices = [3, 7]
+exclude-format-strings = ["poll:: *"]
+omit-internal-attributes = true
+omit-formatted-string = true
+debug-attributes = true
+message-attribute = true
+raw-frame-attributes = true
+metrics-interval = "5s"
+metric-events = true
+save-raw = "capture.bin"
+save-raw-rotate-size = 1048576
+save-raw-rotate-count = 3
+summary-file = "summary.json"
+source-commit = "3f2a1c9"
+additional-event-attributes = ["test_case='thermal-cycle-3'", "fixture=2"]
+open-timeout = "100ms"
+file = "rtt_log.bin"
+unix-socket = "/tmp/defmt.sock"
+unix-socket-listen = true
+named-pipe = '\\.\pipe\defmt'
+named-pipe-listen = true
+compression = "zstd"
+pcap-filter = "udp:5000"
+logic-capture = "sigrok"
+capture-sample-rate = 24000000
+jlink-rtt-logger = true
+rtt-dump = true
+rtt-dump-address = 536870912
+rtt-dump-control-block-address = 536870928
+rtt-dump-up-channel = 1
+rtt-dump-history = true
+
+[[metadata.outcome-rules]]
+name = "panic*"
+outcome = "failure"
+severity = "critical"
+
+[[metadata.outcome-rules]]
+level = "error"
+outcome = "fail"
+
+[[metadata.message-correlations]]
+send = "queue_send*"
+receive = "queue_recv*"
+key = "msg_id"
+
+[metadata.rtos-markers]
+trace-start = "SCHED_START"
+task-enter = "SCHED_SWITCH_IN"
+task-exit = "SCHED_SWITCH_OUT"
+isr-enter = "IRQ_IN"
+isr-exit = "IRQ_OUT"
+task-name-key = "tcb"
+isr-name-key = "event.irq"
+version-key = "sched_version"
+
+[metadata.attribute-renames]
+"event.tid" = "event.task_id"
+"event.temp" = "event.temperature_c"
+
+[[metadata.attribute-coercions]]
+key = "event.status"
+to = "hex-int"
+
+[[metadata.attribute-coercions]]
+key = "event.temp_*"
+scale = 0.1
+
+[metadata.event-filter]
+exclude-levels = ["trace"]
+include-modules = ["fw::*"]
+exclude-names = ["tick*"]
+
+[[metadata.stop-on]]
+name = "panic*"
+level = "error"
+attributes = { "event.code" = "4?" }
+
+[[metadata.source-path-remaps]]
+from = "/home/ci/builds/fw/"
+to = ""
+
+[[metadata.source-urls]]
+prefix = "src/"
+url = "https://github.com/acme/fw/blob/{commit}/src/{path}#L{line}"
+"#;
+
+    const RTT_COLLECTOR_CONFIG: &str = r#"[ingest]
+protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
+additional-timeline-attributes = [
+    "ci_run=1",
+    "platform='RTICv1'",
+    "module='m3'",
+    "defmt-mode='rtt'",
+]
+
+[metadata]
+client-timeout = "1s"
+run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+clock-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+init-task-name = 'fw'
+disable-interactions = true
+rtos-mode = "rtic1"
+pre-start-policy = "pre-start-timeline"
+elf-file = "fw.elf"
+clock-rate = "1/2000000"
+attach-timeout = "100ms"
+up-channel = 1
+control-block-address = 0xFFFFF
+down-channel = 1
+probe-selector = '234:234'
+chip = 'stm32'
+protocol = 'Jtag'
+speed = 1234
+core = 1
+reset = true
+attach-under-reset = true
+download = true
+verify = true
+chip-description-path = "/tmp/stm32.yaml"
+arch = "riscv"
+thumb = true
+setup-on-breakpoint = "main"
+channel-mode = "no-block-skip"
+rtt-poll-interval = "1ms"
+rtt-idle-poll-interval = "50ms"
+rtt-poll-policy = "adaptive"
+rtt-read-buffer-size = 1024
+metrics = true
+shared-probe = true
+reattach = true
+reattach-attempts = 10
+reattach-interval = "500ms"
+exit-on-idle = "30s"
+non-intrusive = true
+halt-on-stop = true
+snapshot = true
+crash-capture = true
+crash-stack-size = 512
+crash-memory = ["ERROR_CODE:4", "0x20000000:0x40"]
+watch = ["HEAP_HIGH_WATER:4", "0x20000100:8"]
+watch-interval = "250ms"
+fault-catch = true
+fault-action = "stop"
+pre-attach = ["write=0x40021000=0x1", "delay=10ms"]
+post-attach = ["reg=r0=0x20", "exec=./host-ready.sh"]
+clear-staged-mutation = true
+
+[[metadata.outcome-rules]]
+name = "TEST_PASS"
+outcome = "success"
+
+[[metadata.up-channels]]
+channel = 0
+
+[[metadata.up-channels]]
+channel = 1
+elf-file = "trace.elf"
+core-id = 1
+
+[[metadata.up-channels]]
+name = "metrics"
+elf-file = "metrics.elf"
+
+[[metadata.text-up-channels]]
+name = "printf"
+
+[[metadata.setup-stages]]
+breakpoint = "main"
+
+[[metadata.setup-stages]]
+breakpoint = "rtt_init_done"
+channel-mode = "block-if-full"
+write = ["READY=1", "0x20000000=0xAB"]
+start = true
+
+[[metadata.crash-events]]
+name = "fault*"
+level = "error"
+
+[metadata.ring-buffer]
+buffer = "LOG_BUF"
+size = 1024
+head = "LOG_HEAD"
+tail = "0x20000010"
+
+[metadata.staged-mutation]
+mutator-id = "8a7b3d6c-4d37-4bb5-9a1d-5c9f1b2e3a40"
+mutation-id = "0f3e2c1b-6a59-4d87-b4c2-e1f0a9b8c7d6"
+"#;
+
+    #[cfg(feature = "rtt-collector")]
+    const RTT_SUPERVISOR_CONFIG: &str = r#"[ingest]
+protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
+
+[metadata]
+elf-file = "fw.elf"
+chip = 'stm32'
+speed = 1234
+restart-interval = "2s"
+status-interval = "30s"
+
+[[metadata.targets]]
+name = "dut0"
+probe-selector = '234:234:SN0'
+
+[[metadata.targets]]
+name = "dut1"
+run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+elf-file = "fw-b.elf"
+probe-selector = '234:234:SN1'
+chip = 'nrf52'
+protocol = 'Jtag'
+speed = 4321
+core = 1
+control-block-address = 0xFFFFF
+up-channel = 2
+setup-on-breakpoint = "main"
+"#;
+
+    #[cfg(feature = "serial-collector")]
+    const SERIAL_COLLECTOR_CONFIG: &str = r#"[ingest]
+protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
+additional-timeline-attributes = [
+    "ci_run=1",
+    "defmt-mode='uart'",
+]
+
+[metadata]
+run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+elf-file = "fw.elf"
+open-timeout = "2s"
+path = "/dev/ttyACM0"
+usb-device = "16c0:27dd:ABC123"
+baud-rate = 921600
+data-bits = 7
+parity = "even"
+stop-bits = 2
+flow-control = "hardware"
+reopen-interval = "500ms"
+disable-reopen = true
+esp-usb-serial-jtag = true
+esp-reset = true
+"#;
+
+    #[cfg(feature = "tcp-collector")]
+    const TCP_COLLECTOR_CONFIG: &str = r#"[ingest]
+protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
+
+[metadata]
+run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+elf-file = "fw.elf"
+connect-timeout = "5s"
+remote = "localhost:3456"
+listen = "0.0.0.0:3456"
+reconnect-interval = "250ms"
+disable-reconnect = true
+"#;
+
+    #[cfg(feature = "openocd-collector")]
+    const OPENOCD_COLLECTOR_CONFIG: &str = r#"[ingest]
+protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
+
+[metadata]
+run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+elf-file = "fw.elf"
+connect-timeout = "5s"
+host = "192.168.1.2"
+tcl-port = 6667
+rtt-port = 8765
+up-channel = 1
+control-block-address = 0x20000000
+control-block-search-size = 0x10000
+control-block-id = "MY RTT"
+disable-setup = true
+reconnect-interval = "2s"
+"#;
+
+    #[cfg(feature = "process-collector")]
+    const PROCESS_COLLECTOR_CONFIG: &str = r#"[ingest]
+protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
+
+[metadata]
+run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+elf-file = "fw.elf"
+command = "qemu-system-arm"
+args = ["-machine", "lm3s6965evb", "-nographic", "-semihosting-config", "enable=on,target=native", "-kernel", "fw.elf"]
+working-directory = "/tmp"
+stream = "fd:3"
+"#;
+
+    #[cfg(feature = "renode-collector")]
+    const RENODE_COLLECTOR_CONFIG: &str = r#"[ingest]
+protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
+
+[metadata]
+run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
+elf-file = "fw.elf"
+connect-timeout = "10s"
+uart-socket = "localhost:3456"
+uart-pty = "/tmp/uart"
+monitor = "localhost:1234"
+monitor-commands = ["start"]
+machine = "atsamd"
+"#;
+
+    // Do a basic round trip check while we're at it
+    fn get_cfg(content: &str, entry: DefmtConfigEntry) -> DefmtConfig {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my_config.toml");
+        {
+            let mut f = File::create(&path).unwrap();
+            f.write_all(content.as_bytes()).unwrap();
+            f.flush().unwrap();
+        }
+
+        let cfg = DefmtConfig::load_merge_with_opts(
+            entry,
+            ReflectorOpts {
+                config_file: Some(path.to_path_buf()),
+                ..Default::default()
+            },
+            Default::default(),
+        )
+        .unwrap();
+
+        env::set_var(CONFIG_ENV_VAR, path);
+        let env_cfg =
+            DefmtConfig::load_merge_with_opts(entry, Default::default(), Default::default())
+                .unwrap();
+        env::remove_var(CONFIG_ENV_VAR);
+        assert_eq!(cfg, env_cfg);
+        cfg
+    }
+
+    #[test]
+    fn importer_cfg() {
+        let cfg = get_cfg(IMPORTER_CONFIG, DefmtConfigEntry::Importer);
+        assert_eq!(
+            cfg,
+            DefmtConfig {
+                auth_token: None,
+                ingest: TopLevelIngest {
+                    protocol_parent_url: Url::parse("modality-ingest://127.0.0.1:14182")
+                        .unwrap()
+                        .into(),
+                    allow_insecure_tls: false,
+                    protocol_child_port: None,
+                    timeline_attributes: TimelineAttributes {
+                        additional_timeline_attributes: vec![
+                            AttrKeyEqValuePair::from_str("ci_run=1").unwrap(),
+                            AttrKeyEqValuePair::from_str("platform='RTICv1'").unwrap(),
+                            AttrKeyEqValuePair::from_str("defmt-mode='rtt'").unwrap(),
+                        ],
+                        override_timeline_attributes: Default::default(),
+                    },
+                    max_write_batch_staleness: None,
+                },
+                plugin: PluginConfig {
+                    client_timeout: HumanTime::from_str("1s").unwrap().into(),
+                    run_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_string().into(),
+                    clock_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_owned().into(),
+                    init_task_name: "main".to_owned().into(),
+                    disable_interactions: true,
+                    disable_linear_causality: true,
+                    rtos_mode: RtosMode::Rtic1,
+                    pre_start_policy: PreStartPolicy::Hold,
+                    rtos_markers: RtosMarkers {
+                        trace_start: "SCHED_START".to_owned().into(),
+                        task_enter: "SCHED_SWITCH_IN".to_owned().into(),
+                        task_exit: "SCHED_SWITCH_OUT".to_owned().into(),
+                        isr_enter: "IRQ_IN".to_owned().into(),
+                        isr_exit: "IRQ_OUT".to_owned().into(),
+                        task_name_key: "tcb".to_owned().into(),
+                        isr_name_key: "event.irq".to_owned().into(),
+                        version_key: "sched_version".to_owned().into(),
+                    },
+                    fold_isrs: vec![Glob::new("SysTick"), Glob::new("UART*")],
+                    clock_rate: Some(Rate::new(1, 1000000).unwrap()),
+                    elf_file: PathBuf::from("fw.elf").into(),
+                    elf_reload: true,
+                    debug_file: PathBuf::from("fw.debug").into(),
+                    outcome_rules: vec![
+                        OutcomeRule {
+                            name: Glob::new("panic*").into(),
+                            level: None,
+                            outcome: Outcome::Failure.into(),
+                            severity: "critical".to_owned().into(),
+                        },
+                        OutcomeRule {
+                            name: None,
+                            level: "error".to_owned().into(),
+                            outcome: Outcome::Failure.into(),
+                            severity: None,
+                        },
+                    ],
+                    message_correlations: vec![MessageCorrelation {
+                        send: Glob::new("queue_send*").into(),
+                        receive: Glob::new("queue_recv*").into(),
+                        key: "msg_id".to_owned().into(),
+                    }],
+                    boot_index: 2.into(),
+                    segment_runs: true,
+                    reboot_event: "booted".to_owned().into(),
+                    reboot_timestamp_threshold: 1000.into(),
+                    core_id: 1.into(),
+                    context_stats: true,
+                    max_context_depth: 8.into(),
+                    unknown_context_name: "ORPHANS".to_owned().into(),
+                    unknown_context_per_source: true,
+                    phase_marker_event: "TEST_PHASE".to_owned().into(),
+                    promote_internal_attributes: vec![
+                        Glob::new("event.internal.defmt.table_index"),
+                        Glob::new("timeline.internal.defmt.*"),
+                    ],
+                    framing: Framing::Cobs,
+                    frame_crc: FrameCrc::Crc32,
+                    max_array_elements: 16.into(),
+                    byte_encoding: ByteEncoding::Base64,
+                    display_hints: true,
+                    println_level: "info".to_owned().into(),
+                    timestamp_composition: TimestampComposition::HighLow,
+                    timestamp_width: 24.into(),
+                    host_time_fallback: true,
+                    backwards_timestamp_policy: BackwardsTimestampPolicy::Rollover,
+                    timestamp_gap_threshold: HumanTime::from_str("10ms").unwrap().into(),
+                    event_name_delimiter: Some("@@".to_owned()),
+                    attr_separator: Some('|'),
+                    literal_event_names: true,
+                    unnamed_event_names: UnnamedEventNames::FormatString,
+                    normalize_event_names: true,
+                    max_event_name_length: 64.into(),
+                    max_attribute_keys: 512.into(),
+                    attribute_key_limit_policy: AttrKeyLimitPolicy::Drop,
+                    infer_units: true,
+                    attribute_renames: vec![
+                        ("event.temp".to_owned(), "event.temperature_c".to_owned()),
+                        ("event.tid".to_owned(), "event.task_id".to_owned()),
+                    ]
+                    .into_iter()
+                    .collect(),
+                    attribute_coercions: vec![
+                        AttrCoercion {
+                            key: Glob::new("event.status").into(),
+                            to: AttrType::HexInt.into(),
+                            scale: None,
+                        },
+                        AttrCoercion {
+                            key: Glob::new("event.temp_*").into(),
+                            to: None,
+                            scale: ScaleFactor(0.1).into(),
+                        },
+                    ],
+                    event_filter: EventFilter {
+                        exclude_levels: vec![Glob::new("trace")],
+                        include_modules: vec![Glob::new("fw::*")],
+                        exclude_names: vec![Glob::new("tick*")],
+                        ..Default::default()
+                    },
+                    stop_on: vec![StopTrigger {
+                        name: Glob::new("panic*").into(),
+                        level: "error".to_owned().into(),
+                        module: None,
+                        attributes: [("event.code".to_owned(), Glob::new("4?"))]
+                            .into_iter()
+                            .collect(),
+                    }],
+                    exclude_table_indices: vec![3, 7],
+                    exclude_format_strings: vec![Glob::new("poll:: *")],
+                    omit_internal_attributes: true,
+                    omit_formatted_string: true,
+                    debug_attributes: true,
+                    message_attribute: true,
+                    raw_frame_attributes: true,
+                    metrics_interval: HumanTime::from_str("5s").unwrap().into(),
+                    metric_events: true,
+                    save_raw: PathBuf::from("capture.bin").into(),
+                    save_raw_rotate_size: Some(1048576),
+                    save_raw_rotate_count: Some(3),
+                    summary_file: PathBuf::from("summary.json").into(),
+                    source_path_remaps: vec![SourcePathRemap {
+                        from: "/home/ci/builds/fw/".to_owned(),
+                        to: "".to_owned(),
+                    }],
+                    source_urls: vec![SourceUrlMapping {
+                        prefix: "src/".to_owned(),
+                        url: "https://github.com/acme/fw/blob/{commit}/src/{path}#L{line}"
+                            .to_owned(),
+                        commit: None,
+                    }],
+                    source_commit: "3f2a1c9".to_owned().into(),
+                    additional_event_attributes: vec![
+                        AttrKeyEqValuePair::from_str("test_case='thermal-cycle-3'").unwrap(),
+                        AttrKeyEqValuePair::from_str("fixture=2").unwrap(),
+                    ],
+                    import: ImportConfig {
+                        open_timeout: HumanTime::from_str("100ms").unwrap().into(),
+                        file: PathBuf::from("rtt_log.bin").into(),
+                        unix_socket: PathBuf::from("/tmp/defmt.sock").into(),
+                        unix_socket_listen: true,
+                        named_pipe: r"\\.\pipe\defmt".to_owned().into(),
+                        named_pipe_listen: true,
+                        compression: InputCompression::Zstd,
+                        pcap_filter: Some(PcapFilter::Udp(5000)),
+                        logic_capture: Some(LogicCapture::Sigrok),
+                        capture_sample_rate: Some(24_000_000),
+                        jlink_rtt_logger: true,
+                        rtt_dump: true,
+                        rtt_dump_address: Some(0x2000_0000),
+                        rtt_dump_control_block_address: Some(0x2000_0010),
+                        rtt_dump_up_channel: 1,
+                        rtt_dump_history: true,
+                    },
+                    rtt_collector: Default::default(),
+                    #[cfg(feature = "serial-collector")]
+                    serial_collector: Default::default(),
+                    #[cfg(feature = "tcp-collector")]
+                    tcp_collector: Default::default(),
+                    #[cfg(feature = "openocd-collector")]
+                    openocd_collector: Default::default(),
+                    #[cfg(feature = "process-collector")]
+                    process_collector: Default::default(),
+                    #[cfg(feature = "renode-collector")]
+                    renode_collector: Default::default(),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn rtt_collector_cfg() {
+        let cfg = get_cfg(RTT_COLLECTOR_CONFIG, DefmtConfigEntry::RttCollector);
+        assert_eq!(
+            cfg,
+            DefmtConfig {
+                auth_token: None,
+                ingest: TopLevelIngest {
+                    protocol_parent_url: Url::parse("modality-ingest://127.0.0.1:14182")
+                        .unwrap()
+                        .into(),
+                    allow_insecure_tls: false,
+                    protocol_child_port: None,
+                    timeline_attributes: TimelineAttributes {
+                        additional_timeline_attributes: vec![
+                            AttrKeyEqValuePair::from_str("ci_run=1").unwrap(),
+                            AttrKeyEqValuePair::from_str("platform='RTICv1'").unwrap(),
+                            AttrKeyEqValuePair::from_str("module='m3'").unwrap(),
+                            AttrKeyEqValuePair::from_str("defmt-mode='rtt'").unwrap(),
+                        ],
+                        override_timeline_attributes: Default::default(),
+                    },
+                    max_write_batch_staleness: None,
+                },
+                plugin: PluginConfig {
+                    client_timeout: HumanTime::from_str("1s").unwrap().into(),
+                    run_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_string().into(),
+                    clock_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_owned().into(),
+                    init_task_name: "fw".to_owned().into(),
+                    disable_interactions: true,
+                    disable_linear_causality: false,
+                    rtos_mode: RtosMode::Rtic1,
+                    pre_start_policy: PreStartPolicy::Timeline,
+                    rtos_markers: Default::default(),
+                    fold_isrs: Vec::new(),
+                    clock_rate: Some(Rate::new(1, 2000000).unwrap()),
+                    elf_file: PathBuf::from("fw.elf").into(),
+                    elf_reload: false,
+                    debug_file: None,
+                    outcome_rules: vec![OutcomeRule {
+                        name: Glob::new("TEST_PASS").into(),
+                        level: None,
+                        outcome: Outcome::Success.into(),
+                        severity: None,
+                    }],
+                    message_correlations: Vec::new(),
+                    boot_index: None,
+                    segment_runs: false,
+                    reboot_event: None,
+                    reboot_timestamp_threshold: None,
+                    core_id: None,
+                    context_stats: false,
+                    max_context_depth: None,
+                    unknown_context_name: None,
+                    unknown_context_per_source: false,
+                    phase_marker_event: None,
+                    promote_internal_attributes: Vec::new(),
+                    framing: Default::default(),
+                    frame_crc: Default::default(),
+                    max_array_elements: None,
+                    byte_encoding: Default::default(),
+                    display_hints: false,
+                    println_level: None,
+                    timestamp_composition: Default::default(),
+                    timestamp_width: None,
+                    host_time_fallback: false,
+                    backwards_timestamp_policy: Default::default(),
+                    timestamp_gap_threshold: None,
+                    event_name_delimiter: None,
+                    attr_separator: None,
+                    literal_event_names: false,
+                    unnamed_event_names: Default::default(),
+                    normalize_event_names: false,
+                    max_event_name_length: None,
+                    max_attribute_keys: None,
+                    attribute_key_limit_policy: Default::default(),
+                    infer_units: false,
+                    attribute_renames: Default::default(),
+                    attribute_coercions: Vec::new(),
+                    event_filter: Default::default(),
+                    stop_on: Vec::new(),
+                    exclude_table_indices: Vec::new(),
+                    exclude_format_strings: Vec::new(),
+                    omit_internal_attributes: false,
+                    omit_formatted_string: false,
+                    debug_attributes: false,
+                    message_attribute: false,
+                    raw_frame_attributes: false,
+                    metrics_interval: None,
+                    metric_events: false,
+                    save_raw: None,
+                    save_raw_rotate_size: None,
+                    save_raw_rotate_count: None,
+                    summary_file: None,
+                    source_path_remaps: Vec::new(),
+                    source_urls: Vec::new(),
+                    source_commit: None,
+                    additional_event_attributes: Vec::new(),
+                    import: Default::default(),
+                    rtt_collector: RttCollectorConfig {
+                        attach_timeout: HumanTime::from_str("100ms").unwrap().into(),
+                        control_block_address: 0xFFFFF_u32.into(),
+                        up_channel: 1,
+                        up_channel_name: None,
+                        up_channels: vec![
+                            RttUpChannel {
+                                channel: 0,
+                                name: None,
+                                elf_file: None,
+                                core_id: None,
+                                format: RttChannelFormat::Defmt,
+                            },
+                            RttUpChannel {
+                                channel: 1,
+                                name: None,
+                                elf_file: PathBuf::from("trace.elf").into(),
+                                core_id: 1.into(),
+                                format: RttChannelFormat::Defmt,
+                            },
+                            RttUpChannel {
+                                channel: 0,
+                                name: "metrics".to_owned().into(),
+                                elf_file: PathBuf::from("metrics.elf").into(),
+                                core_id: None,
+                                format: RttChannelFormat::Defmt,
+                            },
+                        ],
+                        text_up_channels: vec![RttUpChannel {
+                            channel: 0,
+                            name: "printf".to_owned().into(),
+                            elf_file: None,
+                            core_id: None,
+                            format: RttChannelFormat::Defmt,
+                        }],
+                        probe_selector: ProbeSelector::from_str("234:234").unwrap().into(),
+                        chip: "stm32".to_owned().into(),
+                        protocol: probe_rs::probe::WireProtocol::Jtag,
+                        speed: 1234,
+                        core: 1,
+                        reset: true,
+                        attach_under_reset: true,
+                        download: true,
+                        verify: true,
+                        chip_description_path: PathBuf::from("/tmp/stm32.yaml").into(),
+                        arch: TargetArch::RiscV.into(),
+                        thumb: true,
+                        setup_on_breakpoint: Some("main".to_owned()),
+                        setup_stages: vec![
+                            SetupStage {
+                                breakpoint: "main".to_owned(),
+                                ..Default::default()
+                            },
+                            SetupStage {
+                                breakpoint: "rtt_init_done".to_owned(),
+                                channel_mode: RttChannelMode::BlockIfFull.into(),
+                                write: vec![
+                                    MemoryWrite {
+                                        location: "READY".to_owned(),
+                                        value: 1,
+                                    },
+                                    MemoryWrite {
+                                        location: "0x20000000".to_owned(),
+                                        value: 0xAB,
+                                    },
+                                ],
+                                start: true,
+                            },
+                        ],
+                        channel_mode: RttChannelMode::NoBlockSkip.into(),
+                        rtt_poll_interval: HumanTime::from_str("1ms").unwrap().into(),
+                        rtt_idle_poll_interval: HumanTime::from_str("50ms").unwrap().into(),
+                        rtt_poll_policy: RttPollPolicy::Adaptive,
+                        rtt_read_buffer_size: 1024,
+                        metrics: true,
+                        targets: Vec::new(),
+                        restart_interval: None,
+                        status_interval: None,
+                        ring_buffer: RingBufferConfig {
+                            buffer: "LOG_BUF".to_owned(),
+                            size: Some(1024),
+                            head: "LOG_HEAD".to_owned(),
+                            tail: "0x20000010".to_owned(),
+                        }
+                        .into(),
+                        shared_probe: true,
+                        reattach: true,
+                        reattach_attempts: 10.into(),
+                        reattach_interval: HumanTime::from_str("500ms").unwrap().into(),
+                        exit_on_idle: HumanTime::from_str("30s").unwrap().into(),
+                        non_intrusive: true,
+                        halt_on_stop: true,
+                        snapshot: true,
+                        crash_capture: true,
+                        crash_events: vec![StopTrigger {
+                            name: Some(Glob::new("fault*")),
+                            level: Some("error".to_owned()),
+                            ..Default::default()
+                        }],
+                        crash_stack_size: 512.into(),
+                        crash_memory: vec![
+                            MemoryWindow {
+                                location: "ERROR_CODE".to_owned(),
+                                size: 4,
+                            },
+                            MemoryWindow {
+                                location: "0x20000000".to_owned(),
+                                size: 0x40,
+                            },
+                        ],
+                        watch: vec![
+                            MemoryWindow {
+                                location: "HEAP_HIGH_WATER".to_owned(),
+                                size: 4,
+                            },
+                            MemoryWindow {
+                                location: "0x20000100".to_owned(),
+                                size: 8,
+                            },
+                        ],
+                        watch_interval: HumanTime::from_str("250ms").unwrap().into(),
+                        fault_catch: true,
+                        fault_action: FaultAction::Stop,
+                        pre_attach: vec![
+                            ProbeCommand::Write(MemoryWrite {
+                                location: "0x40021000".to_owned(),
+                                value: 1,
+                            }),
+                            ProbeCommand::Delay(HumanTime::from_str("10ms").unwrap()),
+                        ],
+                        post_attach: vec![
+                            ProbeCommand::WriteRegister {
+                                register: "r0".to_owned(),
+                                value: 0x20,
+                            },
+                            ProbeCommand::Exec("./host-ready.sh".to_owned()),
+                        ],
+                        staged_mutation: Some(StagedMutation {
+                            mutator_id: uuid::Uuid::from_str(
+                                "8a7b3d6c-4d37-4bb5-9a1d-5c9f1b2e3a40"
+                            )
+                            .unwrap(),
+                            mutation_id: uuid::Uuid::from_str(
+                                "0f3e2c1b-6a59-4d87-b4c2-e1f0a9b8c7d6"
+                            )
+                            .unwrap(),
+                        }),
+                        clear_staged_mutation: true,
+                    },
+                    #[cfg(feature = "serial-collector")]
+                    serial_collector: Default::default(),
+                    #[cfg(feature = "tcp-collector")]
+                    tcp_collector: Default::default(),
+                    #[cfg(feature = "openocd-collector")]
+                    openocd_collector: Default::default(),
+                    #[cfg(feature = "process-collector")]
+                    process_collector: Default::default(),
+                    #[cfg(feature = "renode-collector")]
+                    renode_collector: Default::default(),
+                },
+            }
+        );
+    }
+
+    #[cfg(feature = "serial-collector")]
+    #[test]
+    fn serial_collector_cfg() {
+        let cfg = get_cfg(SERIAL_COLLECTOR_CONFIG, DefmtConfigEntry::SerialCollector);
+        assert_eq!(
+            cfg,
+            DefmtConfig {
+                auth_token: None,
+                ingest: TopLevelIngest {
+                    protocol_parent_url: Url::parse("modality-ingest://127.0.0.1:14182")
+                        .unwrap()
+                        .into(),
+                    allow_insecure_tls: false,
+                    protocol_child_port: None,
+                    timeline_attributes: TimelineAttributes {
+                        additional_timeline_attributes: vec![
+                            AttrKeyEqValuePair::from_str("ci_run=1").unwrap(),
+                            AttrKeyEqValuePair::from_str("defmt-mode='uart'").unwrap(),
+                        ],
+                        override_timeline_attributes: Default::default(),
+                    },
+                    max_write_batch_staleness: None,
+                },
+                plugin: PluginConfig {
+                    client_timeout: None,
+                    run_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_string().into(),
+                    clock_id: None,
+                    init_task_name: None,
+                    disable_interactions: false,
+                    disable_linear_causality: false,
+                    rtos_mode: RtosMode::None,
+                    pre_start_policy: Default::default(),
+                    rtos_markers: Default::default(),
+                    fold_isrs: Vec::new(),
+                    clock_rate: None,
+                    elf_file: PathBuf::from("fw.elf").into(),
+                    elf_reload: false,
+                    debug_file: None,
+                    outcome_rules: Vec::new(),
+                    message_correlations: Vec::new(),
+                    boot_index: None,
+                    segment_runs: false,
+                    reboot_event: None,
+                    reboot_timestamp_threshold: None,
+                    core_id: None,
+                    context_stats: false,
+                    max_context_depth: None,
+                    unknown_context_name: None,
+                    unknown_context_per_source: false,
+                    phase_marker_event: None,
+                    promote_internal_attributes: Vec::new(),
+                    framing: Default::default(),
+                    frame_crc: Default::default(),
+                    max_array_elements: None,
+                    byte_encoding: Default::default(),
+                    display_hints: false,
+                    println_level: None,
+                    timestamp_composition: Default::default(),
+                    timestamp_width: None,
+                    host_time_fallback: false,
+                    backwards_timestamp_policy: Default::default(),
+                    timestamp_gap_threshold: None,
+                    event_name_delimiter: None,
+                    attr_separator: None,
+                    literal_event_names: false,
+                    unnamed_event_names: Default::default(),
+                    normalize_event_names: false,
+                    max_event_name_length: None,
+                    max_attribute_keys: None,
+                    attribute_key_limit_policy: Default::default(),
+                    infer_units: false,
+                    attribute_renames: Default::default(),
+                    attribute_coercions: Vec::new(),
+                    event_filter: Default::default(),
+                    stop_on: Vec::new(),
+                    exclude_table_indices: Vec::new(),
+                    exclude_format_strings: Vec::new(),
+                    omit_internal_attributes: false,
+                    omit_formatted_string: false,
+                    debug_attributes: false,
+                    message_attribute: false,
+                    raw_frame_attributes: false,
+                    metrics_interval: None,
+                    metric_events: false,
+                    save_raw: None,
+                    save_raw_rotate_size: None,
+                    save_raw_rotate_count: None,
+                    summary_file: None,
+                    source_path_remaps: Vec::new(),
+                    source_urls: Vec::new(),
+                    source_commit: None,
+                    additional_event_attributes: Vec::new(),
+                    import: Default::default(),
+                    rtt_collector: Default::default(),
+                    serial_collector: SerialCollectorConfig {
+                        open_timeout: HumanTime::from_str("2s").unwrap().into(),
+                        path: "/dev/ttyACM0".to_owned().into(),
+                        usb_device: UsbDeviceSelector {
+                            vendor_id: 0x16c0,
+                            product_id: 0x27dd,
+                            serial_number: "ABC123".to_owned().into(),
+                        }
+                        .into(),
+                        baud_rate: 921600,
+                        data_bits: 7,
+                        parity: SerialParity::Even,
+                        stop_bits: 2,
+                        flow_control: SerialFlowControl::Hardware,
+                        reopen_interval: HumanTime::from_str("500ms").unwrap().into(),
+                        disable_reopen: true,
+                        esp_usb_serial_jtag: true,
+                        esp_reset: true,
+                    },
+                    #[cfg(feature = "tcp-collector")]
+                    tcp_collector: Default::default(),
+                    #[cfg(feature = "openocd-collector")]
+                    openocd_collector: Default::default(),
+                    #[cfg(feature = "process-collector")]
+                    process_collector: Default::default(),
+                    #[cfg(feature = "renode-collector")]
+                    renode_collector: Default::default(),
+                },
+            }
+        );
+    }
+
+    #[cfg(feature = "tcp-collector")]
+    #[test]
+    fn tcp_collector_cfg() {
+        let cfg = get_cfg(TCP_COLLECTOR_CONFIG, DefmtConfigEntry::TcpCollector);
+        assert_eq!(
+            cfg,
+            DefmtConfig {
+                auth_token: None,
+                ingest: TopLevelIngest {
+                    protocol_parent_url: Url::parse("modality-ingest://127.0.0.1:14182")
+                        .unwrap()
+                        .into(),
+                    allow_insecure_tls: false,
+                    protocol_child_port: None,
+                    timeline_attributes: Default::default(),
+                    max_write_batch_staleness: None,
+                },
+                plugin: PluginConfig {
+                    run_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_string().into(),
+                    elf_file: PathBuf::from("fw.elf").into(),
+                    tcp_collector: TcpCollectorConfig {
+                        connect_timeout: HumanTime::from_str("5s").unwrap().into(),
+                        remote: "localhost:3456".to_owned().into(),
+                        listen: "0.0.0.0:3456".to_owned().into(),
+                        reconnect_interval: HumanTime::from_str("250ms").unwrap().into(),
+                        disable_reconnect: true,
+                    },
+                    ..Default::default()
+                },
+            }
+        );
+    }
+
+    #[cfg(feature = "openocd-collector")]
+    #[test]
+    fn openocd_collector_cfg() {
+        let cfg = get_cfg(OPENOCD_COLLECTOR_CONFIG, DefmtConfigEntry::OpenOcdCollector);
+        assert_eq!(
+            cfg,
+            DefmtConfig {
+                auth_token: None,
+                ingest: TopLevelIngest {
+                    protocol_parent_url: Url::parse("modality-ingest://127.0.0.1:14182")
+                        .unwrap()
+                        .into(),
+                    allow_insecure_tls: false,
+                    protocol_child_port: None,
+                    timeline_attributes: Default::default(),
+                    max_write_batch_staleness: None,
+                },
+                plugin: PluginConfig {
+                    run_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_string().into(),
+                    elf_file: PathBuf::from("fw.elf").into(),
+                    openocd_collector: OpenOcdCollectorConfig {
+                        connect_timeout: HumanTime::from_str("5s").unwrap().into(),
+                        host: "192.168.1.2".to_owned(),
+                        tcl_port: 6667,
+                        rtt_port: 8765,
+                        up_channel: 1,
+                        control_block_address: 0x20000000_u32.into(),
+                        control_block_search_size: 0x10000,
+                        control_block_id: "MY RTT".to_owned(),
+                        disable_setup: true,
+                        reconnect_interval: HumanTime::from_str("2s").unwrap().into(),
+                    },
+                    ..Default::default()
+                },
+            }
+        );
+    }
+
+    #[cfg(feature = "serial-collector")]
+    #[test]
+    fn usb_device_selector() {
+        let sel = UsbDeviceSelector::from_str("0x16c0:27DD").unwrap();
+        assert_eq!(sel.to_string(), "16c0:27dd");
+        assert!(sel.matches(0x16c0, 0x27dd, None));
+        assert!(sel.matches(0x16c0, 0x27dd, Some("ABC123")));
+        assert!(!sel.matches(0x16c0, 0x27de, None));
+
+        let sel = UsbDeviceSelector::from_str("16c0:27dd:ABC123").unwrap();
+        assert_eq!(sel.to_string(), "16c0:27dd:ABC123");
+        assert!(sel.matches(0x16c0, 0x27dd, Some("ABC123")));
+        assert!(!sel.matches(0x16c0, 0x27dd, Some("XYZ")));
+        assert!(!sel.matches(0x16c0, 0x27dd, None));
+
+        assert!(UsbDeviceSelector::from_str("16c0").is_err());
+        assert!(UsbDeviceSelector::from_str("16c0:xyz").is_err());
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn rtt_supervisor_cfg() {
+        let cfg = get_cfg(RTT_SUPERVISOR_CONFIG, DefmtConfigEntry::RttCollector);
+        let rtt = &cfg.plugin.rtt_collector;
+        assert_eq!(rtt.targets.len(), 2);
+        assert_eq!(rtt.restart_interval, HumanTime::from_str("2s").ok());
+        assert_eq!(rtt.status_interval, HumanTime::from_str("30s").ok());
+
+        let dut0 = cfg.plugin.for_rtt_target(&rtt.targets[0]);
+        assert_eq!(rtt.targets[0].name, "dut0");
+        assert!(dut0.rtt_collector.targets.is_empty());
+        assert_eq!(dut0.run_id, None);
+        assert_eq!(dut0.elf_file, PathBuf::from("fw.elf").into());
+        assert_eq!(
+            dut0.rtt_collector.probe_selector,
+            ProbeSelector::from_str("234:234:SN0").ok()
+        );
+        assert_eq!(dut0.rtt_collector.chip, "stm32".to_owned().into());
+        assert_eq!(dut0.rtt_collector.speed, 1234);
+        assert_eq!(dut0.rtt_collector.core, RttCollectorConfig::DEFAULT_CORE);
+
+        let dut1 = cfg.plugin.for_rtt_target(&rtt.targets[1]);
+        assert_eq!(
+            dut1.run_id,
+            "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_owned().into()
+        );
+        assert_eq!(dut1.elf_file, PathBuf::from("fw-b.elf").into());
+        assert_eq!(
+            dut1.rtt_collector,
+            RttCollectorConfig {
+                probe_selector: ProbeSelector::from_str("234:234:SN1").unwrap().into(),
+                chip: "nrf52".to_owned().into(),
+                protocol: probe_rs::probe::WireProtocol::Jtag,
+                speed: 4321,
+                core: 1,
+                control_block_address: 0xFFFFF_u32.into(),
+                up_channel: 2,
+                setup_on_breakpoint: Some("main".to_owned()),
+                restart_interval: HumanTime::from_str("2s").unwrap().into(),
+                status_interval: HumanTime::from_str("30s").unwrap().into(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn env_var_expansion() {
+        let lookup = |name: &str| match name {
+            "WORKSPACE" => Some("/builds/fw".to_owned()),
+            "CI_PIPELINE_ID" => Some("42".to_owned()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_env_vars_with("${WORKSPACE}/target/fw.elf", lookup),
+            Ok("/builds/fw/target/fw.elf".to_owned())
+        );
+        assert_eq!(
+            expand_env_vars_with("run-${CI_PIPELINE_ID}-${CI_PIPELINE_ID}", lookup),
+            Ok("run-42-42".to_owned())
+        );
+        assert_eq!(
+            expand_env_vars_with("$${WORKSPACE} costs $5", lookup),
+            Ok("${WORKSPACE} costs $5".to_owned())
+        );
+        assert_eq!(
+            expand_env_vars_with("${UNSET}", lookup),
+            Err(EnvVarExpansionError::NotSet("UNSET".to_owned()))
+        );
+        assert_eq!(
+            expand_env_vars_with("${WORKSPACE", lookup),
+            Err(EnvVarExpansionError::Unterminated("${WORKSPACE".to_owned()))
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my_config.toml");
+        let mut f = File::create(&path).unwrap();
+        f.write_all(
+            br#"[ingest]
+additional-timeline-attributes = ["workspace='${MODALITY_DEFMT_TEST_WORKSPACE}'"]
+
+[metadata]
+elf-file = "${MODALITY_DEFMT_TEST_WORKSPACE}/fw.elf"
+fold-isrs = ["${MODALITY_DEFMT_TEST_WORKSPACE}"]
+"#,
+        )
+        .unwrap();
+        f.flush().unwrap();
+        env::set_var("MODALITY_DEFMT_TEST_WORKSPACE", "/builds/fw");
+        let cfg = DefmtConfig::load_merge_with_opts(
+            DefmtConfigEntry::Importer,
+            ReflectorOpts {
+                config_file: Some(path),
+                ..Default::default()
+            },
+            Default::default(),
+        )
+        .unwrap();
+        env::remove_var("MODALITY_DEFMT_TEST_WORKSPACE");
+        assert_eq!(
+            cfg.plugin.elf_file,
+            Some(PathBuf::from("/builds/fw/fw.elf"))
+        );
+        assert_eq!(cfg.plugin.fold_isrs, vec![Glob::new("/builds/fw")]);
+        assert_eq!(
+            cfg.ingest
+                .timeline_attributes
+                .additional_timeline_attributes,
+            vec![AttrKeyEqValuePair::from_str("workspace='/builds/fw'").unwrap()]
+        );
+    }
+
+    #[test]
+    fn config_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            let mut f = File::create(&path).unwrap();
+            f.write_all(content.as_bytes()).unwrap();
+            f.flush().unwrap();
+            path
+        };
+        write(
+            "base.toml",
+            r#"[ingest]
 protocol-parent-url = 'modality-ingest://127.0.0.1:14182'
-additional-timeline-attributes = [
-    "ci_run=1",
-    "platform='RTICv1'",
-    "module='m3'",
-    "defmt-mode='rtt'",
-]
+additional-timeline-attributes = ["platform='RTICv1'"]
 
 [metadata]
-client-timeout = "1s"
-run-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
-clock-id = 'a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3'
-init-task-name = 'fw'
-disable-interactions = true
-rtos-mode = "rtic1"
 elf-file = "fw.elf"
-clock-rate = "1/2000000"
-attach-timeout = "100ms"
-up-channel = 1
-control-block-address = 0xFFFFF
-down-channel = 1
-probe-selector = '234:234'
-chip = 'stm32'
-protocol = 'Jtag'
-speed = 1234
-core = 1
-reset = true
-attach-under-reset = true
-chip-description-path = "/tmp/stm32.yaml"
-thumb = true
-setup-on-breakpoint = "main"
-rtt-poll-interval = "1ms"
-rtt-read-buffer-size = 1024
-metrics = true
-"#;
+rtos-mode = "rtic1"
+fold-isrs = ["SysTick"]
 
-    // Do a basic round trip check while we're at it
-    fn get_cfg(content: &str, entry: DefmtConfigEntry) -> DefmtConfig {
+[metadata.profile.bench]
+clock-rate = "1/1000000"
+fold-isrs = ["UART*"]
+"#,
+        );
+        let path = write(
+            "board-a.toml",
+            r#"[ingest]
+additional-timeline-attributes = ["board='a'"]
+
+[metadata]
+include = "base.toml"
+elf-file = "board-a.elf"
+active-profiles = ["bench"]
+
+[metadata.profile.ci]
+run-id = "ci"
+elf-file = "ci/board-a.elf"
+"#,
+        );
+        let load = |profiles: &[&str]| {
+            DefmtConfig::load_merge_with_opts(
+                DefmtConfigEntry::Importer,
+                ReflectorOpts {
+                    config_file: Some(path.clone()),
+                    profiles: profiles.iter().map(|p| p.to_string()).collect(),
+                    ..Default::default()
+                },
+                Default::default(),
+            )
+        };
+
+        let cfg = load(&[]).unwrap();
+        assert_eq!(
+            cfg.ingest.protocol_parent_url,
+            Url::parse("modality-ingest://127.0.0.1:14182").ok()
+        );
+        assert_eq!(
+            cfg.ingest
+                .timeline_attributes
+                .additional_timeline_attributes,
+            vec![
+                AttrKeyEqValuePair::from_str("platform='RTICv1'").unwrap(),
+                AttrKeyEqValuePair::from_str("board='a'").unwrap(),
+            ]
+        );
+        assert_eq!(cfg.plugin.elf_file, Some(PathBuf::from("board-a.elf")));
+        assert_eq!(cfg.plugin.rtos_mode, RtosMode::Rtic1);
+        assert_eq!(cfg.plugin.fold_isrs, vec![Glob::new("UART*")]);
+        assert_eq!(cfg.plugin.clock_rate, Rate::from_str("1/1000000").ok());
+        assert_eq!(cfg.plugin.run_id, None);
+
+        // The command line profiles replace the active ones
+        let cfg = load(&["ci"]).unwrap();
+        assert_eq!(cfg.plugin.elf_file, Some(PathBuf::from("ci/board-a.elf")));
+        assert_eq!(cfg.plugin.run_id, Some("ci".to_owned()));
+        assert_eq!(cfg.plugin.fold_isrs, vec![Glob::new("SysTick")]);
+        assert_eq!(cfg.plugin.clock_rate, None);
+
+        assert!(load(&["nightly"]).is_err());
+
+        write("base.toml", "[metadata]\ninclude = \"board-a.toml\"\n");
+        assert!(load(&[]).is_err());
+    }
+
+    #[test]
+    fn timeline_attr_opts() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("my_config.toml");
-        {
-            let mut f = File::create(&path).unwrap();
-            f.write_all(content.as_bytes()).unwrap();
-            f.flush().unwrap();
-        }
+        let mut f = File::create(&path).unwrap();
+        f.write_all(
+            br#"[ingest]
+additional-timeline-attributes = ["ci_run=1", "platform='RTICv1'"]
+"#,
+        )
+        .unwrap();
+        f.flush().unwrap();
 
         let cfg = DefmtConfig::load_merge_with_opts(
-            entry,
+            DefmtConfigEntry::Importer,
             ReflectorOpts {
-                config_file: Some(path.to_path_buf()),
+                config_file: Some(path),
+                additional_timeline_attributes: vec![
+                    AttrKeyEqValuePair::from_str("ci_run=42").unwrap(),
+                    AttrKeyEqValuePair::from_str("commit='3f2a1c9'").unwrap(),
+                ],
+                override_timeline_attributes: vec![AttrKeyEqValuePair::from_str(
+                    "timeline.name='main'",
+                )
+                .unwrap()],
                 ..Default::default()
             },
             Default::default(),
         )
         .unwrap();
+        assert_eq!(
+            cfg.ingest.timeline_attributes,
+            TimelineAttributes {
+                additional_timeline_attributes: vec![
+                    AttrKeyEqValuePair::from_str("ci_run=1").unwrap(),
+                    AttrKeyEqValuePair::from_str("platform='RTICv1'").unwrap(),
+                    AttrKeyEqValuePair::from_str("ci_run=42").unwrap(),
+                    AttrKeyEqValuePair::from_str("commit='3f2a1c9'").unwrap(),
+                ],
+                override_timeline_attributes: vec![AttrKeyEqValuePair::from_str(
+                    "timeline.name='main'"
+                )
+                .unwrap()],
+            }
+        );
+    }
 
-        env::set_var(CONFIG_ENV_VAR, path);
-        let env_cfg =
-            DefmtConfig::load_merge_with_opts(entry, Default::default(), Default::default())
-                .unwrap();
-        env::remove_var(CONFIG_ENV_VAR);
-        assert_eq!(cfg, env_cfg);
-        cfg
+    #[test]
+    fn input_compression() {
+        use std::path::Path;
+
+        let gz = [0x1F, 0x8B, 0x08, 0x00];
+        let zst = [0x28, 0xB5, 0x2F, 0xFD, 0x00];
+        let raw = [0x01, 0x02, 0x03, 0x04];
+        let auto = InputCompression::Auto;
+        assert_eq!(auto.detect(&gz, None), InputCompression::Gzip);
+        assert_eq!(auto.detect(&zst, None), InputCompression::Zstd);
+        assert_eq!(auto.detect(&raw, None), InputCompression::None);
+        // Magic bytes win over the extension
+        assert_eq!(
+            auto.detect(&raw, Some(Path::new("rtt.bin.gz"))),
+            InputCompression::None
+        );
+        assert_eq!(
+            auto.detect(&[], Some(Path::new("rtt.bin.ZST"))),
+            InputCompression::Zstd
+        );
+        assert_eq!(
+            InputCompression::Gzip.detect(&raw, None),
+            InputCompression::Gzip
+        );
+        assert_eq!(InputCompression::from_str("gz"), Ok(InputCompression::Gzip));
+        assert!(InputCompression::from_str("xz").is_err());
     }
 
     #[test]
-    fn importer_cfg() {
-        let cfg = get_cfg(IMPORTER_CONFIG, DefmtConfigEntry::Importer);
+    fn stop_trigger() {
+        let ev = EventRecord::from_iter(
+            None,
+            [
+                (
+                    EventRecord::attr_key("name"),
+                    AttrVal::from("panic_handler"),
+                ),
+                (EventRecord::attr_key("level"), AttrVal::from("error")),
+                (EventRecord::attr_key("code"), AttrVal::Integer(42)),
+            ],
+        );
+        let trigger = StopTrigger::from_str("name=panic*, level=ERROR, event.code=4?").unwrap();
+        assert!(trigger.matches(&ev));
+        assert!(StopTrigger::from_str("panic_handler").unwrap().matches(&ev));
+        assert!(!StopTrigger::from_str("code=7").unwrap().matches(&ev));
+        assert!(!StopTrigger::from_str("module=app::*").unwrap().matches(&ev));
+        assert!(StopTrigger::from_str("").is_err());
+        assert!(StopTrigger::from_str("=x").is_err());
+    }
+
+    #[test]
+    fn event_filter() {
+        let filter = EventFilter {
+            exclude_levels: vec![Glob::new("trace")],
+            include_modules: vec![Glob::new("fw::net*")],
+            exclude_names: vec![Glob::new("poll*")],
+            ..Default::default()
+        };
+        assert!(!filter.is_empty());
+        assert!(filter.allows(Some("rx"), Some("info"), Some("fw::net::eth")));
+        assert!(!filter.allows(Some("rx"), Some("trace"), Some("fw::net::eth")));
+        assert!(!filter.allows(Some("rx"), Some("info"), Some("fw::motor")));
+        assert!(!filter.allows(Some("poll_rx"), Some("info"), Some("fw::net::eth")));
+        // Events without location info aren't filtered by module
+        assert!(filter.allows(Some("rx"), Some("info"), None));
+        assert!(EventFilter::default().allows(None, None, None));
+    }
+
+    #[test]
+    fn excluded_frames() {
+        let cfg = PluginConfig {
+            exclude_table_indices: vec![4],
+            exclude_format_strings: vec![Glob::new("poll::*")],
+            ..Default::default()
+        };
+        assert!(cfg.excludes_frame(4, "boot"));
+        assert!(cfg.excludes_frame(1, "poll:: n={=u32}"));
+        assert!(!cfg.excludes_frame(1, "rx:: n={=u32}"));
+    }
+
+    #[test]
+    fn source_path_remaps() {
+        let cfg = PluginConfig {
+            source_path_remaps: vec![
+                SourcePathRemap::from_str("/home/ci/builds/fw/=").unwrap(),
+                SourcePathRemap::from_str("/home/ci/.cargo/registry/src/=cargo/").unwrap(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            cfg.remap_source_path("/home/ci/builds/fw/src/main.rs"),
+            "src/main.rs"
+        );
+        assert_eq!(
+            cfg.remap_source_path("/home/ci/.cargo/registry/src/defmt-0.3.5/src/lib.rs"),
+            "cargo/defmt-0.3.5/src/lib.rs"
+        );
+        assert_eq!(cfg.remap_source_path("/opt/src/lib.rs"), "/opt/src/lib.rs");
+        assert!(SourcePathRemap::from_str("=foo").is_err());
+    }
+
+    #[test]
+    fn source_urls() {
+        let mut cfg = PluginConfig {
+            source_urls: vec![
+                SourceUrlMapping {
+                    prefix: "/home/ci/builds/fw/".to_owned(),
+                    url: "https://github.com/acme/fw/blob/{commit}/{path}#L{line}".to_owned(),
+                    commit: "3f2a1c9".to_owned().into(),
+                },
+                SourceUrlMapping {
+                    prefix: "/home/ci/builds/hal/".to_owned(),
+                    url: "https://gitlab.com/acme/hal/-/blob/{commit}/{path}#L{line}".to_owned(),
+                    commit: None,
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            cfg.source_uri("/home/ci/builds/fw/src/main.rs", 12),
+            "https://github.com/acme/fw/blob/3f2a1c9/src/main.rs#L12"
+        );
+        assert_eq!(
+            cfg.source_uri("/home/ci/builds/hal/src/gpio.rs", 7),
+            "https://gitlab.com/acme/hal/-/blob/HEAD/src/gpio.rs#L7"
+        );
+        assert_eq!(
+            cfg.source_uri("/opt/src/lib.rs", 3),
+            "file:///opt/src/lib.rs:3"
+        );
+        cfg.source_commit = "a1b2c3d".to_owned().into();
+        assert_eq!(
+            cfg.source_uri("/home/ci/builds/fw/src/main.rs", 12),
+            "https://github.com/acme/fw/blob/a1b2c3d/src/main.rs#L12"
+        );
+    }
+
+    #[test]
+    fn omitted_attributes() {
+        let mut cfg = PluginConfig {
+            omit_internal_attributes: true,
+            ..Default::default()
+        };
+        assert!(cfg.omits_internal_attributes());
+        assert!(cfg.omits_formatted_string());
+        cfg.debug_attributes = true;
+        assert!(!cfg.omits_internal_attributes());
+        assert!(!cfg.omits_formatted_string());
+        let cfg = PluginConfig {
+            omit_formatted_string: true,
+            ..Default::default()
+        };
+        assert!(!cfg.omits_internal_attributes());
+        assert!(cfg.omits_formatted_string());
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn rtt_channel_mode() {
+        assert_eq!(
+            RttChannelMode::from_str("block-if-full"),
+            Ok(RttChannelMode::BlockIfFull)
+        );
+        assert_eq!(
+            RttChannelMode::from_str("NoBlockSkip"),
+            Ok(RttChannelMode::NoBlockSkip)
+        );
+        assert_eq!(
+            RttChannelMode::from_str("no_block_trim"),
+            Ok(RttChannelMode::NoBlockTrim)
+        );
+        assert!(RttChannelMode::from_str("blocking").is_err());
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn setup_stages() {
+        assert_eq!(
+            SetupStage::from_str("rtt_init_done, mode=no-block-skip, write=READY=0x1, start"),
+            Ok(SetupStage {
+                breakpoint: "rtt_init_done".to_owned(),
+                channel_mode: RttChannelMode::NoBlockSkip.into(),
+                write: vec![MemoryWrite {
+                    location: "READY".to_owned(),
+                    value: 1,
+                }],
+                start: true,
+            })
+        );
+        assert!(SetupStage::from_str("").is_err());
+        assert!(SetupStage::from_str("main,stop").is_err());
+        assert!(SetupStage::from_str("main,write=READY").is_err());
+        assert!(MemoryWrite::from_str("=1").is_err());
+
+        let cfg = RttCollectorConfig {
+            setup_on_breakpoint: Some("main".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            cfg.selected_setup_stages(),
+            vec![SetupStage {
+                breakpoint: "main".to_owned(),
+                ..Default::default()
+            }]
+        );
+        assert!(RttCollectorConfig::default()
+            .selected_setup_stages()
+            .is_empty());
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn crash_capture() {
+        assert_eq!(
+            MemoryWindow::from_str("ERROR_CODE : 0x10"),
+            Ok(MemoryWindow {
+                location: "ERROR_CODE".to_owned(),
+                size: 16,
+            })
+        );
+        assert!(MemoryWindow::from_str("ERROR_CODE").is_err());
+        assert!(MemoryWindow::from_str(":4").is_err());
+
+        let names = RttCollectorConfig::default()
+            .selected_crash_events()
+            .into_iter()
+            .map(|t| t.name.unwrap().as_str().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["panic*", "*HardFault*"]);
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn target_arch() {
+        assert_eq!(TargetArch::from_str("RISC-V"), Ok(TargetArch::RiscV));
+        assert_eq!(TargetArch::from_str("cortex-m"), Ok(TargetArch::Arm));
+        assert_eq!(TargetArch::RiscV.to_string(), "riscv");
+        assert!(TargetArch::from_str("mips").is_err());
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn probe_commands() {
+        assert_eq!(
+            ProbeCommand::from_str("reg = PC = 0x08000100"),
+            Ok(ProbeCommand::WriteRegister {
+                register: "PC".to_owned(),
+                value: 0x0800_0100,
+            })
+        );
+        assert_eq!(
+            ProbeCommand::from_str("exec=probe-rs erase --chip nRF52840_xxAA"),
+            Ok(ProbeCommand::Exec(
+                "probe-rs erase --chip nRF52840_xxAA".to_owned()
+            ))
+        );
+        assert!(ProbeCommand::from_str("delay=soon").is_err());
+        assert!(ProbeCommand::from_str("reg=0x10").is_err());
+        assert!(ProbeCommand::from_str("exec=").is_err());
+        assert!(ProbeCommand::from_str("halt").is_err());
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn staged_mutation() {
+        let mutation = StagedMutation::from_str(
+            "8a7b3d6c-4d37-4bb5-9a1d-5c9f1b2e3a40:0f3e2c1b-6a59-4d87-b4c2-e1f0a9b8c7d6",
+        )
+        .unwrap();
+        assert_eq!(
+            mutation.mutator_id.to_string(),
+            "8a7b3d6c-4d37-4bb5-9a1d-5c9f1b2e3a40"
+        );
+        assert_eq!(
+            mutation.mutation_id.to_string(),
+            "0f3e2c1b-6a59-4d87-b4c2-e1f0a9b8c7d6"
+        );
+        assert!(StagedMutation::from_str("8a7b3d6c-4d37-4bb5-9a1d-5c9f1b2e3a40").is_err());
+        assert!(StagedMutation::from_str("a:b").is_err());
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn fault_action() {
+        assert_eq!(FaultAction::default(), FaultAction::Resume);
+        assert_eq!(FaultAction::from_str(" Stop "), Ok(FaultAction::Stop));
+        assert_eq!(FaultAction::Stop.to_string(), "stop");
+        assert!(FaultAction::from_str("reset").is_err());
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn rtt_poll_policy() {
+        assert_eq!(
+            RttPollPolicy::from_str("Adaptive"),
+            Ok(RttPollPolicy::Adaptive)
+        );
+        assert!(RttPollPolicy::from_str("busy").is_err());
+
+        let ms = Duration::from_millis;
+        let next = |policy: RttPollPolicy, last, bytes_read| {
+            policy.next_interval(ms(last), bytes_read, 1024, ms(1), ms(100))
+        };
+        assert_eq!(next(RttPollPolicy::Step, 1, 0), ms(100));
+        assert_eq!(next(RttPollPolicy::Step, 100, 1024), ms(1));
+        assert_eq!(next(RttPollPolicy::Fixed, 1, 0), ms(1));
+        assert_eq!(next(RttPollPolicy::Adaptive, 1, 1024), Duration::ZERO);
+        assert_eq!(next(RttPollPolicy::Adaptive, 0, 512), ms(1));
+        assert_eq!(next(RttPollPolicy::Adaptive, 0, 0), ms(1));
+        assert_eq!(next(RttPollPolicy::Adaptive, 8, 0), ms(16));
+        assert_eq!(next(RttPollPolicy::Adaptive, 64, 0), ms(100));
+    }
+
+    #[cfg(feature = "rtt-collector")]
+    #[test]
+    fn rtt_up_channel() {
+        assert_eq!(
+            RttUpChannel::from_str("1"),
+            Ok(RttUpChannel {
+                channel: 1,
+                name: None,
+                elf_file: None,
+                core_id: None,
+                format: RttChannelFormat::Defmt,
+            })
+        );
+        assert_eq!(
+            RttUpChannel::from_str("2=trace.elf"),
+            Ok(RttUpChannel {
+                channel: 2,
+                name: None,
+                elf_file: PathBuf::from("trace.elf").into(),
+                core_id: None,
+                format: RttChannelFormat::Defmt,
+            })
+        );
+        assert_eq!(
+            RttUpChannel::from_str("defmt=trace.elf"),
+            Ok(RttUpChannel {
+                channel: 0,
+                name: "defmt".to_owned().into(),
+                elf_file: PathBuf::from("trace.elf").into(),
+                core_id: None,
+                format: RttChannelFormat::Defmt,
+            })
+        );
+        assert!(RttUpChannel::from_str("2=").is_err());
+        assert!(RttUpChannel::from_str("=trace.elf").is_err());
+
+        let cfg = RttCollectorConfig {
+            up_channel: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            cfg.selected_up_channels(),
+            vec![RttUpChannel {
+                channel: 3,
+                name: None,
+                elf_file: None,
+                core_id: None,
+                format: RttChannelFormat::Defmt,
+            }]
+        );
+        let cfg = RttCollectorConfig {
+            up_channel_name: "defmt".to_owned().into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            cfg.selected_up_channels()[0].name,
+            "defmt".to_owned().into()
+        );
+        let cfg = RttCollectorConfig {
+            text_up_channels: vec![RttUpChannel::from_str("2").unwrap()],
+            ..Default::default()
+        };
+        let channels = cfg.selected_up_channels();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].format, RttChannelFormat::Defmt);
+        assert_eq!(channels[1].channel, 2);
+        assert_eq!(channels[1].format, RttChannelFormat::Text);
+        assert_eq!(
+            RttChannelFormat::from_str(" Text "),
+            Ok(RttChannelFormat::Text)
+        );
+        assert!(RttChannelFormat::from_str("json").is_err());
+    }
+
+    #[cfg(feature = "process-collector")]
+    #[test]
+    fn process_collector_cfg() {
+        let cfg = get_cfg(PROCESS_COLLECTOR_CONFIG, DefmtConfigEntry::ProcessCollector);
         assert_eq!(
             cfg,
             DefmtConfig {
@@ -416,38 +4444,48 @@ metrics = true
                         .into(),
                     allow_insecure_tls: false,
                     protocol_child_port: None,
-                    timeline_attributes: TimelineAttributes {
-                        additional_timeline_attributes: vec![
-                            AttrKeyEqValuePair::from_str("ci_run=1").unwrap(),
-                            AttrKeyEqValuePair::from_str("platform='RTICv1'").unwrap(),
-                            AttrKeyEqValuePair::from_str("defmt-mode='rtt'").unwrap(),
-                        ],
-                        override_timeline_attributes: Default::default(),
-                    },
+                    timeline_attributes: Default::default(),
                     max_write_batch_staleness: None,
                 },
                 plugin: PluginConfig {
-                    client_timeout: HumanTime::from_str("1s").unwrap().into(),
                     run_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_string().into(),
-                    clock_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_owned().into(),
-                    init_task_name: "main".to_owned().into(),
-                    disable_interactions: true,
-                    rtos_mode: RtosMode::Rtic1,
-                    clock_rate: Some(Rate::new(1, 1000000).unwrap()),
                     elf_file: PathBuf::from("fw.elf").into(),
-                    import: ImportConfig {
-                        open_timeout: HumanTime::from_str("100ms").unwrap().into(),
-                        file: PathBuf::from("rtt_log.bin").into(),
+                    process_collector: ProcessCollectorConfig {
+                        command: "qemu-system-arm".to_owned().into(),
+                        args: vec![
+                            "-machine".to_owned(),
+                            "lm3s6965evb".to_owned(),
+                            "-nographic".to_owned(),
+                            "-semihosting-config".to_owned(),
+                            "enable=on,target=native".to_owned(),
+                            "-kernel".to_owned(),
+                            "fw.elf".to_owned(),
+                        ],
+                        working_directory: PathBuf::from("/tmp").into(),
+                        stream: ProcessStream::Fd(3),
                     },
-                    rtt_collector: Default::default(),
+                    ..Default::default()
                 },
             }
         );
     }
 
+    #[cfg(feature = "process-collector")]
     #[test]
-    fn rtt_collector_cfg() {
-        let cfg = get_cfg(RTT_COLLECTOR_CONFIG, DefmtConfigEntry::RttCollector);
+    fn process_stream() {
+        assert_eq!(ProcessStream::from_str("stdout"), Ok(ProcessStream::Stdout));
+        assert_eq!(ProcessStream::from_str("STDERR"), Ok(ProcessStream::Stderr));
+        assert_eq!(ProcessStream::from_str("fd:5"), Ok(ProcessStream::Fd(5)));
+        assert_eq!(ProcessStream::Fd(5).to_string(), "fd:5");
+        assert!(ProcessStream::from_str("fd:1").is_err());
+        assert!(ProcessStream::from_str("fd:x").is_err());
+        assert!(ProcessStream::from_str("stdin").is_err());
+    }
+
+    #[cfg(feature = "renode-collector")]
+    #[test]
+    fn renode_collector_cfg() {
+        let cfg = get_cfg(RENODE_COLLECTOR_CONFIG, DefmtConfigEntry::RenodeCollector);
         assert_eq!(
             cfg,
             DefmtConfig {
@@ -458,45 +4496,21 @@ metrics = true
                         .into(),
                     allow_insecure_tls: false,
                     protocol_child_port: None,
-                    timeline_attributes: TimelineAttributes {
-                        additional_timeline_attributes: vec![
-                            AttrKeyEqValuePair::from_str("ci_run=1").unwrap(),
-                            AttrKeyEqValuePair::from_str("platform='RTICv1'").unwrap(),
-                            AttrKeyEqValuePair::from_str("module='m3'").unwrap(),
-                            AttrKeyEqValuePair::from_str("defmt-mode='rtt'").unwrap(),
-                        ],
-                        override_timeline_attributes: Default::default(),
-                    },
+                    timeline_attributes: Default::default(),
                     max_write_batch_staleness: None,
                 },
                 plugin: PluginConfig {
-                    client_timeout: HumanTime::from_str("1s").unwrap().into(),
                     run_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_string().into(),
-                    clock_id: "a1a2a3a4b1b2c1c2d1d2d3d4d5d6d7d3".to_owned().into(),
-                    init_task_name: "fw".to_owned().into(),
-                    disable_interactions: true,
-                    rtos_mode: RtosMode::Rtic1,
-                    clock_rate: Some(Rate::new(1, 2000000).unwrap()),
                     elf_file: PathBuf::from("fw.elf").into(),
-                    import: Default::default(),
-                    rtt_collector: RttCollectorConfig {
-                        attach_timeout: HumanTime::from_str("100ms").unwrap().into(),
-                        control_block_address: 0xFFFFF_u32.into(),
-                        up_channel: 1,
-                        probe_selector: ProbeSelector::from_str("234:234").unwrap().into(),
-                        chip: "stm32".to_owned().into(),
-                        protocol: probe_rs::probe::WireProtocol::Jtag,
-                        speed: 1234,
-                        core: 1,
-                        reset: true,
-                        attach_under_reset: true,
-                        chip_description_path: PathBuf::from("/tmp/stm32.yaml").into(),
-                        thumb: true,
-                        setup_on_breakpoint: Some("main".to_owned()),
-                        rtt_poll_interval: HumanTime::from_str("1ms").unwrap().into(),
-                        rtt_read_buffer_size: 1024,
-                        metrics: true,
+                    renode_collector: RenodeCollectorConfig {
+                        connect_timeout: HumanTime::from_str("10s").unwrap().into(),
+                        uart_socket: "localhost:3456".to_owned().into(),
+                        uart_pty: PathBuf::from("/tmp/uart").into(),
+                        monitor: "localhost:1234".to_owned().into(),
+                        monitor_commands: vec!["start".to_owned()],
+                        machine: "atsamd".to_owned().into(),
                     },
+                    ..Default::default()
                 },
             }
         );
@@ -0,0 +1,1048 @@
+//! An annotated reflector configuration template, listing every `[metadata]` key
+//! of a plugin.
+//!
+//! The keys come from the `Deserialize` impls of the config structs, so a key added
+//! to a struct shows up in the template. The annotations are looked up by key, and
+//! the tests check that every key has one and that the defaults match the structs.
+
+use crate::config::{internal, DefmtConfigEntry};
+use serde::{
+    de::{self, DeserializeOwned, Visitor},
+    Deserializer,
+};
+use std::fmt::{self, Write};
+
+/// A documented `[metadata]` key
+struct TemplateKey {
+    key: &'static str,
+    ty: &'static str,
+    value: TemplateValue,
+    doc: &'static str,
+}
+
+/// A TOML literal
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum TemplateValue {
+    /// The value the key takes when it's not provided
+    Default(&'static str),
+    /// An example value, for keys without a literal default
+    Example(&'static str),
+}
+
+const fn default(
+    key: &'static str,
+    ty: &'static str,
+    value: &'static str,
+    doc: &'static str,
+) -> TemplateKey {
+    TemplateKey {
+        key,
+        ty,
+        value: TemplateValue::Default(value),
+        doc,
+    }
+}
+
+const fn example(
+    key: &'static str,
+    ty: &'static str,
+    value: &'static str,
+    doc: &'static str,
+) -> TemplateKey {
+    TemplateKey {
+        key,
+        ty,
+        value: TemplateValue::Example(value),
+        doc,
+    }
+}
+
+const COMMON_KEYS: &[TemplateKey] = &[
+    example(
+        "client-timeout",
+        "duration",
+        r#""1s""#,
+        "Ingest client timeout. The default is 1s.",
+    ),
+    example(
+        "run-id",
+        "string",
+        r#""nightly-42""#,
+        "The run ID, instead of generating a random UUID.",
+    ),
+    example(
+        "clock-id",
+        "string",
+        r#""bench-clock""#,
+        "The clock ID, instead of generating a random UUID.",
+    ),
+    example(
+        "init-task-name",
+        "string",
+        r#""main""#,
+        "The initial task/context name. The default is main.",
+    ),
+    default(
+        "disable-interactions",
+        "boolean",
+        "false",
+        "Don't synthesize interactions between tasks and ISRs on context switches.",
+    ),
+    default(
+        "disable-linear-causality",
+        "boolean",
+        "false",
+        "Don't linearize the causality across context switches.",
+    ),
+    example(
+        "clock-rate",
+        "rate",
+        r#""1/1000000""#,
+        "The time base for converting ticks to nanoseconds, 'numerator/denominator' in Hz.",
+    ),
+    default(
+        "rtos-mode",
+        "string",
+        r#""none""#,
+        "One of none, rtic1, rtic2, cmsis-rtos2, bare-metal or custom.",
+    ),
+    default(
+        "pre-start-policy",
+        "string",
+        r#""disable-rtos-mode""#,
+        "Before the RTOS start: disable-rtos-mode, hold, drop, pre-start-timeline or resync.",
+    ),
+    example(
+        "rtos-markers",
+        "table",
+        r#"{ task-enter = "SCHED_SWITCH_IN", task-exit = "SCHED_SWITCH_OUT" }"#,
+        "The marker event names of the custom RTOS mode. Unset members use the rtic1 names.",
+    ),
+    default(
+        "fold-isrs",
+        "array of patterns",
+        "[]",
+        "ISR name patterns folded into the timeline of the context they interrupted.",
+    ),
+    example(
+        "elf-file",
+        "path",
+        r#""fw.elf""#,
+        "The ELF file containing the defmt table.",
+    ),
+    default(
+        "elf-reload",
+        "boolean",
+        "false",
+        "Reload the defmt table and location info when the ELF file changes.",
+    ),
+    example(
+        "debug-file",
+        "path",
+        r#""fw.debug""#,
+        "The separate debug file of a stripped ELF file. Found through .gnu_debuglink by default.",
+    ),
+    example(
+        "outcome-rules",
+        "array of tables",
+        r#"[{ name = "panic*", outcome = "failure", severity = "critical" }]"#,
+        "Tag matching events with event.outcome and event.severity.",
+    ),
+    example(
+        "message-correlations",
+        "array of tables",
+        r#"[{ send = "queue_send*", receive = "queue_recv*", key = "msg_id" }]"#,
+        "Pair the events sending a message with the events receiving it.",
+    ),
+    example(
+        "boot-index",
+        "integer",
+        "1",
+        "The boot index recorded on every timeline. The default is 0.",
+    ),
+    default(
+        "segment-runs",
+        "boolean",
+        "false",
+        "Start a new run when a target reboot is detected.",
+    ),
+    example(
+        "reboot-event",
+        "string",
+        r#""booted""#,
+        "The name of the event the firmware logs on boot.",
+    ),
+    example(
+        "reboot-timestamp-threshold",
+        "integer",
+        "1000",
+        "The raw timestamp at or below which a backwards timestamp is a reboot.",
+    ),
+    example(
+        "core-id",
+        "integer",
+        "1",
+        "The core the events come from, recorded as timeline.core.",
+    ),
+    default(
+        "context-stats",
+        "boolean",
+        "false",
+        "Track the run time of each context, in RTOS mode.",
+    ),
+    example(
+        "max-context-depth",
+        "integer",
+        "32",
+        "The maximum context stack depth, in RTOS mode. The default is 32.",
+    ),
+    example(
+        "unknown-context-name",
+        "string",
+        r#""UNKNOWN_CONTEXT""#,
+        "The timeline of events whose context is unknown. The default is UNKNOWN_CONTEXT.",
+    ),
+    default(
+        "unknown-context-per-source",
+        "boolean",
+        "false",
+        "Split the unknown context by ISR and RTT up channel.",
+    ),
+    example(
+        "phase-marker-event",
+        "string",
+        r#""TEST_PHASE""#,
+        "The marker event setting the event.phase of the following events.",
+    ),
+    example(
+        "promote-internal-attributes",
+        "array of patterns",
+        r#"["event.internal.defmt.table_index"]"#,
+        "Internal attribute keys promoted into the public namespace.",
+    ),
+    default(
+        "framing",
+        "string",
+        r#""none""#,
+        "The link-layer framing: none, cobs, slip or espflash.",
+    ),
+    default(
+        "frame-crc",
+        "string",
+        r#""none""#,
+        "The CRC appended to each frame: none, crc16 or crc32.",
+    ),
+    example(
+        "max-array-elements",
+        "integer",
+        "32",
+        "The maximum number of array elements recorded as attributes. The default is 32.",
+    ),
+    default(
+        "byte-encoding",
+        "string",
+        r#""hex""#,
+        "The encoding of byte slice arguments: hex or base64.",
+    ),
+    default(
+        "display-hints",
+        "boolean",
+        "false",
+        "Add formatted attributes for the parameters' display hints.",
+    ),
+    example(
+        "println-level",
+        "string",
+        r#""info""#,
+        "The level of frames without one. The default is println.",
+    ),
+    default(
+        "timestamp-composition",
+        "string",
+        r#""single""#,
+        "How the timestamp is composed: single, high-low or low-high.",
+    ),
+    example(
+        "timestamp-width",
+        "integer",
+        "24",
+        "The width, in bits, of the target's timestamp counter.",
+    ),
+    default(
+        "host-time-fallback",
+        "boolean",
+        "false",
+        "Use the host receive time for events without a timestamp.",
+    ),
+    default(
+        "backwards-timestamp-policy",
+        "string",
+        r#""warn""#,
+        "Events going back in time: warn, clamp, rollover or drop.",
+    ),
+    example(
+        "timestamp-gap-threshold",
+        "duration",
+        r#""10ms""#,
+        "Flag device time gaps longer than this with event.timestamp_gap.",
+    ),
+    example(
+        "event-name-delimiter",
+        "string",
+        r#""::""#,
+        "The delimiter after the event name. The default is '::'.",
+    ),
+    example(
+        "attr-separator",
+        "character",
+        r#"",""#,
+        "The separator between key/value pairs. The default is ','.",
+    ),
+    default(
+        "literal-event-names",
+        "boolean",
+        "false",
+        "Name events by the literal text before their first parameter.",
+    ),
+    default(
+        "unnamed-event-names",
+        "string",
+        r#""formatted""#,
+        "The name of events without one: formatted or format-string.",
+    ),
+    default(
+        "normalize-event-names",
+        "boolean",
+        "false",
+        "Collapse whitespace and drop control characters in event names.",
+    ),
+    example(
+        "max-event-name-length",
+        "integer",
+        "64",
+        "The maximum event name length, in characters.",
+    ),
+    example(
+        "max-attribute-keys",
+        "integer",
+        "512",
+        "The maximum number of distinct event attribute keys.",
+    ),
+    default(
+        "attribute-key-limit-policy",
+        "string",
+        r#""warn""#,
+        "Past max-attribute-keys: warn or drop.",
+    ),
+    default(
+        "infer-units",
+        "boolean",
+        "false",
+        "Interpret attribute key unit suffixes like _ms and _hz.",
+    ),
+    example(
+        "attribute-renames",
+        "table",
+        r#"{ "event.tid" = "event.task_id" }"#,
+        "Attribute keys renamed before ingest.",
+    ),
+    example(
+        "attribute-coercions",
+        "array of tables",
+        r#"[{ key = "event.status", to = "hex-int" }]"#,
+        "Coerce the values of matching attributes.",
+    ),
+    example(
+        "event-filter",
+        "table",
+        r#"{ exclude-levels = ["trace"], include-modules = ["fw::*"] }"#,
+        "Include/exclude filters applied before ingest.",
+    ),
+    example(
+        "stop-on",
+        "array of tables",
+        r#"[{ name = "panic*" }]"#,
+        "Stop the collection at the first matching event.",
+    ),
+    example(
+        "exclude-table-indices",
+        "array of integers",
+        "[3, 7]",
+        "defmt table indices skipped right after decoding.",
+    ),
+    example(
+        "exclude-format-strings",
+        "array of patterns",
+        r#"["poll::*"]"#,
+        "Format string patterns skipped right after decoding.",
+    ),
+    default(
+        "omit-internal-attributes",
+        "boolean",
+        "false",
+        "Omit the event.internal.defmt.* attributes from ingest.",
+    ),
+    default(
+        "omit-formatted-string",
+        "boolean",
+        "false",
+        "Omit the event.internal.defmt.formatted_string attribute from ingest.",
+    ),
+    default(
+        "debug-attributes",
+        "boolean",
+        "false",
+        "Keep all of the attributes, overriding the omit options.",
+    ),
+    default(
+        "message-attribute",
+        "boolean",
+        "false",
+        "Record the formatted string as event.message.",
+    ),
+    default(
+        "raw-frame-attributes",
+        "boolean",
+        "false",
+        "Record the encoded bytes and stream offset of each frame.",
+    ),
+    example(
+        "metrics-interval",
+        "duration",
+        r#""10s""#,
+        "Periodically log the decode and ingest throughput at this interval.",
+    ),
+    default(
+        "metric-events",
+        "boolean",
+        "false",
+        "Also ingest the metrics reports as AUXON_METRICS events.",
+    ),
+    example(
+        "save-raw",
+        "path",
+        r#""capture.bin""#,
+        "Write a copy of the raw defmt byte stream to this file.",
+    ),
+    example(
+        "save-raw-rotate-size",
+        "integer",
+        "1048576",
+        "Rotate the raw capture file once it reaches this many bytes.",
+    ),
+    example(
+        "save-raw-rotate-count",
+        "integer",
+        "5",
+        "The number of rotated raw capture files to keep. The default is 5.",
+    ),
+    example(
+        "summary-file",
+        "path",
+        r#""summary.json""#,
+        "Write a JSON summary of the run to this file on exit.",
+    ),
+    example(
+        "source-path-remaps",
+        "array of tables",
+        r#"[{ from = "/home/ci/builds/fw/", to = "" }]"#,
+        "Replace source file path prefixes.",
+    ),
+    example(
+        "source-urls",
+        "array of tables",
+        r#"[{ prefix = "src/", url = "https://git.example.com/fw/{commit}/{path}#L{line}" }]"#,
+        "Map source files to web URLs for event.source.uri.",
+    ),
+    example(
+        "source-commit",
+        "string",
+        r#""3f2a1c9""#,
+        "The commit substituted into the source-urls templates.",
+    ),
+    example(
+        "additional-event-attributes",
+        "array of strings",
+        r#"["test_case='thermal-cycle-3'"]"#,
+        "Key-value attributes added to every event.",
+    ),
+];
+
+const IMPORTER_KEYS: &[TemplateKey] = &[
+    example(
+        "open-timeout",
+        "duration",
+        r#""10s""#,
+        "Retry opening the input for this long.",
+    ),
+    example("file", "path", r#""rtt_log.bin""#, "The file to import."),
+    example(
+        "unix-socket",
+        "path",
+        r#""/tmp/defmt.sock""#,
+        "Import from a Unix domain socket instead of a file.",
+    ),
+    default(
+        "unix-socket-listen",
+        "boolean",
+        "false",
+        "Listen on the unix-socket instead of connecting to it.",
+    ),
+    example(
+        "named-pipe",
+        "string",
+        r#"'\\.\pipe\defmt'"#,
+        "Import from a Windows named pipe instead of a file.",
+    ),
+    default(
+        "named-pipe-listen",
+        "boolean",
+        "false",
+        "Create the named-pipe instead of connecting to it.",
+    ),
+    default(
+        "compression",
+        "string",
+        r#""auto""#,
+        "The input compression: auto, none, gzip or zstd.",
+    ),
+    example(
+        "pcap-filter",
+        "string",
+        r#""udp:5000""#,
+        "Import the packets matching the filter of a pcap capture: udp:<PORT> or tcp:<PORT>.",
+    ),
+    example(
+        "logic-capture",
+        "string",
+        r#""saleae""#,
+        "Import a logic analyzer export: binary, saleae or sigrok.",
+    ),
+    example(
+        "capture-sample-rate",
+        "integer",
+        "24000000",
+        "Sample rate (Hz) of the logic analyzer capture.",
+    ),
+    default(
+        "jlink-rtt-logger",
+        "boolean",
+        "false",
+        "Import a JLinkRTTLogger log file.",
+    ),
+    default(
+        "rtt-dump",
+        "boolean",
+        "false",
+        "Import the RTT buffer of a RAM dump or ELF core file.",
+    ),
+    example(
+        "rtt-dump-address",
+        "integer",
+        "0x20000000",
+        "Base address of a raw RAM dump.",
+    ),
+    example(
+        "rtt-dump-control-block-address",
+        "integer",
+        "0x20000010",
+        "The RTT control block address, instead of the _SEGGER_RTT symbol.",
+    ),
+    default(
+        "rtt-dump-up-channel",
+        "integer",
+        "0",
+        "The RTT up channel of the memory dump to import.",
+    ),
+    default(
+        "rtt-dump-history",
+        "boolean",
+        "false",
+        "Also import the data the host already read.",
+    ),
+];
+
+#[cfg(feature = "rtt-collector")]
+const RTT_COLLECTOR_KEYS: &[TemplateKey] = &[
+    example(
+        "attach-timeout",
+        "duration",
+        r#""10s""#,
+        "Keep attempting to attach and find the RTT control block for this long.",
+    ),
+    example(
+        "control-block-address",
+        "integer",
+        "0x20000000",
+        "The RTT control block address, instead of scanning for it.",
+    ),
+    default("up-channel", "integer", "0", "The RTT up channel to read."),
+    example(
+        "up-channel-name",
+        "string",
+        r#""defmt""#,
+        "Select the RTT up channel by name instead of up-channel.",
+    ),
+    example(
+        "up-channels",
+        "array of tables",
+        r#"[{ channel = 0 }, { name = "metrics", elf-file = "metrics.elf" }]"#,
+        "RTT up channels read concurrently, overrides up-channel.",
+    ),
+    example(
+        "text-up-channels",
+        "array of tables",
+        r#"[{ name = "printf" }]"#,
+        "RTT up channels carrying plain text logs.",
+    ),
+    example(
+        "probe-selector",
+        "string",
+        r#""0483:374b""#,
+        "Select a specific probe, VID:PID[:SERIAL].",
+    ),
+    example("chip", "string", r#""STM32F407VE""#, "The target chip."),
+    default(
+        "protocol",
+        "string",
+        r#""Swd""#,
+        "The probe protocol: Swd or Jtag.",
+    ),
+    default("speed", "integer", "4000", "The protocol speed in kHz."),
+    default("core", "integer", "0", "The selected core."),
+    default("reset", "boolean", "false", "Reset the target on startup."),
+    default(
+        "attach-under-reset",
+        "boolean",
+        "false",
+        "Attach to the chip under hard-reset.",
+    ),
+    default(
+        "download",
+        "boolean",
+        "false",
+        "Flash the elf-file before attaching.",
+    ),
+    default(
+        "verify",
+        "boolean",
+        "false",
+        "Verify the flash contents after downloading.",
+    ),
+    example(
+        "chip-description-path",
+        "path",
+        r#""target.yaml""#,
+        "Custom target descriptions based on CMSIS Pack files.",
+    ),
+    example(
+        "arch",
+        "string",
+        r#""arm""#,
+        "The core architecture: arm, riscv or xtensa. Detected from the chip by default.",
+    ),
+    default(
+        "thumb",
+        "boolean",
+        "false",
+        "Assume thumb mode when resolving breakpoint symbols.",
+    ),
+    example(
+        "setup-on-breakpoint",
+        "string",
+        r#""main""#,
+        "Set up RTT once this symbol or address is hit.",
+    ),
+    example(
+        "setup-stages",
+        "array of tables",
+        r#"[{ breakpoint = "main" }, { breakpoint = "rtt_init_done", channel-mode = "block-if-full", start = true }]"#,
+        "A staged start, overrides setup-on-breakpoint.",
+    ),
+    example(
+        "channel-mode",
+        "string",
+        r#""no-block-skip""#,
+        "The RTT up channel mode: block-if-full, no-block-skip or no-block-trim.",
+    ),
+    default(
+        "rtt-read-buffer-size",
+        "integer",
+        "1024",
+        "Size of the host-side RTT read buffer.",
+    ),
+    example(
+        "rtt-poll-interval",
+        "duration",
+        r#""1ms""#,
+        "The RTT polling interval. The default is 1ms.",
+    ),
+    example(
+        "rtt-idle-poll-interval",
+        "duration",
+        r#""100ms""#,
+        "The RTT polling interval while there's no data. The default is 100ms.",
+    ),
+    default(
+        "rtt-poll-policy",
+        "string",
+        r#""step""#,
+        "How polls are paced: step, fixed or adaptive.",
+    ),
+    default(
+        "metrics",
+        "boolean",
+        "false",
+        "Periodically log RTT metrics.",
+    ),
+    example(
+        "targets",
+        "array of tables",
+        r#"[{ name = "dut0", probe-selector = "0483:374b:0670FF" }]"#,
+        "Targets driven from a single process (supervisor mode).",
+    ),
+    example(
+        "restart-interval",
+        "duration",
+        r#""1s""#,
+        "The delay before restarting a failed target. The default is 1s.",
+    ),
+    example(
+        "status-interval",
+        "duration",
+        r#""10s""#,
+        "The supervised targets status log interval. The default is 10s.",
+    ),
+    example(
+        "ring-buffer",
+        "table",
+        r#"{ buffer = "LOG_BUF", size = 1024, head = "LOG_HEAD", tail = "LOG_TAIL" }"#,
+        "Poll a firmware RAM ring buffer instead of RTT.",
+    ),
+    default(
+        "shared-probe",
+        "boolean",
+        "false",
+        "Only hold the probe while polling.",
+    ),
+    default(
+        "reattach",
+        "boolean",
+        "false",
+        "Re-attach when the target resets or the probe reports an error.",
+    ),
+    example(
+        "reattach-attempts",
+        "integer",
+        "10",
+        "The maximum number of re-attaches. Unlimited by default.",
+    ),
+    example(
+        "reattach-interval",
+        "duration",
+        r#""500ms""#,
+        "The delay before re-attaching. The default is 500ms.",
+    ),
+    example(
+        "exit-on-idle",
+        "duration",
+        r#""30s""#,
+        "End the session when no data has arrived for this long.",
+    ),
+    default(
+        "non-intrusive",
+        "boolean",
+        "false",
+        "Never halt, reset, set breakpoints on or reconfigure the target.",
+    ),
+    default(
+        "halt-on-stop",
+        "boolean",
+        "false",
+        "Halt the core when a stop-on trigger ends the collection.",
+    ),
+    default(
+        "snapshot",
+        "boolean",
+        "false",
+        "Drain the RTT buffers once and exit.",
+    ),
+    default(
+        "crash-capture",
+        "boolean",
+        "false",
+        "Capture the core registers and memory on a crash event.",
+    ),
+    example(
+        "crash-events",
+        "array of tables",
+        r#"[{ name = "panic*" }]"#,
+        "The events triggering a crash capture. panic* and *HardFault* by default.",
+    ),
+    example(
+        "crash-stack-size",
+        "integer",
+        "256",
+        "Bytes of stack captured. The default is 256.",
+    ),
+    example(
+        "crash-memory",
+        "array of strings",
+        r#"["ERROR_CODE:4"]"#,
+        "LOCATION:SIZE memory windows captured.",
+    ),
+    example(
+        "watch",
+        "array of strings",
+        r#"["HEAP_HIGH_WATER:4"]"#,
+        "LOCATION:SIZE values sampled at watch-interval.",
+    ),
+    example(
+        "watch-interval",
+        "duration",
+        r#""1s""#,
+        "The watch sampling interval. The default is 1s.",
+    ),
+    default(
+        "fault-catch",
+        "boolean",
+        "false",
+        "Catch HardFault exceptions and add AUXON_FAULT events.",
+    ),
+    default(
+        "fault-action",
+        "string",
+        r#""resume""#,
+        "After a caught fault: resume or stop.",
+    ),
+    example(
+        "pre-attach",
+        "array of strings",
+        r#"["delay=10ms"]"#,
+        "Commands run before attaching: write=, reg=, delay= or exec=.",
+    ),
+    example(
+        "post-attach",
+        "array of strings",
+        r#"["exec=./host-ready.sh"]"#,
+        "Commands run after attaching, before streaming.",
+    ),
+    example(
+        "staged-mutation",
+        "table",
+        r#"{ mutator-id = "8a7b3d6c-4d37-4bb5-9a1d-5c9f1b2e3a40", mutation-id = "0f3e2c1b-6a59-4d87-b4c2-e1f0a9b8c7d6" }"#,
+        "A Deviant mutation staged before the core runs.",
+    ),
+    default(
+        "clear-staged-mutation",
+        "boolean",
+        "false",
+        "Clear any staged Deviant mutation before the core runs.",
+    ),
+];
+
+/// The annotated configuration template of a plugin, every key commented out.
+/// Only the importer and the RTT collector have one.
+pub fn config_template(entry: DefmtConfigEntry) -> Option<String> {
+    render(entry, |_| false)
+}
+
+fn render<F: Fn(TemplateValue) -> bool>(entry: DefmtConfigEntry, uncomment: F) -> Option<String> {
+    let (plugin, sections) = match entry {
+        DefmtConfigEntry::Importer => (
+            "importer",
+            vec![
+                (
+                    "Common",
+                    struct_keys::<internal::CommonPluginConfig>(),
+                    COMMON_KEYS,
+                ),
+                (
+                    "Importer",
+                    struct_keys::<crate::ImportConfig>(),
+                    IMPORTER_KEYS,
+                ),
+            ],
+        ),
+        #[cfg(feature = "rtt-collector")]
+        DefmtConfigEntry::RttCollector => (
+            "RTT collector",
+            vec![
+                (
+                    "Common",
+                    struct_keys::<internal::CommonPluginConfig>(),
+                    COMMON_KEYS,
+                ),
+                (
+                    "RTT collector",
+                    struct_keys::<crate::RttCollectorConfig>(),
+                    RTT_COLLECTOR_KEYS,
+                ),
+            ],
+        ),
+        #[allow(unreachable_patterns)]
+        _ => return None,
+    };
+
+    let mut t = String::new();
+    let _ = writeln!(t, "# The defmt {plugin} configuration");
+    let _ = writeln!(t, "#");
+    let _ = writeln!(
+        t,
+        "# Every key is commented out, showing its default or an example value."
+    );
+    let _ = writeln!(
+        t,
+        "# String values can reference environment variables as ${{VAR}}."
+    );
+    let _ = writeln!(t);
+    let _ = writeln!(t, "[ingest]");
+    let _ = writeln!(t, "# Key-value attributes added to every timeline.");
+    let _ = writeln!(t, "# additional-timeline-attributes = [\"ci_run=1\"]");
+    let _ = writeln!(t, "# Key-value attributes overridden on every timeline.");
+    let _ = writeln!(
+        t,
+        "# override-timeline-attributes = [\"platform='RTICv1'\"]"
+    );
+    let _ = writeln!(t, "# allow-insecure-tls = false");
+    let _ = writeln!(
+        t,
+        "# protocol-parent-url = \"modality-ingest://127.0.0.1:14188\""
+    );
+    let _ = writeln!(t);
+    let _ = writeln!(t, "[metadata]");
+    let _ = writeln!(t, "# Base configuration files this file is layered over.");
+    let _ = writeln!(t, "# include = [\"common.toml\"]");
+    let _ = writeln!(
+        t,
+        "# The [metadata.profile.<name>] tables applied by default."
+    );
+    let _ = writeln!(t, "# active-profiles = [\"ci\"]");
+    for (section, keys, annotations) in sections {
+        let _ = writeln!(t);
+        let _ = writeln!(t, "# --- {section} ---");
+        for key in keys {
+            let _ = writeln!(t);
+            let Some(a) = annotations.iter().find(|a| a.key == *key) else {
+                let _ = writeln!(t, "# {key} =");
+                continue;
+            };
+            let (value, note) = match a.value {
+                TemplateValue::Default(v) => (v, "default"),
+                TemplateValue::Example(v) => (v, "example"),
+            };
+            let _ = writeln!(t, "# {}", a.doc);
+            let _ = writeln!(t, "# Type: {}, {note} shown", a.ty);
+            let comment = if uncomment(a.value) { "" } else { "# " };
+            let _ = writeln!(t, "{comment}{key} = {value}");
+        }
+    }
+    Some(t)
+}
+
+/// The keys of a config struct, the field names its `Deserialize` impl asks for
+fn struct_keys<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut keys: &'static [&'static str] = &[];
+    let _ = T::deserialize(KeyRecorder(&mut keys));
+    keys
+}
+
+/// A deserializer that records the struct fields and bails out
+struct KeyRecorder<'a>(&'a mut &'static [&'static str]);
+
+#[derive(Debug)]
+struct Recorded;
+
+impl fmt::Display for Recorded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("recorded the struct fields")
+    }
+}
+
+impl std::error::Error for Recorded {}
+
+impl de::Error for Recorded {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        Recorded
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for KeyRecorder<'a> {
+    type Error = Recorded;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(Recorded)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(Recorded)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(all(test, feature = "rtt-collector"))]
+mod test {
+    use super::*;
+    use crate::{DefmtConfig, ReflectorOpts};
+    use pretty_assertions::assert_eq;
+    use std::{fs::File, io::Write as _};
+
+    fn load(entry: DefmtConfigEntry, content: &str) -> DefmtConfig {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("template.toml");
+        let mut f = File::create(&path).unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.flush().unwrap();
+        DefmtConfig::load_merge_with_opts(
+            entry,
+            ReflectorOpts {
+                config_file: Some(path),
+                ..Default::default()
+            },
+            Default::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn annotations_match_the_config_structs() {
+        for (keys, annotations) in [
+            (struct_keys::<internal::CommonPluginConfig>(), COMMON_KEYS),
+            (struct_keys::<crate::ImportConfig>(), IMPORTER_KEYS),
+            (
+                struct_keys::<crate::RttCollectorConfig>(),
+                RTT_COLLECTOR_KEYS,
+            ),
+        ] {
+            assert!(!keys.is_empty());
+            let annotated: Vec<_> = annotations.iter().map(|a| a.key).collect();
+            assert_eq!(annotated, keys.to_vec());
+        }
+    }
+
+    #[test]
+    fn template_values() {
+        for entry in [DefmtConfigEntry::Importer, DefmtConfigEntry::RttCollector] {
+            assert!(config_template(entry).is_some());
+
+            // The documented defaults are the struct defaults
+            let defaults = render(entry, |v| matches!(v, TemplateValue::Default(_))).unwrap();
+            assert_eq!(load(entry, &defaults), load(entry, ""));
+
+            // And every value is valid
+            let all = render(entry, |_| true).unwrap();
+            assert_ne!(load(entry, &all), load(entry, ""));
+        }
+    }
+}
@@ -1,7 +1,12 @@
-use crate::{Error, EventRecord, PluginConfig, RtosMode, Timestamp, TrackingInstant};
+use crate::{
+    AttrKey, BackwardsTimestampPolicy, Error, EventRecord, FaultState, LatencySummary,
+    LatencyTracker, MetricsReport, PluginConfig, PreStartPolicy, Rate, RtosMode, Timestamp,
+    TrackingInstant, WrappingInstant,
+};
 use auxon_sdk::api::{AttrVal, BigInt, TimelineId};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 use std::hash::{DefaultHasher, Hash, Hasher};
+use std::mem;
 use tracing::{debug, trace, warn};
 
 #[derive(Debug)]
@@ -22,6 +27,79 @@ pub struct ContextEvent {
     pub add_previous_event_nonce: bool,
 }
 
+/// Stream data lost in transport, by how the loss was detected
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DataLoss {
+    /// Frames the defmt decoder had to resynchronize past
+    pub malformed_frames: u64,
+    /// Link-layer frames dropped by the framing decoder
+    pub dropped_frames: u64,
+    /// Reads that found the target's buffer full in a lossy channel mode
+    pub overflows: u64,
+}
+
+impl DataLoss {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn accumulate(&mut self, other: &DataLoss) {
+        self.malformed_frames = self.malformed_frames.saturating_add(other.malformed_frames);
+        self.dropped_frames = self.dropped_frames.saturating_add(other.dropped_frames);
+        self.overflows = self.overflows.saturating_add(other.overflows);
+    }
+
+    fn counters(&self) -> [(&'static str, u64); 3] {
+        [
+            ("malformed_frames", self.malformed_frames),
+            ("dropped_frames", self.dropped_frames),
+            ("overflows", self.overflows),
+        ]
+    }
+}
+
+/// Core state read from the target at a crash event
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrashState {
+    /// Core register values, by register name
+    pub registers: Vec<(String, u64)>,
+    /// The memory at the stack pointer
+    pub stack: Option<MemoryDump>,
+    /// The configured memory windows
+    pub memory: Vec<MemoryDump>,
+}
+
+/// A window of target memory
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryDump {
+    /// The symbol or address the window was configured with
+    pub location: String,
+    pub address: u64,
+    pub data: Vec<u8>,
+}
+
+impl MemoryDump {
+    const BYTES_PER_LINE: usize = 16;
+
+    /// Hex dump of the window, one line of 16 bytes prefixed with its address
+    pub fn format(&self) -> String {
+        self.data
+            .chunks(Self::BYTES_PER_LINE)
+            .enumerate()
+            .map(|(idx, line)| {
+                let bytes = line
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let address = self.address + (idx * Self::BYTES_PER_LINE) as u64;
+                format!("0x{address:08x}: {bytes}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
 #[derive(Debug)]
 pub struct ContextManager {
     cfg: PluginConfig,
@@ -32,41 +110,181 @@ pub struct ContextManager {
     event_counter: u64,
 
     last_raw_timestamp: Option<u64>,
+    first_raw_timestamp: Option<u64>,
     tracking_timestamp8: TrackingInstant<u8>,
     tracking_timestamp16: TrackingInstant<u16>,
     tracking_timestamp32: TrackingInstant<u32>,
+    /// Tracks every timestamp type at the configured counter width instead, when set
+    tracking_timestamp_wrapping: Option<WrappingInstant>,
+    transport_latency: LatencyTracker,
+    /// Accumulated by the rollover backwards timestamp policy
+    timestamp_offset: u64,
+    /// Device time of the previous event, for the gap detection
+    last_timestamp_ns: Option<u64>,
 
     /// Set when the first EventRecord is the start event in RTOS mode
     integration_version: Option<u16>,
 
+    boot_index: u64,
+    /// The current test phase, set by the phase marker event
+    phase: Option<String>,
+
+    /// Number of events seen before the start event in RTOS mode
+    pre_start_event_count: usize,
+    /// Events held until the start event arrives, with their global ordering
+    held_pre_start_events: Vec<(u128, EventRecord)>,
+    /// Set when events were put on the pre-start timeline
+    pre_start_context: Option<ContextId>,
+
     pending_context_switch_interaction: Option<ContextSwitchInteraction>,
+    /// Spawn events of each task that haven't been followed by an activation yet, oldest first
+    pending_spawns: BTreeMap<ContextId, VecDeque<ContextSwitchInteraction>>,
+    /// Send events waiting for their receive event, by correlation rule index and message key
+    pending_messages: BTreeMap<(usize, String), VecDeque<ContextSwitchInteraction>>,
+    /// Folded ISR contexts and their names, they're on the stack but don't have a timeline
+    folded_contexts: BTreeMap<ContextId, String>,
     /// Invariant: always contains the root context as the first element
     context_stack: Vec<ContextId>,
     contexts_to_timelines: BTreeMap<ContextId, TimelineMeta>,
+
+    /// Cumulative stream data loss, and the number of marker events for it
+    data_loss: DataLoss,
+    data_loss_events: u64,
 }
 
 impl ContextManager {
-    const UNKNOWN_CONTEXT: &'static str = "UNKNOWN_CONTEXT";
     const SYNTHETIC_INTERACTION_EVENT: &'static str = "AUXON_CONTEXT_RETURN";
+    const CONTEXT_STATS_EVENT: &'static str = "AUXON_CONTEXT_STATS";
+    const CONTEXT_STACK_RESET_EVENT: &'static str = "AUXON_CONTEXT_STACK_RESET";
+    const DATA_LOSS_EVENT: &'static str = "AUXON_DATA_LOSS";
+    const CRASH_STATE_EVENT: &'static str = "AUXON_CRASH_STATE";
+    const METRICS_EVENT: &'static str = "AUXON_METRICS";
+    const FAULT_EVENT: &'static str = "AUXON_FAULT";
+    const DEFAULT_MAX_CONTEXT_DEPTH: usize = 32;
     const DEFAULT_SINGLE_TIMELINE_CONTEXT_NAME: &'static str = "main";
+    const PRE_START_CONTEXT: &'static str = "PRE_START";
+    /// Give up waiting for the start event after this many events
+    const MAX_PRE_START_EVENTS: usize = 4096;
+    /// Spawn events kept per task, the oldest are dropped when a task is never activated
+    const MAX_PENDING_SPAWNS: usize = 64;
+    /// Distinct message keys waiting for a receive event, send events of new keys are
+    /// ignored past this
+    const MAX_PENDING_MESSAGE_KEYS: usize = 4096;
 
     pub fn new(cfg: PluginConfig, common_timeline_attrs: TimelineAttributes) -> Self {
         debug!(rtos_mode = %cfg.rtos_mode, "Starting context manager");
 
+        let tracking_timestamp_wrapping = cfg.timestamp_width.and_then(|width| {
+            let instant = WrappingInstant::new(width);
+            if instant.is_none() {
+                warn!(width, "Unsupported timestamp width, must be 1 to 63 bits");
+            }
+            instant
+        });
+
         Self {
+            boot_index: cfg.boot_index.unwrap_or(0),
             cfg,
             common_timeline_attrs,
             global_ordering: 0,
             event_counter: 0,
             last_raw_timestamp: None,
+            first_raw_timestamp: None,
             tracking_timestamp8: TrackingInstant::zero(),
             tracking_timestamp16: TrackingInstant::zero(),
             tracking_timestamp32: TrackingInstant::zero(),
+            tracking_timestamp_wrapping,
+            transport_latency: Default::default(),
+            timestamp_offset: 0,
+            last_timestamp_ns: None,
             integration_version: None,
+            phase: None,
+            pre_start_event_count: 0,
+            held_pre_start_events: Vec::new(),
+            pre_start_context: None,
             pending_context_switch_interaction: None,
+            pending_spawns: Default::default(),
+            pending_messages: Default::default(),
+            folded_contexts: Default::default(),
             context_stack: Default::default(),
             contexts_to_timelines: Default::default(),
+            data_loss: Default::default(),
+            data_loss_events: 0,
+        }
+    }
+
+    /// Flags the event when the device time since the previous event exceeds the gap threshold
+    fn detect_timestamp_gap(&mut self, ts_ns: u64, ev: &mut EventRecord) {
+        let Some(threshold) = self.cfg.timestamp_gap_threshold else {
+            return;
+        };
+        if let Some(last_ns) = self.last_timestamp_ns.replace(ts_ns) {
+            let gap = ts_ns.saturating_sub(last_ns);
+            if u128::from(gap) > threshold.0.as_nanos() {
+                warn!(
+                    gap = %humantime::format_duration(std::time::Duration::from_nanos(gap)),
+                    "Timestamp gap between events, possible data loss or target stall"
+                );
+                ev.insert_attr(ev_attr_key("timestamp_gap"), gap);
+            }
+        }
+    }
+
+    /// Applies the backwards timestamp policy to the tracked ticks of an event,
+    /// returns `None` when the event is to be dropped
+    fn backwards_timestamp(&mut self, ticks: u64) -> Option<u64> {
+        let last = match self.last_raw_timestamp {
+            Some(last) if ticks < last => last,
+            _ => return Some(ticks),
+        };
+        Some(match self.cfg.backwards_timestamp_policy {
+            BackwardsTimestampPolicy::Warn => {
+                warn!(
+                    "Event record has a timestamp that went backwards, timestamp rollover possible"
+                );
+                ticks
+            }
+            BackwardsTimestampPolicy::Clamp => {
+                debug!(
+                    timestamp = ticks,
+                    last_timestamp = last,
+                    "Clamping a timestamp that went backwards"
+                );
+                last
+            }
+            BackwardsTimestampPolicy::Rollover => {
+                debug!(
+                    timestamp = ticks,
+                    last_timestamp = last,
+                    "Correcting a timestamp that went backwards as a rollover"
+                );
+                self.timestamp_offset += last - ticks;
+                last
+            }
+            BackwardsTimestampPolicy::Drop => {
+                warn!(
+                    timestamp = ticks,
+                    last_timestamp = last,
+                    "Dropping an event record with a timestamp that went backwards"
+                );
+                return None;
+            }
+        })
+    }
+
+    /// The transport latency distribution of the events that had both a target timestamp
+    /// and a host receive time
+    pub fn transport_latency(&self) -> Option<LatencySummary> {
+        self.transport_latency.summary()
+    }
+
+    /// Sets a common timeline attribute, on the existing timelines and the ones created later
+    pub fn set_timeline_attr(&mut self, k: String, v: AttrVal) {
+        for timeline in self.contexts_to_timelines.values_mut() {
+            timeline.insert_attr(k.clone(), v.clone());
+            timeline.revision += 1;
         }
+        self.common_timeline_attrs.insert(k, v);
     }
 
     pub fn timeline_meta(&self, context_id: ContextId) -> Result<&TimelineMeta, Error> {
@@ -97,27 +315,50 @@ impl ContextManager {
                 }
             }
 
-            let ts_ticks = match ts {
-                Timestamp::Ticks8(ts8) => self.tracking_timestamp8.elapsed(ts8),
-                Timestamp::Ticks16(ts16) => self.tracking_timestamp16.elapsed(ts16),
-                Timestamp::Ticks32(ts32) => self.tracking_timestamp32.elapsed(ts32),
-                _ => ts.as_u64(),
+            let wrapping = self.tracking_timestamp_wrapping.is_some();
+            let ts_ticks = match (self.tracking_timestamp_wrapping.as_mut(), ts) {
+                (Some(tracking), _) => tracking.elapsed(ts.as_u64()),
+                (None, Timestamp::Ticks8(ts8)) => self.tracking_timestamp8.elapsed(ts8),
+                (None, Timestamp::Ticks16(ts16)) => self.tracking_timestamp16.elapsed(ts16),
+                (None, Timestamp::Ticks32(ts32)) => self.tracking_timestamp32.elapsed(ts32),
+                (None, _) => ts.as_u64(),
             };
+            let tracked_ticks = ts_ticks.saturating_add(self.timestamp_offset);
+            let ts_ticks = match self.backwards_timestamp(tracked_ticks) {
+                Some(t) => t,
+                None => return Ok(ActiveContext { events: Vec::new() }),
+            };
+            let retimed = wrapping || ts_ticks != ts.as_u64();
 
             // Update event timestamp attributes
-            if ts.supports_rollover_tracking() {
+            if ts.supports_rollover_tracking() || retimed {
                 ev.set_internal_raw_timestamp(ts.as_u64());
                 ev.set_internal_timestamp(ts_ticks);
             }
 
             // Convert to time base if we have a clock rate
-            if !ts.has_time_base() {
+            if ts.has_time_base() && retimed {
+                // The record's timestamp was converted from the raw value
+                if let Some(rate) = ts.intrinsic_clock_rate() {
+                    ev.set_timestamp((rate * ts_ticks).into());
+                }
+            } else if !ts.has_time_base() {
                 if let Some(clock_rate) = self.cfg.clock_rate {
                     let ts_ns = clock_rate * ts_ticks;
                     ev.set_timestamp(ts_ns.into());
                 }
             }
 
+            // Both the target and host times are needed for the transport latency
+            if let (Some(target_ns), Some(host_ns)) = (ev.timestamp_ns(), ev.host_received_ns()) {
+                let latency = self.transport_latency.observe(target_ns, host_ns);
+                ev.insert_attr(ev_internal_attr_key("transport_latency"), latency);
+            }
+
+            if let Some(ts_ns) = ev.timestamp_ns() {
+                self.detect_timestamp_gap(ts_ns, &mut ev);
+            }
+
             Some(ts_ticks)
         } else {
             None
@@ -125,14 +366,12 @@ impl ContextManager {
 
         // Sanity check if time went backwards
         match (self.last_raw_timestamp, timestamp_raw) {
-            (Some(last_t), Some(cur_t)) => {
-                if cur_t < last_t {
-                    warn!("Event record has a timestamp that went backwards, timestamp rollover possible");
-                }
+            (Some(_), Some(cur_t)) => {
                 self.last_raw_timestamp = cur_t.into();
             }
             (None, Some(cur_t)) => {
                 self.last_raw_timestamp = cur_t.into();
+                self.first_raw_timestamp = cur_t.into();
             }
             (Some(last_t), None) => {
                 warn!(
@@ -161,8 +400,42 @@ impl ContextManager {
             }
         }
 
-        if self.cfg.rtos_mode == RtosMode::Rtic1 {
-            self.process_rtic1(ev)
+        // Track the test phase
+        if let Some(marker) = self.cfg.phase_marker_event.as_deref() {
+            if ev.event_name() == Some(marker) {
+                match ev.phase() {
+                    Some(phase) => {
+                        debug!(phase, "Entering test phase");
+                        self.phase = Some(phase.to_owned());
+                    }
+                    None => warn!(
+                        event_name = marker,
+                        "Phase marker event is missing the phase parameter"
+                    ),
+                }
+            }
+        }
+        if let Some(phase) = self.phase.as_deref() {
+            ev.insert_attr(ev_attr_key("phase"), phase);
+        }
+
+        // Tag outcomes, first matching rule wins
+        if let Some(rule) = self
+            .cfg
+            .outcome_rules
+            .iter()
+            .find(|r| r.matches(ev.event_name(), ev.level()))
+        {
+            if let Some(outcome) = rule.outcome {
+                ev.insert_attr(ev_attr_key("outcome"), outcome.to_string());
+            }
+            if let Some(severity) = rule.severity.as_ref() {
+                ev.insert_attr(ev_attr_key("severity"), severity.as_str());
+            }
+        }
+
+        if self.cfg.rtos_mode != RtosMode::None {
+            self.process_rtos(ev)
         } else {
             // Vanilla mode, all events on a single timeline
 
@@ -180,6 +453,7 @@ impl ContextManager {
             }
 
             let active_ctx_id = self.active_context()?;
+            let active_ctx_id = self.unknown_source_context(active_ctx_id, &ev);
             let timeline = self
                 .contexts_to_timelines
                 .get_mut(&active_ctx_id)
@@ -187,6 +461,11 @@ impl ContextManager {
             timeline.increment_nonce();
             ev.add_internal_nonce(timeline.nonce);
 
+            let source = timeline.interaction_source();
+            if let Some(interaction) = self.correlate_message(source, &mut ev) {
+                ev.add_interaction(!self.cfg.disable_interactions, interaction.1, interaction.2);
+            }
+
             Ok(ActiveContext {
                 events: vec![ContextEvent {
                     context: active_ctx_id,
@@ -198,19 +477,58 @@ impl ContextManager {
         }
     }
 
-    fn process_rtic1(&mut self, mut ev: EventRecord) -> Result<ActiveContext, Error> {
+    fn process_rtos(&mut self, mut ev: EventRecord) -> Result<ActiveContext, Error> {
         let mut events = Vec::new();
-
+        // The stack before this event, to find the contexts it activated or deactivated
+        let prev_context_stack = self.cfg.context_stats.then(|| self.context_stack.clone());
+        let attached_mid_stream = self.cfg.pre_start_policy == PreStartPolicy::Resync
+            && self.integration_version.is_none()
+            && ev.event_name() != Some(self.start_event_name());
+
+        if self.cfg.rtos_mode == RtosMode::BareMetal {
+            // There's no start event, the main loop is the root context
+            if self.context_stack.is_empty() {
+                let ctx_name = self
+                    .cfg
+                    .init_task_name
+                    .as_deref()
+                    .unwrap_or(Self::DEFAULT_SINGLE_TIMELINE_CONTEXT_NAME)
+                    .to_owned();
+                let ctx_id = self.alloc_context(&ctx_name);
+                self.context_stack.push(ctx_id);
+            }
+        }
+        // Start on the unknown context, the context markers resynchronize the stack
+        else if attached_mid_stream {
+            if self.context_stack.is_empty() {
+                warn!("Missing start event, resynchronizing the context stack from the unknown context");
+                let ctx_id = self.alloc_unknown_context();
+                self.context_stack.push(ctx_id);
+                // Linked to the init task timeline if the start event arrives later
+                self.pre_start_context = Some(ctx_id);
+            }
+        }
         // Look for the start event, disable RTOS mode if anything doesn't match expectations
-        if self.event_counter == 1 && self.integration_version.is_none() {
+        else if self.integration_version.is_none() {
+            let start_event_name = self.start_event_name().to_owned();
+            let is_start_event = ev.event_name() == Some(start_event_name.as_str());
+            if !is_start_event && self.cfg.pre_start_policy != PreStartPolicy::DisableRtosMode {
+                if self.pre_start_event_count < Self::MAX_PRE_START_EVENTS {
+                    return self.process_pre_start_event(ev);
+                }
+                warn!(
+                    max_events = Self::MAX_PRE_START_EVENTS,
+                    "Never received the start event"
+                );
+            }
+
             let mut start_event_valid = true;
-            let event_name = ev.event_name();
-            let task_name = ev.task_name();
-            let version = ev.integration_version();
+            let task_name = self.start_event_task_name(&ev);
+            let version = self.start_event_version(&ev);
 
-            if event_name != Some(rtic1::TRACE_START) {
+            if !is_start_event {
                 warn!(
-                    expected_event = rtic1::TRACE_START,
+                    expected_event = start_event_name,
                     "Missing start event, disabling RTOS mode"
                 );
                 start_event_valid = false;
@@ -227,9 +545,10 @@ impl ContextManager {
             // Setup a fallback context
             if !start_event_valid {
                 self.cfg.rtos_mode = RtosMode::None;
-                let ctx_id = self.alloc_context(Self::UNKNOWN_CONTEXT);
+                let ctx_id = self.alloc_unknown_context();
                 self.context_stack.push(ctx_id);
 
+                self.release_held_pre_start_events(ctx_id, &mut events)?;
                 events.push(ContextEvent {
                     context: ctx_id,
                     global_ordering: self.global_ordering,
@@ -240,15 +559,44 @@ impl ContextManager {
             };
         }
 
-        let task_or_isr_name = ev.task_name().or_else(|| ev.isr_name());
-        let (active_ctx_id, pending_context_switch_interaction) = match (
-            ev.event_name(),
-            task_or_isr_name,
-        ) {
+        let marker = RtosMarker::classify(&self.cfg, &ev);
+        let spawned_ctx_id = match &marker {
+            Some(RtosMarker::Spawn(ctx_name)) => Some(self.scoped_context_id(ctx_name)),
+            _ => None,
+        };
+        // Set when the context is activated by a previous spawn event
+        let mut spawn_interaction = None;
+        let (active_ctx_id, pending_context_switch_interaction) = match marker {
+            // Folded ISR enter, the event goes on the interrupted context
+            Some(RtosMarker::Enter(ctx_name))
+                if self.cfg.fold_isrs.iter().any(|g| g.is_match(&ctx_name)) =>
+            {
+                let host_ctx_id = self.active_context()?;
+                let ctx_id = self.scoped_context_id(&ctx_name);
+                trace!(ctx_id, host_ctx_id, "Push folded ISR");
+                ev.insert_attr(ev_attr_key("isr"), ctx_name.as_str());
+                self.folded_contexts.insert(ctx_id, ctx_name);
+                self.context_stack.push(ctx_id);
+                self.host_event(host_ctx_id)?
+            }
+
+            // Folded ISR exit
+            Some(RtosMarker::Exit) if self.folded_isr_name().is_some() => {
+                if let Some(isr_name) = self.folded_isr_name() {
+                    ev.insert_attr(ev_attr_key("isr"), isr_name.to_owned());
+                }
+                self.context_stack.pop();
+                let host_ctx_id = self.active_context()?;
+                trace!(host_ctx_id, "Pop folded ISR");
+                self.host_event(host_ctx_id)?
+            }
+
             // Context enter
-            (Some(rtic1::TASK_ENTER), Some(ctx_name))
-            | (Some(rtic1::ISR_ENTER), Some(ctx_name)) => {
-                let ctx_id = self.alloc_context(ctx_name);
+            Some(RtosMarker::Enter(ctx_name)) => {
+                let ctx_id = self.alloc_context(&ctx_name);
+                if let Some(priority) = ev.int_param("priority") {
+                    self.track_priority(ctx_id, priority, &mut ev)?;
+                }
 
                 let active_ctx_id = self.active_context()?;
                 let active_timeline = self
@@ -301,13 +649,27 @@ impl ContextManager {
                     self.global_ordering = self.global_ordering.saturating_add(1);
                 }
 
+                let max_depth = self
+                    .cfg
+                    .max_context_depth
+                    .unwrap_or(Self::DEFAULT_MAX_CONTEXT_DEPTH);
+                if self.context_stack.len() >= max_depth.max(1) {
+                    self.reset_context_stack("overflow", &ev, &mut events)?;
+                }
+
                 // Push newly active context, return pending interaction for this event
                 let interaction = self.push_context(ctx_id)?;
+                spawn_interaction = self.take_spawn_interaction(ctx_id);
                 (ctx_id, Some(interaction))
             }
 
             // Context exit
-            (Some(rtic1::TASK_EXIT), _) | (Some(rtic1::ISR_EXIT), _) => {
+            Some(RtosMarker::Exit) => {
+                // Nothing to exit from the root context, unless we started mid-stream
+                if self.context_stack.len() == 1 && self.integration_version.is_some() {
+                    self.reset_context_stack("underflow", &ev, &mut events)?;
+                }
+
                 let ctx_id = self.active_context()?;
 
                 // Return pending interaction for this event
@@ -321,39 +683,90 @@ impl ContextManager {
             }
 
             // Start event
-            (Some(rtic1::TRACE_START), Some(ctx_name)) if self.event_counter == 1 => {
+            Some(RtosMarker::Start(ctx_name)) if self.integration_version.is_none() => {
                 // SAFETY: start event semantics checked above
-                let version = ev.integration_version().unwrap();
+                let version = self.start_event_version(&ev).unwrap();
                 debug!(version, task_name = ctx_name, "Found start event");
                 self.integration_version = version.into();
-                let init_task_name = self
-                    .cfg
-                    .init_task_name
-                    .as_deref()
-                    .unwrap_or(ctx_name)
-                    .to_owned();
-                // Setup initial context stack
+                let init_task_name = self.cfg.init_task_name.clone().unwrap_or(ctx_name);
+                // Setup initial context stack, replacing the one resynchronized mid-stream
                 let ctx_id = self.alloc_context(&init_task_name);
+                self.context_stack.clear();
                 self.context_stack.push(ctx_id);
-                (ctx_id, None)
+                self.pending_context_switch_interaction = None;
+
+                // Held events precede the start event on the init timeline
+                self.release_held_pre_start_events(ctx_id, &mut events)?;
+
+                // Link the pre-start timeline to the init timeline
+                let pre_start_interaction = match self.pre_start_context {
+                    Some(pre_start_ctx_id) => Some(
+                        self.contexts_to_timelines
+                            .get(&pre_start_ctx_id)
+                            .ok_or(Error::ContextManagerInternalState)?
+                            .interaction_source(),
+                    ),
+                    None => None,
+                };
+                (ctx_id, pre_start_interaction)
+            }
+
+            // Flat context switch, the new context replaces the one at the bottom of the stack.
+            // Any ISRs preempting it stay active, the new context runs once they exit.
+            Some(RtosMarker::Switch(ctx_name)) => {
+                let ctx_id = self.alloc_context(&ctx_name);
+                let active_ctx_id = self.active_context()?;
+                let active_timeline = self
+                    .contexts_to_timelines
+                    .get_mut(&active_ctx_id)
+                    .ok_or(Error::ContextManagerInternalState)?;
+                active_timeline.requires_synthetic_interaction_event = false;
+                let nested = self
+                    .context_stack
+                    .iter()
+                    .skip(1)
+                    .any(|c| !self.folded_contexts.contains_key(c));
+                if nested || ctx_id == active_ctx_id {
+                    trace!(ctx_id, size = self.context_stack.len(), "Switch task");
+                    self.context_stack[0] = ctx_id;
+                    (
+                        active_ctx_id,
+                        self.pending_context_switch_interaction.take(),
+                    )
+                } else {
+                    // Prefer the interaction from an ISR that just exited, it caused the switch
+                    let interaction = self
+                        .pending_context_switch_interaction
+                        .take()
+                        .unwrap_or_else(|| active_timeline.interaction_source());
+                    trace!(ctx_id, prev_ctx_id = active_ctx_id, "Switch task");
+                    self.context_stack[0] = ctx_id;
+                    spawn_interaction = self.take_spawn_interaction(ctx_id);
+                    (ctx_id, Some(interaction))
+                }
             }
 
-            event => {
+            marker => {
                 // Unexpected instrumentation and/or corrupt data
-                match event.0 {
-                    Some(rtic1::TASK_ENTER) | Some(rtic1::ISR_ENTER) => {
-                        warn!("Context enter event is missing the task/isr name parameter, disabling RTOS mode");
-                        self.cfg.rtos_mode = RtosMode::None;
-                        // Transition to the unknown context
-                        let ctx_id = self.alloc_context(Self::UNKNOWN_CONTEXT);
-                        self.context_stack.push(ctx_id);
-                        self.pending_context_switch_interaction = None;
-                    }
-                    _ => (),
+                if marker == Some(RtosMarker::MissingContextName) {
+                    warn!("Context enter event is missing the context name parameter, disabling RTOS mode");
+                    self.cfg.rtos_mode = RtosMode::None;
+                    // Transition to the unknown context
+                    let ctx_id = self.alloc_unknown_context();
+                    self.context_stack.push(ctx_id);
+                    self.pending_context_switch_interaction = None;
                 }
 
-                // Normal event on the active context
+                // Normal event on the active context, or on the unknown context of its source
+                if let Some(isr_name) = self.folded_isr_name() {
+                    ev.insert_attr(ev_attr_key("isr"), isr_name.to_owned());
+                }
                 let active_ctx_id = self.active_context()?;
+                let source_ctx_id = self.unknown_source_context(active_ctx_id, &ev);
+                if source_ctx_id != active_ctx_id {
+                    // Leave any pending interaction for the next event on the active context
+                    return self.push_orphan_event(source_ctx_id, ev, events);
+                }
                 let active_timeline = self
                     .contexts_to_timelines
                     .get_mut(&active_ctx_id)
@@ -377,10 +790,46 @@ impl ContextManager {
         active_timeline.increment_nonce();
         ev.add_internal_nonce(active_timeline.nonce);
 
-        let add_previous_event_nonce = if let Some(interaction) = pending_context_switch_interaction
-        {
+        // The spawned task's next activation will interact with this event
+        if let Some(spawned_ctx_id) = spawned_ctx_id {
+            let queue = self.pending_spawns.entry(spawned_ctx_id).or_default();
+            if queue.len() == Self::MAX_PENDING_SPAWNS {
+                queue.pop_front();
+            }
+            queue.push_back(active_timeline.interaction_source());
+            if !self.cfg.disable_interactions {
+                ev.promote_internal_nonce();
+            }
+        }
+        let source = active_timeline.interaction_source();
+        let message_interaction = self.correlate_message(source, &mut ev);
+
+        if let (Some(prev_context_stack), Some(now)) = (
+            prev_context_stack,
+            ev.timestamp().and(self.last_raw_timestamp),
+        ) {
+            self.track_stats(&prev_context_stack, now, active_ctx_id, &mut ev)?;
+        }
+
+        // Only the explicit interactions remain without linear causality
+        let pending_context_switch_interaction =
+            pending_context_switch_interaction.filter(|_| !self.cfg.disable_linear_causality);
+
+        // The spawn event is the cause of the activation, rather than the previous event
+        let add_previous_event_nonce = if let Some(interaction) = spawn_interaction {
+            ev.add_interaction(!self.cfg.disable_interactions, interaction.1, interaction.2);
+            false
+        } else if let Some(interaction) = pending_context_switch_interaction {
+            if message_interaction.is_some() {
+                debug!(
+                    "Context switch event is also a message receive event, ignoring the message"
+                );
+            }
             ev.add_interaction(!self.cfg.disable_interactions, interaction.1, interaction.2);
             !self.cfg.disable_interactions
+        } else if let Some(interaction) = message_interaction {
+            ev.add_interaction(!self.cfg.disable_interactions, interaction.1, interaction.2);
+            false
         } else {
             false
         };
@@ -396,99 +845,798 @@ impl ContextManager {
         Ok(ActiveContext { events })
     }
 
-    fn alloc_context(&mut self, ctx_name: &str) -> ContextId {
-        let ctx_id = context_id(ctx_name);
-        self.contexts_to_timelines.entry(ctx_id).or_insert_with(|| {
-            let mut tl_meta = TimelineMeta::new(ctx_name, ctx_id);
-            if let Some(v) = self.integration_version {
-                tl_meta.insert_attr(TimelineMeta::internal_attr_key("integration_version"), v);
-            }
-            tl_meta.insert_attr(
-                TimelineMeta::internal_attr_key("rtos_mode"),
-                self.cfg.rtos_mode.to_string(),
-            );
-            for (k, v) in self.common_timeline_attrs.iter() {
-                tl_meta.insert_attr(k.clone(), v.clone());
-            }
+    fn start_event_name(&self) -> &str {
+        match self.cfg.rtos_mode {
+            RtosMode::Custom => self
+                .cfg
+                .rtos_markers
+                .trace_start
+                .as_deref()
+                .unwrap_or(rtic1::TRACE_START),
+            _ => rtic1::TRACE_START,
+        }
+    }
 
-            tl_meta
-        });
+    fn start_event_task_name<'a>(&self, ev: &'a EventRecord) -> Option<&'a str> {
+        match self.cfg.rtos_mode {
+            RtosMode::Custom => custom::task_name(&self.cfg.rtos_markers, ev),
+            _ => ev.task_name(),
+        }
+    }
 
-        ctx_id
+    fn start_event_version(&self, ev: &EventRecord) -> Option<u16> {
+        match (self.cfg.rtos_mode, &self.cfg.rtos_markers.version_key) {
+            (RtosMode::Custom, Some(key)) => ev.int_param(key).map(|v| v as u16),
+            (RtosMode::Custom, None) => Some(ev.integration_version().unwrap_or(0)),
+            _ => ev.integration_version(),
+        }
     }
 
-    fn active_context(&self) -> Result<ContextId, Error> {
-        Ok(*self
-            .context_stack
-            .last()
-            .ok_or(Error::ContextManagerInternalState)?)
+    /// Returns any events the context manager is still holding on to, i.e.
+    /// pre-start events when the stream ended before the start event arrived.
+    /// Should be called once at the end of the stream.
+    pub fn flush(&mut self) -> Result<ActiveContext, Error> {
+        let mut events = Vec::new();
+        if !self.held_pre_start_events.is_empty() {
+            warn!(
+                held_events = self.held_pre_start_events.len(),
+                "Never received the start event, releasing held events"
+            );
+            let ctx_id = self.alloc_unknown_context();
+            self.release_held_pre_start_events(ctx_id, &mut events)?;
+        }
+        if self.cfg.context_stats && self.cfg.rtos_mode != RtosMode::None {
+            self.add_context_stats_events(&mut events);
+        }
+        Ok(ActiveContext { events })
     }
 
-    /// Returns the interaction source from the previous context to be added
-    /// to the newly active context.
-    fn push_context(
-        &mut self,
-        ctx_id: ContextId,
-    ) -> Result<(RemoteContextId, RemoteTimelineId, RemoteInteractionNonce), Error> {
-        // Get the previous event's interaction source from the currently active context
-        let active_ctx_id = self.active_context()?;
-        let active_timeline = self
+    /// Records stream data lost since the previous event, adding a marker event to the
+    /// active context and updating the cumulative loss counters on every timeline.
+    /// Loss before the first event only counts towards the timeline attributes.
+    pub fn data_loss(&mut self, loss: DataLoss) -> Result<ActiveContext, Error> {
+        let mut events = Vec::new();
+        if loss.is_empty() {
+            return Ok(ActiveContext { events });
+        }
+        self.data_loss.accumulate(&loss);
+        warn!(
+            malformed_frames = loss.malformed_frames,
+            dropped_frames = loss.dropped_frames,
+            overflows = loss.overflows,
+            "Stream data loss"
+        );
+
+        let Ok(ctx_id) = self.active_context() else {
+            self.update_data_loss_attrs();
+            return Ok(ActiveContext { events });
+        };
+        self.data_loss_events += 1;
+        self.update_data_loss_attrs();
+
+        let mut record = EventRecord::new(Default::default());
+        record.insert_attr(ev_attr_key("name"), Self::DATA_LOSS_EVENT);
+        record.insert_attr(ev_internal_attr_key("synthetic"), true);
+        for (k, v) in loss.counters() {
+            if v != 0 {
+                record.insert_attr(ev_attr_key(k), v);
+            }
+        }
+        self.global_ordering = self.global_ordering.saturating_add(1);
+        let timeline = self
             .contexts_to_timelines
-            .get_mut(&active_ctx_id)
+            .get_mut(&ctx_id)
             .ok_or(Error::ContextManagerInternalState)?;
-        let interaction = active_timeline.interaction_source();
-
-        // Clear the synthetic event flag since we just got a new context
-        // to hang the interaction on
-        active_timeline.requires_synthetic_interaction_event = false;
+        timeline.increment_nonce();
+        record.add_internal_nonce(timeline.nonce);
+        events.push(ContextEvent {
+            context: ctx_id,
+            global_ordering: self.global_ordering,
+            record,
+            add_previous_event_nonce: false,
+        });
+        Ok(ActiveContext { events })
+    }
 
-        // Set new context as active
-        self.context_stack.push(ctx_id);
+    /// Adds a crash event carrying the core state to the active context, following
+    /// the event that triggered the capture
+    pub fn crash_state(
+        &mut self,
+        trigger: Option<&str>,
+        state: CrashState,
+    ) -> Result<ActiveContext, Error> {
+        let mut events = Vec::new();
+        let Ok(ctx_id) = self.active_context() else {
+            warn!("Crash state captured before the first event, dropping it");
+            return Ok(ActiveContext { events });
+        };
 
-        trace!(ctx_id, size = self.context_stack.len(), "Push task");
+        let mut record = EventRecord::new(Default::default());
+        record.insert_attr(ev_attr_key("name"), Self::CRASH_STATE_EVENT);
+        record.insert_attr(ev_internal_attr_key("synthetic"), true);
+        if let Some(trigger) = trigger {
+            record.insert_attr(ev_attr_key("crash.trigger"), trigger);
+        }
+        if !state.registers.is_empty() {
+            let registers = state
+                .registers
+                .iter()
+                .map(|(name, value)| format!("{name}=0x{value:08x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            record.insert_attr(ev_attr_key("crash.registers"), registers);
+        }
+        for (name, value) in state.registers.iter() {
+            record.insert_attr(
+                ev_attr_key(&format!("crash.register.{}", name.to_lowercase())),
+                *value,
+            );
+        }
+        if let Some(stack) = &state.stack {
+            record.insert_attr(ev_attr_key("crash.stack.address"), stack.address);
+            record.insert_attr(ev_attr_key("crash.stack"), stack.format());
+        }
+        for (idx, window) in state.memory.iter().enumerate() {
+            let key = |k: &str| ev_attr_key(&format!("crash.memory.{idx}.{k}"));
+            record.insert_attr(key("location"), window.location.as_str());
+            record.insert_attr(key("address"), window.address);
+            record.insert_attr(key("dump"), window.format());
+        }
 
-        Ok(interaction)
+        self.global_ordering = self.global_ordering.saturating_add(1);
+        let timeline = self
+            .contexts_to_timelines
+            .get_mut(&ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        timeline.increment_nonce();
+        record.add_internal_nonce(timeline.nonce);
+        events.push(ContextEvent {
+            context: ctx_id,
+            global_ordering: self.global_ordering,
+            record,
+            add_previous_event_nonce: false,
+        });
+        Ok(ActiveContext { events })
     }
 
-    /// Returns Ok(None) when we're back on the root init/unknown context.
-    /// This can happen when we started mid-stream and we don't know which tasks we're in.
-    fn pop_context(
-        &mut self,
-    ) -> Result<Option<(RemoteContextId, RemoteTimelineId, RemoteInteractionNonce)>, Error> {
-        if self.context_stack.len() == 1 {
-            // We're back on the init/unknown context
-            if self.integration_version.is_some() {
-                warn!("The target should never emit a context exit event from the initial task");
-            }
-            Ok(None)
-        } else {
-            // Pop the active context off the stack, previous context now active
-            let ctx_id = self
-                .context_stack
-                .pop()
-                .ok_or(Error::ContextManagerInternalState)?;
+    /// Adds a fault event carrying the decoded fault status registers to the active context.
+    /// A fault before the first event starts the initial timeline.
+    pub fn fault(&mut self, fault: &FaultState) -> Result<ActiveContext, Error> {
+        let mut record = EventRecord::new(Default::default());
+        record.insert_attr(ev_attr_key("name"), Self::FAULT_EVENT);
+        record.insert_attr(ev_internal_attr_key("synthetic"), true);
+        record.insert_attr(ev_attr_key("fault.kind"), fault.kind());
+        record.insert_attr(ev_attr_key("fault.flags"), fault.flags().join(" "));
+        record.insert_attr(ev_attr_key("fault.cfsr"), u64::from(fault.cfsr));
+        record.insert_attr(ev_attr_key("fault.hfsr"), u64::from(fault.hfsr));
+        if let Some(mmfar) = fault.mmfar() {
+            record.insert_attr(ev_attr_key("fault.mmfar"), u64::from(mmfar));
+        }
+        if let Some(bfar) = fault.bfar() {
+            record.insert_attr(ev_attr_key("fault.bfar"), u64::from(bfar));
+        }
+        if let Some(pc) = fault.pc {
+            record.insert_attr(ev_attr_key("fault.pc"), pc);
+        }
+        record.insert_attr(ev_attr_key("fault.halted"), fault.halted);
 
-            let timeline = self
-                .contexts_to_timelines
-                .get_mut(&ctx_id)
-                .ok_or(Error::ContextManagerInternalState)?;
+        let Ok(ctx_id) = self.active_context() else {
+            return self.process_record(record);
+        };
+        let mut events = Vec::new();
+        self.global_ordering = self.global_ordering.saturating_add(1);
+        let timeline = self
+            .contexts_to_timelines
+            .get_mut(&ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        timeline.increment_nonce();
+        record.add_internal_nonce(timeline.nonce);
+        events.push(ContextEvent {
+            context: ctx_id,
+            global_ordering: self.global_ordering,
+            record,
+            add_previous_event_nonce: false,
+        });
+        Ok(ActiveContext { events })
+    }
 
-            // Clear the synthetic event flag since we just got a new context
-            // to hang the interaction on
-            timeline.requires_synthetic_interaction_event = false;
+    /// Adds a periodic metrics event to the active context
+    pub fn metrics(&mut self, report: &MetricsReport) -> Result<ActiveContext, Error> {
+        let mut events = Vec::new();
+        let Ok(ctx_id) = self.active_context() else {
+            return Ok(ActiveContext { events });
+        };
 
-            // Get the interaction source from the previously active context
-            let pending_interaction = timeline.next_interaction_source();
+        let mut record = EventRecord::new(Default::default());
+        record.insert_attr(ev_attr_key("name"), Self::METRICS_EVENT);
+        record.insert_attr(ev_internal_attr_key("synthetic"), true);
+        record.insert_attr(
+            ev_attr_key("metrics.window_ms"),
+            report.window.as_millis() as u64,
+        );
+        record.insert_attr(ev_attr_key("metrics.bytes_read"), report.bytes_read);
+        record.insert_attr(ev_attr_key("metrics.bytes_per_sec"), report.bytes_per_sec());
+        record.insert_attr(ev_attr_key("metrics.frames"), report.frames);
+        record.insert_attr(
+            ev_attr_key("metrics.frames_per_sec"),
+            report.frames_per_sec(),
+        );
+        record.insert_attr(ev_attr_key("metrics.events"), report.events);
+        record.insert_attr(
+            ev_attr_key("metrics.events_per_sec"),
+            report.events_per_sec(),
+        );
+        record.insert_attr(
+            ev_attr_key("metrics.malformed_frames"),
+            report.malformed_frames,
+        );
+        if let Some(pending) = report.ingest_pending {
+            record.insert_attr(ev_attr_key("metrics.ingest_pending"), pending);
+        }
 
-            // Mark this context as needed a synthetic interaction event, gets
-            // cleared if it receives any events before another context switch.
-            // This keeps the causality linear.
+        self.global_ordering = self.global_ordering.saturating_add(1);
+        let timeline = self
+            .contexts_to_timelines
+            .get_mut(&ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        timeline.increment_nonce();
+        record.add_internal_nonce(timeline.nonce);
+        events.push(ContextEvent {
+            context: ctx_id,
+            global_ordering: self.global_ordering,
+            record,
+            add_previous_event_nonce: false,
+        });
+        Ok(ActiveContext { events })
+    }
+
+    fn update_data_loss_attrs(&mut self) {
+        for timeline in self.contexts_to_timelines.values_mut() {
+            timeline.insert_data_loss_attrs(&self.data_loss, self.data_loss_events);
+            timeline.revision += 1;
+        }
+    }
+
+    /// Recovers from unbalanced context markers by resetting the stack to the root context,
+    /// adding a diagnostic event to it
+    fn reset_context_stack(
+        &mut self,
+        reason: &str,
+        ev: &EventRecord,
+        events: &mut Vec<ContextEvent>,
+    ) -> Result<(), Error> {
+        let depth = self.context_stack.len();
+        warn!(
+            reason,
+            depth, "Unbalanced context markers, resetting the context stack"
+        );
+
+        // The diagnostic event follows the previous event
+        let active_ctx_id = self.active_context()?;
+        let interaction = match self.pending_context_switch_interaction.take() {
+            _ if self.cfg.disable_linear_causality => None,
+            Some(interaction) => Some(interaction),
+            None if depth > 1 => Some(
+                self.contexts_to_timelines
+                    .get(&active_ctx_id)
+                    .ok_or(Error::ContextManagerInternalState)?
+                    .interaction_source(),
+            ),
+            None => None,
+        };
+        self.context_stack.truncate(1);
+
+        let root_ctx_id = self.active_context()?;
+        let root_timeline = self
+            .contexts_to_timelines
+            .get_mut(&root_ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        root_timeline.requires_synthetic_interaction_event = false;
+
+        let mut record = EventRecord::new(Default::default());
+        record.insert_attr(ev_attr_key("name"), Self::CONTEXT_STACK_RESET_EVENT);
+        record.insert_attr(ev_internal_attr_key("synthetic"), true);
+        record.insert_attr(ev_attr_key("reason"), reason);
+        record.insert_attr(ev_attr_key("depth"), depth as u64);
+        if let Some(ts) = ev.attributes().get("event.timestamp") {
+            record.insert_attr(ev_attr_key("timestamp"), ts.clone());
+        }
+        root_timeline.increment_nonce();
+        record.add_internal_nonce(root_timeline.nonce);
+
+        let add_previous_event_nonce = match interaction {
+            Some(interaction) => {
+                record.add_interaction(
+                    !self.cfg.disable_interactions,
+                    interaction.1,
+                    interaction.2,
+                );
+                !self.cfg.disable_interactions
+            }
+            None => false,
+        };
+        events.push(ContextEvent {
+            context: root_ctx_id,
+            global_ordering: self.global_ordering,
+            record,
+            add_previous_event_nonce,
+        });
+        self.global_ordering = self.global_ordering.saturating_add(1);
+        Ok(())
+    }
+
+    /// Updates the run time statistics of the contexts this event activated, deactivated,
+    /// preempted or resumed. A context exit event gets the duration of the activation.
+    fn track_stats(
+        &mut self,
+        prev_context_stack: &[ContextId],
+        now: u64,
+        ev_ctx_id: ContextId,
+        ev: &mut EventRecord,
+    ) -> Result<(), Error> {
+        let clock_rate = self.cfg.clock_rate;
+        // Folded ISRs are accounted to the context they interrupted
+        let unfolded = |stack: &[ContextId]| -> Vec<ContextId> {
+            stack
+                .iter()
+                .filter(|ctx_id| !self.folded_contexts.contains_key(ctx_id))
+                .copied()
+                .collect()
+        };
+        let prev_context_stack = unfolded(prev_context_stack);
+        let context_stack = unfolded(&self.context_stack);
+
+        // The context on top of the stack is the one running
+        let prev_running = prev_context_stack.last();
+        let running = context_stack.last().copied();
+        if prev_running != running.as_ref() {
+            if let Some(ctx_id) = prev_running {
+                let s = self.context_stats_mut(ctx_id)?;
+                if let Some(since) = s.running_since.take() {
+                    s.run_time += now.saturating_sub(since);
+                }
+            }
+            if let Some(ctx_id) = running.as_ref() {
+                self.context_stats_mut(ctx_id)?.running_since = Some(now);
+            }
+        }
+
+        // Only pushes, pops and root switches happen, so the stacks differ
+        // where contexts were activated or deactivated
+        for i in 0..prev_context_stack.len().max(context_stack.len()) {
+            let (prev, cur) = (prev_context_stack.get(i), context_stack.get(i));
+            if prev == cur {
+                continue;
+            }
+            if let Some(ctx_id) = prev {
+                let s = self.context_stats_mut(ctx_id)?;
+                if let Some(activated_at) = s.activated_at.take() {
+                    let duration = now.saturating_sub(activated_at);
+                    s.max_duration = s.max_duration.max(duration);
+                    if *ctx_id == ev_ctx_id {
+                        ev.insert_attr(
+                            ev_attr_key("activation_duration"),
+                            ticks_to_duration(clock_rate, duration),
+                        );
+                    }
+                }
+            }
+            if let Some(ctx_id) = cur {
+                let s = self.context_stats_mut(ctx_id)?;
+                s.activations += 1;
+                s.activated_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+
+    fn context_stats_mut(&mut self, ctx_id: &ContextId) -> Result<&mut ContextStats, Error> {
+        self.contexts_to_timelines
+            .get_mut(ctx_id)
+            .map(|tl| &mut tl.stats)
+            .ok_or(Error::ContextManagerInternalState)
+    }
+
+    /// Adds an event with the run time statistics totals to each activated context timeline
+    fn add_context_stats_events(&mut self, events: &mut Vec<ContextEvent>) {
+        let clock_rate = self.cfg.clock_rate;
+        let now = self.last_raw_timestamp;
+        let elapsed = match (self.first_raw_timestamp, now) {
+            (Some(first), Some(now)) => now.saturating_sub(first),
+            _ => 0,
+        };
+        for (ctx_id, timeline) in self.contexts_to_timelines.iter_mut() {
+            let s = &timeline.stats;
+            if s.activations == 0 {
+                continue;
+            }
+            // Include the activation still in progress
+            let mut run_time = s.run_time;
+            let mut max_duration = s.max_duration;
+            if let Some(now) = now {
+                if let Some(since) = s.running_since {
+                    run_time += now.saturating_sub(since);
+                }
+                if let Some(activated_at) = s.activated_at {
+                    max_duration = max_duration.max(now.saturating_sub(activated_at));
+                }
+            }
+
+            let mut record = EventRecord::new(Default::default());
+            record.insert_attr(ev_attr_key("name"), Self::CONTEXT_STATS_EVENT);
+            record.insert_attr(ev_internal_attr_key("synthetic"), true);
+            record.insert_attr(ev_attr_key("activations"), s.activations);
+            record.insert_attr(
+                ev_attr_key("run_time"),
+                ticks_to_duration(clock_rate, run_time),
+            );
+            record.insert_attr(
+                ev_attr_key("max_duration"),
+                ticks_to_duration(clock_rate, max_duration),
+            );
+            if elapsed != 0 {
+                record.insert_attr(ev_attr_key("utilization"), run_time as f64 / elapsed as f64);
+            }
+            timeline.increment_nonce();
+            record.add_internal_nonce(timeline.nonce);
+
+            self.global_ordering = self.global_ordering.saturating_add(1);
+            events.push(ContextEvent {
+                context: *ctx_id,
+                global_ordering: self.global_ordering,
+                record,
+                add_previous_event_nonce: false,
+            });
+        }
+    }
+
+    fn process_pre_start_event(&mut self, mut ev: EventRecord) -> Result<ActiveContext, Error> {
+        self.pre_start_event_count += 1;
+        if self.pre_start_event_count == 1 {
+            debug!(policy = %self.cfg.pre_start_policy, "Received events before the start event");
+        }
+
+        let mut events = Vec::new();
+        match self.cfg.pre_start_policy {
+            PreStartPolicy::Hold => {
+                self.held_pre_start_events.push((self.global_ordering, ev));
+            }
+            PreStartPolicy::Drop => {
+                trace!("Dropping pre-start event");
+            }
+            PreStartPolicy::Timeline => {
+                let ctx_id = self.alloc_context(Self::PRE_START_CONTEXT);
+                self.pre_start_context = Some(ctx_id);
+                let timeline = self
+                    .contexts_to_timelines
+                    .get_mut(&ctx_id)
+                    .ok_or(Error::ContextManagerInternalState)?;
+                timeline.increment_nonce();
+                ev.add_internal_nonce(timeline.nonce);
+                events.push(ContextEvent {
+                    context: ctx_id,
+                    global_ordering: self.global_ordering,
+                    record: ev,
+                    add_previous_event_nonce: false,
+                });
+            }
+            PreStartPolicy::DisableRtosMode | PreStartPolicy::Resync => {
+                return Err(Error::ContextManagerInternalState)
+            }
+        }
+
+        Ok(ActiveContext { events })
+    }
+
+    fn release_held_pre_start_events(
+        &mut self,
+        ctx_id: ContextId,
+        events: &mut Vec<ContextEvent>,
+    ) -> Result<(), Error> {
+        let timeline = self
+            .contexts_to_timelines
+            .get_mut(&ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        for (global_ordering, mut record) in mem::take(&mut self.held_pre_start_events) {
+            timeline.increment_nonce();
+            record.add_internal_nonce(timeline.nonce);
+            events.push(ContextEvent {
+                context: ctx_id,
+                global_ordering,
+                record,
+                add_previous_event_nonce: false,
+            });
+        }
+        Ok(())
+    }
+
+    /// The context ID of the named context, scoped to the core when there is one
+    fn scoped_context_id(&self, ctx_name: &str) -> ContextId {
+        match self.cfg.core_id {
+            Some(core_id) => {
+                let mut h = DefaultHasher::new();
+                ctx_name.hash(&mut h);
+                core_id.hash(&mut h);
+                h.finish()
+            }
+            None => context_id(ctx_name),
+        }
+    }
+
+    fn alloc_unknown_context(&mut self) -> ContextId {
+        let ctx_name = self.cfg.unknown_context_name().to_owned();
+        self.alloc_context(&ctx_name)
+    }
+
+    /// The context of an event on the unknown context, split by the ISR it names when enabled
+    fn unknown_source_context(&mut self, ctx_id: ContextId, ev: &EventRecord) -> ContextId {
+        if !self.cfg.unknown_context_per_source
+            || ctx_id != self.scoped_context_id(self.cfg.unknown_context_name())
+        {
+            return ctx_id;
+        }
+        let Some(source) = ev.irq_name().or_else(|| ev.isr_name().map(str::to_owned)) else {
+            return ctx_id;
+        };
+        let ctx_name = format!("{}:{source}", self.cfg.unknown_context_name());
+        self.alloc_context(&ctx_name)
+    }
+
+    /// Adds an event to a context off the context stack, outside of the linearized causality
+    fn push_orphan_event(
+        &mut self,
+        ctx_id: ContextId,
+        mut ev: EventRecord,
+        mut events: Vec<ContextEvent>,
+    ) -> Result<ActiveContext, Error> {
+        let timeline = self
+            .contexts_to_timelines
+            .get_mut(&ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        timeline.increment_nonce();
+        ev.add_internal_nonce(timeline.nonce);
+        events.push(ContextEvent {
+            context: ctx_id,
+            global_ordering: self.global_ordering,
+            record: ev,
+            add_previous_event_nonce: false,
+        });
+        Ok(ActiveContext { events })
+    }
+
+    fn alloc_context(&mut self, ctx_name: &str) -> ContextId {
+        let ctx_id = self.scoped_context_id(ctx_name);
+        self.contexts_to_timelines.entry(ctx_id).or_insert_with(|| {
+            let mut tl_meta = TimelineMeta::new(ctx_name, ctx_id);
+            if let Some(v) = self.integration_version {
+                tl_meta.insert_attr(TimelineMeta::internal_attr_key("integration_version"), v);
+            }
+            tl_meta.insert_attr(
+                TimelineMeta::internal_attr_key("rtos_mode"),
+                self.cfg.rtos_mode.to_string(),
+            );
+            tl_meta.insert_attr(TimelineMeta::attr_key("boot_index"), self.boot_index);
+            if let Some(core_id) = self.cfg.core_id {
+                tl_meta.insert_attr(TimelineMeta::attr_key("core"), core_id);
+            }
+            for (k, v) in self.common_timeline_attrs.iter() {
+                tl_meta.insert_attr(k.clone(), v.clone());
+            }
+            if !self.data_loss.is_empty() {
+                tl_meta.insert_data_loss_attrs(&self.data_loss, self.data_loss_events);
+            }
+
+            tl_meta
+        });
+
+        ctx_id
+    }
+
+    /// Matches the event against the message correlation rules, recording it when it sends
+    /// a message, and returning the interaction from the oldest matching send event when
+    /// it receives one
+    fn correlate_message(
+        &mut self,
+        source: ContextSwitchInteraction,
+        ev: &mut EventRecord,
+    ) -> Option<ContextSwitchInteraction> {
+        if self.cfg.message_correlations.is_empty() {
+            return None;
+        }
+        let event_name = ev.event_name()?.to_owned();
+        let mut is_send = false;
+        let mut interaction = None;
+        for (idx, rule) in self.cfg.message_correlations.iter().enumerate() {
+            let Some(msg_key) = rule
+                .key
+                .as_deref()
+                .and_then(|k| ev.param(k))
+                .and_then(message_key)
+            else {
+                continue;
+            };
+            let key = (idx, msg_key);
+
+            if rule.send.as_ref().is_some_and(|g| g.is_match(&event_name)) {
+                if !self.pending_messages.contains_key(&key)
+                    && self.pending_messages.len() >= Self::MAX_PENDING_MESSAGE_KEYS
+                {
+                    warn!(
+                        max_keys = Self::MAX_PENDING_MESSAGE_KEYS,
+                        event_name, "Too many messages waiting for a receive event"
+                    );
+                    continue;
+                }
+                let queue = self.pending_messages.entry(key.clone()).or_default();
+                if queue.len() == Self::MAX_PENDING_SPAWNS {
+                    queue.pop_front();
+                }
+                queue.push_back(source);
+                is_send = true;
+            }
+
+            if interaction.is_none()
+                && rule
+                    .receive
+                    .as_ref()
+                    .is_some_and(|g| g.is_match(&event_name))
+            {
+                if let Some(queue) = self.pending_messages.get_mut(&key) {
+                    interaction = queue.pop_front();
+                    if queue.is_empty() {
+                        self.pending_messages.remove(&key);
+                    }
+                }
+                if interaction.is_none() {
+                    debug!(
+                        event_name,
+                        "Received a message without a matching send event"
+                    );
+                }
+            }
+        }
+
+        if is_send && !self.cfg.disable_interactions {
+            ev.promote_internal_nonce();
+        }
+        interaction
+    }
+
+    /// Returns the interaction from the oldest pending spawn event of the context
+    fn take_spawn_interaction(&mut self, ctx_id: ContextId) -> Option<ContextSwitchInteraction> {
+        let queue = self.pending_spawns.get_mut(&ctx_id)?;
+        let interaction = queue.pop_front();
+        if queue.is_empty() {
+            self.pending_spawns.remove(&ctx_id);
+        }
+        interaction
+    }
+
+    /// Records the priority of the entered context, and the context it preempted
+    fn track_priority(
+        &mut self,
+        ctx_id: ContextId,
+        priority: i64,
+        ev: &mut EventRecord,
+    ) -> Result<(), Error> {
+        let active_ctx_id = self.active_context()?;
+        let active_timeline = self
+            .contexts_to_timelines
+            .get(&active_ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        // The root context (init/idle) has the lowest priority, unless it says otherwise
+        let active_priority = active_timeline
+            .priority
+            .or((self.context_stack.len() == 1).then_some(0));
+        if ctx_id != active_ctx_id && active_priority.is_some_and(|p| priority > p) {
+            if let Some(name) = active_timeline.name() {
+                ev.insert_attr(ev_attr_key("preempted_context"), name.to_owned());
+            }
+        }
+
+        let timeline = self
+            .contexts_to_timelines
+            .get_mut(&ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        if timeline.priority != Some(priority) {
+            timeline.priority = Some(priority);
+            timeline.insert_attr(TimelineMeta::attr_key("priority"), priority);
+        }
+        Ok(())
+    }
+
+    /// The context on top of the stack, skipping the folded ISRs
+    fn active_context(&self) -> Result<ContextId, Error> {
+        self.context_stack
+            .iter()
+            .rev()
+            .find(|ctx_id| !self.folded_contexts.contains_key(ctx_id))
+            .copied()
+            .ok_or(Error::ContextManagerInternalState)
+    }
+
+    /// The name of the folded ISR on top of the stack
+    fn folded_isr_name(&self) -> Option<&str> {
+        let ctx_id = self.context_stack.last()?;
+        self.folded_contexts.get(ctx_id).map(String::as_str)
+    }
+
+    /// Folded ISR markers are events on the interrupted (host) context,
+    /// returns it with any pending interaction for it
+    fn host_event(
+        &mut self,
+        host_ctx_id: ContextId,
+    ) -> Result<(ContextId, Option<ContextSwitchInteraction>), Error> {
+        self.contexts_to_timelines
+            .get_mut(&host_ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?
+            .requires_synthetic_interaction_event = false;
+        Ok((host_ctx_id, self.pending_context_switch_interaction.take()))
+    }
+
+    /// Returns the interaction source from the previous context to be added
+    /// to the newly active context.
+    fn push_context(
+        &mut self,
+        ctx_id: ContextId,
+    ) -> Result<(RemoteContextId, RemoteTimelineId, RemoteInteractionNonce), Error> {
+        // Get the previous event's interaction source from the currently active context
+        let active_ctx_id = self.active_context()?;
+        let active_timeline = self
+            .contexts_to_timelines
+            .get_mut(&active_ctx_id)
+            .ok_or(Error::ContextManagerInternalState)?;
+        let interaction = active_timeline.interaction_source();
+
+        // Clear the synthetic event flag since we just got a new context
+        // to hang the interaction on
+        active_timeline.requires_synthetic_interaction_event = false;
+
+        // Set new context as active
+        self.context_stack.push(ctx_id);
+
+        trace!(ctx_id, size = self.context_stack.len(), "Push task");
+
+        Ok(interaction)
+    }
+
+    /// Returns Ok(None) when we're back on the root init/unknown context.
+    /// This can happen when we started mid-stream and we don't know which tasks we're in.
+    fn pop_context(
+        &mut self,
+    ) -> Result<Option<(RemoteContextId, RemoteTimelineId, RemoteInteractionNonce)>, Error> {
+        if self.context_stack.len() == 1 {
+            // We're back on the init/unknown context, the caller recovers from unbalanced markers
+            Ok(None)
+        } else {
+            // Pop the active context off the stack, previous context now active
+            let ctx_id = self
+                .context_stack
+                .pop()
+                .ok_or(Error::ContextManagerInternalState)?;
+
+            let timeline = self
+                .contexts_to_timelines
+                .get_mut(&ctx_id)
+                .ok_or(Error::ContextManagerInternalState)?;
+
+            // Clear the synthetic event flag since we just got a new context
+            // to hang the interaction on
+            timeline.requires_synthetic_interaction_event = false;
+
+            // Get the interaction source from the previously active context
+            let pending_interaction = timeline.next_interaction_source();
+
+            // Mark this context as needed a synthetic interaction event, gets
+            // cleared if it receives any events before another context switch.
+            // This keeps the causality linear.
             let active_ctx_id = self.active_context()?;
             let active_timeline = self
                 .contexts_to_timelines
                 .get_mut(&active_ctx_id)
                 .ok_or(Error::ContextManagerInternalState)?;
-            active_timeline.requires_synthetic_interaction_event = true;
+            active_timeline.requires_synthetic_interaction_event =
+                !self.cfg.disable_linear_causality;
 
             trace!(
                 active_ctx_id,
@@ -504,6 +1652,23 @@ impl ContextManager {
 type RemoteTimelineId = TimelineId;
 type RemoteInteractionNonce = i64;
 type InteractionNonce = i64;
+/// Converts ticks to nanoseconds when the clock rate is known
+fn ticks_to_duration(clock_rate: Option<Rate>, ticks: u64) -> u64 {
+    match clock_rate {
+        Some(clock_rate) => clock_rate * ticks,
+        None => ticks,
+    }
+}
+
+/// The correlation key of a message identifier parameter
+fn message_key(v: &AttrVal) -> Option<String> {
+    match v {
+        AttrVal::String(s) => Some(s.to_string()),
+        AttrVal::Integer(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
 type ContextSwitchInteraction = (RemoteContextId, RemoteTimelineId, RemoteInteractionNonce);
 
 pub type TimelineAttributes = BTreeMap<String, AttrVal>;
@@ -517,11 +1682,30 @@ pub struct TimelineMeta {
     /// Effectively a timeline-local event counter so we can draw arbitrary interactions
     nonce: InteractionNonce,
     requires_synthetic_interaction_event: bool,
+    /// The priority parameter of the context's enter events, when provided
+    priority: Option<i64>,
+    stats: ContextStats,
+    /// Bumped whenever the attributes change after the timeline was created
+    revision: u64,
+}
+
+/// Run time statistics of a context, in ticks
+#[derive(Clone, Debug, Default)]
+struct ContextStats {
+    activations: u64,
+    /// Time spent running, excluding the time preempted
+    run_time: u64,
+    /// Longest activation, including the time preempted
+    max_duration: u64,
+    /// Start of the activation in progress
+    activated_at: Option<u64>,
+    /// Set while the context is running, i.e. on top of the stack
+    running_since: Option<u64>,
 }
 
 impl TimelineMeta {
-    const ATTR_KEY_PREFIX: &'static str = "timeline.";
-    const INTERNAL_ATTR_KEY_PREFIX: &'static str = "timeline.internal.defmt.";
+    pub(crate) const ATTR_KEY_PREFIX: &'static str = "timeline.";
+    pub(crate) const INTERNAL_ATTR_KEY_PREFIX: &'static str = "timeline.internal.defmt.";
 
     pub(crate) fn attr_key(k: &str) -> String {
         format!("{}{k}", Self::ATTR_KEY_PREFIX)
@@ -541,6 +1725,9 @@ impl TimelineMeta {
             attributes: Default::default(),
             nonce: 0,
             requires_synthetic_interaction_event: false,
+            priority: None,
+            stats: Default::default(),
+            revision: 0,
         };
         tlm.insert_attr(Self::attr_key("name"), ctx_name);
         tlm.insert_attr(
@@ -555,6 +1742,13 @@ impl TimelineMeta {
         self.attributes.insert(k, v.into());
     }
 
+    fn insert_data_loss_attrs(&mut self, loss: &DataLoss, events: u64) {
+        self.insert_attr(Self::attr_key("data_loss.events"), events);
+        for (k, v) in loss.counters() {
+            self.insert_attr(Self::attr_key(&format!("data_loss.{k}")), v);
+        }
+    }
+
     fn increment_nonce(&mut self) {
         self.nonce = self.nonce.wrapping_add(1);
     }
@@ -573,6 +1767,18 @@ impl TimelineMeta {
         self.id
     }
 
+    /// Changes whenever the attributes need to be sent again
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self.attributes.get("timeline.name") {
+            Some(AttrVal::String(s)) => Some(s.as_ref()),
+            _ => None,
+        }
+    }
+
     pub fn attributes(&self) -> &TimelineAttributes {
         &self.attributes
     }
@@ -587,28 +1793,171 @@ fn context_id(ctx_name: &str) -> ContextId {
     h.finish()
 }
 
-fn ev_attr_key(k: &str) -> String {
+fn ev_attr_key(k: &str) -> AttrKey {
     EventRecord::attr_key(k)
 }
 
-fn ev_internal_attr_key(k: &str) -> String {
+fn ev_internal_attr_key(k: &str) -> AttrKey {
     EventRecord::internal_attr_key(k)
 }
 
-mod rtic1 {
-    pub const TRACE_START: &str = "AUXON_TRACE_START";
-    pub const TASK_ENTER: &str = "AUXON_TASK_ENTER";
+/// The RTOS instrumentation events that change the active context
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum RtosMarker {
+    /// The start event, with the init context name
+    Start(String),
+    /// A task, ISR or executor context was entered
+    Enter(String),
+    /// The active context was exited, or yielded
+    Exit,
+    /// The context at the bottom of the stack was switched out,
+    /// e.g. a thread switch or the idle context starting after init
+    Switch(String),
+    /// A context enter event without the context name parameter
+    MissingContextName,
+    /// The active context spawned (or scheduled) the named task
+    Spawn(String),
+}
+
+impl RtosMarker {
+    fn classify(cfg: &PluginConfig, ev: &EventRecord) -> Option<Self> {
+        let mode = cfg.rtos_mode;
+        let name = ev.event_name()?;
+        let enter = |ctx_name: Option<&str>| {
+            Some(match ctx_name {
+                Some(n) => RtosMarker::Enter(n.to_owned()),
+                None => RtosMarker::MissingContextName,
+            })
+        };
+        match (mode, name) {
+            (RtosMode::None, _) => None,
+            (RtosMode::BareMetal, bare_metal::IRQ_ENTER) => Some(
+                match ev.irq_name().or_else(|| ev.isr_name().map(str::to_owned)) {
+                    Some(n) => RtosMarker::Enter(n),
+                    None => RtosMarker::MissingContextName,
+                },
+            ),
+            (RtosMode::BareMetal, bare_metal::IRQ_EXIT) => Some(RtosMarker::Exit),
+            (RtosMode::BareMetal, _) => None,
+            (_, spawn::TASK_SPAWN | spawn::TASK_SCHEDULE) => {
+                let task_name = match mode {
+                    RtosMode::Custom => custom::task_name(&cfg.rtos_markers, ev),
+                    _ => ev.task_name(),
+                };
+                Some(RtosMarker::Spawn(task_name?.to_owned()))
+            }
+            (RtosMode::Custom, _) => {
+                let m = &cfg.rtos_markers;
+                let is = |role: &Option<String>, default: &str| {
+                    name == role.as_deref().unwrap_or(default)
+                };
+                let task_name = custom::task_name(m, ev);
+                let isr_name = custom::isr_name(m, ev);
+                if is(&m.trace_start, rtic1::TRACE_START) {
+                    Some(RtosMarker::Start(task_name?.to_owned()))
+                } else if is(&m.task_enter, rtic1::TASK_ENTER) {
+                    enter(task_name.or(isr_name))
+                } else if is(&m.isr_enter, rtic1::ISR_ENTER) {
+                    enter(isr_name.or(task_name))
+                } else if is(&m.task_exit, rtic1::TASK_EXIT) || is(&m.isr_exit, rtic1::ISR_EXIT) {
+                    Some(RtosMarker::Exit)
+                } else {
+                    None
+                }
+            }
+            (_, rtic1::TRACE_START) => Some(RtosMarker::Start(ev.task_name()?.to_owned())),
+            (RtosMode::Rtic1, rtic1::TASK_ENTER | rtic1::ISR_ENTER) => {
+                enter(ev.task_name().or_else(|| ev.isr_name()))
+            }
+            (RtosMode::Rtic1, rtic1::TASK_EXIT | rtic1::ISR_EXIT) => Some(RtosMarker::Exit),
+            (RtosMode::Rtic2, rtic2::ISR_ENTER) => enter(ev.isr_name()),
+            (RtosMode::Rtic2, rtic2::EXECUTOR_ENTER) => enter(ev.executor_name()),
+            (RtosMode::Rtic2, rtic2::TASK_POLL) => enter(ev.task_name()),
+            (
+                RtosMode::Rtic2,
+                rtic2::ISR_EXIT | rtic2::EXECUTOR_EXIT | rtic2::TASK_PENDING | rtic2::TASK_COMPLETE,
+            ) => Some(RtosMarker::Exit),
+            (RtosMode::CmsisRtos2, cmsis_rtos2::ISR_ENTER) => enter(ev.isr_name()),
+            (RtosMode::CmsisRtos2, cmsis_rtos2::ISR_EXIT) => Some(RtosMarker::Exit),
+            (RtosMode::CmsisRtos2, cmsis_rtos2::THREAD_SWITCH) => Some(match ev.thread_name() {
+                Some(n) => RtosMarker::Switch(n.to_owned()),
+                None => RtosMarker::MissingContextName,
+            }),
+            (RtosMode::Rtic2, rtic2::IDLE_ENTER) => {
+                let ctx_name = ev.task_name().unwrap_or(rtic2::DEFAULT_IDLE_NAME);
+                Some(RtosMarker::Switch(ctx_name.to_owned()))
+            }
+            _ => None,
+        }
+    }
+}
+
+mod rtic1 {
+    pub const TRACE_START: &str = "AUXON_TRACE_START";
+    pub const TASK_ENTER: &str = "AUXON_TASK_ENTER";
     pub const TASK_EXIT: &str = "AUXON_TASK_EXIT";
     pub const ISR_ENTER: &str = "AUXON_INTERRUPT_ENTER";
     pub const ISR_EXIT: &str = "AUXON_INTERRUPT_EXIT";
 }
 
+/// RTIC 2 hardware tasks are bound to interrupts and run to completion.
+/// Software tasks are async, they're polled by an executor (a dispatcher interrupt
+/// handler) and either complete or yield back to it when pending.
+mod rtic2 {
+    pub use super::rtic1::{ISR_ENTER, ISR_EXIT};
+    pub const EXECUTOR_ENTER: &str = "AUXON_EXECUTOR_ENTER";
+    pub const EXECUTOR_EXIT: &str = "AUXON_EXECUTOR_EXIT";
+    pub const TASK_POLL: &str = "AUXON_TASK_POLL";
+    pub const TASK_PENDING: &str = "AUXON_TASK_PENDING";
+    pub const TASK_COMPLETE: &str = "AUXON_TASK_COMPLETE";
+    pub const IDLE_ENTER: &str = "AUXON_IDLE_ENTER";
+    pub const DEFAULT_IDLE_NAME: &str = "idle";
+}
+
+/// Software tasks are spawned (or scheduled to run later) rather than called,
+/// these markers name the spawned task and are common to the RTOS modes with tasks
+mod spawn {
+    pub const TASK_SPAWN: &str = "AUXON_TASK_SPAWN";
+    pub const TASK_SCHEDULE: &str = "AUXON_TASK_SCHEDULE";
+}
+
+/// In-house instrumentation, the names come from the `rtos-markers` configuration
+mod custom {
+    use crate::{EventRecord, RtosMarkers};
+
+    pub fn task_name<'a>(m: &RtosMarkers, ev: &'a EventRecord) -> Option<&'a str> {
+        ev.str_param(m.task_name_key.as_deref().unwrap_or("task"))
+    }
+
+    pub fn isr_name<'a>(m: &RtosMarkers, ev: &'a EventRecord) -> Option<&'a str> {
+        ev.str_param(m.isr_name_key.as_deref().unwrap_or("isr"))
+    }
+}
+
+/// Super-loop firmware without tasks, only the (nesting) interrupts get their own contexts
+mod bare_metal {
+    pub const IRQ_ENTER: &str = "IRQ_ENTER";
+    pub const IRQ_EXIT: &str = "IRQ_EXIT";
+}
+
+/// CMSIS-RTOS2 (e.g. Keil RTX5) threads don't nest, the kernel switches between them,
+/// typically from its PendSV handler (the RTX `osRtxThreadSwitch` hook).
+/// ISRs nest on top of the running thread.
+mod cmsis_rtos2 {
+    pub use super::rtic1::{ISR_ENTER, ISR_EXIT};
+    pub const THREAD_SWITCH: &str = "AUXON_THREAD_SWITCH";
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::opts::RtosMode;
+    use crate::{
+        config::HumanTime, glob::Glob, opts::RtosMode, MessageCorrelation, Outcome, OutcomeRule,
+        RtosMarkers,
+    };
     use auxon_sdk::api::BigInt;
     use pretty_assertions::assert_eq;
+    use std::str::FromStr;
     use tracing_test::traced_test;
 
     fn trace_start(ts: u64) -> EventRecord {
@@ -764,4 +2113,1254 @@ mod test {
         // Synthetic event bumped global_ordering to 9
         check_ctx_event(&ctx.events[0], "task", 9, 4, true);
     }
+
+    fn rtic2_event(name: &str, param: Option<(&str, &str)>, ts: u64) -> EventRecord {
+        let mut ev = event(name, ts);
+        if let Some((k, v)) = param {
+            ev.insert_attr(EventRecord::attr_key(k), v);
+        }
+        ev
+    }
+
+    #[test]
+    fn rtic2_context_switching() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic2,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        mngr.process_record(trace_start(1)).unwrap();
+        check_mngr_state(&mut mngr, "init", 1);
+
+        let ctx = mngr
+            .process_record(rtic2_event(rtic2::IDLE_ENTER, None, 2))
+            .unwrap();
+        check_mngr_state(&mut mngr, "idle", 2);
+        check_ctx_event(&ctx.events[0], "idle", 2, 1, true);
+        assert_eq!(mngr.context_stack.len(), 1);
+
+        let ctx = mngr
+            .process_record(rtic2_event(
+                rtic2::EXECUTOR_ENTER,
+                Some(("executor", "dispatcher_p1")),
+                3,
+            ))
+            .unwrap();
+        check_mngr_state(&mut mngr, "dispatcher_p1", 3);
+        check_ctx_event(&ctx.events[0], "dispatcher_p1", 3, 1, true);
+
+        let ctx = mngr
+            .process_record(rtic2_event(rtic2::TASK_POLL, Some(("task", "blinky")), 4))
+            .unwrap();
+        check_mngr_state(&mut mngr, "blinky", 4);
+        check_ctx_event(&ctx.events[0], "blinky", 4, 1, true);
+
+        mngr.process_record(event("foo", 5)).unwrap();
+        check_mngr_state(&mut mngr, "blinky", 5);
+
+        // Pending, back to the executor
+        let ctx = mngr
+            .process_record(rtic2_event(rtic2::TASK_PENDING, None, 6))
+            .unwrap();
+        check_mngr_state(&mut mngr, "dispatcher_p1", 6);
+        check_ctx_event(&ctx.events[0], "blinky", 6, 3, false);
+
+        // Hardware task preempts the executor
+        let ctx = mngr
+            .process_record(rtic2_event(rtic2::ISR_ENTER, Some(("isr", "UART0")), 7))
+            .unwrap();
+        check_mngr_state(&mut mngr, "UART0", 7);
+        assert_eq!(ctx.events.len(), 2);
+        check_ctx_event(&ctx.events[0], "dispatcher_p1", 7, 2, true);
+        check_ctx_event(&ctx.events[1], "UART0", 8, 1, true);
+
+        mngr.process_record(rtic2_event(rtic2::ISR_EXIT, None, 8))
+            .unwrap();
+        check_mngr_state(&mut mngr, "dispatcher_p1", 8);
+
+        // Resumed, then completes
+        mngr.process_record(rtic2_event(rtic2::TASK_POLL, Some(("task", "blinky")), 9))
+            .unwrap();
+        check_mngr_state(&mut mngr, "blinky", 9);
+        mngr.process_record(rtic2_event(rtic2::TASK_COMPLETE, None, 10))
+            .unwrap();
+        mngr.process_record(rtic2_event(rtic2::EXECUTOR_EXIT, None, 11))
+            .unwrap();
+        check_mngr_state(&mut mngr, "idle", 11);
+        assert_eq!(mngr.cfg.rtos_mode, RtosMode::Rtic2);
+
+        let timeline = mngr.timeline_meta(context_id("blinky")).unwrap();
+        assert_eq!(
+            timeline
+                .attributes()
+                .get("timeline.internal.defmt.rtos_mode"),
+            Some(&AttrVal::from("rtic2"))
+        );
+
+        // Missing the executor name
+        mngr.process_record(rtic2_event(rtic2::EXECUTOR_ENTER, None, 12))
+            .unwrap();
+        check_mngr_state(&mut mngr, PluginConfig::DEFAULT_UNKNOWN_CONTEXT_NAME, 12);
+        assert_eq!(mngr.cfg.rtos_mode, RtosMode::None);
+    }
+
+    #[test]
+    fn cmsis_rtos2_thread_switching() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::CmsisRtos2,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let switch = |thread: &str, ts| {
+            rtic2_event(cmsis_rtos2::THREAD_SWITCH, Some(("thread", thread)), ts)
+        };
+
+        mngr.process_record(trace_start(1)).unwrap();
+        check_mngr_state(&mut mngr, "init", 1);
+
+        let ctx = mngr.process_record(switch("app_main", 2)).unwrap();
+        check_mngr_state(&mut mngr, "app_main", 2);
+        check_ctx_event(&ctx.events[0], "app_main", 2, 1, true);
+
+        let ctx = mngr.process_record(switch("worker", 3)).unwrap();
+        check_mngr_state(&mut mngr, "worker", 3);
+        check_ctx_event(&ctx.events[0], "worker", 3, 1, true);
+        assert_eq!(mngr.context_stack.len(), 1);
+
+        // Switching from within an ISR takes effect when it exits
+        mngr.process_record(rtic2_event(
+            cmsis_rtos2::ISR_ENTER,
+            Some(("isr", "SysTick")),
+            4,
+        ))
+        .unwrap();
+        check_mngr_state(&mut mngr, "SysTick", 4);
+        let ctx = mngr.process_record(switch("app_main", 5)).unwrap();
+        check_mngr_state(&mut mngr, "SysTick", 5);
+        check_ctx_event(&ctx.events[0], "SysTick", 5, 2, false);
+        mngr.process_record(rtic2_event(cmsis_rtos2::ISR_EXIT, None, 6))
+            .unwrap();
+        check_mngr_state(&mut mngr, "app_main", 6);
+
+        let ctx = mngr.process_record(event("foo", 7)).unwrap();
+        check_ctx_event(&ctx.events[0], "app_main", 7, 2, true);
+
+        // Switching to the running thread is a normal event
+        let ctx = mngr.process_record(switch("app_main", 8)).unwrap();
+        check_ctx_event(&ctx.events[0], "app_main", 8, 3, false);
+        assert_eq!(mngr.context_stack.len(), 1);
+    }
+
+    #[test]
+    fn bare_metal_irq_nesting() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::BareMetal,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        let ctx = mngr.process_record(event("loop", 1)).unwrap();
+        check_mngr_state(&mut mngr, "main", 1);
+        check_ctx_event(&ctx.events[0], "main", 1, 1, false);
+
+        let ctx = mngr
+            .process_record(rtic2_event(bare_metal::IRQ_ENTER, Some(("irq", "TIM2")), 2))
+            .unwrap();
+        check_mngr_state(&mut mngr, "TIM2", 2);
+        check_ctx_event(&ctx.events[0], "TIM2", 2, 1, true);
+
+        // Numbered and nested
+        let mut ev = event(bare_metal::IRQ_ENTER, 3);
+        ev.insert_attr(EventRecord::attr_key("irq"), 5_u64);
+        mngr.process_record(ev).unwrap();
+        check_mngr_state(&mut mngr, "IRQ5", 3);
+
+        mngr.process_record(event(bare_metal::IRQ_EXIT, 4)).unwrap();
+        check_mngr_state(&mut mngr, "TIM2", 4);
+        mngr.process_record(event(bare_metal::IRQ_EXIT, 5)).unwrap();
+        check_mngr_state(&mut mngr, "main", 5);
+
+        let ctx = mngr.process_record(event("loop", 6)).unwrap();
+        check_ctx_event(&ctx.events[0], "main", 6, 2, true);
+
+        // Start events have no meaning here
+        let ctx = mngr.process_record(trace_start(7)).unwrap();
+        check_mngr_state(&mut mngr, "main", 7);
+        check_ctx_event(&ctx.events[0], "main", 7, 3, false);
+        assert_eq!(mngr.context_stack.len(), 1);
+    }
+
+    #[test]
+    fn custom_rtos_markers() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Custom,
+            rtos_markers: RtosMarkers {
+                trace_start: "SCHED_START".to_owned().into(),
+                task_enter: "SCHED_IN".to_owned().into(),
+                task_exit: "SCHED_OUT".to_owned().into(),
+                task_name_key: "event.tcb".to_owned().into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        // No version parameter required
+        let ctx = mngr
+            .process_record(rtic2_event("SCHED_START", Some(("tcb", "boot")), 1))
+            .unwrap();
+        assert_eq!(mngr.integration_version, Some(0));
+        check_mngr_state(&mut mngr, "boot", 1);
+        check_ctx_event(&ctx.events[0], "boot", 1, 1, false);
+
+        let ctx = mngr
+            .process_record(rtic2_event("SCHED_IN", Some(("tcb", "net")), 2))
+            .unwrap();
+        check_mngr_state(&mut mngr, "net", 2);
+        check_ctx_event(&ctx.events[0], "net", 2, 1, true);
+
+        // Unset roles use the rtic1 names
+        mngr.process_record(isr_enter(3)).unwrap();
+        check_mngr_state(&mut mngr, "ISR", 3);
+        mngr.process_record(isr_exit(4)).unwrap();
+        check_mngr_state(&mut mngr, "net", 4);
+
+        // The rtic1 task events are normal events now
+        mngr.process_record(task_exit(5)).unwrap();
+        check_mngr_state(&mut mngr, "net", 5);
+        mngr.process_record(event("SCHED_OUT", 6)).unwrap();
+        check_mngr_state(&mut mngr, "boot", 6);
+        assert_eq!(mngr.cfg.rtos_mode, RtosMode::Custom);
+    }
+
+    #[test]
+    fn priority_preemption() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let with_priority = |mut ev: EventRecord, priority: u64| {
+            ev.insert_attr(EventRecord::attr_key("priority"), priority);
+            ev
+        };
+        let preempted = |ctx: &ActiveContext| {
+            ctx.events
+                .last()
+                .unwrap()
+                .record
+                .attributes()
+                .get("event.preempted_context")
+                .cloned()
+        };
+
+        mngr.process_record(trace_start(1)).unwrap();
+        let ctx = mngr
+            .process_record(with_priority(task_enter(2), 1))
+            .unwrap();
+        assert_eq!(preempted(&ctx), Some(AttrVal::from("init")));
+        let ctx = mngr.process_record(with_priority(isr_enter(3), 3)).unwrap();
+        assert_eq!(preempted(&ctx), Some(AttrVal::from("task")));
+        assert_eq!(
+            mngr.timeline_meta(context_id("ISR"))
+                .unwrap()
+                .attributes()
+                .get("timeline.priority"),
+            Some(&AttrVal::Integer(3))
+        );
+        mngr.process_record(isr_exit(4)).unwrap();
+        mngr.process_record(task_exit(5)).unwrap();
+
+        // Entering a lower priority context isn't preemption
+        mngr.process_record(with_priority(isr_enter(6), 3)).unwrap();
+        let ctx = mngr
+            .process_record(with_priority(task_enter(7), 1))
+            .unwrap();
+        assert_eq!(preempted(&ctx), None);
+    }
+
+    #[test]
+    fn spawn_interactions() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let remote_nonce = |ctx: &ActiveContext| {
+            ctx.events
+                .last()
+                .unwrap()
+                .record
+                .attributes()
+                .get("event.interaction.remote_nonce")
+                .cloned()
+        };
+
+        mngr.process_record(trace_start(1)).unwrap();
+        mngr.process_record(isr_enter(2)).unwrap();
+        let ctx = mngr
+            .process_record(rtic2_event(spawn::TASK_SPAWN, Some(("task", "task")), 3))
+            .unwrap();
+        check_mngr_state(&mut mngr, "ISR", 3);
+        assert_eq!(ctx.events[0].context, context_id("ISR"));
+        // The spawn event's nonce is public, it's the remote of a later interaction
+        assert_eq!(ctx.events[0].record.internal_nonce(), None);
+        assert_eq!(
+            ctx.events[0].record.attributes().get("event.nonce"),
+            Some(&AttrVal::Integer(2))
+        );
+        mngr.process_record(event("foo", 4)).unwrap();
+        mngr.process_record(isr_exit(5)).unwrap();
+
+        // The activation interacts with the spawn event, not the init context
+        let ctx = mngr.process_record(task_enter(6)).unwrap();
+        assert_eq!(ctx.events.len(), 2);
+        check_ctx_event(&ctx.events[1], "task", 7, 1, false);
+        assert_eq!(remote_nonce(&ctx), Some(AttrVal::Integer(2)));
+        assert_eq!(
+            ctx.events[1]
+                .record
+                .attributes()
+                .get("event.interaction.remote_timeline_id"),
+            Some(&AttrVal::from(
+                mngr.timeline_meta(context_id("ISR")).unwrap().id()
+            ))
+        );
+        mngr.process_record(task_exit(7)).unwrap();
+
+        // The next activation wasn't spawned, back to the stack-based interaction
+        let ctx = mngr.process_record(task_enter(8)).unwrap();
+        check_ctx_event(ctx.events.last().unwrap(), "task", 10, 3, true);
+    }
+
+    #[test]
+    fn rtic1_pre_start_hold() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            pre_start_policy: PreStartPolicy::Hold,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        let ctx = mngr.process_record(event("boot", 1)).unwrap();
+        assert!(ctx.events.is_empty());
+        let ctx = mngr.process_record(event("boot", 2)).unwrap();
+        assert!(ctx.events.is_empty());
+
+        let ctx = mngr.process_record(trace_start(3)).unwrap();
+        assert_eq!(mngr.integration_version, Some(1));
+        check_mngr_state(&mut mngr, "init", 3);
+        assert_eq!(ctx.events.len(), 3);
+        check_ctx_event(&ctx.events[0], "init", 1, 1, false);
+        check_ctx_event(&ctx.events[1], "init", 2, 2, false);
+        check_ctx_event(&ctx.events[2], "init", 3, 3, false);
+        assert_eq!(ctx.events[2].record.event_name(), Some(rtic1::TRACE_START));
+        assert!(mngr.flush().unwrap().events.is_empty());
+    }
+
+    #[test]
+    fn rtic1_pre_start_hold_flush() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            pre_start_policy: PreStartPolicy::Hold,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        let ctx = mngr.process_record(event("boot", 1)).unwrap();
+        assert!(ctx.events.is_empty());
+
+        let ctx = mngr.flush().unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(
+            &ctx.events[0],
+            PluginConfig::DEFAULT_UNKNOWN_CONTEXT_NAME,
+            1,
+            1,
+            false,
+        );
+    }
+
+    #[test]
+    fn rtic1_pre_start_resync() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            pre_start_policy: PreStartPolicy::Resync,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let unknown = PluginConfig::DEFAULT_UNKNOWN_CONTEXT_NAME;
+
+        let ctx = mngr.process_record(event("foo", 1)).unwrap();
+        check_mngr_state(&mut mngr, unknown, 1);
+        check_ctx_event(&ctx.events[0], unknown, 1, 1, false);
+
+        // Exit of a task we never saw entered
+        let ctx = mngr.process_record(task_exit(2)).unwrap();
+        check_mngr_state(&mut mngr, unknown, 2);
+        check_ctx_event(&ctx.events[0], unknown, 2, 2, false);
+
+        let ctx = mngr.process_record(isr_enter(3)).unwrap();
+        check_mngr_state(&mut mngr, "ISR", 3);
+        check_ctx_event(&ctx.events[0], "ISR", 3, 1, true);
+        let ctx = mngr.process_record(event("bar", 4)).unwrap();
+        check_ctx_event(&ctx.events[0], "ISR", 4, 2, false);
+        let ctx = mngr.process_record(isr_exit(5)).unwrap();
+        check_ctx_event(&ctx.events[0], "ISR", 5, 3, false);
+        let ctx = mngr.process_record(event("baz", 6)).unwrap();
+        check_mngr_state(&mut mngr, unknown, 6);
+        check_ctx_event(&ctx.events[0], unknown, 6, 3, true);
+
+        // A later start event resets the stack, linked to the unknown context
+        let ctx = mngr.process_record(trace_start(7)).unwrap();
+        assert_eq!(mngr.integration_version, Some(1));
+        assert_eq!(mngr.context_stack, vec![context_id("init")]);
+        check_ctx_event(&ctx.events[0], "init", 7, 1, true);
+        let ctx = mngr.process_record(task_enter(8)).unwrap();
+        check_mngr_state(&mut mngr, "task", 8);
+        check_ctx_event(&ctx.events[0], "task", 8, 1, true);
+        assert_eq!(mngr.cfg.rtos_mode, RtosMode::Rtic1);
+    }
+
+    #[test]
+    fn context_stack_recovery() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            max_context_depth: 3.into(),
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let check_reset_event = |ev: &ContextEvent, reason: &str, depth: i64| {
+            let attrs = ev.record.attributes();
+            assert_eq!(
+                attrs.get("event.name"),
+                Some(&AttrVal::from(ContextManager::CONTEXT_STACK_RESET_EVENT))
+            );
+            assert_eq!(attrs.get("event.reason"), Some(&AttrVal::from(reason)));
+            assert_eq!(attrs.get("event.depth"), Some(&AttrVal::Integer(depth)));
+        };
+
+        mngr.process_record(trace_start(1)).unwrap();
+        mngr.process_record(task_enter(2)).unwrap();
+        mngr.process_record(isr_enter(3)).unwrap();
+
+        // Missed the ISR exit
+        let ctx = mngr.process_record(task_enter(4)).unwrap();
+        check_mngr_state(&mut mngr, "task", 4);
+        assert_eq!(
+            mngr.context_stack,
+            vec![context_id("init"), context_id("task")]
+        );
+        assert_eq!(ctx.events.len(), 2);
+        check_ctx_event(&ctx.events[0], "init", 4, 2, true);
+        check_reset_event(&ctx.events[0], "overflow", 3);
+        check_ctx_event(&ctx.events[1], "task", 5, 2, true);
+
+        let ctx = mngr.process_record(task_exit(5)).unwrap();
+        check_ctx_event(&ctx.events[0], "task", 6, 3, false);
+
+        // Exit from the root context
+        let ctx = mngr.process_record(task_exit(6)).unwrap();
+        check_mngr_state(&mut mngr, "init", 6);
+        assert_eq!(ctx.events.len(), 2);
+        check_ctx_event(&ctx.events[0], "init", 7, 3, true);
+        check_reset_event(&ctx.events[0], "underflow", 1);
+        check_ctx_event(&ctx.events[1], "init", 8, 4, false);
+    }
+
+    #[test]
+    fn unknown_context_per_source() {
+        let irq_event = |name: &str, irq: i64, ts: u64| {
+            let mut ev = event(name, ts);
+            ev.insert_attr(EventRecord::attr_key("irq"), irq);
+            ev
+        };
+
+        // RTOS mode disabled for lack of a start event
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            unknown_context_name: "ORPHANS".to_owned().into(),
+            unknown_context_per_source: true,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let ctx = mngr.process_record(event("foo", 1)).unwrap();
+        assert_eq!(ctx.events[0].context, context_id("ORPHANS"));
+        let ctx = mngr.process_record(irq_event("bar", 5, 2)).unwrap();
+        check_ctx_event(&ctx.events[0], "ORPHANS:IRQ5", 2, 1, false);
+        let ctx = mngr.process_record(event("baz", 3)).unwrap();
+        check_mngr_state(&mut mngr, "ORPHANS", 3);
+        check_ctx_event(&ctx.events[0], "ORPHANS", 3, 1, false);
+
+        // Attached mid-stream, the orphan events are off the linearized causality
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            pre_start_policy: PreStartPolicy::Resync,
+            unknown_context_per_source: true,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let unknown = PluginConfig::DEFAULT_UNKNOWN_CONTEXT_NAME;
+        mngr.process_record(event("foo", 1)).unwrap();
+        let ctx = mngr.process_record(irq_event("bar", 7, 2)).unwrap();
+        check_ctx_event(&ctx.events[0], "UNKNOWN_CONTEXT:IRQ7", 2, 1, false);
+        let ctx = mngr.process_record(isr_enter(3)).unwrap();
+        check_ctx_event(&ctx.events[0], "ISR", 3, 1, true);
+        assert_eq!(
+            ctx.events[0]
+                .record
+                .attributes()
+                .get("event.interaction.remote_timeline_id"),
+            Some(&AttrVal::from(
+                mngr.timeline_meta(context_id(unknown)).unwrap().id()
+            ))
+        );
+        // Events in a known context aren't orphans
+        let ctx = mngr.process_record(irq_event("baz", 7, 4)).unwrap();
+        check_ctx_event(&ctx.events[0], "ISR", 4, 2, false);
+    }
+
+    #[test]
+    fn non_linear_causality() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            disable_linear_causality: true,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let remote_nonce = |ev: &ContextEvent| {
+            ev.record
+                .attributes()
+                .get("event.interaction.remote_nonce")
+                .cloned()
+        };
+
+        mngr.process_record(trace_start(1)).unwrap();
+        let ctx = mngr
+            .process_record(rtic2_event(spawn::TASK_SPAWN, Some(("task", "task")), 2))
+            .unwrap();
+        check_mngr_state(&mut mngr, "init", 2);
+        assert_eq!(ctx.events[0].record.internal_nonce(), None);
+
+        // Only the spawn interaction
+        let ctx = mngr.process_record(task_enter(3)).unwrap();
+        check_ctx_event(&ctx.events[0], "task", 3, 1, false);
+        assert_eq!(remote_nonce(&ctx.events[0]), Some(AttrVal::Integer(2)));
+        let ctx = mngr.process_record(task_exit(4)).unwrap();
+        check_ctx_event(&ctx.events[0], "task", 4, 2, false);
+
+        // No synthetic return event on init
+        let ctx = mngr.process_record(task_enter(5)).unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(&ctx.events[0], "task", 5, 3, false);
+        assert_eq!(remote_nonce(&ctx.events[0]), None);
+        mngr.process_record(task_exit(6)).unwrap();
+
+        let ctx = mngr.process_record(event("foo", 7)).unwrap();
+        check_mngr_state(&mut mngr, "init", 7);
+        check_ctx_event(&ctx.events[0], "init", 7, 3, false);
+        assert_eq!(remote_nonce(&ctx.events[0]), None);
+    }
+
+    #[test]
+    fn fold_isrs() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            fold_isrs: vec![Glob::new("IS?")],
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let isr_attr =
+            |ctx: &ActiveContext| ctx.events[0].record.attributes().get("event.isr").cloned();
+
+        mngr.process_record(trace_start(1)).unwrap();
+        mngr.process_record(task_enter(2)).unwrap();
+
+        // The ISR's events are on the task's timeline
+        let ctx = mngr.process_record(isr_enter(3)).unwrap();
+        check_mngr_state(&mut mngr, "task", 3);
+        check_ctx_event(&ctx.events[0], "task", 3, 2, false);
+        assert_eq!(isr_attr(&ctx), Some(AttrVal::from("ISR")));
+        let ctx = mngr.process_record(event("foo", 4)).unwrap();
+        check_ctx_event(&ctx.events[0], "task", 4, 3, false);
+        assert_eq!(isr_attr(&ctx), Some(AttrVal::from("ISR")));
+        let ctx = mngr.process_record(isr_exit(5)).unwrap();
+        check_ctx_event(&ctx.events[0], "task", 5, 4, false);
+        assert_eq!(isr_attr(&ctx), Some(AttrVal::from("ISR")));
+        assert!(mngr.timeline_meta(context_id("ISR")).is_err());
+
+        let ctx = mngr.process_record(event("bar", 6)).unwrap();
+        check_mngr_state(&mut mngr, "task", 6);
+        check_ctx_event(&ctx.events[0], "task", 6, 5, false);
+        assert_eq!(isr_attr(&ctx), None);
+
+        // Context switches around it are unchanged
+        mngr.process_record(task_exit(7)).unwrap();
+        let ctx = mngr.process_record(event("baz", 8)).unwrap();
+        check_mngr_state(&mut mngr, "init", 8);
+        check_ctx_event(&ctx.events[0], "init", 8, 2, true);
+    }
+
+    #[test]
+    fn rtic1_pre_start_drop() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            pre_start_policy: PreStartPolicy::Drop,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        let ctx = mngr.process_record(event("boot", 1)).unwrap();
+        assert!(ctx.events.is_empty());
+
+        let ctx = mngr.process_record(trace_start(2)).unwrap();
+        check_mngr_state(&mut mngr, "init", 2);
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(&ctx.events[0], "init", 2, 1, false);
+        assert!(mngr.flush().unwrap().events.is_empty());
+    }
+
+    #[test]
+    fn rtic1_pre_start_timeline() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            pre_start_policy: PreStartPolicy::Timeline,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        let ctx = mngr.process_record(event("boot", 1)).unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(
+            &ctx.events[0],
+            ContextManager::PRE_START_CONTEXT,
+            1,
+            1,
+            false,
+        );
+
+        let ctx = mngr.process_record(event("boot", 2)).unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(
+            &ctx.events[0],
+            ContextManager::PRE_START_CONTEXT,
+            2,
+            2,
+            false,
+        );
+
+        // Start event interacts with the last pre-start event
+        let ctx = mngr.process_record(trace_start(3)).unwrap();
+        check_mngr_state(&mut mngr, "init", 3);
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(&ctx.events[0], "init", 3, 1, true);
+        let pre_start_tid = mngr
+            .timeline_meta(context_id(ContextManager::PRE_START_CONTEXT))
+            .unwrap()
+            .id();
+        assert_eq!(
+            ctx.events[0]
+                .record
+                .attributes()
+                .get("event.interaction.remote_timeline_id"),
+            Some(&AttrVal::from(pre_start_tid))
+        );
+        assert_eq!(
+            ctx.events[0]
+                .record
+                .attributes()
+                .get("event.interaction.remote_nonce"),
+            Some(&AttrVal::Integer(2))
+        );
+    }
+
+    #[test]
+    fn message_correlations() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            message_correlations: vec![MessageCorrelation {
+                send: Glob::new("queue_send").into(),
+                receive: Glob::new("queue_recv").into(),
+                key: "msg_id".to_owned().into(),
+            }],
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let msg_event = |name: &str, msg_id: i64, ts: u64| {
+            let mut ev = event(name, ts);
+            ev.insert_attr(EventRecord::attr_key("msg_id"), msg_id);
+            ev
+        };
+        let attr = |ctx: &ActiveContext, key: &str| {
+            ctx.events
+                .last()
+                .unwrap()
+                .record
+                .attributes()
+                .get(key)
+                .cloned()
+        };
+
+        mngr.process_record(trace_start(1)).unwrap();
+        mngr.process_record(isr_enter(2)).unwrap();
+        let ctx = mngr.process_record(msg_event("queue_send", 7, 3)).unwrap();
+        assert_eq!(attr(&ctx, "event.nonce"), Some(AttrVal::Integer(2)));
+        let ctx = mngr.process_record(msg_event("queue_send", 8, 4)).unwrap();
+        assert_eq!(attr(&ctx, "event.nonce"), Some(AttrVal::Integer(3)));
+        mngr.process_record(isr_exit(5)).unwrap();
+        mngr.process_record(task_enter(6)).unwrap();
+
+        // Matched by key, not by order
+        let ctx = mngr.process_record(msg_event("queue_recv", 8, 7)).unwrap();
+        check_ctx_event(ctx.events.last().unwrap(), "task", 8, 2, false);
+        assert_eq!(
+            attr(&ctx, "event.interaction.remote_timeline_id"),
+            Some(AttrVal::from(
+                mngr.timeline_meta(context_id("ISR")).unwrap().id()
+            ))
+        );
+        assert_eq!(
+            attr(&ctx, "event.interaction.remote_nonce"),
+            Some(AttrVal::Integer(3))
+        );
+
+        // Each send is received once
+        let ctx = mngr.process_record(msg_event("queue_recv", 8, 8)).unwrap();
+        assert_eq!(attr(&ctx, "event.interaction.remote_nonce"), None);
+        let ctx = mngr.process_record(msg_event("queue_recv", 7, 9)).unwrap();
+        assert_eq!(
+            attr(&ctx, "event.interaction.remote_nonce"),
+            Some(AttrVal::Integer(2))
+        );
+        assert!(mngr.pending_messages.is_empty());
+    }
+
+    #[test]
+    fn outcome_rules() {
+        let cfg = PluginConfig {
+            outcome_rules: vec![
+                OutcomeRule {
+                    name: Glob::new("panic*").into(),
+                    outcome: Outcome::Failure.into(),
+                    severity: "critical".to_owned().into(),
+                    ..Default::default()
+                },
+                OutcomeRule {
+                    name: Glob::new("TEST_*").into(),
+                    level: "info".to_owned().into(),
+                    outcome: Outcome::Success.into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        let ctx = mngr.process_record(event("panicked", 1)).unwrap();
+        let attrs = ctx.events[0].record.attributes();
+        assert_eq!(attrs.get("event.outcome"), Some(&AttrVal::from("failure")));
+        assert_eq!(
+            attrs.get("event.severity"),
+            Some(&AttrVal::from("critical"))
+        );
+
+        // Level doesn't match
+        let ctx = mngr.process_record(event("TEST_PASS", 2)).unwrap();
+        assert_eq!(ctx.events[0].record.attributes().get("event.outcome"), None);
+
+        let mut ev = event("TEST_PASS", 3);
+        ev.insert_attr(EventRecord::attr_key("level"), "info");
+        let ctx = mngr.process_record(ev).unwrap();
+        let attrs = ctx.events[0].record.attributes();
+        assert_eq!(attrs.get("event.outcome"), Some(&AttrVal::from("success")));
+        assert_eq!(attrs.get("event.severity"), None);
+    }
+
+    #[test]
+    fn context_stats() {
+        let cfg = PluginConfig {
+            rtos_mode: RtosMode::Rtic1,
+            context_stats: true,
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let attr = |ev: &ContextEvent, key: &str| ev.record.attributes().get(key).cloned();
+
+        mngr.process_record(trace_start(1)).unwrap();
+        mngr.process_record(task_enter(2)).unwrap();
+        mngr.process_record(isr_enter(3)).unwrap();
+        let ctx = mngr.process_record(isr_exit(5)).unwrap();
+        let ev = ctx.events.last().unwrap();
+        assert_eq!(ev.context, context_id("ISR"));
+        assert_eq!(
+            attr(ev, "event.activation_duration"),
+            Some(AttrVal::Integer(2))
+        );
+        // Includes the time preempted by the ISR
+        let ctx = mngr.process_record(task_exit(8)).unwrap();
+        assert_eq!(
+            attr(ctx.events.last().unwrap(), "event.activation_duration"),
+            Some(AttrVal::Integer(6))
+        );
+        let ctx = mngr.process_record(event("foo", 10)).unwrap();
+        assert_eq!(
+            attr(ctx.events.last().unwrap(), "event.activation_duration"),
+            None
+        );
+
+        let ctx = mngr.flush().unwrap();
+        assert_eq!(ctx.events.len(), 3);
+        let stats = |name: &str| {
+            let ev = ctx
+                .events
+                .iter()
+                .find(|ev| ev.context == context_id(name))
+                .unwrap();
+            assert_eq!(
+                attr(ev, "event.name"),
+                Some(AttrVal::from(ContextManager::CONTEXT_STATS_EVENT))
+            );
+            (
+                attr(ev, "event.activations"),
+                attr(ev, "event.run_time"),
+                attr(ev, "event.max_duration"),
+                attr(ev, "event.utilization"),
+            )
+        };
+        assert_eq!(
+            stats("init"),
+            (
+                Some(AttrVal::Integer(1)),
+                Some(AttrVal::Integer(3)),
+                Some(AttrVal::Integer(9)),
+                Some(AttrVal::from(3.0 / 9.0)),
+            )
+        );
+        assert_eq!(
+            stats("task"),
+            (
+                Some(AttrVal::Integer(1)),
+                Some(AttrVal::Integer(4)),
+                Some(AttrVal::Integer(6)),
+                Some(AttrVal::from(4.0 / 9.0)),
+            )
+        );
+        assert_eq!(
+            stats("ISR"),
+            (
+                Some(AttrVal::Integer(1)),
+                Some(AttrVal::Integer(2)),
+                Some(AttrVal::Integer(2)),
+                Some(AttrVal::from(2.0 / 9.0)),
+            )
+        );
+    }
+
+    #[test]
+    fn per_core_contexts() {
+        let mut mngrs: Vec<ContextManager> = [0, 1]
+            .into_iter()
+            .map(|core_id| {
+                let cfg = PluginConfig {
+                    rtos_mode: RtosMode::Rtic1,
+                    core_id: Some(core_id),
+                    ..Default::default()
+                };
+                ContextManager::new(cfg, Default::default())
+            })
+            .collect();
+
+        let mut task_ctx_ids = Vec::new();
+        for (core_id, mngr) in mngrs.iter_mut().enumerate() {
+            mngr.process_record(trace_start(1)).unwrap();
+            let ctx = mngr.process_record(task_enter(2)).unwrap();
+            let ctx_id = ctx.events.last().unwrap().context;
+            let timeline = mngr.timeline_meta(ctx_id).unwrap();
+            assert_eq!(
+                timeline.attributes().get("timeline.core"),
+                Some(&AttrVal::Integer(core_id as i64))
+            );
+            assert_eq!(
+                timeline.attributes().get("timeline.name"),
+                Some(&AttrVal::from("task"))
+            );
+            task_ctx_ids.push(ctx_id);
+        }
+        assert_ne!(task_ctx_ids[0], task_ctx_ids[1]);
+        assert_ne!(task_ctx_ids[0], context_id("task"));
+    }
+
+    #[test]
+    fn segmentation_attrs() {
+        let cfg = PluginConfig {
+            boot_index: 3.into(),
+            phase_marker_event: "TEST_PHASE".to_owned().into(),
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+
+        let ctx = mngr.process_record(event("foo", 1)).unwrap();
+        assert_eq!(ctx.events[0].record.attributes().get("event.phase"), None);
+        let timeline = mngr.timeline_meta(ctx.events[0].context).unwrap();
+        assert_eq!(
+            timeline.attributes().get("timeline.boot_index"),
+            Some(&AttrVal::Integer(3))
+        );
+
+        let mut ev = event("TEST_PHASE", 2);
+        ev.insert_attr(EventRecord::attr_key("phase"), "setup");
+        let ctx = mngr.process_record(ev).unwrap();
+        assert_eq!(
+            ctx.events[0].record.attributes().get("event.phase"),
+            Some(&AttrVal::from("setup"))
+        );
+
+        let ctx = mngr.process_record(event("bar", 3)).unwrap();
+        assert_eq!(
+            ctx.events[0].record.attributes().get("event.phase"),
+            Some(&AttrVal::from("setup"))
+        );
+    }
+
+    #[traced_test]
+    #[test]
+    fn timestamp_width() {
+        let cfg = PluginConfig {
+            clock_rate: Rate::new(1, 1_000_000),
+            timestamp_width: 24.into(),
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let max24: u32 = (1 << 24) - 1;
+
+        let ev = |ts: u32| {
+            EventRecord::from_iter(
+                Timestamp::Ticks32(ts).into(),
+                vec![(EventRecord::attr_key("name"), "foo".into())],
+            )
+        };
+        let check = |ctx: &ActiveContext, raw: u32, ticks: u64| {
+            let attrs = ctx.events[0].record.attributes();
+            assert_eq!(
+                attrs.get("event.internal.defmt.timestamp.raw"),
+                Some(&AttrVal::from(u64::from(raw)))
+            );
+            assert_eq!(
+                attrs.get("event.internal.defmt.timestamp"),
+                Some(&AttrVal::from(ticks))
+            );
+            assert_eq!(
+                attrs.get("event.timestamp"),
+                Some(&AttrVal::Timestamp((ticks * 1_000).into()))
+            );
+        };
+
+        let ctx = mngr.process_record(ev(max24 - 5)).unwrap();
+        check(&ctx, max24 - 5, u64::from(max24 - 5));
+
+        // Rolls over at 24 bits, not 32
+        let ctx = mngr.process_record(ev(10)).unwrap();
+        check(&ctx, 10, u64::from(max24) + 11);
+        assert!(!logs_contain("went backwards"));
+
+        let ctx = mngr.process_record(ev(20)).unwrap();
+        check(&ctx, 20, u64::from(max24) + 21);
+    }
+
+    #[traced_test]
+    #[test]
+    fn backwards_timestamp_policies() {
+        let ticks = |ctx: &ActiveContext| {
+            ctx.events[0]
+                .record
+                .attributes()
+                .get("event.internal.defmt.timestamp")
+                .cloned()
+        };
+        let mngr = |policy| {
+            let cfg = PluginConfig {
+                backwards_timestamp_policy: policy,
+                ..Default::default()
+            };
+            ContextManager::new(cfg, Default::default())
+        };
+
+        let mut m = mngr(BackwardsTimestampPolicy::Warn);
+        m.process_record(event("a", 10)).unwrap();
+        let ctx = m.process_record(event("b", 5)).unwrap();
+        assert_eq!(ticks(&ctx), Some(BigInt::new_attr_val(5)));
+        assert!(logs_contain("went backwards"));
+
+        let mut m = mngr(BackwardsTimestampPolicy::Clamp);
+        m.process_record(event("a", 10)).unwrap();
+        let ctx = m.process_record(event("b", 5)).unwrap();
+        assert_eq!(ticks(&ctx), Some(AttrVal::from(10_u64)));
+        let ctx = m.process_record(event("c", 12)).unwrap();
+        assert_eq!(ticks(&ctx), Some(BigInt::new_attr_val(12)));
+
+        let mut m = mngr(BackwardsTimestampPolicy::Rollover);
+        m.process_record(event("a", 10)).unwrap();
+        let ctx = m.process_record(event("b", 5)).unwrap();
+        assert_eq!(ticks(&ctx), Some(AttrVal::from(10_u64)));
+        let ctx = m.process_record(event("c", 7)).unwrap();
+        assert_eq!(ticks(&ctx), Some(AttrVal::from(12_u64)));
+        assert_eq!(m.last_raw_timestamp, Some(12));
+
+        let mut m = mngr(BackwardsTimestampPolicy::Drop);
+        m.process_record(event("a", 10)).unwrap();
+        let ctx = m.process_record(event("b", 5)).unwrap();
+        assert!(ctx.events.is_empty());
+        let ctx = m.process_record(event("c", 12)).unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        assert_eq!(m.last_raw_timestamp, Some(12));
+    }
+
+    #[traced_test]
+    #[test]
+    fn timestamp_gaps() {
+        let cfg = PluginConfig {
+            clock_rate: Rate::new(1, 1_000),
+            timestamp_gap_threshold: HumanTime::from_str("10ms").unwrap().into(),
+            ..Default::default()
+        };
+        let mut mngr = ContextManager::new(cfg, Default::default());
+        let gap = |ctx: &ActiveContext| {
+            ctx.events[0]
+                .record
+                .attributes()
+                .get("event.timestamp_gap")
+                .cloned()
+        };
+
+        let ctx = mngr.process_record(event("a", 1)).unwrap();
+        assert_eq!(gap(&ctx), None);
+        let ctx = mngr.process_record(event("b", 11)).unwrap();
+        assert_eq!(gap(&ctx), None);
+        let ctx = mngr.process_record(event("c", 26)).unwrap();
+        assert_eq!(gap(&ctx), Some(AttrVal::from(15_000_000_u64)));
+        assert!(logs_contain("Timestamp gap between events"));
+        let ctx = mngr.process_record(event("d", 27)).unwrap();
+        assert_eq!(gap(&ctx), None);
+    }
+
+    #[test]
+    fn data_loss() {
+        let mut mngr = ContextManager::new(PluginConfig::default(), Default::default());
+        let loss = DataLoss {
+            malformed_frames: 2,
+            ..Default::default()
+        };
+        let timeline_attr = |mngr: &ContextManager, key: &str| {
+            mngr.timeline_meta(context_id("main"))
+                .unwrap()
+                .attributes()
+                .get(key)
+                .cloned()
+        };
+
+        // Nothing to mark before the first event
+        let ctx = mngr.data_loss(loss).unwrap();
+        assert!(ctx.events.is_empty());
+
+        mngr.process_record(event("a", 1)).unwrap();
+        assert_eq!(
+            timeline_attr(&mngr, "timeline.data_loss.malformed_frames"),
+            Some(AttrVal::Integer(2))
+        );
+        assert_eq!(
+            timeline_attr(&mngr, "timeline.data_loss.events"),
+            Some(AttrVal::Integer(0))
+        );
+        let revision = mngr.timeline_meta(context_id("main")).unwrap().revision();
+
+        let ctx = mngr.data_loss(Default::default()).unwrap();
+        assert!(ctx.events.is_empty());
+
+        let ctx = mngr
+            .data_loss(DataLoss {
+                overflows: 1,
+                ..loss
+            })
+            .unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(&ctx.events[0], "main", 2, 2, false);
+        let attrs = ctx.events[0].record.attributes();
+        assert_eq!(
+            attrs.get("event.name"),
+            Some(&AttrVal::from(ContextManager::DATA_LOSS_EVENT))
+        );
+        assert_eq!(
+            attrs.get("event.malformed_frames"),
+            Some(&AttrVal::Integer(2))
+        );
+        assert_eq!(attrs.get("event.overflows"), Some(&AttrVal::Integer(1)));
+        assert_eq!(attrs.get("event.dropped_frames"), None);
+
+        assert_eq!(
+            timeline_attr(&mngr, "timeline.data_loss.malformed_frames"),
+            Some(AttrVal::Integer(4))
+        );
+        assert_eq!(
+            timeline_attr(&mngr, "timeline.data_loss.overflows"),
+            Some(AttrVal::Integer(1))
+        );
+        assert_eq!(
+            timeline_attr(&mngr, "timeline.data_loss.events"),
+            Some(AttrVal::Integer(1))
+        );
+        assert_ne!(
+            mngr.timeline_meta(context_id("main")).unwrap().revision(),
+            revision
+        );
+
+        let ctx = mngr.process_record(event("b", 2)).unwrap();
+        check_ctx_event(&ctx.events[0], "main", 3, 3, false);
+    }
+
+    #[test]
+    fn crash_state() {
+        let mut mngr = ContextManager::new(PluginConfig::default(), Default::default());
+        let state = CrashState {
+            registers: vec![
+                ("PC".to_owned(), 0x0800_1234),
+                ("SP".to_owned(), 0x2000_0ff0),
+            ],
+            stack: MemoryDump {
+                location: "SP".to_owned(),
+                address: 0x2000_0ff0,
+                data: (0..20).collect(),
+            }
+            .into(),
+            memory: vec![MemoryDump {
+                location: "ERROR_CODE".to_owned(),
+                address: 0x2000_0000,
+                data: vec![0xde, 0xad],
+            }],
+        };
+
+        // Nothing to attach it to before the first event
+        let ctx = mngr.crash_state(None, state.clone()).unwrap();
+        assert!(ctx.events.is_empty());
+
+        mngr.process_record(event("panic", 1)).unwrap();
+        let ctx = mngr.crash_state(Some("panic"), state).unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(&ctx.events[0], "main", 2, 2, false);
+        let attrs = ctx.events[0].record.attributes();
+        assert_eq!(
+            attrs.get("event.name"),
+            Some(&AttrVal::from(ContextManager::CRASH_STATE_EVENT))
+        );
+        assert_eq!(
+            attrs.get("event.crash.trigger"),
+            Some(&AttrVal::from("panic"))
+        );
+        assert_eq!(
+            attrs.get("event.crash.registers"),
+            Some(&AttrVal::from("PC=0x08001234 SP=0x20000ff0"))
+        );
+        assert_eq!(
+            attrs.get("event.crash.register.pc"),
+            Some(&AttrVal::Integer(0x0800_1234))
+        );
+        assert_eq!(
+            attrs.get("event.crash.stack"),
+            Some(&AttrVal::from(
+                "0x20000ff0: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n0x20001000: 10 11 12 13"
+            ))
+        );
+        assert_eq!(
+            attrs.get("event.crash.memory.0.location"),
+            Some(&AttrVal::from("ERROR_CODE"))
+        );
+        assert_eq!(
+            attrs.get("event.crash.memory.0.dump"),
+            Some(&AttrVal::from("0x20000000: de ad"))
+        );
+    }
+
+    #[test]
+    fn fault() {
+        let mut mngr = ContextManager::new(PluginConfig::default(), Default::default());
+        let fault = FaultState {
+            pc: Some(0x0800_1234),
+            cfsr: (1 << 9) | (1 << 15),
+            hfsr: 1 << 30,
+            mmfar: 0,
+            bfar: 0x3000_0000,
+            halted: true,
+        };
+
+        // Faulting before the first event starts the initial timeline
+        let ctx = mngr.fault(&fault).unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(&ctx.events[0], "main", 1, 1, false);
+
+        mngr.process_record(event("a", 1)).unwrap();
+        let ctx = mngr.fault(&fault).unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(&ctx.events[0], "main", 3, 3, false);
+        let attrs = ctx.events[0].record.attributes();
+        assert_eq!(
+            attrs.get("event.name"),
+            Some(&AttrVal::from(ContextManager::FAULT_EVENT))
+        );
+        assert_eq!(
+            attrs.get("event.fault.kind"),
+            Some(&AttrVal::from("BusFault"))
+        );
+        assert_eq!(
+            attrs.get("event.fault.flags"),
+            Some(&AttrVal::from("FORCED PRECISERR"))
+        );
+        assert_eq!(
+            attrs.get("event.fault.bfar"),
+            Some(&AttrVal::Integer(0x3000_0000))
+        );
+        assert_eq!(attrs.get("event.fault.mmfar"), None);
+        assert_eq!(
+            attrs.get("event.fault.pc"),
+            Some(&AttrVal::Integer(0x0800_1234))
+        );
+        assert_eq!(attrs.get("event.fault.halted"), Some(&AttrVal::from(true)));
+    }
+
+    #[test]
+    fn metrics() {
+        let mut mngr = ContextManager::new(PluginConfig::default(), Default::default());
+        let report = MetricsReport {
+            window: std::time::Duration::from_secs(2),
+            bytes_read: 2048,
+            frames: 10,
+            events: 8,
+            malformed_frames: 1,
+            ingest_pending: Some(3),
+        };
+
+        let ctx = mngr.metrics(&report).unwrap();
+        assert!(ctx.events.is_empty());
+
+        mngr.process_record(event("a", 1)).unwrap();
+        let ctx = mngr.metrics(&report).unwrap();
+        assert_eq!(ctx.events.len(), 1);
+        check_ctx_event(&ctx.events[0], "main", 2, 2, false);
+        let attrs = ctx.events[0].record.attributes();
+        assert_eq!(
+            attrs.get("event.name"),
+            Some(&AttrVal::from(ContextManager::METRICS_EVENT))
+        );
+        assert_eq!(
+            attrs.get("event.metrics.bytes_per_sec"),
+            Some(&AttrVal::from(1024.0))
+        );
+        assert_eq!(
+            attrs.get("event.metrics.frames"),
+            Some(&AttrVal::Integer(10))
+        );
+        assert_eq!(
+            attrs.get("event.metrics.ingest_pending"),
+            Some(&AttrVal::Integer(3))
+        );
+    }
 }
@@ -0,0 +1,155 @@
+//! Separate debug files of stripped ELF files, e.g. from `objcopy --only-keep-debug`,
+//! found through the `.gnu_debuglink` section.
+
+use crate::framing::crc32;
+use goblin::elf::Elf;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// The `.gnu_debuglink` section of an ELF file, naming its separate debug file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugLink {
+    /// The file name of the debug file
+    pub file_name: String,
+    /// CRC-32 of the debug file contents
+    pub crc: u32,
+}
+
+impl DebugLink {
+    pub const SECTION_NAME: &'static str = ".gnu_debuglink";
+
+    /// The debug link of an ELF file, when it has one
+    pub fn from_elf(elf_contents: &[u8]) -> Option<Self> {
+        let elf = Elf::parse(elf_contents).ok()?;
+        let section = elf
+            .section_headers
+            .iter()
+            .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(Self::SECTION_NAME))?;
+        let start = usize::try_from(section.sh_offset).ok()?;
+        let end = start.checked_add(usize::try_from(section.sh_size).ok()?)?;
+        Self::from_section(elf_contents.get(start..end)?, elf.little_endian)
+    }
+
+    /// Parse the section contents: the NUL-terminated file name, padded to a 4 byte
+    /// boundary, followed by the CRC
+    pub fn from_section(data: &[u8], little_endian: bool) -> Option<Self> {
+        let name_len = data.iter().position(|b| *b == 0)?;
+        let file_name = std::str::from_utf8(&data[..name_len]).ok()?.to_owned();
+        if file_name.is_empty() {
+            return None;
+        }
+        let crc_offset = (name_len + 1).next_multiple_of(4);
+        let crc: [u8; 4] = data.get(crc_offset..crc_offset + 4)?.try_into().ok()?;
+        let crc = if little_endian {
+            u32::from_le_bytes(crc)
+        } else {
+            u32::from_be_bytes(crc)
+        };
+        Some(Self { file_name, crc })
+    }
+
+    /// The paths the debug file is looked up at, in GDB's order: next to the ELF file,
+    /// in its `.debug` directory, then in the global `/usr/lib/debug` directory
+    pub fn candidates(&self, elf_file: &Path) -> Vec<PathBuf> {
+        let dir = elf_file.parent().unwrap_or(Path::new(""));
+        let mut paths = vec![
+            dir.join(&self.file_name),
+            dir.join(".debug").join(&self.file_name),
+        ];
+        if let Ok(abs_dir) = dir.canonicalize() {
+            if let Ok(rel_dir) = abs_dir.strip_prefix("/") {
+                paths.push(
+                    Path::new("/usr/lib/debug")
+                        .join(rel_dir)
+                        .join(&self.file_name),
+                );
+            }
+        }
+        paths
+    }
+
+    /// Find and read the debug file of the ELF file. Candidates with a mismatched CRC,
+    /// like the stripped ELF file itself, are skipped.
+    pub fn find(&self, elf_file: &Path) -> Option<(PathBuf, Vec<u8>)> {
+        self.candidates(elf_file).into_iter().find_map(|path| {
+            let contents = fs::read(&path).ok()?;
+            if crc32(&contents) != self.crc {
+                debug!(path = %path.display(), "Skipping debug file with a mismatched CRC");
+                return None;
+            }
+            Some((path, contents))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn section(name: &str, crc: u32) -> Vec<u8> {
+        let mut data = name.as_bytes().to_vec();
+        data.push(0);
+        data.resize(data.len().next_multiple_of(4), 0);
+        data.extend_from_slice(&crc.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn parse_section() {
+        assert_eq!(
+            DebugLink::from_section(&section("fw.debug", 0x1234_5678), true),
+            Some(DebugLink {
+                file_name: "fw.debug".to_owned(),
+                crc: 0x1234_5678
+            })
+        );
+        assert_eq!(
+            DebugLink::from_section(&section("fw", 0xAABB_CCDD), true)
+                .unwrap()
+                .crc,
+            0xAABB_CCDD
+        );
+        assert_eq!(
+            DebugLink::from_section(&section("fw.dbg", 0xAABB_CCDD), false)
+                .unwrap()
+                .crc,
+            0xDDCC_BBAA
+        );
+        assert_eq!(DebugLink::from_section(b"fw.debug", true), None);
+        assert_eq!(DebugLink::from_section(&section("", 0), true), None);
+        assert_eq!(DebugLink::from_section(b"fw.debug\0\0\0\0\0", true), None);
+    }
+
+    #[test]
+    fn find_debug_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let elf_file = dir.path().join("fw");
+        fs::write(&elf_file, b"stripped").unwrap();
+        fs::create_dir(dir.path().join(".debug")).unwrap();
+        let debug_file = dir.path().join(".debug").join("fw.debug");
+        fs::write(&debug_file, b"debug info").unwrap();
+
+        let link = DebugLink {
+            file_name: "fw.debug".to_owned(),
+            crc: crc32(b"debug info"),
+        };
+        assert_eq!(
+            link.find(&elf_file),
+            Some((debug_file.clone(), b"debug info".to_vec()))
+        );
+
+        // A stale debug file next to the ELF file doesn't shadow the matching one
+        fs::write(dir.path().join("fw.debug"), b"stale debug info").unwrap();
+        assert_eq!(link.find(&elf_file).map(|(p, _)| p), Some(debug_file));
+
+        let link = DebugLink {
+            file_name: "fw".to_owned(),
+            crc: crc32(b"debug info") ^ 1,
+        };
+        assert_eq!(link.find(&elf_file), None);
+    }
+}
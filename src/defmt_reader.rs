@@ -1,29 +1,672 @@
 use crate::{
-    Client, ContextEvent, ContextManager, DefmtConfig, Error, EventRecord, Interruptor,
-    TimelineAttributes, TimelineMeta,
+    debuglink::DebugLink, elf_watch::ElfWatcher, promotion::AttrPromoter, rename::AttrRenamer,
+    ActiveContext, AttrKey, Client, ContextEvent, ContextManager, CrashState, DefmtConfig, Error,
+    EventAttributes, EventRecord, FaultState, FramePlan, FramedReader, Framing, IngestSummary,
+    Interruptor, LineSplitter, LogicCaptureReader, PluginConfig, RawCaptureWriter, RawFrameTracker,
+    ReaderMetrics, RebootDetector, RebootReason, RtosMode, RunSummary, TimelineAttributes,
+    TimelineMeta, WatchSample,
 };
-use auxon_sdk::ingest_client::IngestClient;
-use defmt_decoder::{DecodeError, Table};
-use std::collections::{BTreeMap, BTreeSet};
-use std::{fs, io::Read, time::Duration};
-use tracing::{debug, warn};
+use auxon_sdk::{
+    api::{AttrVal, TimelineId},
+    ingest_client::IngestClient,
+};
+use defmt_decoder::{DecodeError, Locations, Table};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+use std::{
+    fs,
+    io::{self, BufRead, Read},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 pub const PLUGIN_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub async fn run<R: Read + Send>(
+/// How a read loop that didn't fail ended
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReadOutcome {
+    /// The stream ended, or the reader was interrupted
+    Finished,
+    /// An event matched one of the stop triggers, or a fault left the core halted
+    Stopped,
+}
+
+pub async fn run<R: Read + Send>(r: R, cfg: DefmtConfig, intr: Interruptor) -> Result<(), Error> {
+    run_reader(r, cfg, intr, |_| None, |_| 0, |_, _| None, |_| None)
+        .await
+        .map(|_| ())
+}
+
+/// Like [`run`], additionally recording the transport's cumulative buffer overflow count
+/// as data loss, adding a crash event when `crash_state` captures the target state
+/// for an event, adding a fault event for each fault `faults` reports,
+/// and reporting whether a stop trigger or a halting fault ended the read loop
+pub async fn run_with_overflows<
+    R: Read + Send,
+    F: Fn(&R) -> u64 + Send,
+    C: Fn(&R, &EventRecord) -> Option<CrashState> + Send,
+    X: Fn(&R) -> Option<FaultState> + Send,
+>(
+    r: R,
+    cfg: DefmtConfig,
+    intr: Interruptor,
+    overflows: F,
+    crash_state: C,
+    faults: X,
+) -> Result<ReadOutcome, Error> {
+    run_reader(r, cfg, intr, |_| None, overflows, crash_state, faults).await
+}
+
+/// Like [`run`], additionally using the capture time of each decoded byte for event timing
+pub async fn run_logic_capture<R: BufRead + Send>(
+    r: LogicCaptureReader<R>,
+    cfg: DefmtConfig,
+    intr: Interruptor,
+) -> Result<(), Error> {
+    run_reader(
+        r,
+        cfg,
+        intr,
+        |r| r.capture_time_ns(),
+        |_| 0,
+        |_, _| None,
+        |_| None,
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Reads lines of plain text (e.g. a legacy printf channel) instead of defmt frames,
+/// ingesting each line as an event named after the line on a single timeline
+pub async fn run_text<R: Read + Send>(
     mut r: R,
     cfg: DefmtConfig,
     intr: Interruptor,
 ) -> Result<(), Error> {
-    let elf_file = cfg
-        .plugin
-        .elf_file
-        .as_ref()
-        .ok_or(Error::MissingElfFile)?
-        .clone();
+    let (common_timeline_attrs, _) = common_timeline_attrs(&cfg);
+    let mut sender = EventSender::connect(&cfg).await?;
+
+    // There are no context markers in the text
+    let mut plugin_cfg = cfg.plugin.clone();
+    plugin_cfg.rtos_mode = RtosMode::None;
+    let mut ctx_mngr = ContextManager::new(plugin_cfg, common_timeline_attrs);
+
+    let mut lines = LineSplitter::default();
+    let mut buffer = vec![0_u8; cfg.plugin.read_buffer_size()];
+    let mut maybe_read_result: Option<Result<(), Error>> = None;
+    debug!("Starting text read loop");
+    while !intr.is_set() {
+        let bytes_read = match r.read(&mut buffer) {
+            Ok(b) => b,
+            Err(e) => {
+                maybe_read_result = Some(Err(e.into()));
+                break;
+            }
+        };
+        if bytes_read == 0 {
+            // EOF
+            break;
+        }
+        let host_received_ns = host_time_ns();
+        for line in lines.received(&buffer[..bytes_read]) {
+            let record = text_record(line, host_received_ns, &cfg.plugin);
+            let ctx = ctx_mngr.process_record(record)?;
+            sender.send(ctx, &ctx_mngr).await?;
+        }
+    }
+    if let Some(line) = lines.finish() {
+        let record = text_record(line, host_time_ns(), &cfg.plugin);
+        let ctx = ctx_mngr.process_record(record)?;
+        sender.send(ctx, &ctx_mngr).await?;
+    }
+
+    let ctx = ctx_mngr.flush()?;
+    sender.send(ctx, &ctx_mngr).await?;
+    sender.flush(&ctx_mngr).await?;
+
+    maybe_read_result.unwrap_or(Ok(()))
+}
+
+fn text_record(line: String, host_received_ns: u64, cfg: &PluginConfig) -> EventRecord {
+    let mut record = EventRecord::new(Default::default());
+    record.insert_attr(EventRecord::attr_key("name"), line);
+    record.set_internal_host_received(host_received_ns);
+    if cfg.host_time_fallback {
+        record.set_timestamp(host_received_ns.into());
+    }
+    record
+}
+
+/// The timeline name of the sampled memory values
+pub const WATCH_TIMELINE_NAME: &str = "metrics";
+
+/// How often a sampling loop waiting for its next sample checks the interruptor
+const WATCH_INTERRUPT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Samples target memory at a fixed interval, ingesting each value as an event named
+/// after its location on the `metrics` timeline.
+///
+/// `sample` returns `None` when the values couldn't be read this time (e.g. the probe is in use),
+/// the sample is skipped.
+pub async fn run_watch<S: FnMut() -> Option<Vec<WatchSample>> + Send>(
+    cfg: DefmtConfig,
+    intr: Interruptor,
+    interval: Duration,
+    mut sample: S,
+) -> Result<(), Error> {
+    let (common_timeline_attrs, _) = common_timeline_attrs(&cfg);
+    let mut sender = EventSender::connect(&cfg).await?;
+
+    let mut plugin_cfg = cfg.plugin.clone();
+    plugin_cfg.rtos_mode = RtosMode::None;
+    plugin_cfg.init_task_name = Some(WATCH_TIMELINE_NAME.to_owned());
+    let mut ctx_mngr = ContextManager::new(plugin_cfg, common_timeline_attrs);
+
+    debug!(interval = ?interval, "Starting watch sampling loop");
+    let mut next_sample = Instant::now();
+    while !intr.is_set() {
+        let now = Instant::now();
+        if now < next_sample {
+            tokio::time::sleep((next_sample - now).min(WATCH_INTERRUPT_CHECK_INTERVAL)).await;
+            continue;
+        }
+        // Fixed rate, unless sampling fell behind
+        next_sample = (next_sample + interval).max(now);
+
+        let Some(samples) = sample() else {
+            continue;
+        };
+        let host_received_ns = host_time_ns();
+        for s in samples.into_iter() {
+            let record = watch_record(s, host_received_ns, &cfg.plugin);
+            let ctx = ctx_mngr.process_record(record)?;
+            sender.send(ctx, &ctx_mngr).await?;
+        }
+    }
+
+    let ctx = ctx_mngr.flush()?;
+    sender.send(ctx, &ctx_mngr).await?;
+    sender.flush(&ctx_mngr).await?;
+
+    Ok(())
+}
+
+fn watch_record(sample: WatchSample, host_received_ns: u64, cfg: &PluginConfig) -> EventRecord {
+    let mut record = EventRecord::new(Default::default());
+    record.insert_attr(EventRecord::attr_key("name"), sample.location);
+    record.insert_attr(EventRecord::attr_key("value"), sample.value);
+    record.insert_attr(EventRecord::internal_attr_key("address"), sample.address);
+    record.set_internal_host_received(host_received_ns);
+    if cfg.host_time_fallback {
+        record.set_timestamp(host_received_ns.into());
+    }
+    record
+}
+
+async fn run_reader<
+    R: Read + Send,
+    F: Fn(&R) -> Option<u64> + Send,
+    O: Fn(&R) -> u64 + Send,
+    C: Fn(&R, &EventRecord) -> Option<CrashState> + Send,
+    X: Fn(&R) -> Option<FaultState> + Send,
+>(
+    r: R,
+    cfg: DefmtConfig,
+    intr: Interruptor,
+    capture_time_ns: F,
+    overflows: O,
+    crash_state: C,
+    faults: X,
+) -> Result<ReadOutcome, Error> {
+    // Run totals for the summary file, which is written however the read loop ends
+    let started_at = SystemTime::now();
+    let start = Instant::now();
+    let mut summary = RunSummary::default();
+    let mut sender: Option<EventSender> = None;
+
+    let result = async {
+        let elf_file = cfg
+            .plugin
+            .elf_file
+            .as_ref()
+            .ok_or(Error::MissingElfFile)?
+            .clone();
+        let mut elf_watcher = cfg
+            .plugin
+            .elf_reload
+            .then(|| ElfWatcher::new(&elf_file, ElfWatcher::DEFAULT_POLL_INTERVAL));
+        let debug_file = cfg.plugin.debug_file.as_deref();
+        let (mut table, mut location_info) = load_elf(&elf_file, debug_file)?;
+
+        let (mut common_timeline_attrs, overridden_timeline_attrs) = common_timeline_attrs(&cfg);
+        let encoding_key = TimelineMeta::internal_attr_key("table.encoding");
+        if !overridden_timeline_attrs.contains(&encoding_key) {
+            common_timeline_attrs.insert(
+                encoding_key.clone(),
+                format!("{:?}", table.encoding()).into(),
+            );
+        }
+
+        let sender = sender.insert(EventSender::connect(&cfg).await?);
+
+        let mut ctx_mngr = ContextManager::new(cfg.plugin.clone(), common_timeline_attrs.clone());
+        let mut reboots = cfg
+            .plugin
+            .segment_runs
+            .then(|| RebootDetector::new(&cfg.plugin));
+        let mut boot_index = cfg.plugin.boot_index.unwrap_or(0);
+        // Start a new run at the first event decoded with a reloaded defmt table
+        let mut elf_reloaded = false;
+        // Extraction plans, built on the first occurrence of each table index.
+        // Excluded table indices don't have one.
+        let mut frame_plans: BTreeMap<u64, Option<FramePlan>> = BTreeMap::new();
+        let filter_events = !cfg.plugin.event_filter.is_empty();
+        let mut host_time_fallback = HostTimeFallback::new(cfg.plugin.host_time_fallback);
+
+        let mut decoder = table.new_stream_decoder();
+        let mut raw_frames = cfg
+            .plugin
+            .raw_frame_attributes
+            .then(|| RawFrameTracker::new(&table));
+        let mut decoder_buffer = vec![0_u8; cfg.plugin.read_buffer_size()];
+        let mut r = FramedReader::new(
+            r,
+            cfg.plugin.framing,
+            cfg.plugin.frame_crc,
+            cfg.plugin.read_buffer_size(),
+        );
+
+        // Data lost since the previous event, and the transport counters it's derived from
+        let mut data_loss = DataLoss::default();
+        let mut dropped_frames: u64 = 0;
+        let mut overflow_count: u64 = 0;
+
+        let mut metrics = cfg
+            .plugin
+            .metrics_interval
+            .map(|interval| ReaderMetrics::new(interval.0.into()));
+
+        // The deframed defmt stream, which can be re-imported without any framing
+        let mut raw_capture = cfg
+            .plugin
+            .save_raw
+            .as_ref()
+            .map(|path| {
+                debug!(path = %path.display(), "Writing the raw defmt stream to a capture file");
+                RawCaptureWriter::create(
+                    path,
+                    cfg.plugin.save_raw_rotate_size,
+                    cfg.plugin
+                        .save_raw_rotate_count
+                        .unwrap_or(RawCaptureWriter::DEFAULT_ROTATE_COUNT),
+                )
+            })
+            .transpose()?;
+
+        debug!("Starting read loop");
+
+        let mut outcome = ReadOutcome::Finished;
+        let mut maybe_read_result: Option<Result<(), Error>> = None;
+        'stream_loop: while !intr.is_set() {
+            let bytes_read = match r.read(&mut decoder_buffer) {
+                Ok(b) => b,
+                Err(e) => {
+                    // Store the result so we can pass it along after flushing buffered events
+                    maybe_read_result = Some(Err(e.into()));
+                    break;
+                }
+            };
+            // The data read after a fault was written once the core resumed
+            if let Some(fault) = faults(r.get_ref()) {
+                warn!(
+                    kind = fault.kind(),
+                    flags = fault.flags().join(" "),
+                    halted = fault.halted,
+                    "Target faulted"
+                );
+                let ctx = ctx_mngr.fault(&fault)?;
+                sender.send(ctx, &ctx_mngr).await?;
+                if fault.halted {
+                    outcome = ReadOutcome::Stopped;
+                    break;
+                }
+            }
+            if bytes_read == 0 {
+                // EOF
+                break;
+            }
+            let host_received_ns = host_time_ns();
+            summary.bytes_read += bytes_read as u64;
+            if let Some(metrics) = metrics.as_mut() {
+                metrics.bytes_read(bytes_read);
+            }
+            if let Some(capture) = raw_capture.as_mut() {
+                if let Err(e) = capture.write(&decoder_buffer[..bytes_read]) {
+                    maybe_read_result = Some(Err(e));
+                    break;
+                }
+            }
+            let capture_time_ns = capture_time_ns(r.get_ref());
+            let overflows = overflows(r.get_ref());
+            data_loss.overflows += overflows.saturating_sub(overflow_count);
+            overflow_count = overflows;
+            let dropped = r.stats().dropped;
+            data_loss.dropped_frames += dropped.saturating_sub(dropped_frames);
+            dropped_frames = dropped;
+
+            if elf_watcher.as_mut().is_some_and(ElfWatcher::changed) {
+                match load_elf(&elf_file, debug_file) {
+                    Ok((new_table, new_location_info)) => {
+                        info!(elf_file = %elf_file.display(), "ELF file changed, reloaded the defmt table");
+                        // Whatever the previous decoder buffered belongs to the previous firmware
+                        drop(decoder);
+                        table = new_table;
+                        location_info = new_location_info;
+                        decoder = table.new_stream_decoder();
+                        raw_frames = cfg
+                            .plugin
+                            .raw_frame_attributes
+                            .then(|| RawFrameTracker::new(&table));
+                        frame_plans.clear();
+                        if !overridden_timeline_attrs.contains(&encoding_key) {
+                            common_timeline_attrs.insert(
+                                encoding_key.clone(),
+                                format!("{:?}", table.encoding()).into(),
+                            );
+                        }
+                        if cfg.plugin.segment_runs {
+                            // The reflashed target starts over, don't detect its boot as a reboot
+                            reboots = Some(RebootDetector::new(&cfg.plugin));
+                            elf_reloaded = true;
+                        }
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to reload the ELF file, keeping the previous defmt table")
+                    }
+                }
+            }
+
+            decoder.received(&decoder_buffer[..bytes_read]);
+            if let Some(raw_frames) = raw_frames.as_mut() {
+                raw_frames.received(&decoder_buffer[..bytes_read]);
+            }
+            'read_loop: loop {
+                let decoded = decoder.decode();
+                if matches!(decoded, Err(DecodeError::UnexpectedEof)) {
+                    // Need more data
+                    break 'read_loop;
+                }
+                let raw_frame = raw_frames
+                    .as_mut()
+                    .and_then(|raw_frames| raw_frames.next_frame(&table));
+                let frame = match decoded {
+                    Ok(f) => {
+                        summary.frames += 1;
+                        if let Some(metrics) = metrics.as_mut() {
+                            metrics.frame();
+                        }
+                        f
+                    }
+                    Err(_) => {
+                        match &raw_frame {
+                            Some((offset, bytes)) => {
+                                warn!(offset, len = bytes.len(), "Malformed defmt frame")
+                            }
+                            None => warn!("Malformed defmt frame"),
+                        }
+                        data_loss.malformed_frames += 1;
+                        summary.malformed_frames += 1;
+                        if let Some(metrics) = metrics.as_mut() {
+                            metrics.malformed_frame();
+                        }
+                        continue;
+                    }
+                };
+                let plan = match frame_plans.entry(frame.index()) {
+                    Entry::Occupied(e) => e.into_mut(),
+                    Entry::Vacant(e) => {
+                        if cfg.plugin.excludes_frame(frame.index(), frame.format()) {
+                            debug!(
+                                table_index = frame.index(),
+                                format = frame.format(),
+                                "Excluding table index"
+                            );
+                            e.insert(None)
+                        } else {
+                            // SAFETY: all of the indices in the table exist in the locations map
+                            let loc: Option<_> =
+                                location_info.as_ref().map(|locs| &locs[&frame.index()]);
+                            e.insert(Some(FramePlan::with_config(&frame, loc, &cfg.plugin)?))
+                        }
+                    }
+                };
+                let Some(plan) = plan else {
+                    summary.excluded_frames += 1;
+                    continue;
+                };
+                debug!(msg = %frame.display(false), "Received defmt frame");
+
+                let mut event_record = plan.record(&frame);
+                if filter_events
+                    && !cfg.plugin.event_filter.allows(
+                        event_record.event_name(),
+                        event_record.level(),
+                        event_record.str_param("source.module"),
+                    )
+                {
+                    summary.filtered_events += 1;
+                    continue;
+                }
+                event_record.set_internal_host_received(host_received_ns);
+                if let Some((offset, bytes)) = raw_frame {
+                    event_record.set_internal_raw_frame(offset, &bytes);
+                }
+                if let Some(ns) = capture_time_ns {
+                    event_record.set_internal_capture_time(ns);
+                } else if host_time_fallback.apply(&mut event_record, host_received_ns) {
+                    // The timelines are on the device clock from here on
+                    let mut attrs = vec![(
+                        TimelineMeta::internal_attr_key("host_time_fallback"),
+                        AttrVal::from(false),
+                    )];
+                    let clock_style_key = TimelineMeta::attr_key("clock_style");
+                    if !overridden_timeline_attrs.contains(&clock_style_key) {
+                        attrs.push((clock_style_key, "relative".into()));
+                    }
+                    for (k, v) in attrs {
+                        common_timeline_attrs.insert(k.clone(), v.clone());
+                        ctx_mngr.set_timeline_attr(k, v);
+                    }
+                }
+
+                let new_run = if std::mem::take(&mut elf_reloaded) {
+                    Some(RebootReason::ElfReload)
+                } else {
+                    reboots.as_mut().and_then(|r| r.detect(&event_record))
+                };
+                if let Some(reason) = new_run {
+                    boot_index += 1;
+                    info!(%reason, boot_index, "Target reboot detected, starting a new run");
+                    // Finish off the previous boot's timelines
+                    let ctx = ctx_mngr.flush()?;
+                    sender.send(ctx, &ctx_mngr).await?;
+                    sender.flush(&ctx_mngr).await?;
+
+                    for (k, v) in [
+                        ("run_id", Uuid::new_v4().to_string()),
+                        ("clock_id", Uuid::new_v4().to_string()),
+                    ] {
+                        if !overridden_timeline_attrs.contains(&TimelineMeta::attr_key(k)) {
+                            common_timeline_attrs.insert(TimelineMeta::attr_key(k), v.into());
+                        }
+                    }
+                    let mut segment_cfg = cfg.plugin.clone();
+                    segment_cfg.boot_index = Some(boot_index);
+                    ctx_mngr = ContextManager::new(segment_cfg, common_timeline_attrs.clone());
+                }
+
+                // Mark the hole in the trace ahead of the event that follows it
+                let ctx = ctx_mngr.data_loss(std::mem::take(&mut data_loss))?;
+                sender.send(ctx, &ctx_mngr).await?;
+
+                let stop = cfg
+                    .plugin
+                    .stop_on
+                    .iter()
+                    .any(|trigger| trigger.matches(&event_record));
+                let event_name = stop.then(|| event_record.event_name().map(str::to_owned));
+                let crash = crash_state(r.get_ref(), &event_record)
+                    .map(|state| (event_record.event_name().map(str::to_owned), state));
+
+                let ctx = ctx_mngr.process_record(event_record)?;
+                sender.send(ctx, &ctx_mngr).await?;
+                summary.events += 1;
+                if let Some(metrics) = metrics.as_mut() {
+                    metrics.event();
+                }
+
+                if let Some((trigger, state)) = crash {
+                    let ctx = ctx_mngr.crash_state(trigger.as_deref(), state)?;
+                    sender.send(ctx, &ctx_mngr).await?;
+                }
+
+                if let Some(event_name) = event_name {
+                    info!(
+                        event_name = event_name.as_deref(),
+                        "Stop trigger matched, stopping collection"
+                    );
+                    outcome = ReadOutcome::Stopped;
+                    break 'stream_loop;
+                }
+            }
+
+            if let Some(mut report) = metrics.as_mut().and_then(|m| m.poll(Instant::now())) {
+                report.ingest_pending = sender
+                    .client
+                    .inner
+                    .status()
+                    .await
+                    .ok()
+                    .map(|status| status.events_pending);
+                info!(
+                    bytes_per_sec = report.bytes_per_sec() as u64,
+                    frames_per_sec = report.frames_per_sec() as u64,
+                    events_per_sec = report.events_per_sec() as u64,
+                    malformed_frames = report.malformed_frames,
+                    ingest_pending = report.ingest_pending,
+                    "Collector metrics"
+                );
+                if cfg.plugin.metric_events {
+                    let ctx = ctx_mngr.metrics(&report)?;
+                    sender.send(ctx, &ctx_mngr).await?;
+                }
+            }
+        }
+
+        let ctx = ctx_mngr.data_loss(data_loss)?;
+        sender.send(ctx, &ctx_mngr).await?;
+
+        // Release anything the context manager is still holding on to
+        let ctx = ctx_mngr.flush()?;
+        sender.send(ctx, &ctx_mngr).await?;
+        sender.flush(&ctx_mngr).await?;
+
+        if let Some(latency) = ctx_mngr.transport_latency() {
+            info!(
+                events = latency.count,
+                mean_ns = latency.mean,
+                p50_ns = latency.p50,
+                p99_ns = latency.p99,
+                max_ns = latency.max,
+                "Transport latency"
+            );
+        }
+
+        let dropped_attrs = sender.client.dropped_event_attrs();
+        if dropped_attrs != 0 {
+            warn!(
+                dropped_attrs,
+                "Dropped attributes over the attribute key limit"
+            );
+        }
+
+        if filter_events || summary.excluded_frames != 0 {
+            debug!(
+                filtered_events = summary.filtered_events,
+                excluded_frames = summary.excluded_frames,
+                "Event filter stats"
+            );
+        }
+
+        if let Some(mut capture) = raw_capture {
+            if let Err(e) = capture.flush() {
+                warn!(error = %e, "Failed to flush the raw capture file");
+            }
+        }
+
+        if cfg.plugin.framing != Framing::None {
+            let stats = r.stats();
+            debug!(
+                frames = stats.frames,
+                dropped = stats.dropped,
+                "Link-layer framing stats"
+            );
+        }
+
+        if let Some(res) = maybe_read_result {
+            res.map(|()| outcome)
+        } else {
+            Ok(outcome)
+        }
+    }
+    .await;
+
+    let ingest_status = match sender.as_mut() {
+        Some(sender) => sender.client.inner.status().await.ok(),
+        None => None,
+    };
+    if let Some(status) = &ingest_status {
+        debug!(
+            events_received = status.events_received,
+            events_written = status.events_written,
+            events_pending = status.events_pending,
+            "Ingest status"
+        );
+    }
+
+    if let Some(path) = cfg.plugin.summary_file.as_ref() {
+        summary.timelines = sender.map(|s| s.observed_timelines.len()).unwrap_or(0);
+        summary.ingest = ingest_status.map(|status| IngestSummary {
+            events_received: status.events_received,
+            events_written: status.events_written,
+            events_pending: status.events_pending,
+        });
+        set_summary_outcome(&mut summary, &result);
+        summary.started_at = humantime::format_rfc3339_seconds(started_at).to_string();
+        summary.duration_secs = start.elapsed().as_secs_f64();
+        debug!(path = %path.display(), "Writing the run summary");
+        if let Err(e) = write_summary(path, &summary) {
+            // Don't mask the error that ended the run
+            if result.is_ok() {
+                return Err(e);
+            }
+            warn!(error = %e, "Failed to write the run summary");
+        }
+    }
+
+    result
+}
+
+/// Read the defmt table and, when it's complete, the location info from the ELF file,
+/// or its separate debug file
+fn load_elf(
+    elf_file: &Path,
+    debug_file: Option<&Path>,
+) -> Result<(Table, Option<Locations>), Error> {
     debug!(elf_file = %elf_file.display(), "Reading ELF file");
-    let elf_contents = fs::read(&elf_file).map_err(|e| Error::ElfFileRead(elf_file, e))?;
+    let elf_contents =
+        fs::read(elf_file).map_err(|e| Error::ElfFileRead(elf_file.to_owned(), e))?;
 
     debug!("Reading defmt table");
     let table = Table::parse(&elf_contents)
@@ -31,12 +674,34 @@ pub async fn run<R: Read + Send>(
         .ok_or(Error::MissingDefmtSection)?;
 
     let location_info = {
+        let mut locs = match debug_file {
+            Some(debug_file) => {
+                debug!(debug_file = %debug_file.display(), "Reading debug file");
+                let debug_contents = fs::read(debug_file)
+                    .map_err(|e| Error::DebugFileRead(debug_file.to_owned(), e))?;
+                table
+                    .get_locations(&debug_contents)
+                    .map_err(Error::DefmtLocation)?
+            }
+            None => table
+                .get_locations(&elf_contents)
+                .map_err(Error::DefmtLocation)?,
+        };
+        if !table.is_empty() && locs.is_empty() && debug_file.is_none() {
+            // A stripped ELF file
+            if let Some((path, debug_contents)) =
+                DebugLink::from_elf(&elf_contents).and_then(|link| link.find(elf_file))
+            {
+                debug!(debug_file = %path.display(), "Reading location info from the linked debug file");
+                locs = table
+                    .get_locations(&debug_contents)
+                    .map_err(Error::DefmtLocation)?;
+            }
+        }
+
         // This is essentially what probe-rs reports to the user
-        let locs = table
-            .get_locations(&elf_contents)
-            .map_err(Error::DefmtLocation)?;
         if !table.is_empty() && locs.is_empty() {
-            warn!("Insufficient DWARF info; compile your program with `debug = 2` to enable location info.");
+            warn!("Insufficient DWARF info; compile your program with `debug = 2` to enable location info, or provide the debug file of a stripped ELF file with `debug-file`.");
             None
         } else if table.indices().all(|idx| locs.contains_key(&(idx as u64))) {
             Some(locs)
@@ -46,6 +711,57 @@ pub async fn run<R: Read + Send>(
         }
     };
 
+    Ok((table, location_info))
+}
+
+/// Times the events of untimed frames with the host receive time.
+/// Host time is never mixed with device time, so the fallback is turned off for the rest
+/// of the stream once a frame has a device timestamp.
+#[derive(Debug)]
+struct HostTimeFallback {
+    enabled: bool,
+}
+
+impl HostTimeFallback {
+    fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Returns true when the fallback was turned off by this record
+    fn apply(&mut self, record: &mut EventRecord, host_received_ns: u64) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if record.timestamp().is_some() {
+            warn!("Received a frame with a device timestamp, disabling the host time fallback");
+            self.enabled = false;
+            return true;
+        }
+        record.set_timestamp(host_received_ns.into());
+        false
+    }
+}
+
+fn set_summary_outcome(summary: &mut RunSummary, result: &Result<ReadOutcome, Error>) {
+    summary.outcome = match result {
+        Ok(ReadOutcome::Finished) => "finished",
+        Ok(ReadOutcome::Stopped) => "stopped",
+        Err(_) => "failed",
+    }
+    .to_owned();
+    summary.error = result.as_ref().err().map(|e| e.to_string());
+}
+
+fn write_summary(path: &Path, summary: &RunSummary) -> Result<(), Error> {
+    serde_json::to_vec_pretty(summary)
+        .map_err(io::Error::from)
+        .and_then(|json| fs::write(path, json))
+        .map_err(|e| Error::SummaryFile(path.to_owned(), e))
+}
+
+/// The timeline attributes shared by every timeline of the stream, and the keys
+/// the user overrides
+fn common_timeline_attrs(cfg: &DefmtConfig) -> (BTreeMap<String, AttrVal>, BTreeSet<String>) {
     let mut common_timeline_attrs = BTreeMap::new();
     for kv in cfg
         .ingest
@@ -65,17 +781,25 @@ pub async fn run<R: Read + Send>(
         Uuid::new_v4().to_string().into()
     };
     common_timeline_attrs.insert(TimelineMeta::attr_key("run_id"), run_id);
-    common_timeline_attrs.insert(
-        TimelineMeta::internal_attr_key("table.encoding"),
-        format!("{:?}", table.encoding()).into(),
-    );
     let clock_id = cfg
         .plugin
         .clock_id
         .clone()
         .unwrap_or_else(|| Uuid::new_v4().to_string());
     common_timeline_attrs.insert(TimelineMeta::attr_key("clock_id"), clock_id.into());
-    common_timeline_attrs.insert(TimelineMeta::attr_key("clock_style"), "relative".into());
+    // Host time fallback timestamps are host wall-clock time, not device ticks
+    let clock_style = if cfg.plugin.host_time_fallback {
+        "utc"
+    } else {
+        "relative"
+    };
+    common_timeline_attrs.insert(TimelineMeta::attr_key("clock_style"), clock_style.into());
+    if cfg.plugin.host_time_fallback {
+        common_timeline_attrs.insert(
+            TimelineMeta::internal_attr_key("host_time_fallback"),
+            true.into(),
+        );
+    }
     if let Some(clock_rate) = cfg.plugin.clock_rate.as_ref() {
         common_timeline_attrs.insert(
             TimelineMeta::attr_key("clock_rate"),
@@ -94,146 +818,357 @@ pub async fn run<R: Read + Send>(
         TimelineMeta::internal_attr_key("plugin.version"),
         PLUGIN_VERSION.into(),
     );
+    let mut overridden_timeline_attrs = BTreeSet::new();
     for kv in cfg
         .ingest
         .timeline_attributes
         .override_timeline_attributes
         .iter()
     {
-        common_timeline_attrs.insert(kv.0.to_string(), kv.1.clone());
+        let key = kv.0.to_string();
+        common_timeline_attrs.insert(key.clone(), kv.1.clone());
+        overridden_timeline_attrs.insert(match key.strip_prefix("timeline.") {
+            Some(k) => TimelineMeta::attr_key(k),
+            None => TimelineMeta::attr_key(&key),
+        });
     }
 
-    let client = IngestClient::connect_with_timeout(
-        &cfg.protocol_parent_url()?,
-        cfg.ingest.allow_insecure_tls,
-        cfg.plugin
-            .client_timeout
-            .map(|t| t.0.into())
-            .unwrap_or_else(|| Duration::from_secs(1)),
-    )
-    .await?
-    .authenticate(cfg.resolve_auth()?.into())
-    .await?;
-    let mut client = Client::new(client);
+    (common_timeline_attrs, overridden_timeline_attrs)
+}
 
-    let mut ctx_mngr = ContextManager::new(cfg.plugin.clone(), common_timeline_attrs);
-    let mut observed_timelines = BTreeSet::new();
-    let mut buffered_event: Option<ContextEvent> = None;
+/// Host wall-clock time, in nanoseconds since the Unix epoch
+fn host_time_ns() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
 
-    let mut decoder = table.new_stream_decoder();
-    let mut decoder_buffer = vec![0_u8; cfg.plugin.rtt_collector.rtt_read_buffer_size];
+/// Sends context events to modality.
+///
+/// Maintains a 1-element buffer so we can ensure the interaction nonce attr key
+/// is present on the previous event when we encounter a context switch
+/// on the current event.
+struct EventSender {
+    client: Client,
+    attrs: EventAttrPipeline,
+    /// Timelines sent so far, with the revision of the attributes they were sent with
+    observed_timelines: BTreeMap<TimelineId, u64>,
+    buffered_event: Option<ContextEvent>,
+}
 
-    debug!("Starting read loop");
+impl EventSender {
+    async fn connect(cfg: &DefmtConfig) -> Result<Self, Error> {
+        let client = IngestClient::connect_with_timeout(
+            &cfg.protocol_parent_url()?,
+            cfg.ingest.allow_insecure_tls,
+            cfg.plugin
+                .client_timeout
+                .map(|t| t.0.into())
+                .unwrap_or_else(|| Duration::from_secs(1)),
+        )
+        .await?
+        .authenticate(cfg.resolve_auth()?.into())
+        .await?;
+        let client = Client::new(client).with_event_key_limit(
+            cfg.plugin.max_attribute_keys,
+            cfg.plugin.attribute_key_limit_policy,
+        );
+        Ok(Self::new(client, &cfg.plugin))
+    }
 
-    let mut maybe_read_result: Option<Result<(), Error>> = None;
-    while !intr.is_set() {
-        let bytes_read = match r.read(&mut decoder_buffer) {
-            Ok(b) => b,
-            Err(e) => {
-                // Store the result so we can pass it along after flushing buffered events
-                maybe_read_result = Some(Err(e.into()));
-                break;
-            }
-        };
-        if bytes_read == 0 {
-            // EOF
-            break;
+    fn new(client: Client, cfg: &PluginConfig) -> Self {
+        Self {
+            client,
+            attrs: EventAttrPipeline::new(cfg),
+            observed_timelines: Default::default(),
+            buffered_event: None,
         }
+    }
 
-        decoder.received(&decoder_buffer[..bytes_read]);
-        'read_loop: loop {
-            let frame = match decoder.decode() {
-                Ok(f) => f,
-                Err(e) => match e {
-                    DecodeError::UnexpectedEof => {
-                        // Need more data
-                        break 'read_loop;
-                    }
-                    DecodeError::Malformed => {
-                        warn!("Malformed defmt frame");
-                        continue;
-                    }
-                },
-            };
-            debug!(msg = %frame.display(false), "Received defmt frame");
-
-            // SAFETY: all of the indices in the table exist in the locations map
-            let loc: Option<_> = location_info.as_ref().map(|locs| &locs[&frame.index()]);
+    async fn send(&mut self, ctx: ActiveContext, ctx_mngr: &ContextManager) -> Result<(), Error> {
+        for ev in ctx.events.into_iter() {
+            if let Some(mut prev_event) = self.buffered_event.take() {
+                if ev.add_previous_event_nonce {
+                    prev_event.record.promote_internal_nonce();
+                }
+                self.send_event(prev_event, ctx_mngr).await?;
+            }
 
-            let event_record = EventRecord::from_frame(frame, loc)?;
+            // Buffer the current event
+            self.buffered_event = Some(ev);
+        }
+        Ok(())
+    }
 
-            let ctx = ctx_mngr.process_record(event_record)?;
+    /// Sends the last buffered event and flushes the client
+    async fn flush(&mut self, ctx_mngr: &ContextManager) -> Result<(), Error> {
+        if let Some(last_event) = self.buffered_event.take() {
+            debug!("Flushing buffered events");
+            self.send_event(last_event, ctx_mngr).await?;
+        }
+        self.client.inner.flush().await?;
+        Ok(())
+    }
 
-            for ev in ctx.events.into_iter() {
-                // Maintain a 1-element buffer so we can ensure the interaction nonce attr key
-                // is present on the previous event when we encounter a context switch
-                // on the current event
-                match buffered_event.take() {
-                    Some(mut prev_event) => {
-                        if ev.add_previous_event_nonce {
-                            prev_event.record.promote_internal_nonce();
-                        }
+    async fn send_event(
+        &mut self,
+        mut ev: ContextEvent,
+        ctx_mngr: &ContextManager,
+    ) -> Result<(), Error> {
+        let timeline = ctx_mngr.timeline_meta(ev.context)?;
+        let mut new_timeline_attrs: Option<TimelineAttributes> = None;
+        if self
+            .observed_timelines
+            .insert(timeline.id(), timeline.revision())
+            != Some(timeline.revision())
+        {
+            let promoted = self
+                .attrs
+                .promoter
+                .promote_timeline_attrs(timeline.attributes());
+            new_timeline_attrs = Some(self.attrs.renamer.rename_timeline_attrs(&promoted));
+        }
 
-                        // Buffer the current event
-                        buffered_event = Some(ev);
+        self.client
+            .switch_timeline(timeline.id(), new_timeline_attrs.as_ref())
+            .await?;
 
-                        // Send the previous event
-                        let timeline = ctx_mngr.timeline_meta(prev_event.context)?;
-                        let mut new_timeline_attrs: Option<&TimelineAttributes> = None;
-                        if observed_timelines.insert(timeline.id()) {
-                            new_timeline_attrs = Some(timeline.attributes());
-                        }
+        // Synthetic events only carry the plugin's own keys
+        let synthetic = ev
+            .record
+            .attributes()
+            .contains_key("event.internal.defmt.synthetic");
+        self.attrs.apply(ev.record.attributes_mut());
+        self.client
+            .send_event(ev.global_ordering, ev.record.attributes(), synthetic)
+            .await?;
+        Ok(())
+    }
+}
 
-                        client
-                            .switch_timeline(timeline.id(), new_timeline_attrs)
-                            .await?;
+/// What's done to the attributes of every event on their way to the ingest client
+struct EventAttrPipeline {
+    promoter: AttrPromoter,
+    renamer: AttrRenamer,
+    /// Stamped onto every event, unless already present
+    additional_attrs: Vec<(AttrKey, AttrVal)>,
+    omit_internal_attributes: bool,
+    omit_formatted_string: bool,
+}
 
-                        client
-                            .send_event(prev_event.global_ordering, prev_event.record.attributes())
-                            .await?;
-                    }
+impl EventAttrPipeline {
+    fn new(cfg: &PluginConfig) -> Self {
+        Self {
+            promoter: AttrPromoter::new(cfg.promote_internal_attributes.clone()),
+            renamer: AttrRenamer::new(cfg.attribute_renames.clone()),
+            additional_attrs: cfg
+                .additional_event_attributes
+                .iter()
+                .map(|kv| {
+                    let key = kv.0.to_string();
+                    let key = key.strip_prefix("event.").unwrap_or(&key);
+                    (EventRecord::attr_key(key), kv.1.clone())
+                })
+                .collect(),
+            omit_internal_attributes: cfg.omits_internal_attributes(),
+            omit_formatted_string: cfg.omits_formatted_string(),
+        }
+    }
 
-                    // First iter of the loop
-                    None => {
-                        buffered_event = Some(ev);
-                    }
-                }
+    /// Promotes and renames the attributes, then adds the additional attributes and
+    /// removes the omitted ones
+    fn apply(&mut self, attrs: &mut EventAttributes) {
+        self.promoter.promote_event_attrs(attrs);
+        self.renamer.rename_event_attrs(attrs);
+        for (k, v) in self.additional_attrs.iter() {
+            if !attrs.contains_key(k.as_str()) {
+                attrs.insert(*k, v.clone());
             }
         }
+        if self.omit_internal_attributes {
+            attrs.retain(|k, _| {
+                !k.as_str()
+                    .starts_with(EventRecord::INTERNAL_ATTR_KEY_PREFIX)
+            });
+        } else if self.omit_formatted_string {
+            attrs.remove("event.internal.defmt.formatted_string");
+        }
     }
+}
 
-    // Flush the last event
-    if let Some(last_event) = buffered_event.take() {
-        debug!("Flushing buffered events");
-        let timeline = ctx_mngr.timeline_meta(last_event.context)?;
-        let mut new_timeline_attrs: Option<&TimelineAttributes> = None;
-        if observed_timelines.insert(timeline.id()) {
-            new_timeline_attrs = Some(timeline.attributes());
-        }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{glob::Glob, Timestamp};
+    use auxon_sdk::{api::Nanoseconds, reflector_config::AttrKeyEqValuePair};
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
 
-        client
-            .switch_timeline(timeline.id(), new_timeline_attrs)
-            .await?;
+    #[test]
+    fn summary_json() {
+        let mut summary = RunSummary {
+            started_at: "2024-03-01T12:00:00Z".to_owned(),
+            duration_secs: 1.5,
+            bytes_read: 1024,
+            frames: 10,
+            events: 8,
+            malformed_frames: 1,
+            excluded_frames: 1,
+            filtered_events: 1,
+            timelines: 2,
+            ingest: Some(IngestSummary {
+                events_received: 8,
+                events_written: 6,
+                events_pending: 2,
+            }),
+            ..Default::default()
+        };
+        set_summary_outcome(&mut summary, &Ok(ReadOutcome::Stopped));
+        assert_eq!(
+            serde_json::to_value(&summary).unwrap(),
+            serde_json::json!({
+                "outcome": "stopped",
+                "error": null,
+                "started_at": "2024-03-01T12:00:00Z",
+                "duration_secs": 1.5,
+                "bytes_read": 1024,
+                "frames": 10,
+                "events": 8,
+                "malformed_frames": 1,
+                "excluded_frames": 1,
+                "filtered_events": 1,
+                "timelines": 2,
+                "ingest": {
+                    "events_received": 8,
+                    "events_written": 6,
+                    "events_pending": 2,
+                },
+            })
+        );
+    }
 
-        client
-            .send_event(last_event.global_ordering, last_event.record.attributes())
-            .await?;
+    #[test]
+    fn host_time_fallback() {
+        let record = |ts: Option<u64>| {
+            EventRecord::from_iter(
+                ts.map(Timestamp::Micros),
+                [(EventRecord::attr_key("name"), AttrVal::from("a"))],
+            )
+        };
+        let timestamp = |record: &EventRecord| record.attributes().get("event.timestamp").cloned();
+
+        let mut fallback = HostTimeFallback::new(false);
+        let mut r = record(None);
+        assert!(!fallback.apply(&mut r, 1_000));
+        assert_eq!(timestamp(&r), None);
+
+        let mut fallback = HostTimeFallback::new(true);
+        let mut r = record(None);
+        assert!(!fallback.apply(&mut r, 1_000));
+        assert_eq!(timestamp(&r), Some(Nanoseconds::from(1_000_u64).into()));
+
+        // Device time takes over for good
+        let mut r = record(Some(5));
+        assert!(fallback.apply(&mut r, 2_000));
+        assert_eq!(timestamp(&r), None);
+        let mut r = record(None);
+        assert!(!fallback.apply(&mut r, 3_000));
+        assert_eq!(timestamp(&r), None);
     }
 
-    client.inner.flush().await?;
+    #[test]
+    fn host_time_fallback_clock_style() {
+        let clock_style = |cfg: &DefmtConfig| {
+            common_timeline_attrs(cfg)
+                .0
+                .get("timeline.clock_style")
+                .cloned()
+        };
 
-    if let Ok(status) = client.inner.status().await {
-        debug!(
-            events_received = status.events_received,
-            events_written = status.events_written,
-            events_pending = status.events_pending,
-            "Ingest status"
+        let mut cfg = DefmtConfig::default();
+        assert_eq!(clock_style(&cfg), Some(AttrVal::from("relative")));
+
+        cfg.plugin.host_time_fallback = true;
+        assert_eq!(clock_style(&cfg), Some(AttrVal::from("utc")));
+        assert_eq!(
+            common_timeline_attrs(&cfg)
+                .0
+                .get("timeline.internal.defmt.host_time_fallback"),
+            Some(&AttrVal::from(true))
         );
     }
 
-    if let Some(res) = maybe_read_result {
-        res
-    } else {
-        Ok(())
+    #[test]
+    fn additional_event_attrs() {
+        let mut cfg = PluginConfig {
+            promote_internal_attributes: vec![Glob::new("event.internal.defmt.table_index")],
+            attribute_renames: [("event.table_index".to_owned(), "event.idx".to_owned())]
+                .into_iter()
+                .collect(),
+            additional_event_attributes: vec![
+                AttrKeyEqValuePair::from_str("idx=99").unwrap(),
+                AttrKeyEqValuePair::from_str("event.site='lab'").unwrap(),
+            ],
+            ..Default::default()
+        };
+        let attrs = || -> EventAttributes {
+            [
+                (EventRecord::attr_key("name"), AttrVal::from("a")),
+                (
+                    EventRecord::internal_attr_key("table_index"),
+                    AttrVal::from(3_i64),
+                ),
+                (
+                    EventRecord::internal_attr_key("formatted_string"),
+                    AttrVal::from("a"),
+                ),
+            ]
+            .into_iter()
+            .collect()
+        };
+
+        let mut a = attrs();
+        EventAttrPipeline::new(&cfg).apply(&mut a);
+        // Applied after promotion and renaming, without overriding them
+        assert_eq!(a.get("event.idx"), Some(&AttrVal::from(3_i64)));
+        assert_eq!(a.get("event.site"), Some(&AttrVal::from("lab")));
+        assert_eq!(a.get("event.table_index"), None);
+        assert_eq!(
+            a.get("event.internal.defmt.formatted_string"),
+            Some(&AttrVal::from("a"))
+        );
+
+        cfg.omit_internal_attributes = true;
+        let mut a = attrs();
+        EventAttrPipeline::new(&cfg).apply(&mut a);
+        assert_eq!(
+            a.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+            vec!["event.idx", "event.name", "event.site"]
+        );
+    }
+
+    #[tokio::test]
+    async fn summary_of_a_failed_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let summary_file = dir.path().join("summary.json");
+        let cfg = DefmtConfig {
+            plugin: PluginConfig {
+                elf_file: dir.path().join("missing.elf").into(),
+                summary_file: summary_file.clone().into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let err = run(io::empty(), cfg, Interruptor::new()).await.unwrap_err();
+        assert!(matches!(err, Error::ElfFileRead(..)));
+
+        let summary: serde_json::Value =
+            serde_json::from_slice(&fs::read(&summary_file).unwrap()).unwrap();
+        assert_eq!(summary["outcome"], "failed");
+        assert_eq!(summary["error"], err.to_string());
+        assert_eq!(summary["events"], 0);
+        assert_eq!(summary["timelines"], 0);
+        assert_eq!(summary["ingest"], serde_json::Value::Null);
     }
 }
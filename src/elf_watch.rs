@@ -0,0 +1,121 @@
+//! Detects rebuilds of the ELF file, so a long-lived reader can reload the defmt
+//! table instead of decoding with a stale one.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::debug;
+
+/// Polls the modification time and size of the ELF file.
+///
+/// A change is only reported once the file stopped changing for a poll interval,
+/// so a file that's still being written by the linker isn't loaded.
+#[derive(Debug)]
+pub struct ElfWatcher {
+    path: PathBuf,
+    interval: Duration,
+    last_poll: Instant,
+    loaded: Option<FileStamp>,
+    pending: Option<FileStamp>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        })
+    }
+}
+
+impl ElfWatcher {
+    pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Start watching the file, in its current state.
+    /// Create the watcher before reading the file so a change in between isn't missed.
+    pub fn new<P: AsRef<Path>>(path: P, interval: Duration) -> Self {
+        let path = path.as_ref().to_owned();
+        let loaded = FileStamp::of(&path);
+        Self {
+            path,
+            interval,
+            last_poll: Instant::now(),
+            loaded,
+            pending: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true when the file changed since the last reported change, or since the
+    /// watcher was created. Polls the file at most once per interval.
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < self.interval {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let Some(stamp) = FileStamp::of(&self.path) else {
+            // Removed while it's rebuilt
+            self.pending = None;
+            return false;
+        };
+        if Some(stamp) == self.loaded {
+            self.pending = None;
+            return false;
+        }
+        if self.pending.replace(stamp) != Some(stamp) {
+            debug!(elf_file = %self.path.display(), "ELF file changed, waiting for it to settle");
+            return false;
+        }
+        self.pending = None;
+        self.loaded = Some(stamp);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settled_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fw.elf");
+        fs::write(&path, [0; 4]).unwrap();
+
+        let mut watcher = ElfWatcher::new(&path, Duration::ZERO);
+        assert!(!watcher.changed());
+
+        fs::write(&path, [0; 8]).unwrap();
+        assert!(!watcher.changed());
+        // Still being written
+        fs::write(&path, [0; 16]).unwrap();
+        assert!(!watcher.changed());
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.changed());
+        assert!(!watcher.changed());
+        fs::write(&path, [0; 32]).unwrap();
+        assert!(!watcher.changed());
+        assert!(watcher.changed());
+
+        let mut watcher = ElfWatcher::new(&path, Duration::from_secs(60));
+        fs::write(&path, [0; 4]).unwrap();
+        assert!(!watcher.changed());
+        assert!(!watcher.changed());
+    }
+}
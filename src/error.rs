@@ -9,6 +9,9 @@ pub enum Error {
     #[error("Failed to read the ELF file '{0}'")]
     ElfFileRead(PathBuf, #[source] io::Error),
 
+    #[error("Failed to read the debug file '{0}'")]
+    DebugFileRead(PathBuf, #[source] io::Error),
+
     #[error("The ELF file does not contain a '.defmt' section")]
     MissingDefmtSection,
 
@@ -21,6 +24,12 @@ pub enum Error {
     #[error("Encountered a defmt parser error")]
     DefmtParser(#[from] defmt_parser::Error),
 
+    #[error("Failed to write the raw capture file '{0}'")]
+    RawCapture(PathBuf, #[source] io::Error),
+
+    #[error("Failed to write the summary file '{0}'")]
+    SummaryFile(PathBuf, #[source] io::Error),
+
     #[error("Context manager is in an inconsistent state")]
     ContextManagerInternalState,
 
@@ -30,18 +39,27 @@ pub enum Error {
     )]
     Io(#[from] io::Error),
 
+    #[cfg(feature = "ingest")]
     #[error("Encountered an ingest client error. {0}")]
     Ingest(#[from] auxon_sdk::ingest_client::IngestError),
 
+    #[cfg(feature = "ingest")]
     #[error("Encountered an ingest client error. {0}")]
     DynamicIngest(#[from] auxon_sdk::ingest_client::dynamic::DynamicIngestError),
 
+    #[cfg(feature = "ingest")]
     #[error("Encountered an ingest client initialization error. {0}")]
     IngestClientInitialization(#[from] auxon_sdk::ingest_client::IngestClientInitializationError),
 
+    #[cfg(feature = "ingest")]
     #[error("Failed to authenticate. {0}")]
     Auth(#[from] crate::config::AuthTokenError),
 
+    #[cfg(feature = "ingest")]
     #[error(transparent)]
     UrlParse(#[from] url::ParseError),
+
+    #[cfg(feature = "rtt-dump")]
+    #[error("Failed to recover the RTT buffer from the memory dump. {0}")]
+    RttDump(#[from] crate::rtt_dump::RttDumpError),
 }
@@ -0,0 +1,230 @@
+//! Espressif console support: the ROM and second-stage bootloader messages the
+//! chip prints on reset, which share the console with the defmt data.
+
+use std::io;
+use tracing::{debug, info, trace};
+
+/// The USB VID:PID of the USB-Serial-JTAG peripheral built into the ESP32-C3, -C6, -H2, -S3, etc.
+pub const USB_SERIAL_JTAG_VID: u16 = 0x303A;
+pub const USB_SERIAL_JTAG_PID: u16 = 0x1001;
+
+/// The first bytes the ROM bootloader prints after a reset, e.g.
+/// `ESP-ROM:esp32c3-api1-20210207` or `ets Jun  8 2016 00:22:57` (ESP32),
+/// and the reset reason line common to all of them
+const BOOT_BANNERS: &[&[u8]] = &[
+    b"ESP-ROM:",
+    b"ets Jun  8 2016",
+    b"ets Jan  8 2013",
+    b"rst:0x",
+];
+
+/// Boot log lines longer than this are treated as data
+const MAX_BOOT_LINE_LEN: usize = 1024;
+
+/// Returns the reset reason of a ROM reset line,
+/// e.g. `USB_UART_CHIP_RESET` for `rst:0x15 (USB_UART_CHIP_RESET),boot:0xd (SPI_FAST_FLASH_BOOT)`
+pub fn reset_reason(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix("rst:")?;
+    match rest.split_once('(') {
+        Some((_, reason)) => reason.split(')').next(),
+        None => rest.split(',').next(),
+    }
+    .map(str::trim)
+    .filter(|r| !r.is_empty())
+}
+
+/// Logs a line of console text, noting the resets
+pub(crate) fn log_console_line(line: &[u8]) {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end();
+    if let Some(reason) = reset_reason(line) {
+        info!(reason, "Target reset");
+    } else {
+        debug!(%line, "Console output");
+    }
+}
+
+/// Bytes the boot messages are made of, including the ANSI color escapes
+/// of the ESP-IDF bootloader logs
+fn is_text(b: u8) -> bool {
+    matches!(b, 0x20..=0x7E | b'\r' | b'\n' | b'\t' | 0x1B)
+}
+
+/// Strips the reset and boot messages from a raw defmt stream.
+///
+/// A boot log starts with one of the ROM banners and extends over the following
+/// lines of text; the first line containing binary data ends it.
+pub struct EspBootLogReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    eof: bool,
+    in_boot_log: bool,
+    resets: u64,
+}
+
+impl<R: io::Read> EspBootLogReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            eof: false,
+            in_boot_log: false,
+            resets: 0,
+        }
+    }
+
+    /// Number of boot logs removed so far
+    pub fn resets(&self) -> u64 {
+        self.resets
+    }
+
+    /// Returns the number of leading bytes of the buffer that are data,
+    /// after stripping the boot log lines at the start of the buffer.
+    /// Zero means more input is needed.
+    fn process(&mut self) -> usize {
+        loop {
+            if self.in_boot_log {
+                let line_len = self
+                    .buf
+                    .iter()
+                    .take(MAX_BOOT_LINE_LEN)
+                    .position(|b| !is_text(*b) || *b == b'\n');
+                match line_len {
+                    Some(end) if self.buf[end] == b'\n' => {
+                        trace!(
+                            line = %String::from_utf8_lossy(&self.buf[..end]).trim_end(),
+                            "Stripping boot log line"
+                        );
+                        log_console_line(&self.buf[..end]);
+                        self.buf.drain(..=end);
+                        continue;
+                    }
+                    None if !self.eof && self.buf.len() < MAX_BOOT_LINE_LEN => return 0,
+                    None if self.eof => {
+                        self.buf.clear();
+                        return 0;
+                    }
+                    // Binary data, or a line too long to be part of the boot log
+                    _ => {
+                        debug!("Boot log ended");
+                        self.in_boot_log = false;
+                    }
+                }
+            }
+
+            let Some(start) = find_banner_start(&self.buf, self.eof) else {
+                return self.buf.len();
+            };
+            if start > 0 {
+                return start;
+            }
+            if !BOOT_BANNERS.iter().any(|b| self.buf.starts_with(b)) {
+                // Partial banner prefix at the end of the input
+                return if self.eof { self.buf.len() } else { 0 };
+            }
+            self.in_boot_log = true;
+            self.resets += 1;
+        }
+    }
+}
+
+/// Finds the start of the first complete banner, or of a banner prefix
+/// cut off at the end of the buffer
+fn find_banner_start(buf: &[u8], eof: bool) -> Option<usize> {
+    (0..buf.len()).find(|&i| {
+        BOOT_BANNERS.iter().any(|b| {
+            let rest = &buf[i..];
+            rest.starts_with(b) || (!eof && b.starts_with(rest))
+        })
+    })
+}
+
+impl<R: io::Read> io::Read for EspBootLogReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let data_len = self.process();
+            if data_len != 0 {
+                let n = data_len.min(buf.len());
+                buf[..n].copy_from_slice(&self.buf[..n]);
+                self.buf.drain(..n);
+                return Ok(n);
+            }
+            if self.eof {
+                return Ok(0);
+            }
+
+            let mut chunk = [0_u8; 1024];
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                self.eof = true;
+            } else {
+                self.buf.extend_from_slice(&chunk[..n]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    /// Yields a single byte per read to exercise banners split across reads
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    const STREAM: &[u8] = b"\x01\x02\0ESP-ROM:esp32c3-api1-20210207\r\n\
+Build:Feb  7 2021\r\n\
+rst:0x15 (USB_UART_CHIP_RESET),boot:0xd (SPI_FAST_FLASH_BOOT)\r\n\
+load:0x3fcd5820,len:0x1714\r\n\
+entry 0x403cc710\r\n\
+\x1b[0;32mI (24) boot: ESP-IDF v5.1 2nd stage bootloader\x1b[0m\r\n\
+\x03E\n\x04\0";
+
+    #[test]
+    fn strips_boot_log() {
+        for r in [
+            Box::new(STREAM) as Box<dyn Read>,
+            Box::new(Trickle(STREAM)) as Box<dyn Read>,
+        ] {
+            let mut r = EspBootLogReader::new(r);
+            let mut out = Vec::new();
+            r.read_to_end(&mut out).unwrap();
+            assert_eq!(out, b"\x01\x02\0\x03E\n\x04\0");
+            assert_eq!(r.resets(), 1);
+        }
+    }
+
+    #[test]
+    fn data_passthrough() {
+        let data = b"\x00ESP-RO\xFF\nrst:";
+        let mut r = EspBootLogReader::new(Trickle(data));
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(r.resets(), 0);
+    }
+
+    #[test]
+    fn reset_reasons() {
+        assert_eq!(
+            reset_reason("rst:0x15 (USB_UART_CHIP_RESET),boot:0xd (SPI_FAST_FLASH_BOOT)"),
+            Some("USB_UART_CHIP_RESET")
+        );
+        assert_eq!(reset_reason("rst:0x1,boot:0x13"), Some("0x1"));
+        assert_eq!(reset_reason("load:0x3fcd5820"), None);
+    }
+}
@@ -1,11 +1,98 @@
-use crate::{Error, Rate};
+use crate::{
+    AttrCoercion, AttrKey, AttrType, ByteEncoding, Error, PluginConfig, Rate, TimestampComposition,
+    UnnamedEventNames,
+};
 use auxon_sdk::api::{AttrVal, BigInt, Nanoseconds, TimelineId, Uuid};
 use defmt_decoder::{Arg, Frame, Location};
-use defmt_parser::{Fragment, ParserMode};
-use std::collections::BTreeMap;
+use defmt_parser::{DisplayHint, Fragment, ParserMode, Type};
+use std::{
+    collections::{BTreeMap, HashMap},
+    iter, mem, slice, vec,
+};
 use tracing::{debug, warn};
 
-pub type EventAttributes = BTreeMap<String, AttrVal>;
+/// Event attributes, kept sorted by key.
+///
+/// Events only carry a handful of attributes, so a sorted vector is
+/// considerably cheaper than a tree map to build, query, and iterate.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventAttributes(Vec<(AttrKey, AttrVal)>);
+
+impl EventAttributes {
+    pub fn insert(&mut self, key: AttrKey, val: AttrVal) -> Option<AttrVal> {
+        match self.0.binary_search_by(|(k, _)| k.cmp(&key)) {
+            Ok(idx) => Some(mem::replace(&mut self.0[idx].1, val)),
+            Err(idx) => {
+                self.0.insert(idx, (key, val));
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&AttrVal> {
+        self.position(key).map(|idx| &self.0[idx].1)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<AttrVal> {
+        self.position(key).map(|idx| self.0.remove(idx).1)
+    }
+
+    /// Keeps only the attributes for which the predicate returns true
+    pub fn retain<F: FnMut(&AttrKey, &AttrVal) -> bool>(&mut self, mut f: F) {
+        self.0.retain(|(k, v)| f(k, v))
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> EventAttributesIter<'_> {
+        self.into_iter()
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.0.binary_search_by(|(k, _)| k.as_str().cmp(key)).ok()
+    }
+}
+
+pub type EventAttributesIter<'a> =
+    iter::Map<slice::Iter<'a, (AttrKey, AttrVal)>, fn(&(AttrKey, AttrVal)) -> (&AttrKey, &AttrVal)>;
+
+impl<'a> IntoIterator for &'a EventAttributes {
+    type Item = (&'a AttrKey, &'a AttrVal);
+    type IntoIter = EventAttributesIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl IntoIterator for EventAttributes {
+    type Item = (AttrKey, AttrVal);
+    type IntoIter = vec::IntoIter<(AttrKey, AttrVal)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl FromIterator<(AttrKey, AttrVal)> for EventAttributes {
+    fn from_iter<I: IntoIterator<Item = (AttrKey, AttrVal)>>(iter: I) -> Self {
+        let mut attrs = EventAttributes::default();
+        for (k, v) in iter {
+            attrs.insert(k, v);
+        }
+        attrs
+    }
+}
 
 #[derive(Debug)]
 pub struct EventRecord {
@@ -14,15 +101,15 @@ pub struct EventRecord {
 }
 
 impl EventRecord {
-    const ATTR_KEY_PREFIX: &'static str = "event.";
-    const INTERNAL_ATTR_KEY_PREFIX: &'static str = "event.internal.defmt.";
+    pub(crate) const ATTR_KEY_PREFIX: &'static str = "event.";
+    pub(crate) const INTERNAL_ATTR_KEY_PREFIX: &'static str = "event.internal.defmt.";
 
-    pub(crate) fn attr_key(k: &str) -> String {
-        format!("{}{k}", Self::ATTR_KEY_PREFIX)
+    pub(crate) fn attr_key(k: &str) -> AttrKey {
+        AttrKey::new(&format!("{}{k}", Self::ATTR_KEY_PREFIX))
     }
 
-    pub(crate) fn internal_attr_key(k: &str) -> String {
-        format!("{}{k}", Self::INTERNAL_ATTR_KEY_PREFIX)
+    pub(crate) fn internal_attr_key(k: &str) -> AttrKey {
+        AttrKey::new(&format!("{}{k}", Self::INTERNAL_ATTR_KEY_PREFIX))
     }
 
     pub(crate) fn new(attributes: EventAttributes) -> Self {
@@ -35,7 +122,7 @@ impl EventRecord {
     #[cfg(test)]
     pub(crate) fn from_iter(
         timestamp: Option<Timestamp>,
-        attrs: impl IntoIterator<Item = (String, AttrVal)>,
+        attrs: impl IntoIterator<Item = (AttrKey, AttrVal)>,
     ) -> Self {
         Self {
             timestamp,
@@ -43,7 +130,7 @@ impl EventRecord {
         }
     }
 
-    pub(crate) fn insert_attr<V: Into<AttrVal>>(&mut self, k: String, v: V) {
+    pub(crate) fn insert_attr<V: Into<AttrVal>>(&mut self, k: AttrKey, v: V) {
         self.attributes.insert(k, v.into());
     }
 
@@ -74,7 +161,7 @@ impl EventRecord {
             .insert(Self::internal_attr_key("nonce"), nonce.into());
     }
 
-    pub(crate) fn promote_internal_nonce(&mut self) {
+    pub fn promote_internal_nonce(&mut self) {
         if let Some(nonce) = self.attributes.remove("event.internal.defmt.nonce") {
             self.attributes.insert(Self::attr_key("nonce"), nonce);
         }
@@ -89,6 +176,24 @@ impl EventRecord {
         }
     }
 
+    pub(crate) fn level(&self) -> Option<&str> {
+        let v = self.attributes.get("event.level")?;
+        if let AttrVal::String(s) = v {
+            Some(s.as_ref())
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn phase(&self) -> Option<&str> {
+        let v = self.attributes.get("event.phase")?;
+        if let AttrVal::String(s) = v {
+            Some(s.as_ref())
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn task_name(&self) -> Option<&str> {
         let v = self.attributes.get("event.task")?;
         if let AttrVal::String(s) = v {
@@ -107,6 +212,61 @@ impl EventRecord {
         }
     }
 
+    /// A string parameter of the event, e.g. `task` for `event.task`
+    pub(crate) fn str_param(&self, name: &str) -> Option<&str> {
+        let name = name.strip_prefix("event.").unwrap_or(name);
+        let v = self.attributes.get(&format!("event.{name}"))?;
+        if let AttrVal::String(s) = v {
+            Some(s.as_ref())
+        } else {
+            None
+        }
+    }
+
+    /// A parameter of the event, of any type
+    pub(crate) fn param(&self, name: &str) -> Option<&AttrVal> {
+        let name = name.strip_prefix("event.").unwrap_or(name);
+        self.attributes.get(&format!("event.{name}"))
+    }
+
+    /// An integer parameter of the event
+    pub(crate) fn int_param(&self, name: &str) -> Option<i64> {
+        let name = name.strip_prefix("event.").unwrap_or(name);
+        let v = self.attributes.get(&format!("event.{name}"))?;
+        if let AttrVal::Integer(n) = v {
+            Some(*n)
+        } else {
+            None
+        }
+    }
+
+    /// The interrupt name, or its number
+    pub(crate) fn irq_name(&self) -> Option<String> {
+        match self.attributes.get("event.irq")? {
+            AttrVal::String(s) => Some(s.to_string()),
+            AttrVal::Integer(n) => Some(format!("IRQ{n}")),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn thread_name(&self) -> Option<&str> {
+        let v = self.attributes.get("event.thread")?;
+        if let AttrVal::String(s) = v {
+            Some(s.as_ref())
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn executor_name(&self) -> Option<&str> {
+        let v = self.attributes.get("event.executor")?;
+        if let AttrVal::String(s) = v {
+            Some(s.as_ref())
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn integration_version(&self) -> Option<u16> {
         let v = self.attributes.get("event.version")?;
         if let AttrVal::Integer(version) = v {
@@ -130,6 +290,30 @@ impl EventRecord {
             .insert(Self::internal_attr_key("timestamp"), ts.into());
     }
 
+    pub fn set_internal_host_received(&mut self, ns: u64) {
+        self.attributes
+            .insert(Self::internal_attr_key("host_received_ns"), ns.into());
+    }
+
+    pub fn set_internal_raw_frame(&mut self, offset: u64, bytes: &[u8]) {
+        self.attributes
+            .insert(Self::internal_attr_key("stream_offset"), offset.into());
+        self.attributes.insert(
+            Self::internal_attr_key("raw_frame"),
+            encode_bytes(bytes, ByteEncoding::Base64).into(),
+        );
+    }
+
+    /// Sets the capture-relative receive time, which is also used as the
+    /// event timestamp when the frame doesn't have one
+    pub fn set_internal_capture_time(&mut self, ns: u64) {
+        self.attributes
+            .insert(Self::internal_attr_key("capture_time_ns"), ns.into());
+        if self.timestamp.is_none() {
+            self.set_timestamp(ns.into());
+        }
+    }
+
     pub(crate) fn set_timestamp(&mut self, ts: Nanoseconds) {
         self.attributes
             .insert(Self::attr_key("timestamp"), ts.into());
@@ -149,6 +333,31 @@ impl EventRecord {
         &self.attributes
     }
 
+    pub fn attributes_mut(&mut self) -> &mut EventAttributes {
+        &mut self.attributes
+    }
+
+    pub(crate) fn timestamp_ns(&self) -> Option<u64> {
+        match self.attributes.get("event.timestamp")? {
+            AttrVal::Timestamp(ns) => Some(ns.get_raw()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn host_received_ns(&self) -> Option<u64> {
+        match self
+            .attributes
+            .get("event.internal.defmt.host_received_ns")?
+        {
+            AttrVal::Integer(ns) => u64::try_from(*ns).ok(),
+            AttrVal::BigInt(ns) => {
+                let ns: &i128 = ns.as_ref();
+                u64::try_from(*ns).ok()
+            }
+            _ => None,
+        }
+    }
+
     pub(crate) fn auxon_instant(&self) -> Option<u64> {
         let v = self.attributes.get("event.instant")?;
         match v {
@@ -187,79 +396,229 @@ impl EventRecord {
     }
 
     pub fn from_frame(f: Frame<'_>, location: Option<&Location>) -> Result<Self, Error> {
-        let fragments = defmt_parser::parse(f.format(), ParserMode::ForwardsCompatible)?;
+        Ok(FramePlan::new(&f, location)?.record(&f))
+    }
+}
 
-        let mut attributes = BTreeMap::default();
-        let mut name = None;
-        let mut pending_attr_key = None;
+/// Attribute extraction plan for a single defmt table entry.
+///
+/// Everything that only depends on the table entry (format string, level,
+/// location and timestamp format) is computed once per table index, so handling
+/// a frame only needs to bind its argument values.
+#[derive(Debug)]
+pub struct FramePlan {
+    name: Option<String>,
+    deviant_event: Option<DeviantEventKind>,
+    timestamp_format: Option<TimestampFormat>,
+    /// Attributes that don't depend on the frame's arguments
+    static_attrs: EventAttributes,
+    params: Vec<ParamPlan>,
+    /// Indices of the parameters without an attribute key
+    unkeyed_params: Vec<usize>,
+    arg_opts: ArgOptions,
+    infer_units: bool,
+    /// The `.raw` keys of the keys with a unit suffix, when inferring units
+    raw_keys: BTreeMap<AttrKey, AttrKey>,
+    /// Keys built while handling the entry's frames
+    frame_keys: FrameKeys,
+    /// The nested `Format` strings of the entry's arguments, parsed on first use
+    formats: HashMap<String, FormatShape>,
+    coercions: Vec<AttrCoercion>,
+    message_attribute: bool,
+    /// The event name when there isn't an explicit one, and unnamed events
+    /// aren't named by their formatted string
+    fallback_name: Option<String>,
+    event_names: EventNamePolicy,
+}
 
-        let formatted_string = f.format_args(f.format(), f.args(), None).replace('\n', " ");
+/// The attribute keys of a table entry that are only known once its frames are handled.
+///
+/// Keys are interned for the lifetime of the process, so the keys found in formatted
+/// argument content, which the firmware can vary at will, are limited per table entry.
+#[derive(Debug, Default)]
+struct FrameKeys {
+    keys: HashMap<String, AttrKey>,
+    /// The number of distinct keys found in formatted argument content
+    extracted: usize,
+    limit_reached: bool,
+}
 
-        // NOTE: context manager will update these when doing rollover tracking
-        // and/or time conversions
-        let timestamp = if let Some(ts) = Timestamp::from_frame(&f) {
-            attributes.insert(
-                Self::internal_attr_key("timestamp.type"),
-                ts.typ_str().into(),
-            );
-            attributes.insert(Self::internal_attr_key("timestamp"), ts.as_u64().into());
-            if let Some(ns) = ts.as_nanoseconds() {
-                attributes.insert(Self::attr_key("timestamp"), ns.into());
+impl FrameKeys {
+    const MAX_EXTRACTED_KEYS: usize = 64;
+
+    /// A key derived from the table entry, like the fields of a `Format` argument
+    fn get(&mut self, key: &str) -> AttrKey {
+        if let Some(k) = self.keys.get(key) {
+            return *k;
+        }
+        let k = EventRecord::attr_key(key);
+        self.keys.insert(key.to_owned(), k);
+        k
+    }
+
+    /// A key found in formatted argument content, or None once the limit is reached
+    fn extracted(&mut self, key: &str) -> Option<AttrKey> {
+        if let Some(k) = self.keys.get(key) {
+            return Some(*k);
+        }
+        if self.extracted >= Self::MAX_EXTRACTED_KEYS {
+            if !mem::replace(&mut self.limit_reached, true) {
+                warn!(
+                    attr_key = key,
+                    limit = Self::MAX_EXTRACTED_KEYS,
+                    "Too many distinct attribute keys in formatted arguments, dropping the new ones"
+                );
             }
-            Some(ts)
+            return None;
+        }
+        self.extracted += 1;
+        Some(self.get(key))
+    }
+}
+
+/// How event names are normalized and truncated
+#[derive(Copy, Clone, Debug)]
+struct EventNamePolicy {
+    normalize: bool,
+    max_len: Option<usize>,
+}
+
+impl EventNamePolicy {
+    /// Length of the hash suffix of truncated names, including the '~' separator
+    const HASH_SUFFIX_LEN: usize = 9;
+
+    fn apply(&self, name: String) -> String {
+        let name = if self.normalize {
+            name.split_whitespace()
+                .flat_map(|word| iter::once(' ').chain(word.chars()))
+                .skip(1)
+                .filter(|c| !c.is_control())
+                .collect()
         } else {
-            None
+            name
         };
+        match self.max_len {
+            Some(max_len) if name.chars().count() > max_len => {
+                if max_len <= Self::HASH_SUFFIX_LEN {
+                    return name.chars().take(max_len).collect();
+                }
+                // The hash of the full name keeps distinct names distinct
+                let mut truncated: String =
+                    name.chars().take(max_len - Self::HASH_SUFFIX_LEN).collect();
+                truncated.push_str(&format!("~{:08x}", fnv1a_hash(name.as_bytes())));
+                truncated
+            }
+            _ => name,
+        }
+    }
+}
+
+/// How composite arguments are flattened into attributes
+#[derive(Copy, Clone, Debug)]
+struct ArgOptions {
+    max_array_elements: usize,
+    byte_encoding: ByteEncoding,
+    /// Separates key/value pairs in formatted string arguments
+    attr_separator: char,
+}
+
+#[derive(Debug)]
+struct ParamPlan {
+    /// Index into the frame's arguments
+    index: usize,
+    key: String,
+    attr_key: AttrKey,
+    ty: Type,
+    /// The display hint to apply, when enabled and supported
+    hint: Option<DisplayHint>,
+    /// The key of the formatted value, when there's a display hint
+    formatted_attr_key: Option<AttrKey>,
+}
+
+impl FramePlan {
+    pub fn new(f: &Frame<'_>, location: Option<&Location>) -> Result<Self, Error> {
+        Self::with_config(f, location, &PluginConfig::default())
+    }
+
+    pub fn with_config(
+        f: &Frame<'_>,
+        location: Option<&Location>,
+        cfg: &PluginConfig,
+    ) -> Result<Self, Error> {
+        let fragments = defmt_parser::parse(f.format(), ParserMode::ForwardsCompatible)?;
+
+        let mut static_attrs = EventAttributes::default();
+        let mut params = Vec::new();
+        let mut unkeyed_params = Vec::new();
+        let mut name = None;
+        let mut deviant_event = None;
+        let mut pending_attr_key = None;
+        let sep = cfg.attr_separator();
+
+        let timestamp_format = f
+            .timestamp_format()
+            .and_then(|fmt| TimestampFormat::parse(fmt, cfg.timestamp_composition));
 
         if let Some(loc) = location {
-            attributes.insert(
-                Self::attr_key("source.file"),
-                loc.file.display().to_string().into(),
+            let file = cfg.remap_source_path(&loc.file.display().to_string());
+            static_attrs.insert(
+                EventRecord::attr_key("source.uri"),
+                cfg.source_uri(&file, loc.line).into(),
             );
-            attributes.insert(Self::attr_key("source.line"), loc.line.into());
-            attributes.insert(Self::attr_key("source.module"), loc.module.clone().into());
-            attributes.insert(
-                Self::attr_key("source.uri"),
-                format!("file://{}:{}", loc.file.display(), loc.line).into(),
+            static_attrs.insert(EventRecord::attr_key("source.file"), file.into());
+            static_attrs.insert(EventRecord::attr_key("source.line"), loc.line.into());
+            static_attrs.insert(
+                EventRecord::attr_key("source.module"),
+                loc.module.clone().into(),
             );
+            if let Some(krate) = crate_name(&loc.module) {
+                static_attrs.insert(EventRecord::attr_key("source.crate"), krate.into());
+            }
         }
 
-        if let Some(level) = f.level() {
-            attributes.insert(Self::attr_key("level"), level.as_str().into());
-        }
-        attributes.insert(Self::internal_attr_key("table_index"), f.index().into());
-        attributes.insert(
-            Self::internal_attr_key("formatted_string"),
-            formatted_string.clone().into(),
+        let level = f
+            .level()
+            .map(|l| l.as_str())
+            .unwrap_or_else(|| cfg.println_level());
+        static_attrs.insert(EventRecord::attr_key("level"), level.into());
+        static_attrs.insert(
+            EventRecord::internal_attr_key("table_index"),
+            f.index().into(),
         );
 
-        let mut deviant_event = None;
-
         for (frag_idx, frag) in fragments.iter().enumerate() {
             match frag {
                 Fragment::Literal(l) => {
                     let mut s: &str = l.as_ref();
                     // Look for <event_name>:: convention
                     if frag_idx == 0 {
-                        if let Some((n, rem)) = s.split_once("::") {
-                            let ev_name = n.trim();
+                        let ev_name =
+                            if let Some((n, rem)) = s.split_once(cfg.event_name_delimiter()) {
+                                s = rem;
+                                Some(n.trim())
+                            } else if cfg.literal_event_names {
+                                // The whole literal before the first parameter is the name
+                                Some(mem::take(&mut s).trim()).filter(|n| !n.is_empty())
+                            } else {
+                                None
+                            };
+                        if let Some(ev_name) = ev_name {
                             deviant_event = DeviantEventKind::from_event_name(ev_name);
                             name = ev_name.to_owned().into();
-                            s = rem;
                         }
                     }
 
                     // Look for literal key/value pairs
-                    for (k, v) in extract_literal_key_value_pairs(s).into_iter() {
-                        attributes.insert(Self::attr_key(&k), v);
+                    for (k, v) in extract_literal_key_value_pairs(s, sep).into_iter() {
+                        static_attrs.insert(EventRecord::attr_key(&k), v);
                     }
 
                     // Look for attribute keys that'll have parameter values.
                     // defmt will yield literal-param pairs in order, so if we
                     // have a param value, it's literal key will be last
                     // (after any literal key/value pairs)
-                    s = s.trim_start_matches(',');
-                    if let Some((_, rest)) = s.rsplit_once(',') {
+                    s = s.trim_start_matches(sep);
+                    if let Some((_, rest)) = s.rsplit_once(sep) {
                         s = rest;
                     }
                     if let Some((k, _)) = s.split_once('=') {
@@ -274,145 +633,722 @@ impl EventRecord {
 
                         let mut key_type = key.clone();
                         key_type.push_str(".type");
-                        attributes.insert(
-                            Self::internal_attr_key(&key_type),
+                        static_attrs.insert(
+                            EventRecord::internal_attr_key(&key_type),
                             format!("{:?}", p.ty).to_lowercase().into(),
                         );
 
-                        // SAFETY: decoder/frame already checks args and params
-                        let arg = &f.args()[p.index];
-                        match arg_to_attr_val(arg) {
-                            Some(val) => {
-                                attributes.insert(Self::attr_key(&key), val);
-                            }
-                            None if deviant_event.is_none() => {
-                                warn!(
-                                    formatted_string,
-                                    attr_key = key,
-                                    ty = ?p.ty,
-                                    "Unsupported arg type"
-                                );
-                            }
-                            None => {
-                                // We have a deviant event, special case handle the UUID slices
-                                match key.as_ref() {
-                                    "mutator.id" | "mutation.id" => {
-                                        if let Arg::Slice(uuid_bytes) = arg {
-                                            if let Ok(uuid) = Uuid::try_from(uuid_bytes.clone()) {
-                                                debug!(attr_key = key, attr_val = %uuid, "Found Deviant attribute");
-                                                attributes.insert(
-                                                    Self::attr_key(&key),
-                                                    uuid_to_integer_attr_val(&uuid),
-                                                );
-                                            } else {
-                                                warn!(attr_key = key, "Invalid UUID bytes");
-                                            }
-                                        } else {
-                                            warn!(
-                                                attr_key = key,
-                                                "Unsupported argument type for Deviant event"
-                                            );
-                                        }
-                                    }
-                                    _ => (),
-                                }
-                            }
-                        }
+                        let hint = p.hint.clone().filter(|h| {
+                            cfg.display_hints
+                                && matches!(
+                                    h,
+                                    DisplayHint::Hexadecimal { .. }
+                                        | DisplayHint::Binary { .. }
+                                        | DisplayHint::Ascii
+                                        | DisplayHint::Debug
+                                )
+                        });
+                        params.push(ParamPlan {
+                            index: p.index,
+                            attr_key: EventRecord::attr_key(&key),
+                            formatted_attr_key: hint
+                                .as_ref()
+                                .map(|_| EventRecord::attr_key(&format!("{key}.formatted"))),
+                            key,
+                            ty: p.ty.clone(),
+                            hint,
+                        });
+                    } else {
+                        unkeyed_params.push(p.index);
                     }
                 }
             }
         }
 
-        // Use formatted string as event name if we don't have an explicit one
-        if let Some(event_name) = name {
-            attributes.insert(Self::attr_key("name"), event_name.into());
+        let event_names = EventNamePolicy {
+            normalize: cfg.normalize_event_names,
+            max_len: cfg.max_event_name_length,
+        };
+        let fallback_name = match cfg.unnamed_event_names {
+            UnnamedEventNames::FormatString if name.is_none() => {
+                Some(event_names.apply(f.format().replace('\n', " ")))
+            }
+            _ => None,
+        };
+
+        let raw_keys = if cfg.infer_units {
+            static_attrs
+                .iter()
+                .map(|(k, _)| *k)
+                .chain(params.iter().map(|p| p.attr_key))
+                .filter(|k| {
+                    !k.as_str()
+                        .starts_with(EventRecord::INTERNAL_ATTR_KEY_PREFIX)
+                        && KeyUnit::from_key(k.as_str()).is_some()
+                })
+                .map(|k| (k, raw_attr_key(k)))
+                .collect()
         } else {
-            attributes.insert(Self::attr_key("name"), formatted_string.clone().into());
-        }
+            BTreeMap::new()
+        };
 
-        Ok(EventRecord {
-            timestamp,
-            attributes,
+        Ok(Self {
+            name: name.map(|n| event_names.apply(n)),
+            fallback_name,
+            event_names,
+            deviant_event,
+            timestamp_format,
+            static_attrs,
+            params,
+            unkeyed_params,
+            infer_units: cfg.infer_units,
+            raw_keys,
+            frame_keys: FrameKeys::default(),
+            formats: HashMap::new(),
+            coercions: cfg.attribute_coercions.clone(),
+            message_attribute: cfg.message_attribute,
+            arg_opts: ArgOptions {
+                max_array_elements: cfg.max_array_elements(),
+                attr_separator: sep,
+                byte_encoding: cfg.byte_encoding,
+            },
         })
     }
-}
 
-// TODO - support nested variants and destructuring
-fn arg_to_attr_val(arg: &Arg) -> Option<AttrVal> {
-    Some(match arg {
-        Arg::Bool(v) => (*v).into(),
-        Arg::F32(v) => (*v).into(),
-        Arg::F64(v) => (*v).into(),
-        // NOTE: we only support i128 currently
-        Arg::Uxx(v) => BigInt::new_attr_val(*v as i128),
-        Arg::Ixx(v) => BigInt::new_attr_val(*v),
-        Arg::Str(v) => v.replace('\n', " ").into(),
-        Arg::IStr(v) => v.replace('\n', " ").into(),
-        Arg::Char(v) => v.to_string().into(),
-        Arg::Preformatted(v) => v.replace('\n', " ").into(),
-        Arg::Format { format: _, args } => {
-            // We only support single terminal types here currently
-            if args.len() == 1 {
-                return arg_to_attr_val(&args[0]);
-            } else {
-                return None;
+    pub fn record(&mut self, f: &Frame<'_>) -> EventRecord {
+        let mut attributes = self.static_attrs.clone();
+
+        let formatted_string = f.format_args(f.format(), f.args(), None).replace('\n', " ");
+
+        // Entry attributes take precedence over the per-frame ones
+        let mut insert_absent = |k: AttrKey, v: AttrVal| {
+            if !attributes.contains_key(k.as_str()) {
+                attributes.insert(k, v);
             }
-        }
-        Arg::FormatSlice { elements: _ } | Arg::FormatSequence { args: _ } | Arg::Slice(_) => {
-            return None
-        }
-    })
-}
+        };
 
-fn extract_literal_key_value_pairs(s: &str) -> BTreeMap<String, AttrVal> {
-    let mut pairs = BTreeMap::new();
-    let possible_pairs: Vec<&str> = s.split(',').collect();
-    for pair in possible_pairs.into_iter() {
-        let parts: Vec<&str> = pair.trim().split('=').map(|p| p.trim()).collect();
-        if parts.len() != 2
-            || parts[0].is_empty()
-            || parts[1].is_empty()
-            || parts[0].starts_with('.')
-        {
-            continue;
+        // NOTE: context manager will update these when doing rollover tracking
+        // and/or time conversions
+        let timestamp = self.timestamp_format.and_then(|ts_fmt| ts_fmt.timestamp(f));
+        if let Some(ts) = timestamp {
+            insert_absent(
+                EventRecord::internal_attr_key("timestamp.type"),
+                ts.typ_str().into(),
+            );
+            insert_absent(
+                EventRecord::internal_attr_key("timestamp"),
+                ts.as_u64().into(),
+            );
+            if let Some(ns) = ts.as_nanoseconds() {
+                insert_absent(EventRecord::attr_key("timestamp"), ns.into());
+            }
         }
+        insert_absent(
+            EventRecord::internal_attr_key("formatted_string"),
+            formatted_string.clone().into(),
+        );
 
-        let key: &str = parts[0];
-        let val_str: &str = parts[1];
-        if let Ok(val) = val_str.parse() {
-            pairs.insert(key.to_owned(), val);
-        }
-    }
-    pairs
-}
+        for param in self.params.iter() {
+            // SAFETY: decoder/frame already checks args and params
+            let arg = &f.args()[param.index];
+            let key = param.key.as_str();
+            match arg_to_attr_val(arg) {
+                Some(val) => {
+                    attributes.insert(param.attr_key, val);
+                    if let Arg::Preformatted(s) = arg {
+                        for (k, v) in
+                            preformatted_key_value_pairs(key, s, self.arg_opts.attr_separator)
+                        {
+                            if let Some(k) = self.frame_keys.extracted(&k) {
+                                attributes.insert(k, v);
+                            }
+                        }
+                    }
+                }
+                None if self.deviant_event.is_some()
+                    && matches!(key, "mutator.id" | "mutation.id") =>
+                {
+                    // We have a deviant event, special case handle the UUID slices
+                    if let Arg::Slice(uuid_bytes) = arg {
+                        if let Ok(uuid) = Uuid::try_from(uuid_bytes.clone()) {
+                            debug!(attr_key = key, attr_val = %uuid, "Found Deviant attribute");
+                            attributes.insert(param.attr_key, uuid_to_integer_attr_val(&uuid));
+                        } else {
+                            warn!(attr_key = key, "Invalid UUID bytes");
+                        }
+                    } else {
+                        warn!(
+                            attr_key = key,
+                            "Unsupported argument type for Deviant event"
+                        );
+                    }
+                }
+                None => {
+                    let mut flattener = ArgFlattener::new(f, self.arg_opts, &mut self.formats);
+                    flattener.arg(key, arg);
+                    if flattener.fields.is_empty() {
+                        warn!(
+                            formatted_string,
+                            attr_key = key,
+                            ty = ?param.ty,
+                            "Unsupported arg type"
+                        );
+                    }
+                    for (k, v) in flattener.fields.into_iter() {
+                        attributes.insert(self.frame_keys.get(&k), v);
+                    }
+                    for (k, v) in flattener.extracted.into_iter() {
+                        if let Some(k) = self.frame_keys.extracted(&k) {
+                            attributes.insert(k, v);
+                        }
+                    }
+                }
+            }
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-pub enum Timestamp {
-    Micros(u64),
-    Millis(u64),
-    Seconds(u64),
-    Ticks64(u64),
-    Ticks32(u32),
-    Ticks16(u16),
-    Ticks8(u8),
-}
+            if let Some(hint) = param.hint.as_ref() {
+                let formatted = f.format_args("{}", slice::from_ref(arg), Some(hint));
+                if let (DisplayHint::Ascii, Arg::Slice(_)) = (hint, arg) {
+                    // Byte string literal, b"..."
+                    let s = formatted
+                        .strip_prefix("b\"")
+                        .and_then(|s| s.strip_suffix('"'))
+                        .unwrap_or(&formatted);
+                    attributes.insert(param.attr_key, s.into());
+                } else if let Some(formatted_key) = param.formatted_attr_key {
+                    attributes.insert(formatted_key, formatted.into());
+                }
+            }
+        }
 
-impl Timestamp {
-    fn from_frame(f: &Frame<'_>) -> Option<Self> {
-        let fmt = f.timestamp_format()?;
+        // Key/value pairs in unkeyed core::fmt content (Display2Format/Debug2Format)
+        // are top-level attributes, but never override the keyed ones
+        for idx in self.unkeyed_params.iter() {
+            let mut strings = Vec::new();
+            collect_preformatted(&f.args()[*idx], &mut strings);
+            for (k, v) in strings
+                .into_iter()
+                .flat_map(|s| extract_literal_key_value_pairs(s, self.arg_opts.attr_separator))
+            {
+                let Some(attr_key) = self.frame_keys.extracted(&k) else {
+                    continue;
+                };
+                if !attributes.contains_key(attr_key.as_str()) {
+                    attributes.insert(attr_key, v);
+                }
+            }
+        }
 
-        // TODO: refactor so we don't spam the log every frame when unsupported
-        if f.timestamp_args().len() != 1 {
-            warn!("Unsupported timestamp format, only a single argument is supported");
-            return None;
+        if self.infer_units {
+            let typed: Vec<(AttrKey, AttrVal)> = attributes
+                .iter()
+                .filter(|(k, _)| {
+                    !k.as_str()
+                        .starts_with(EventRecord::INTERNAL_ATTR_KEY_PREFIX)
+                })
+                .filter_map(|(k, v)| Some((*k, KeyUnit::from_key(k.as_str())?.convert(v)?)))
+                .collect();
+            for (k, v) in typed.into_iter() {
+                if let Some(raw) = attributes.insert(k, v) {
+                    // Keys from the frame's arguments aren't known up front
+                    let raw_key = *self.raw_keys.entry(k).or_insert_with(|| raw_attr_key(k));
+                    attributes.insert(raw_key, raw);
+                }
+            }
         }
 
-        let ts = if let Some(ts) = ts_from_arg(&f.timestamp_args()[0]) {
-            ts
+        // The rendered log line, unless a parameter is already named 'message'
+        if self.message_attribute {
+            let message_key = EventRecord::attr_key("message");
+            if !attributes.contains_key(message_key.as_str()) {
+                attributes.insert(message_key, formatted_string.clone().into());
+            }
+        }
+
+        // Use formatted string as event name if we don't have an explicit one
+        if let Some(event_name) = self.name.as_ref().or(self.fallback_name.as_ref()) {
+            attributes.insert(EventRecord::attr_key("name"), event_name.clone().into());
         } else {
-            warn!("Unsupported timestamp format, only u64 compatible types are supported");
+            attributes.insert(
+                EventRecord::attr_key("name"),
+                self.event_names.apply(formatted_string).into(),
+            );
+        }
+
+        if !self.coercions.is_empty() {
+            // First matching rule wins
+            let coerced: Vec<(AttrKey, AttrVal)> = attributes
+                .iter()
+                .filter_map(|(k, v)| {
+                    let rule = self.coercions.iter().find(|c| c.matches(k.as_str()))?;
+                    match coerce_attr_val(rule, v) {
+                        Some(v) => Some((*k, v)),
+                        None => {
+                            debug!(attr_key = %k, attr_val = ?v, "Failed to coerce attribute");
+                            None
+                        }
+                    }
+                })
+                .collect();
+            for (k, v) in coerced.into_iter() {
+                attributes.insert(k, v);
+            }
+        }
+
+        EventRecord {
+            timestamp,
+            attributes,
+        }
+    }
+}
+
+/// The key of an attribute's value before its unit was applied
+fn raw_attr_key(k: AttrKey) -> AttrKey {
+    AttrKey::new(&format!("{}.raw", k.as_str()))
+}
+
+/// Flattens (possibly nested) arguments into dotted-key attributes, e.g. a `Msg { data, flags }`
+/// struct logged as `msg={}` yields `msg.data` and `msg.flags`.
+/// Fields without a name (tuple structs and sequences) use their position instead, and
+/// fieldless enum variants are recorded by name.
+/// Slice and array elements are indexed up to `max_array_elements`, and byte slices are
+/// encoded into a string, both along with a `len` attribute.
+struct ArgFlattener<'a, 't> {
+    frame: &'a Frame<'t>,
+    opts: ArgOptions,
+    formats: &'a mut HashMap<String, FormatShape>,
+    fields: Vec<(String, AttrVal)>,
+    /// Key/value pairs found in formatted (`core::fmt`) content
+    extracted: Vec<(String, AttrVal)>,
+}
+
+impl<'a, 't> ArgFlattener<'a, 't> {
+    fn new(
+        frame: &'a Frame<'t>,
+        opts: ArgOptions,
+        formats: &'a mut HashMap<String, FormatShape>,
+    ) -> Self {
+        Self {
+            frame,
+            opts,
+            formats,
+            fields: Vec::new(),
+            extracted: Vec::new(),
+        }
+    }
+
+    fn arg(&mut self, key: &str, arg: &Arg) {
+        match arg {
+            Arg::Format { format, args } => self.format(key, format, args),
+            Arg::FormatSequence { args } => {
+                for (idx, arg) in args.iter().enumerate() {
+                    self.arg(&format!("{key}.{idx}"), arg);
+                }
+            }
+            Arg::Slice(bytes) => {
+                self.fields
+                    .push((format!("{key}.len"), (bytes.len() as u64).into()));
+                self.fields.push((
+                    key.to_owned(),
+                    encode_bytes(bytes, self.opts.byte_encoding).into(),
+                ));
+            }
+            Arg::FormatSlice { elements } => {
+                self.fields
+                    .push((format!("{key}.len"), (elements.len() as u64).into()));
+                for (idx, elem) in elements
+                    .iter()
+                    .take(self.opts.max_array_elements)
+                    .enumerate()
+                {
+                    self.format(&format!("{key}.{idx}"), elem.format, &elem.args);
+                }
+            }
+            Arg::Preformatted(s) => {
+                self.fields
+                    .push((key.to_owned(), s.replace('\n', " ").into()));
+                self.extracted.extend(preformatted_key_value_pairs(
+                    key,
+                    s,
+                    self.opts.attr_separator,
+                ));
+            }
+            _ => {
+                if let Some(val) = arg_to_attr_val(arg) {
+                    self.fields.push((key.to_owned(), val));
+                }
+            }
+        }
+    }
+
+    fn format(&mut self, key: &str, format: &str, args: &[Arg]) {
+        if args.is_empty() {
+            // A fieldless enum variant (or unit struct), the format string is its name
+            let name = self.frame.format_args(format, args, None);
+            self.fields.push((key.to_owned(), name.into()));
+            return;
+        }
+        if !self.formats.contains_key(format) {
+            self.formats
+                .insert(format.to_owned(), FormatShape::parse(format));
+        }
+        let shape = &self.formats[format];
+        let bitflags = shape.bitflags;
+        let keys: Vec<String> = (0..args.len())
+            .map(|idx| match shape.field_names.get(&idx) {
+                Some(field) => format!("{key}.{field}"),
+                // A tuple struct or a bitflags value wrapping a single value
+                None if args.len() == 1 => key.to_owned(),
+                None => format!("{key}.{idx}"),
+            })
+            .collect();
+        for (key, arg) in keys.iter().zip(args.iter()) {
+            self.arg(key, arg);
+        }
+        if bitflags {
+            // The decoder resolves the names of the set flags from the table
+            let rendered = self.frame.format_args(format, args, None);
+            for flag in bitflag_names(&rendered) {
+                self.fields
+                    .push((format!("{key}.flags.{}", flag.to_lowercase()), true.into()));
+            }
+        }
+    }
+}
+
+/// What's needed from a nested `Format` string to flatten its arguments
+#[derive(Debug, Default)]
+struct FormatShape {
+    /// Whether it's the format string generated by `defmt::bitflags!`
+    bitflags: bool,
+    /// The field names of a derived `Format` string, by parameter index
+    field_names: BTreeMap<usize, String>,
+}
+
+impl FormatShape {
+    fn parse(format: &str) -> Self {
+        let Ok(fragments) = defmt_parser::parse(format, ParserMode::ForwardsCompatible) else {
+            return Self::default();
+        };
+        let bitflags = fragments.iter().any(|f| {
+            matches!(
+                f,
+                Fragment::Parameter(p) if matches!(p.hint, Some(DisplayHint::Bitflags { .. }))
+            )
+        });
+        Self {
+            bitflags,
+            field_names: format_field_names(&fragments),
+        }
+    }
+}
+
+/// The flag names of a rendered bitflags value (`TX_READY | RX_FULL`), nothing when
+/// it's `(empty)` or the flags are unknown and the raw value was rendered instead
+fn bitflag_names(rendered: &str) -> impl Iterator<Item = &str> {
+    rendered.split('|').map(str::trim).filter(|n| {
+        n.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && n.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+fn encode_bytes(bytes: &[u8], encoding: ByteEncoding) -> String {
+    match encoding {
+        ByteEncoding::Hex => bytes.iter().map(|b| format!("{b:02x}")).collect(),
+        ByteEncoding::Base64 => {
+            const ALPHABET: &[u8; 64] =
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+            let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+            for chunk in bytes.chunks(3) {
+                let b = [
+                    chunk[0],
+                    chunk.get(1).copied().unwrap_or(0),
+                    chunk.get(2).copied().unwrap_or(0),
+                ];
+                let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+                for i in 0..4 {
+                    if i <= chunk.len() {
+                        s.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+                    } else {
+                        s.push('=');
+                    }
+                }
+            }
+            s
+        }
+    }
+}
+
+/// Maps the parameter indices of a derived `Format` string (`Msg {{ data: {=u8}, flags: {=u16} }}`)
+/// to their field names
+fn format_field_names(fragments: &[Fragment<'_>]) -> BTreeMap<usize, String> {
+    let mut names = BTreeMap::new();
+    let mut pending_name = None;
+    for frag in fragments.iter() {
+        match frag {
+            Fragment::Literal(l) => {
+                pending_name = l
+                    .trim_end()
+                    .strip_suffix(':')
+                    .and_then(|s| {
+                        s.rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+                            .next()
+                    })
+                    .filter(|n| !n.is_empty())
+                    .map(str::to_owned);
+            }
+            Fragment::Parameter(p) => {
+                if let Some(name) = pending_name.take() {
+                    names.insert(p.index, name);
+                }
+            }
+        }
+    }
+    names
+}
+
+fn arg_to_attr_val(arg: &Arg) -> Option<AttrVal> {
+    Some(match arg {
+        Arg::Bool(v) => (*v).into(),
+        Arg::F32(v) => (*v).into(),
+        Arg::F64(v) => (*v).into(),
+        // Modality integers top out at i128, u128 values beyond that are
+        // kept as their decimal string rather than wrapping negative
+        Arg::Uxx(v) => match i128::try_from(*v) {
+            Ok(v) => BigInt::new_attr_val(v),
+            Err(_) => v.to_string().into(),
+        },
+        Arg::Ixx(v) => BigInt::new_attr_val(*v),
+        Arg::Str(v) => v.replace('\n', " ").into(),
+        Arg::IStr(v) => v.replace('\n', " ").into(),
+        Arg::Char(v) => v.to_string().into(),
+        Arg::Preformatted(v) => v.replace('\n', " ").into(),
+        // Composite types are flattened into multiple attributes
+        Arg::Format { .. }
+        | Arg::FormatSlice { elements: _ }
+        | Arg::FormatSequence { args: _ }
+        | Arg::Slice(_) => return None,
+    })
+}
+
+/// A unit inferred from an attribute key's suffix
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum KeyUnit {
+    /// A duration, in nanoseconds per unit
+    Duration(u64),
+    Hertz,
+    Percent,
+}
+
+impl KeyUnit {
+    fn from_key(key: &str) -> Option<Self> {
+        let (_, suffix) = key.rsplit_once('_')?;
+        Some(match suffix {
+            "ns" => KeyUnit::Duration(1),
+            "us" => KeyUnit::Duration(1_000),
+            "ms" => KeyUnit::Duration(1_000_000),
+            "s" => KeyUnit::Duration(1_000_000_000),
+            "hz" => KeyUnit::Hertz,
+            "pct" => KeyUnit::Percent,
+            _ => return None,
+        })
+    }
+
+    /// The typed value, or None if it can't or doesn't need to be converted
+    fn convert(self, val: &AttrVal) -> Option<AttrVal> {
+        let AttrVal::Integer(v) = val else {
             return None;
         };
+        Some(match self {
+            KeyUnit::Duration(ns_per_unit) => u64::try_from(*v)
+                .ok()?
+                .checked_mul(ns_per_unit)
+                .map(Nanoseconds::from)?
+                .into(),
+            KeyUnit::Hertz | KeyUnit::Percent => (*v as f64).into(),
+        })
+    }
+}
+
+/// 32-bit FNV-1a, stable across runs and platforms
+fn fnv1a_hash(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, b| {
+        (hash ^ u32::from(*b)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// The crate of a module path, i.e. the first path segment, without any
+/// generic impl angle brackets or raw identifier prefix
+fn crate_name(module: &str) -> Option<&str> {
+    let first = module.split("::").next()?.trim_start_matches('<');
+    let first = first.strip_prefix("r#").unwrap_or(first).trim();
+    (!first.is_empty()).then_some(first)
+}
+
+/// Converts, then scales, an attribute value, None if it can't be coerced
+fn coerce_attr_val(rule: &AttrCoercion, val: &AttrVal) -> Option<AttrVal> {
+    let val = match rule.to {
+        Some(ty) => coerce_attr_type(ty, val)?,
+        None => val.clone(),
+    };
+    let Some(scale) = rule.scale else {
+        return Some(val);
+    };
+    let v = match &val {
+        AttrVal::Integer(v) => *v as f64,
+        AttrVal::BigInt(v) => {
+            let v: &i128 = v.as_ref();
+            *v as f64
+        }
+        AttrVal::Float(v) => v.0,
+        _ => return None,
+    };
+    Some((v * scale.0).into())
+}
+
+fn coerce_attr_type(ty: AttrType, val: &AttrVal) -> Option<AttrVal> {
+    Some(match (ty, val) {
+        (AttrType::Int, AttrVal::Integer(_) | AttrVal::BigInt(_)) => val.clone(),
+        (AttrType::Int, AttrVal::Float(v)) => (v.0 as i64).into(),
+        (AttrType::Int, AttrVal::Bool(v)) => i64::from(*v).into(),
+        (AttrType::Int, AttrVal::String(s)) => {
+            let s = s.trim();
+            match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                Some(hex) => BigInt::new_attr_val(i128::from_str_radix(hex, 16).ok()?),
+                None => BigInt::new_attr_val(s.parse::<i128>().ok()?),
+            }
+        }
+        (AttrType::HexInt, AttrVal::String(s)) => {
+            let s = s.trim();
+            let hex = s
+                .strip_prefix("0x")
+                .or_else(|| s.strip_prefix("0X"))
+                .unwrap_or(s);
+            BigInt::new_attr_val(i128::from_str_radix(hex, 16).ok()?)
+        }
+        (AttrType::HexInt, AttrVal::Integer(_) | AttrVal::BigInt(_)) => val.clone(),
+        (AttrType::Float, AttrVal::Float(_)) => val.clone(),
+        (AttrType::Float, AttrVal::Integer(v)) => (*v as f64).into(),
+        (AttrType::Float, AttrVal::BigInt(v)) => {
+            let v: &i128 = v.as_ref();
+            (*v as f64).into()
+        }
+        (AttrType::Float, AttrVal::String(s)) => s.trim().parse::<f64>().ok()?.into(),
+        (AttrType::Bool, AttrVal::Bool(_)) => val.clone(),
+        (AttrType::Bool, AttrVal::Integer(v)) => (*v != 0).into(),
+        (AttrType::Bool, AttrVal::String(s)) => match s.trim().to_lowercase().as_ref() {
+            "true" | "yes" | "on" | "1" => true.into(),
+            "false" | "no" | "off" | "0" => false.into(),
+            _ => return None,
+        },
+        (AttrType::String, AttrVal::String(_)) => val.clone(),
+        (AttrType::String, AttrVal::Integer(v)) => v.to_string().into(),
+        (AttrType::String, AttrVal::BigInt(v)) => {
+            let v: &i128 = v.as_ref();
+            v.to_string().into()
+        }
+        (AttrType::String, AttrVal::Float(v)) => v.0.to_string().into(),
+        (AttrType::String, AttrVal::Bool(v)) => v.to_string().into(),
+        _ => return None,
+    })
+}
+
+/// Literal key/value pairs found in a `core::fmt` formatted argument, nested under its key
+fn preformatted_key_value_pairs<'a>(
+    key: &'a str,
+    s: &str,
+    sep: char,
+) -> impl Iterator<Item = (String, AttrVal)> + 'a {
+    extract_literal_key_value_pairs(s, sep)
+        .into_iter()
+        .map(move |(k, v)| (format!("{key}.{k}"), v))
+}
+
+/// Collects the `core::fmt` formatted strings of an argument, including those
+/// nested in `Format` implementations
+fn collect_preformatted<'a>(arg: &'a Arg, out: &mut Vec<&'a str>) {
+    match arg {
+        Arg::Preformatted(s) => out.push(s),
+        Arg::Format { args, .. } | Arg::FormatSequence { args } => {
+            for arg in args.iter() {
+                collect_preformatted(arg, out);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn extract_literal_key_value_pairs(s: &str, sep: char) -> BTreeMap<String, AttrVal> {
+    let mut pairs = BTreeMap::new();
+    let possible_pairs: Vec<&str> = s.split(sep).collect();
+    for pair in possible_pairs.into_iter() {
+        let parts: Vec<&str> = pair.trim().split('=').map(|p| p.trim()).collect();
+        if parts.len() != 2
+            || parts[0].is_empty()
+            || parts[1].is_empty()
+            || parts[0].starts_with('.')
+        {
+            continue;
+        }
+
+        let key: &str = parts[0];
+        let val_str: &str = parts[1];
+        if let Ok(val) = val_str.parse() {
+            pairs.insert(key.to_owned(), val);
+        }
+    }
+    pairs
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Timestamp {
+    Micros(u64),
+    Millis(u64),
+    Seconds(u64),
+    Ticks64(u64),
+    Ticks32(u32),
+    Ticks16(u16),
+    Ticks8(u8),
+}
+
+/// The timestamp format of a defmt table, resolved once per table entry
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum TimestampFormat {
+    Micros,
+    Millis,
+    Seconds,
+    Ticks64,
+    Ticks32,
+    Ticks16,
+    Ticks8,
+    /// 64-bit ticks composed of two arguments, the low one being `low_bits` wide
+    Composed {
+        high_first: bool,
+        low_bits: u32,
+    },
+}
+
+impl TimestampFormat {
+    fn parse(fmt: &str, composition: TimestampComposition) -> Option<Self> {
+        let params: Vec<_> = defmt_parser::parse(fmt, ParserMode::ForwardsCompatible)
+            .map(|frags| {
+                frags
+                    .into_iter()
+                    .filter_map(|f| match f {
+                        Fragment::Parameter(p) => Some(p),
+                        Fragment::Literal(_) => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if composition != TimestampComposition::Single {
+            return Self::parse_composed(&params, composition);
+        }
+
+        if params.len() != 1 {
+            warn!("Unsupported timestamp format, only a single argument is supported");
+            return None;
+        }
 
         let ts_fmt = fmt
             .trim_end_matches('}')
@@ -420,30 +1356,112 @@ impl Timestamp {
             .map(|(_, rhs)| rhs);
 
         Some(match ts_fmt {
-            Some("us") | Some("tus") => Timestamp::Micros(ts),
-            Some("ms") | Some("tms") => Timestamp::Millis(ts),
-            Some("ts") => Timestamp::Seconds(ts),
+            Some("us") | Some("tus") => TimestampFormat::Micros,
+            Some("ms") | Some("tms") => TimestampFormat::Millis,
+            Some("ts") => TimestampFormat::Seconds,
             Some(_) => {
                 warn!("Unsupported timestamp format hint, only us, ms, ts, tms, and tus are supported");
                 return None;
             }
             None => {
                 if fmt.contains("u8") {
-                    let ts8 = u8::try_from(ts).expect("Type mismatch, defmt::timestamp! macro should have produced a compiler error");
-                    Timestamp::Ticks8(ts8)
+                    TimestampFormat::Ticks8
                 } else if fmt.contains("u16") {
-                    let ts16 = u16::try_from(ts).expect("Type mismatch, defmt::timestamp! macro should have produced a compiler error");
-                    Timestamp::Ticks16(ts16)
+                    TimestampFormat::Ticks16
                 } else if fmt.contains("u32") {
-                    let ts32 = u32::try_from(ts).expect("Type mismatch, defmt::timestamp! macro should have produced a compiler error");
-                    Timestamp::Ticks32(ts32)
+                    TimestampFormat::Ticks32
                 } else {
-                    Timestamp::Ticks64(ts)
+                    TimestampFormat::Ticks64
                 }
             }
         })
     }
 
+    fn parse_composed(
+        params: &[defmt_parser::Parameter],
+        composition: TimestampComposition,
+    ) -> Option<Self> {
+        if params.len() != 2 {
+            warn!(%composition, "Unsupported timestamp format, two arguments are required");
+            return None;
+        }
+        let high_first = composition == TimestampComposition::HighLow;
+        let low_index = if high_first { 1 } else { 0 };
+        let low = params.iter().find(|p| p.index == low_index)?;
+        let low_bits = match low.ty {
+            Type::U8 => 8,
+            Type::U16 => 16,
+            Type::U32 => 32,
+            _ => {
+                warn!(
+                    %composition,
+                    "Unsupported timestamp format, the low argument must be a u8, u16 or u32"
+                );
+                return None;
+            }
+        };
+        Some(TimestampFormat::Composed {
+            high_first,
+            low_bits,
+        })
+    }
+
+    fn timestamp(self, f: &Frame<'_>) -> Option<Timestamp> {
+        let ts = if let Some(ts) = f.timestamp_args().first().and_then(ts_from_arg) {
+            ts
+        } else {
+            warn!("Unsupported timestamp format, only u64 compatible types are supported");
+            return None;
+        };
+
+        Some(match self {
+            TimestampFormat::Micros => Timestamp::Micros(ts),
+            TimestampFormat::Millis => Timestamp::Millis(ts),
+            TimestampFormat::Seconds => Timestamp::Seconds(ts),
+            TimestampFormat::Ticks64 => Timestamp::Ticks64(ts),
+            TimestampFormat::Ticks32 => {
+                let ts32 = u32::try_from(ts).expect(
+                    "Type mismatch, defmt::timestamp! macro should have produced a compiler error",
+                );
+                Timestamp::Ticks32(ts32)
+            }
+            TimestampFormat::Ticks16 => {
+                let ts16 = u16::try_from(ts).expect(
+                    "Type mismatch, defmt::timestamp! macro should have produced a compiler error",
+                );
+                Timestamp::Ticks16(ts16)
+            }
+            TimestampFormat::Ticks8 => {
+                let ts8 = u8::try_from(ts).expect(
+                    "Type mismatch, defmt::timestamp! macro should have produced a compiler error",
+                );
+                Timestamp::Ticks8(ts8)
+            }
+            TimestampFormat::Composed {
+                high_first,
+                low_bits,
+            } => {
+                let (high, low) = match f.timestamp_args().get(1).and_then(ts_from_arg) {
+                    Some(second) if high_first => (ts, second),
+                    Some(second) => (second, ts),
+                    None => {
+                        warn!(
+                            "Unsupported timestamp format, only u64 compatible types are supported"
+                        );
+                        return None;
+                    }
+                };
+                if high.leading_zeros() < low_bits {
+                    warn!(high, low_bits, "Composed timestamp overflows 64 bits");
+                    return None;
+                }
+                Timestamp::Ticks64((high << low_bits) | low)
+            }
+        })
+    }
+}
+
+impl Timestamp {
     pub(crate) fn supports_rollover_tracking(&self) -> bool {
         use Timestamp::*;
         match self {
@@ -545,6 +1563,7 @@ fn uuid_to_integer_attr_val(u: &Uuid) -> AttrVal {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{glob::Glob, ScaleFactor};
     use defmt_decoder::{Table, TableEntry, Tag};
     use pretty_assertions::assert_eq;
     use std::path::PathBuf;
@@ -565,14 +1584,14 @@ mod test {
         let loc = Location {
             file: PathBuf::from("/foo/src/main.rs"),
             line: 12,
-            module: "bar".to_owned(),
+            module: "bar::baz".to_owned(),
         };
         let event_record = EventRecord::from_frame(frame, Some(&loc)).unwrap();
         assert_eq!(event_record.event_name(), Some("Hello, world!"));
         let attrs = event_record
             .attributes
             .into_iter()
-            .map(|(k, v)| (k, v))
+            .map(|(k, v)| (k.to_string(), v))
             .collect::<Vec<_>>();
         dbg!(&attrs);
         assert_eq!(
@@ -602,6 +1621,10 @@ mod test {
                     "event.name".to_owned(),
                     AttrVal::String("Hello, world!".to_owned().into())
                 ),
+                (
+                    "event.source.crate".to_owned(),
+                    AttrVal::String("bar".to_owned().into())
+                ),
                 (
                     "event.source.file".to_owned(),
                     AttrVal::String("/foo/src/main.rs".to_owned().into())
@@ -609,7 +1632,7 @@ mod test {
                 ("event.source.line".to_owned(), AttrVal::Integer(12)),
                 (
                     "event.source.module".to_owned(),
-                    AttrVal::String("bar".to_owned().into())
+                    AttrVal::String("bar::baz".to_owned().into())
                 ),
                 (
                     "event.source.uri".to_owned(),
@@ -642,7 +1665,7 @@ mod test {
         let attrs = event_record
             .attributes
             .into_iter()
-            .map(|(k, v)| (k, v))
+            .map(|(k, v)| (k.to_string(), v))
             .collect::<Vec<_>>();
         dbg!(&attrs);
         assert_eq!(
@@ -681,7 +1704,7 @@ mod test {
         let attrs = event_record
             .attributes
             .into_iter()
-            .map(|(k, v)| (k, v))
+            .map(|(k, v)| (k.to_string(), v))
             .collect::<Vec<_>>();
         dbg!(&attrs);
         assert_eq!(
@@ -713,7 +1736,7 @@ mod test {
         let attrs = event_record
             .attributes
             .into_iter()
-            .map(|(k, v)| (k, v))
+            .map(|(k, v)| (k.to_string(), v))
             .collect::<Vec<_>>();
         dbg!(&attrs);
         assert_eq!(attrs[0], ("event.arg_cnt".to_owned(), 0_u8.into()));
@@ -728,6 +1751,679 @@ mod test {
         assert_eq!(attrs[9], ("event.task".to_owned(), "blinky_blue".into()));
     }
 
+    #[test]
+    fn destructured_format_args() {
+        let entries = vec![
+            TableEntry::new_without_symbol(Tag::Info, "rx:: msg={=?}, hdr={=?}".to_owned()),
+            TableEntry::new_without_symbol(
+                Tag::Derived,
+                "Msg {{ data: {=u8:?}, flags: {=u16:?} }}".to_owned(),
+            ),
+            TableEntry::new_without_symbol(Tag::Derived, "Hdr({=u8:?}, {=bool:?})".to_owned()),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+            1, 0, // Msg format index
+            7, // data
+            2, 1, // flags
+            2, 0, // Hdr format index
+            3, // .0
+            1, // .1
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = EventRecord::from_frame(frame, None).unwrap();
+        assert_eq!(event_record.event_name(), Some("rx"));
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.msg"), None);
+        assert_eq!(attrs.get("event.msg.data"), Some(&7_u8.into()));
+        assert_eq!(attrs.get("event.msg.flags"), Some(&258_u16.into()));
+        assert_eq!(attrs.get("event.hdr.0"), Some(&3_u8.into()));
+        assert_eq!(attrs.get("event.hdr.1"), Some(&true.into()));
+    }
+
+    #[test]
+    fn single_field_format_args() {
+        let entries = vec![
+            TableEntry::new_without_symbol(Tag::Info, "tx:: id={=?} seq={=?}".to_owned()),
+            TableEntry::new_without_symbol(Tag::Derived, "Id {{ raw: {=u8:?} }}".to_owned()),
+            TableEntry::new_without_symbol(Tag::Derived, "Seq({=u16:?})".to_owned()),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let frame_bytes = |id: u8| {
+            [
+                0, 0, // index
+                1, 0,  // Id format index
+                id, // raw
+                2, 0, // Seq format index
+                9, 0, // .0
+            ]
+        };
+        let bytes = frame_bytes(4);
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let mut plan = FramePlan::new(&frame, None).unwrap();
+        let event_record = plan.record(&frame);
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.id"), None);
+        assert_eq!(attrs.get("event.id.raw"), Some(&4_u8.into()));
+        assert_eq!(attrs.get("event.seq"), Some(&9_u16.into()));
+
+        // The nested format strings are only parsed once
+        let bytes = frame_bytes(5);
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = plan.record(&frame);
+        assert_eq!(
+            event_record.attributes().get("event.id.raw"),
+            Some(&5_u8.into())
+        );
+        assert_eq!(plan.formats.len(), 2);
+    }
+
+    #[test]
+    fn format_slice_args() {
+        let entries = vec![
+            TableEntry::new_without_symbol(Tag::Info, "samples={=[?]}".to_owned()),
+            TableEntry::new_without_symbol(
+                Tag::Derived,
+                "Sample {{ ch: {=u8:?}, val: {=u16:?} }}".to_owned(),
+            ),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+            3, 0, 0, 0, // length of the slice
+            1, 0, // Sample format index
+            1, 10, 0, // [0]
+            2, 20, 0, // [1]
+            3, 30, 0, // [2]
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let cfg = PluginConfig {
+            max_array_elements: 2.into(),
+            ..Default::default()
+        };
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.samples.len"), Some(&3_u64.into()));
+        assert_eq!(attrs.get("event.samples.0.ch"), Some(&1_u8.into()));
+        assert_eq!(attrs.get("event.samples.0.val"), Some(&10_u16.into()));
+        assert_eq!(attrs.get("event.samples.1.ch"), Some(&2_u8.into()));
+        assert_eq!(attrs.get("event.samples.1.val"), Some(&20_u16.into()));
+        assert_eq!(attrs.get("event.samples.2.ch"), None);
+    }
+
+    #[test]
+    fn byte_slice_args() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Info,
+            "rx:: payload={=[u8]}".to_owned(),
+        )];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+            4, 0, 0, 0, // length of the slice
+            0xDE, 0xAD, 0xBE, 0xEF, // payload
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = FramePlan::new(&frame, None).unwrap().record(&frame);
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.payload"), Some(&"deadbeef".into()));
+        assert_eq!(attrs.get("event.payload.len"), Some(&4_u64.into()));
+
+        let cfg = PluginConfig {
+            byte_encoding: ByteEncoding::Base64,
+            ..Default::default()
+        };
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.payload"), Some(&"3q2+7w==".into()));
+        assert_eq!(attrs.get("event.payload.len"), Some(&4_u64.into()));
+
+        assert_eq!(encode_bytes(b"", ByteEncoding::Base64), "");
+        assert_eq!(encode_bytes(b"f", ByteEncoding::Base64), "Zg==");
+        assert_eq!(encode_bytes(b"fo", ByteEncoding::Base64), "Zm8=");
+        assert_eq!(encode_bytes(b"foobar", ByteEncoding::Base64), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn bitflags_args() {
+        let entries = vec![
+            TableEntry::new_without_symbol(Tag::Info, "status={=?}".to_owned()),
+            TableEntry::new_without_symbol(
+                Tag::Bitflags,
+                "{=u8:__internal_bitflags_Status@fw@0@fw}".to_owned(),
+            ),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+            1, 0,     // Status format index
+            0b101, // bits
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = EventRecord::from_frame(frame, None).unwrap();
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.status"), Some(&0b101_u8.into()));
+
+        assert!(FormatShape::parse("{=u8:__internal_bitflags_Status@fw@0@fw}").bitflags);
+        assert!(!FormatShape::parse("{=u8:#x}").bitflags);
+        assert_eq!(
+            bitflag_names("TX_READY | RX_FULL").collect::<Vec<_>>(),
+            vec!["TX_READY", "RX_FULL"]
+        );
+        assert_eq!(bitflag_names("(empty)").count(), 0);
+        assert_eq!(bitflag_names("5").count(), 0);
+    }
+
+    #[test]
+    fn enum_variant_args() {
+        let entries = vec![
+            TableEntry::new_without_symbol(Tag::Info, "link:: state={=?}".to_owned()),
+            TableEntry::new_without_symbol(
+                Tag::Derived,
+                "Idle|Connected|Error({=u8:?})".to_owned(),
+            ),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+            1, 0, // State format index
+            1, // discriminant
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = EventRecord::from_frame(frame, None).unwrap();
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.state"), Some(&"Connected".into()));
+    }
+
+    #[test]
+    fn preformatted_key_value_args() {
+        let entries = vec![
+            TableEntry::new_without_symbol(Tag::Info, "uart:: cfg={=?}, {=?}".to_owned()),
+            TableEntry::new_without_symbol(Tag::Derived, "{=__internal_Display}".to_owned()),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let cfg = b"baud=9600, parity=none";
+        let stats = b"rx=10, tx=20, cfg.baud=1";
+        let mut bytes = vec![
+            0, 0, // index
+            1, 0, // Display2Format format index
+        ];
+        bytes.extend_from_slice(&(cfg.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(cfg);
+        bytes.extend_from_slice(&[1, 0]);
+        bytes.extend_from_slice(&(stats.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(stats);
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = EventRecord::from_frame(frame, None).unwrap();
+        let attrs = event_record.attributes();
+        assert_eq!(
+            attrs.get("event.cfg"),
+            Some(&"baud=9600, parity=none".into())
+        );
+        assert_eq!(attrs.get("event.cfg.baud"), Some(&9600_u16.into()));
+        assert_eq!(attrs.get("event.cfg.parity"), Some(&"none".into()));
+        assert_eq!(attrs.get("event.rx"), Some(&10_u8.into()));
+        assert_eq!(attrs.get("event.tx"), Some(&20_u8.into()));
+    }
+
+    #[test]
+    fn extracted_key_limit() {
+        let entries = vec![
+            TableEntry::new_without_symbol(Tag::Info, "stats:: {=?}".to_owned()),
+            TableEntry::new_without_symbol(Tag::Derived, "{=__internal_Display}".to_owned()),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let frame_bytes = |content: &str| {
+            let mut bytes = vec![
+                0, 0, // index
+                1, 0, // Display2Format format index
+            ];
+            bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(content.as_bytes());
+            bytes
+        };
+
+        let bytes = frame_bytes("first=1");
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let mut plan = FramePlan::new(&frame, None).unwrap();
+        for idx in 0..FrameKeys::MAX_EXTRACTED_KEYS + 10 {
+            let bytes = frame_bytes(&format!("runtime_key_{idx}=1"));
+            let (frame, _) = table.decode(&bytes).unwrap();
+            let attrs = plan.record(&frame).attributes;
+            let key = format!("event.runtime_key_{idx}");
+            if idx < FrameKeys::MAX_EXTRACTED_KEYS {
+                assert_eq!(attrs.get(&key), Some(&1_u8.into()));
+            } else {
+                assert_eq!(attrs.get(&key), None);
+            }
+        }
+        assert_eq!(plan.frame_keys.extracted, FrameKeys::MAX_EXTRACTED_KEYS);
+
+        // Keys already seen are still extracted
+        let bytes = frame_bytes("runtime_key_0=2");
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let attrs = plan.record(&frame).attributes;
+        assert_eq!(attrs.get("event.runtime_key_0"), Some(&2_u8.into()));
+    }
+
+    #[test]
+    fn display_hints() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Info,
+            "reg:: addr={=u16:#x}, mask={=u8:b}, label={=[u8]:a}, cnt={=u8}".to_owned(),
+        )];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+            0x1F, 0,     // addr
+            0b101, // mask
+            2, 0, 0, 0, // length of the slice
+            b'o', b'k', // label
+            3,    // cnt
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+
+        let attrs = FramePlan::new(&frame, None)
+            .unwrap()
+            .record(&frame)
+            .attributes()
+            .clone();
+        assert_eq!(attrs.get("event.addr"), Some(&0x1F_u16.into()));
+        assert_eq!(attrs.get("event.addr.formatted"), None);
+        assert_eq!(attrs.get("event.label"), Some(&"6f6b".into()));
+
+        let cfg = PluginConfig {
+            display_hints: true,
+            ..Default::default()
+        };
+        let attrs = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame)
+            .attributes()
+            .clone();
+        assert_eq!(attrs.get("event.addr"), Some(&0x1F_u16.into()));
+        assert_eq!(attrs.get("event.addr.formatted"), Some(&"0x1f".into()));
+        assert_eq!(attrs.get("event.mask"), Some(&0b101_u8.into()));
+        assert_eq!(attrs.get("event.mask.formatted"), Some(&"101".into()));
+        assert_eq!(attrs.get("event.label"), Some(&"ok".into()));
+        assert_eq!(attrs.get("event.label.len"), Some(&2_u64.into()));
+        assert_eq!(attrs.get("event.cnt"), Some(&3_u8.into()));
+        assert_eq!(attrs.get("event.cnt.formatted"), None);
+    }
+
+    #[test]
+    fn println_level() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Println,
+            "booted".to_owned(),
+        )];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = EventRecord::from_frame(frame, None).unwrap();
+        assert_eq!(event_record.event_name(), Some("booted"));
+        assert_eq!(
+            event_record.attributes().get("event.level"),
+            Some(&"println".into())
+        );
+
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let cfg = PluginConfig {
+            println_level: "info".to_owned().into(),
+            ..Default::default()
+        };
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        assert_eq!(
+            event_record.attributes().get("event.level"),
+            Some(&"info".into())
+        );
+    }
+
+    #[test]
+    fn key_value_syntax() {
+        let entries = vec![
+            TableEntry::new_without_symbol(
+                Tag::Info,
+                "motor # mode=run; dir=cw; speed={=u16}".to_owned(),
+            ),
+            TableEntry::new_without_symbol(Tag::Info, "Motor started {=u16}".to_owned()),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let cfg = PluginConfig {
+            event_name_delimiter: "#".to_owned().into(),
+            attr_separator: ';'.into(),
+            literal_event_names: true,
+            ..Default::default()
+        };
+
+        let bytes = [
+            0, 0, // index
+            10, 0, // speed
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        let attrs = event_record.attributes();
+        assert_eq!(event_record.event_name(), Some("motor"));
+        assert_eq!(attrs.get("event.mode"), Some(&"run".into()));
+        assert_eq!(attrs.get("event.dir"), Some(&"cw".into()));
+        assert_eq!(attrs.get("event.speed"), Some(&10_u16.into()));
+
+        let bytes = [
+            1, 0, // index
+            10, 0, // unkeyed
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        assert_eq!(event_record.event_name(), Some("Motor started"));
+
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = EventRecord::from_frame(frame, None).unwrap();
+        assert_eq!(event_record.event_name(), Some("Motor started 10"));
+    }
+
+    #[test]
+    fn infer_units() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Info,
+            "pwm:: timeout_ms=5, duty_pct={=u8}, clk_hz={=u32}, mode_s={=str}".to_owned(),
+        )];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0,  // index
+            50, // duty_pct
+            0x40, 0x42, 0x0F, 0, // clk_hz
+            2, 0, 0, 0, // length of the string
+            b'o', b'n', // mode_s
+        ];
+        let cfg = PluginConfig {
+            infer_units: true,
+            ..Default::default()
+        };
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let mut plan = FramePlan::with_config(&frame, None, &cfg).unwrap();
+        // The raw keys are resolved with the plan
+        assert_eq!(
+            plan.raw_keys
+                .values()
+                .map(AttrKey::as_str)
+                .collect::<Vec<_>>(),
+            vec![
+                "event.clk_hz.raw",
+                "event.duty_pct.raw",
+                "event.mode_s.raw",
+                "event.timeout_ms.raw"
+            ]
+        );
+        let event_record = plan.record(&frame);
+        let attrs = event_record.attributes();
+        assert_eq!(
+            attrs.get("event.timeout_ms"),
+            Some(&AttrVal::Timestamp(5_000_000_u64.into()))
+        );
+        assert_eq!(attrs.get("event.timeout_ms.raw"), Some(&5_u8.into()));
+        assert_eq!(attrs.get("event.duty_pct"), Some(&50.0_f64.into()));
+        assert_eq!(attrs.get("event.duty_pct.raw"), Some(&50_u8.into()));
+        assert_eq!(attrs.get("event.clk_hz"), Some(&1_000_000.0_f64.into()));
+        assert_eq!(attrs.get("event.mode_s"), Some(&"on".into()));
+        assert_eq!(attrs.get("event.mode_s.raw"), None);
+
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = EventRecord::from_frame(frame, None).unwrap();
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.timeout_ms"), Some(&5_u8.into()));
+        assert_eq!(attrs.get("event.timeout_ms.raw"), None);
+    }
+
+    #[test]
+    fn event_name_policy() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Info,
+            "Temperature  is\t{=u8} degrees".to_owned(),
+        )];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [0, 0, 21];
+        let (frame, _) = table.decode(&bytes).unwrap();
+
+        let cfg = PluginConfig {
+            normalize_event_names: true,
+            ..Default::default()
+        };
+        let mut plan = FramePlan::with_config(&frame, None, &cfg).unwrap();
+        assert_eq!(
+            plan.record(&frame).event_name(),
+            Some("Temperature is 21 degrees")
+        );
+
+        let cfg = PluginConfig {
+            unnamed_event_names: UnnamedEventNames::FormatString,
+            normalize_event_names: true,
+            ..Default::default()
+        };
+        let mut plan = FramePlan::with_config(&frame, None, &cfg).unwrap();
+        assert_eq!(
+            plan.record(&frame).event_name(),
+            Some("Temperature is {=u8} degrees")
+        );
+
+        let policy = EventNamePolicy {
+            normalize: false,
+            max_len: 16.into(),
+        };
+        let truncated = policy.apply("Temperature is 21 degrees".to_owned());
+        assert_eq!(truncated.chars().count(), 16);
+        assert!(truncated.starts_with("Tempera~"));
+        assert_ne!(
+            truncated,
+            policy.apply("Temperature is 22 degrees".to_owned())
+        );
+        assert_eq!(policy.apply("Temperature".to_owned()), "Temperature");
+        let policy = EventNamePolicy {
+            normalize: false,
+            max_len: 4.into(),
+        };
+        assert_eq!(policy.apply("Temperature".to_owned()), "Temp");
+    }
+
+    #[test]
+    fn source_crate_name() {
+        assert_eq!(crate_name("app::net::tcp"), Some("app"));
+        assert_eq!(crate_name("app"), Some("app"));
+        assert_eq!(crate_name("<hal::gpio::Pin as app::Led>"), Some("hal"));
+        assert_eq!(crate_name("r#async::io"), Some("async"));
+        assert_eq!(crate_name(""), None);
+    }
+
+    #[test]
+    fn message_attribute() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Info,
+            "motor:: Motor started, rpm={=u16}".to_owned(),
+        )];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+            0xE8, 0x03, // rpm
+        ];
+        let cfg = PluginConfig {
+            message_attribute: true,
+            ..Default::default()
+        };
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        assert_eq!(event_record.event_name(), Some("motor"));
+        assert_eq!(
+            event_record.attributes().get("event.message"),
+            Some(&"motor:: Motor started, rpm=1000".into())
+        );
+
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = EventRecord::from_frame(frame, None).unwrap();
+        assert_eq!(event_record.attributes().get("event.message"), None);
+    }
+
+    #[test]
+    fn attribute_coercions() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Info,
+            "status:: status={=str}, temp_raw={=u16}, ready={=u8}, count={=str}, id={=str}"
+                .to_owned(),
+        )];
+        let table = Table::new_test_table(None, entries);
+        let bytes = [
+            0, 0, // index
+            4, 0, 0, 0, // length of the string
+            b'0', b'x', b'1', b'F', // status
+            0xEA, 0x00, // temp_raw
+            1,    // ready
+            2, 0, 0, 0, // length of the string
+            b'4', b'2', // count
+            1, 0, 0, 0,    // length of the string
+            b'z', // id
+        ];
+        let cfg = PluginConfig {
+            attribute_coercions: vec![
+                AttrCoercion {
+                    key: Glob::new("event.status").into(),
+                    to: AttrType::HexInt.into(),
+                    scale: None,
+                },
+                AttrCoercion {
+                    key: Glob::new("event.temp_*").into(),
+                    to: None,
+                    scale: ScaleFactor(0.5).into(),
+                },
+                AttrCoercion {
+                    key: Glob::new("event.ready").into(),
+                    to: AttrType::Bool.into(),
+                    scale: None,
+                },
+                AttrCoercion {
+                    key: Glob::new("event.count").into(),
+                    to: AttrType::Int.into(),
+                    scale: None,
+                },
+                AttrCoercion {
+                    key: Glob::new("event.id").into(),
+                    to: AttrType::Int.into(),
+                    scale: None,
+                },
+            ],
+            ..Default::default()
+        };
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        let attrs = event_record.attributes();
+        assert_eq!(attrs.get("event.status"), Some(&AttrVal::Integer(0x1F)));
+        assert_eq!(attrs.get("event.temp_raw"), Some(&117.0_f64.into()));
+        assert_eq!(attrs.get("event.ready"), Some(&true.into()));
+        assert_eq!(attrs.get("event.count"), Some(&AttrVal::Integer(42)));
+        // Values that can't be coerced are kept as-is
+        assert_eq!(attrs.get("event.id"), Some(&"z".into()));
+    }
+
+    #[test]
+    fn composed_timestamps() {
+        let entries = vec![TableEntry::new_without_symbol(Tag::Info, "tick".to_owned())];
+        let timestamp = TableEntry::new_without_symbol(Tag::Timestamp, "{=u16}{=u32}".to_owned());
+        let table = Table::new_test_table(Some(timestamp), entries);
+        let bytes = [
+            0, 0, // index
+            2, 0, // high/overflow count
+            5, 0, 0, 0, // low/counter
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+
+        // Unsupported without a composition
+        let event_record = FramePlan::new(&frame, None).unwrap().record(&frame);
+        assert_eq!(event_record.timestamp(), None);
+
+        let cfg = PluginConfig {
+            timestamp_composition: TimestampComposition::HighLow,
+            ..Default::default()
+        };
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        assert_eq!(
+            event_record.timestamp(),
+            Some(Timestamp::Ticks64((2 << 32) | 5))
+        );
+
+        // Low word is the u16
+        let cfg = PluginConfig {
+            timestamp_composition: TimestampComposition::LowHigh,
+            ..Default::default()
+        };
+        let event_record = FramePlan::with_config(&frame, None, &cfg)
+            .unwrap()
+            .record(&frame);
+        assert_eq!(
+            event_record.timestamp(),
+            Some(Timestamp::Ticks64((5 << 16) | 2))
+        );
+    }
+
+    #[test]
+    fn frame_plan_reuse() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Info,
+            "my_event::k=1,val={=u8}".to_owned(),
+        )];
+        let timestamp = TableEntry::new_without_symbol(Tag::Timestamp, "{=u8:ms}".to_owned());
+        let table = Table::new_test_table(Some(timestamp), entries);
+
+        let (frame, _) = table.decode(&[0, 0, 1, 10]).unwrap();
+        let mut plan = FramePlan::new(&frame, None).unwrap();
+        let first = plan.record(&frame);
+
+        let (frame, _) = table.decode(&[0, 0, 2, 20]).unwrap();
+        let second = plan.record(&frame);
+
+        assert_eq!(first.timestamp(), Some(Timestamp::Millis(1)));
+        assert_eq!(second.timestamp(), Some(Timestamp::Millis(2)));
+        assert_eq!(first.event_name(), Some("my_event"));
+        assert_eq!(second.event_name(), Some("my_event"));
+        assert_eq!(
+            first.attributes().get("event.k"),
+            Some(&AttrVal::Integer(1))
+        );
+        assert_eq!(
+            second.attributes().get("event.k"),
+            Some(&AttrVal::Integer(1))
+        );
+        assert_eq!(
+            first.attributes().get("event.val"),
+            Some(&BigInt::new_attr_val(10))
+        );
+        assert_eq!(
+            second.attributes().get("event.val"),
+            Some(&BigInt::new_attr_val(20))
+        );
+        assert_eq!(
+            second
+                .attributes()
+                .get("event.internal.defmt.formatted_string"),
+            Some(&AttrVal::from("my_event::k=1,val=20"))
+        );
+    }
+
     #[test]
     fn timestamps() {
         assert_eq!(
@@ -743,4 +2439,63 @@ mod test {
             Some(Nanoseconds::from(10_000_000_000))
         );
     }
+
+    #[test]
+    fn host_received_time() {
+        let entries = vec![TableEntry::new_without_symbol(
+            Tag::Info,
+            "Hello, world!".to_owned(),
+        )];
+        let timestamp = TableEntry::new_without_symbol(Tag::Timestamp, "{=u8:us}".to_owned());
+        let table = Table::new_test_table(Some(timestamp), entries);
+        let bytes = [
+            0, 0, // index
+            2, // timestamp
+        ];
+        let (frame, _) = table.decode(&bytes).unwrap();
+        let mut event_record = EventRecord::from_frame(frame, None).unwrap();
+        assert_eq!(
+            event_record
+                .attributes()
+                .get("event.internal.defmt.host_received_ns"),
+            None
+        );
+
+        let host_received_ns = 1_709_294_400_123_456_789_u64;
+        event_record.set_internal_host_received(host_received_ns);
+        let attrs = event_record.attributes();
+        assert_eq!(
+            attrs.get("event.internal.defmt.host_received_ns"),
+            Some(&AttrVal::from(host_received_ns))
+        );
+        // The device time is kept
+        assert_eq!(
+            attrs.get("event.timestamp"),
+            Some(&AttrVal::Timestamp(2_000_u64.into()))
+        );
+    }
+
+    #[test]
+    fn large_integer_args() {
+        assert_eq!(
+            arg_to_attr_val(&Arg::Uxx(u64::MAX.into())),
+            Some(BigInt::new_attr_val(u64::MAX.into()))
+        );
+        assert_eq!(
+            arg_to_attr_val(&Arg::Uxx(i128::MAX as u128)),
+            Some(BigInt::new_attr_val(i128::MAX))
+        );
+        assert_eq!(
+            arg_to_attr_val(&Arg::Uxx(u128::MAX)),
+            Some(u128::MAX.to_string().into())
+        );
+        assert_eq!(
+            arg_to_attr_val(&Arg::Ixx(i128::MIN)),
+            Some(BigInt::new_attr_val(i128::MIN))
+        );
+        assert_eq!(
+            arg_to_attr_val(&Arg::Ixx(i64::MIN.into())),
+            Some(AttrVal::Integer(i64::MIN))
+        );
+    }
 }
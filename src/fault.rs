@@ -0,0 +1,130 @@
+//! Cortex-M fault exceptions, caught by the collector's vector catch.
+
+/// The fault status registers of a caught fault exception
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaultState {
+    /// The program counter of the faulting instruction, from the exception stack frame
+    pub pc: Option<u64>,
+    /// Configurable Fault Status Register
+    pub cfsr: u32,
+    /// HardFault Status Register
+    pub hfsr: u32,
+    /// MemManage Fault Address Register
+    pub mmfar: u32,
+    /// BusFault Address Register
+    pub bfar: u32,
+    /// The core was left halted, ending the collection
+    pub halted: bool,
+}
+
+impl FaultState {
+    pub const CFSR_ADDRESS: u64 = 0xE000_ED28;
+    pub const HFSR_ADDRESS: u64 = 0xE000_ED2C;
+    pub const MMFAR_ADDRESS: u64 = 0xE000_ED34;
+    pub const BFAR_ADDRESS: u64 = 0xE000_ED38;
+
+    const MMARVALID: u32 = 1 << 7;
+    const BFARVALID: u32 = 1 << 15;
+
+    const CFSR_FLAGS: [(u32, &'static str); 18] = [
+        // MemManage
+        (1 << 0, "IACCVIOL"),
+        (1 << 1, "DACCVIOL"),
+        (1 << 3, "MUNSTKERR"),
+        (1 << 4, "MSTKERR"),
+        (1 << 5, "MLSPERR"),
+        // BusFault
+        (1 << 8, "IBUSERR"),
+        (1 << 9, "PRECISERR"),
+        (1 << 10, "IMPRECISERR"),
+        (1 << 11, "UNSTKERR"),
+        (1 << 12, "STKERR"),
+        (1 << 13, "LSPERR"),
+        // UsageFault
+        (1 << 16, "UNDEFINSTR"),
+        (1 << 17, "INVSTATE"),
+        (1 << 18, "INVPC"),
+        (1 << 19, "NOCP"),
+        (1 << 20, "STKOF"),
+        (1 << 24, "UNALIGNED"),
+        (1 << 25, "DIVBYZERO"),
+    ];
+    const HFSR_FLAGS: [(u32, &'static str); 3] = [
+        (1 << 1, "VECTTBL"),
+        (1 << 30, "FORCED"),
+        (1 << 31, "DEBUGEVT"),
+    ];
+
+    /// The MemManage fault address, when it's valid
+    pub fn mmfar(&self) -> Option<u32> {
+        (self.cfsr & Self::MMARVALID != 0).then_some(self.mmfar)
+    }
+
+    /// The BusFault address, when it's valid
+    pub fn bfar(&self) -> Option<u32> {
+        (self.cfsr & Self::BFARVALID != 0).then_some(self.bfar)
+    }
+
+    /// The names of the status bits set in the HFSR and CFSR, e.g. `["FORCED", "PRECISERR"]`
+    pub fn flags(&self) -> Vec<&'static str> {
+        let hfsr = Self::HFSR_FLAGS
+            .iter()
+            .filter(|(bit, _)| self.hfsr & bit != 0);
+        let cfsr = Self::CFSR_FLAGS
+            .iter()
+            .filter(|(bit, _)| self.cfsr & bit != 0);
+        hfsr.chain(cfsr).map(|(_, name)| *name).collect()
+    }
+
+    /// The kind of fault, from the fault status register with bits set
+    pub fn kind(&self) -> &'static str {
+        if self.cfsr & 0x0000_00FF != 0 {
+            "MemManage"
+        } else if self.cfsr & 0x0000_FF00 != 0 {
+            "BusFault"
+        } else if self.cfsr & 0xFFFF_0000 != 0 {
+            "UsageFault"
+        } else {
+            "HardFault"
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn decode_status_registers() {
+        // Escalated precise bus fault
+        let fault = FaultState {
+            pc: Some(0x0800_1234),
+            cfsr: (1 << 9) | (1 << 15),
+            hfsr: 1 << 30,
+            mmfar: 0xE000_EDF8,
+            bfar: 0x3000_0000,
+            halted: false,
+        };
+        assert_eq!(fault.flags(), vec!["FORCED", "PRECISERR"]);
+        assert_eq!(fault.kind(), "BusFault");
+        assert_eq!(fault.bfar(), Some(0x3000_0000));
+        assert_eq!(fault.mmfar(), None);
+
+        let fault = FaultState {
+            cfsr: 1 << 25,
+            hfsr: 1 << 30,
+            ..Default::default()
+        };
+        assert_eq!(fault.flags(), vec!["FORCED", "DIVBYZERO"]);
+        assert_eq!(fault.kind(), "UsageFault");
+        assert_eq!(fault.bfar(), None);
+
+        let fault = FaultState {
+            hfsr: 1 << 1,
+            ..Default::default()
+        };
+        assert_eq!(fault.flags(), vec!["VECTTBL"]);
+        assert_eq!(fault.kind(), "HardFault");
+    }
+}
@@ -0,0 +1,493 @@
+//! Optional link-layer framing in front of the defmt stream decoder.
+//!
+//! Noisy transports (e.g. a UART without flow control) can corrupt or drop bytes.
+//! Wrapping the defmt data in COBS or SLIP frames with a trailing CRC lets us discard
+//! damaged frames instead of feeding garbage to the decoder.
+
+use derive_more::Display;
+use serde_with::DeserializeFromStr;
+use std::{io, str::FromStr};
+use tracing::{debug, warn};
+
+/// The link-layer framing used by the transport
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, DeserializeFromStr,
+)]
+pub enum Framing {
+    /// The raw defmt stream
+    #[default]
+    #[display(fmt = "none")]
+    None,
+    /// Consistent Overhead Byte Stuffing, frames are terminated by a 0x00 byte
+    #[display(fmt = "cobs")]
+    Cobs,
+    /// RFC 1055 SLIP, frames are terminated by a 0xC0 byte
+    #[display(fmt = "slip")]
+    Slip,
+    /// The `esp-println` `defmt-espflash` framing: the defmt frames start with 0xFF 0x00 and
+    /// are interleaved with console text (e.g. the chip's boot messages), which is logged
+    #[display(fmt = "espflash")]
+    Espflash,
+}
+
+impl FromStr for Framing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "none" => Framing::None,
+            "cobs" => Framing::Cobs,
+            "slip" => Framing::Slip,
+            "espflash" => Framing::Espflash,
+            _ => return Err(format!("Unsupported framing '{s}'")),
+        })
+    }
+}
+
+/// The integrity check appended (little-endian) to each frame's payload
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, DeserializeFromStr,
+)]
+pub enum FrameCrc {
+    #[default]
+    #[display(fmt = "none")]
+    None,
+    /// CRC-16/CCITT-FALSE (poly 0x1021, init 0xFFFF)
+    #[display(fmt = "crc16")]
+    Crc16,
+    /// CRC-32/ISO-HDLC, as used by Ethernet and zlib
+    #[display(fmt = "crc32")]
+    Crc32,
+}
+
+impl FrameCrc {
+    fn len(self) -> usize {
+        match self {
+            FrameCrc::None => 0,
+            FrameCrc::Crc16 => 2,
+            FrameCrc::Crc32 => 4,
+        }
+    }
+
+    /// Returns true if the trailing CRC matches the rest of the frame
+    fn check(self, frame: &[u8]) -> bool {
+        let Some(split) = frame.len().checked_sub(self.len()) else {
+            return false;
+        };
+        let (payload, crc) = frame.split_at(split);
+        match self {
+            FrameCrc::None => true,
+            FrameCrc::Crc16 => u16::from_le_bytes([crc[0], crc[1]]) == crc16(payload),
+            FrameCrc::Crc32 => {
+                u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) == crc32(payload)
+            }
+        }
+    }
+}
+
+impl FromStr for FrameCrc {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "none" => FrameCrc::None,
+            "crc16" | "crc-16" => FrameCrc::Crc16,
+            "crc32" | "crc-32" => FrameCrc::Crc32,
+            _ => return Err(format!("Unsupported frame CRC '{s}'")),
+        })
+    }
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for b in data {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct FrameStats {
+    /// Frames that passed decoding and validation
+    pub frames: u64,
+    /// Frames that were discarded
+    pub dropped: u64,
+}
+
+/// Splits a byte stream into frames, yielding the validated payloads
+#[derive(Debug)]
+pub struct FrameDecoder {
+    framing: Framing,
+    crc: FrameCrc,
+    frame: Vec<u8>,
+    /// Set when the current frame has exceeded the maximum length
+    overflowed: bool,
+    /// Espflash framing state: inside a frame, and a possible frame start seen
+    in_frame: bool,
+    start_pending: bool,
+    /// The console text line between espflash frames
+    text: Vec<u8>,
+    stats: FrameStats,
+}
+
+impl FrameDecoder {
+    /// Frames longer than this are assumed to be the result of a lost delimiter
+    pub const MAX_FRAME_LEN: usize = 4096;
+
+    const COBS_DELIMITER: u8 = 0x00;
+    const SLIP_END: u8 = 0xC0;
+    const SLIP_ESC: u8 = 0xDB;
+    const SLIP_ESC_END: u8 = 0xDC;
+    const SLIP_ESC_ESC: u8 = 0xDD;
+    const ESPFLASH_START: [u8; 2] = [0xFF, 0x00];
+
+    pub fn new(framing: Framing, crc: FrameCrc) -> Self {
+        Self {
+            framing,
+            crc,
+            frame: Vec::new(),
+            overflowed: false,
+            in_frame: false,
+            start_pending: false,
+            text: Vec::new(),
+            stats: Default::default(),
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    /// Appends the payloads of the frames completed by `bytes` to `out`
+    pub fn received(&mut self, bytes: &[u8], out: &mut Vec<u8>) {
+        if self.framing == Framing::None {
+            out.extend_from_slice(bytes);
+            return;
+        }
+        if self.framing == Framing::Espflash {
+            return self.received_espflash(bytes, out);
+        }
+
+        let delimiter = match self.framing {
+            Framing::Slip => Self::SLIP_END,
+            _ => Self::COBS_DELIMITER,
+        };
+        for b in bytes.iter().copied() {
+            if b == delimiter {
+                self.end_frame(out);
+            } else if self.frame.len() < Self::MAX_FRAME_LEN {
+                self.frame.push(b);
+            } else {
+                self.overflowed = true;
+            }
+        }
+    }
+
+    /// The frame content is passed through, including the 0x00 terminator of the
+    /// defmt rzCOBS encoding, which also ends the espflash frame
+    fn received_espflash(&mut self, bytes: &[u8], out: &mut Vec<u8>) {
+        for b in bytes.iter().copied() {
+            if self.in_frame {
+                if b == Self::COBS_DELIMITER {
+                    self.in_frame = false;
+                    if std::mem::take(&mut self.overflowed) {
+                        self.frame.clear();
+                        self.stats.dropped += 1;
+                        warn!(framing = %self.framing, dropped = self.stats.dropped, overflowed = true, "Dropped a corrupt frame");
+                    } else if !self.frame.is_empty() {
+                        out.append(&mut self.frame);
+                        out.push(Self::COBS_DELIMITER);
+                        self.stats.frames += 1;
+                    }
+                } else if self.frame.len() < Self::MAX_FRAME_LEN {
+                    self.frame.push(b);
+                } else {
+                    self.overflowed = true;
+                }
+            } else if self.start_pending && b == Self::ESPFLASH_START[1] {
+                self.start_pending = false;
+                self.in_frame = true;
+            } else {
+                if std::mem::replace(&mut self.start_pending, b == Self::ESPFLASH_START[0]) {
+                    self.text.push(Self::ESPFLASH_START[0]);
+                }
+                if self.start_pending {
+                    continue;
+                }
+                if b == b'\n' || self.text.len() >= Self::MAX_FRAME_LEN {
+                    crate::esp::log_console_line(&self.text);
+                    self.text.clear();
+                } else {
+                    self.text.push(b);
+                }
+            }
+        }
+    }
+
+    fn end_frame(&mut self, out: &mut Vec<u8>) {
+        let overflowed = std::mem::take(&mut self.overflowed);
+        if self.frame.is_empty() && !overflowed {
+            // Back-to-back delimiters, SLIP senders commonly lead with one to flush line noise
+            return;
+        }
+
+        let start = out.len();
+        let decoded = !overflowed
+            && match self.framing {
+                Framing::None | Framing::Espflash => unreachable!(),
+                Framing::Cobs => cobs_decode(&self.frame, out),
+                Framing::Slip => slip_decode(&self.frame, out),
+            };
+        self.frame.clear();
+
+        if decoded && self.crc.check(&out[start..]) {
+            out.truncate(out.len() - self.crc.len());
+            self.stats.frames += 1;
+        } else {
+            out.truncate(start);
+            self.stats.dropped += 1;
+            warn!(
+                framing = %self.framing,
+                dropped = self.stats.dropped,
+                overflowed,
+                "Dropped a corrupt frame"
+            );
+        }
+    }
+}
+
+/// Returns false if the encoding is invalid
+fn cobs_decode(frame: &[u8], out: &mut Vec<u8>) -> bool {
+    let mut i = 0;
+    while i < frame.len() {
+        let code = frame[i] as usize;
+        // Zero never appears in an encoded frame, it's the delimiter
+        if code == 0 || i + code > frame.len() {
+            return false;
+        }
+        out.extend_from_slice(&frame[i + 1..i + code]);
+        i += code;
+        if code < 0xFF && i < frame.len() {
+            out.push(0);
+        }
+    }
+    true
+}
+
+/// Returns false if the encoding is invalid
+fn slip_decode(frame: &[u8], out: &mut Vec<u8>) -> bool {
+    let mut bytes = frame.iter().copied();
+    while let Some(b) = bytes.next() {
+        if b == FrameDecoder::SLIP_ESC {
+            match bytes.next() {
+                Some(FrameDecoder::SLIP_ESC_END) => out.push(FrameDecoder::SLIP_END),
+                Some(FrameDecoder::SLIP_ESC_ESC) => out.push(FrameDecoder::SLIP_ESC),
+                _ => return false,
+            }
+        } else {
+            out.push(b);
+        }
+    }
+    true
+}
+
+/// Strips the link-layer framing from the inner reader's data
+pub struct FramedReader<R> {
+    inner: R,
+    decoder: FrameDecoder,
+    buf: Vec<u8>,
+    payload: Vec<u8>,
+    payload_pos: usize,
+}
+
+impl<R: io::Read> FramedReader<R> {
+    pub fn new(inner: R, framing: Framing, crc: FrameCrc, buffer_size: usize) -> Self {
+        if framing != Framing::None {
+            debug!(%framing, %crc, "Using link-layer framing");
+        }
+        if framing == Framing::Espflash && crc != FrameCrc::None {
+            warn!(%crc, "The espflash framing has no CRC, ignoring the frame CRC");
+        }
+        Self {
+            inner,
+            decoder: FrameDecoder::new(framing, crc),
+            buf: vec![0; buffer_size.max(1)],
+            payload: Vec::new(),
+            payload_pos: 0,
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.decoder.stats()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: io::Read> io::Read for FramedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.payload_pos == self.payload.len() {
+            self.payload.clear();
+            self.payload_pos = 0;
+            let n = self.inner.read(&mut self.buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.decoder.received(&self.buf[..n], &mut self.payload);
+        }
+
+        let n = buf.len().min(self.payload.len() - self.payload_pos);
+        buf[..n].copy_from_slice(&self.payload[self.payload_pos..self.payload_pos + n]);
+        self.payload_pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    fn cobs_encode(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0];
+        let mut code_idx = 0;
+        for b in data.iter().copied() {
+            if b == 0 {
+                out[code_idx] = (out.len() - code_idx) as u8;
+                code_idx = out.len();
+                out.push(0);
+            } else {
+                out.push(b);
+                if out.len() - code_idx == 0xFF {
+                    out[code_idx] = 0xFF;
+                    code_idx = out.len();
+                    out.push(0);
+                }
+            }
+        }
+        out[code_idx] = (out.len() - code_idx) as u8;
+        out.push(0);
+        out
+    }
+
+    fn with_crc16(data: &[u8]) -> Vec<u8> {
+        let mut v = data.to_vec();
+        v.extend_from_slice(&crc16(data).to_le_bytes());
+        v
+    }
+
+    #[test]
+    fn crc_check_values() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn cobs_frames() {
+        let mut dec = FrameDecoder::new(Framing::Cobs, FrameCrc::Crc16);
+        let mut stream = cobs_encode(&with_crc16(&[1, 0, 2, 0]));
+        let mut corrupt = cobs_encode(&with_crc16(&[3, 4]));
+        corrupt[1] ^= 0xFF;
+        stream.extend_from_slice(&corrupt);
+        stream.extend_from_slice(&cobs_encode(&with_crc16(&[0xAA; 300])));
+
+        // Feed it in pieces to make sure frames can span reads
+        let mut out = Vec::new();
+        for chunk in stream.chunks(7) {
+            dec.received(chunk, &mut out);
+        }
+        let mut expected = vec![1, 0, 2, 0];
+        expected.extend_from_slice(&[0xAA; 300]);
+        assert_eq!(out, expected);
+        assert_eq!(
+            dec.stats(),
+            FrameStats {
+                frames: 2,
+                dropped: 1
+            }
+        );
+    }
+
+    #[test]
+    fn slip_frames() {
+        let mut dec = FrameDecoder::new(Framing::Slip, FrameCrc::None);
+        let mut out = Vec::new();
+        dec.received(
+            &[
+                0xC0, 1, 0xDB, 0xDC, 2, 0xDB, 0xDD, 0xC0, 3, 0xDB, 0x01, 0xC0,
+            ],
+            &mut out,
+        );
+        assert_eq!(out, vec![1, 0xC0, 2, 0xDB]);
+        assert_eq!(
+            dec.stats(),
+            FrameStats {
+                frames: 1,
+                dropped: 1
+            }
+        );
+    }
+
+    #[test]
+    fn oversized_frames_are_dropped() {
+        let mut dec = FrameDecoder::new(Framing::Slip, FrameCrc::None);
+        let mut out = Vec::new();
+        dec.received(&vec![1; FrameDecoder::MAX_FRAME_LEN + 1], &mut out);
+        dec.received(&[0xC0, 2, 0xC0], &mut out);
+        assert_eq!(out, vec![2]);
+        assert_eq!(dec.stats().dropped, 1);
+    }
+
+    #[test]
+    fn espflash_frames() {
+        let mut dec = FrameDecoder::new(Framing::Espflash, FrameCrc::None);
+        let stream = b"ESP-ROM:esp32c3\r\n\xFF\x00\x01\xFF\x02\x00\xFF\xFF\x00\x03\x00text\xFF\n";
+        let mut out = Vec::new();
+        for chunk in stream.chunks(3) {
+            dec.received(chunk, &mut out);
+        }
+        assert_eq!(out, vec![1, 0xFF, 2, 0, 3, 0]);
+        assert_eq!(dec.stats().frames, 2);
+        assert_eq!(dec.text, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn framed_reader() {
+        let mut stream = cobs_encode(&with_crc16(b"hello "));
+        stream.extend_from_slice(&cobs_encode(&with_crc16(b"world")));
+        let mut r = FramedReader::new(stream.as_slice(), Framing::Cobs, FrameCrc::Crc16, 4);
+        let mut s = String::new();
+        r.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "hello world");
+        assert_eq!(r.stats().frames, 2);
+
+        let mut r = FramedReader::new(&b"raw"[..], Framing::None, FrameCrc::None, 16);
+        let mut s = String::new();
+        r.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "raw");
+    }
+}
@@ -0,0 +1,79 @@
+use serde_with::DeserializeFromStr;
+use std::{convert::Infallible, fmt, str::FromStr};
+
+/// A simple glob pattern, supporting `*` (any sequence of characters)
+/// and `?` (any single character)
+#[derive(Clone, Debug, PartialEq, Eq, Hash, DeserializeFromStr)]
+pub struct Glob(String);
+
+impl Glob {
+    pub fn new<S: Into<String>>(pattern: S) -> Self {
+        Self(pattern.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_match(&self, s: &str) -> bool {
+        let pattern: Vec<char> = self.0.chars().collect();
+        let input: Vec<char> = s.chars().collect();
+
+        let (mut p, mut i) = (0, 0);
+        // Position of the last '*' in the pattern, and the input position it matched up to
+        let mut backtrack: Option<(usize, usize)> = None;
+
+        while i < input.len() {
+            if p < pattern.len() && (pattern[p] == '?' || pattern[p] == input[i]) {
+                p += 1;
+                i += 1;
+            } else if p < pattern.len() && pattern[p] == '*' {
+                backtrack = Some((p, i));
+                p += 1;
+            } else if let Some((star_p, star_i)) = backtrack {
+                // Let the last '*' consume one more character
+                backtrack = Some((star_p, star_i + 1));
+                p = star_p + 1;
+                i = star_i + 1;
+            } else {
+                return false;
+            }
+        }
+
+        pattern[p..].iter().all(|c| *c == '*')
+    }
+}
+
+impl FromStr for Glob {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s))
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        assert!(Glob::new("panic").is_match("panic"));
+        assert!(!Glob::new("panic").is_match("panicked"));
+        assert!(Glob::new("panic*").is_match("panicked"));
+        assert!(!Glob::new("*fault*").is_match("HardFault"));
+        assert!(Glob::new("*Fault*").is_match("HardFault handler"));
+        assert!(Glob::new("test_?_pass").is_match("test_1_pass"));
+        assert!(!Glob::new("test_?_pass").is_match("test_12_pass"));
+        assert!(Glob::new("a*b*c").is_match("aXbYbZc"));
+        assert!(!Glob::new("a*b*c").is_match("aXbYbZ"));
+        assert!(Glob::new("*").is_match(""));
+        assert!(!Glob::new("?").is_match(""));
+    }
+}
@@ -0,0 +1,184 @@
+//! Reads RTT channel data logged by SEGGER's `JLinkRTTLogger` (or the J-Link RTT Viewer),
+//! stripping the log header and per-block marker lines surrounding the raw data.
+
+use std::io;
+use tracing::{debug, trace};
+
+/// Prefixes of the lines SEGGER tools write around the logged data, e.g.
+/// `# SEGGER J-Link RTT Viewer V7.92 Terminal Log File`,
+/// `# Logging started @ 2024-01-01 12:00:00` and `# Logging stopped @ ...`
+const MARKERS: &[&[u8]] = &[b"# SEGGER J-Link", b"# Compiled", b"# Logging "];
+
+/// Marker lines longer than this are treated as data
+const MAX_MARKER_LINE_LEN: usize = 256;
+
+pub struct JLinkLogReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    eof: bool,
+    markers_stripped: u64,
+}
+
+impl<R: io::Read> JLinkLogReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            eof: false,
+            markers_stripped: 0,
+        }
+    }
+
+    /// Number of header and block marker lines removed so far
+    pub fn markers_stripped(&self) -> u64 {
+        self.markers_stripped
+    }
+
+    /// Returns the number of leading bytes of the buffer that are data,
+    /// after stripping any complete marker line at the start of the buffer.
+    /// Zero means more input is needed.
+    fn process(&mut self) -> usize {
+        loop {
+            let Some(start) = find_marker_start(&self.buf, self.eof) else {
+                return self.buf.len();
+            };
+            if start > 0 {
+                return start;
+            }
+
+            // A (possible) marker at the start of the buffer
+            let is_marker = MARKERS.iter().any(|m| self.buf.starts_with(m));
+            if !is_marker {
+                // Partial marker prefix at the end of the input
+                if self.eof {
+                    return self.buf.len();
+                }
+                return 0;
+            }
+            let line_end = self
+                .buf
+                .iter()
+                .take(MAX_MARKER_LINE_LEN)
+                .position(|b| *b == b'\n');
+            match line_end {
+                Some(end) => {
+                    trace!(
+                        line = %String::from_utf8_lossy(&self.buf[..end]).trim_end(),
+                        "Stripping J-Link log marker line"
+                    );
+                    self.buf.drain(..=end);
+                    self.markers_stripped += 1;
+                }
+                None if self.eof && self.buf.len() <= MAX_MARKER_LINE_LEN => {
+                    // Unterminated marker line at the end of the file
+                    self.buf.clear();
+                    self.markers_stripped += 1;
+                }
+                None if self.buf.len() < MAX_MARKER_LINE_LEN && !self.eof => return 0,
+                None => {
+                    // Too long to be a marker line
+                    return 1;
+                }
+            }
+        }
+    }
+}
+
+/// Finds the start of the first complete marker, or of a marker prefix
+/// cut off at the end of the buffer
+fn find_marker_start(buf: &[u8], eof: bool) -> Option<usize> {
+    (0..buf.len()).find(|&i| {
+        buf[i] == b'#'
+            && MARKERS.iter().any(|m| {
+                let rest = &buf[i..];
+                rest.starts_with(m) || (!eof && m.starts_with(rest))
+            })
+    })
+}
+
+impl<R: io::Read> io::Read for JLinkLogReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let data_len = self.process();
+            if data_len != 0 {
+                let n = data_len.min(buf.len());
+                buf[..n].copy_from_slice(&self.buf[..n]);
+                self.buf.drain(..n);
+                return Ok(n);
+            }
+            if self.eof {
+                debug!(
+                    markers = self.markers_stripped,
+                    "Finished reading J-Link RTT log"
+                );
+                return Ok(0);
+            }
+
+            let mut chunk = [0_u8; 1024];
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                self.eof = true;
+            } else {
+                self.buf.extend_from_slice(&chunk[..n]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    /// Yields a single byte per read to exercise markers split across reads
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    const LOG: &[u8] = b"# SEGGER J-Link RTT Viewer V7.92 Terminal Log File\r\n\
+# Compiled: 17:07:24 on Aug 22 2023\r\n\
+# Logging started @ 01 Jan 2024 12:00:00\r\n\
+\x01\x02#\x03\n# not a marker\n\
+# Logging stopped @ 01 Jan 2024 12:00:01\r\n\
+# Logging started @ 01 Jan 2024 12:00:05\r\n\
+\x04\x05\n\
+# Logging stopped @ 01 Jan 2024 12:00:06";
+
+    const DATA: &[u8] = b"\x01\x02#\x03\n# not a marker\n\x04\x05\n";
+
+    #[test]
+    fn strips_markers() {
+        let mut r = JLinkLogReader::new(LOG);
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).unwrap();
+        assert_eq!(out, DATA);
+        assert_eq!(r.markers_stripped(), 6);
+
+        let mut r = JLinkLogReader::new(Trickle(LOG));
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).unwrap();
+        assert_eq!(out, DATA);
+    }
+
+    #[test]
+    fn raw_data_passthrough() {
+        let data = b"\x00# Logg\xFF\n# Log";
+        let mut r = JLinkLogReader::new(Trickle(data));
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(r.markers_stripped(), 0);
+    }
+}
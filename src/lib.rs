@@ -1,23 +1,93 @@
+pub use crate::attr::AttrKey;
+#[cfg(feature = "ingest")]
 pub use crate::client::Client;
+#[cfg(feature = "openocd-collector")]
+pub use crate::config::OpenOcdCollectorConfig;
+#[cfg(feature = "renode-collector")]
+pub use crate::config::RenodeCollectorConfig;
+#[cfg(feature = "tcp-collector")]
+pub use crate::config::TcpCollectorConfig;
 pub use crate::config::{
-    DefmtConfig, DefmtConfigEntry, ImportConfig, PluginConfig, RttCollectorConfig,
+    AttrCoercion, AttrType, EventFilter, ImportConfig, InputCompression, MessageCorrelation,
+    Outcome, OutcomeRule, PluginConfig, RtosMarkers, ScaleFactor, SourcePathRemap,
+    SourceUrlMapping, StopTrigger,
 };
+#[cfg(feature = "ingest")]
+pub use crate::config::{DefmtConfig, DefmtConfigEntry, EnvVarExpansionError};
+#[cfg(feature = "rtt-collector")]
+pub use crate::config::{
+    FaultAction, MemoryWindow, MemoryWrite, ProbeCommand, RingBufferConfig, RttChannelFormat,
+    RttChannelMode, RttCollectorConfig, RttPollPolicy, RttTargetConfig, RttUpChannel, SetupStage,
+    StagedMutation, TargetArch,
+};
+#[cfg(feature = "process-collector")]
+pub use crate::config::{ProcessCollectorConfig, ProcessStream};
+#[cfg(feature = "serial-collector")]
+pub use crate::config::{
+    SerialCollectorConfig, SerialFlowControl, SerialParity, UsbDeviceSelector,
+};
+#[cfg(feature = "ingest")]
+pub use crate::config_template::config_template;
 pub use crate::context_manager::{
-    ActiveContext, ContextEvent, ContextManager, TimelineAttributes, TimelineMeta,
+    ActiveContext, ContextEvent, ContextManager, CrashState, DataLoss, MemoryDump,
+    TimelineAttributes, TimelineMeta,
 };
 pub use crate::error::Error;
-pub use crate::event_record::{EventAttributes, EventRecord, Timestamp};
+pub use crate::esp::EspBootLogReader;
+pub use crate::event_record::{EventAttributes, EventRecord, FramePlan, Timestamp};
+pub use crate::fault::FaultState;
+pub use crate::framing::{FrameCrc, FrameDecoder, FrameStats, FramedReader, Framing};
 pub use crate::interruptor::Interruptor;
-pub use crate::opts::{DefmtOpts, ReflectorOpts, RtosMode};
-pub use crate::time::{Rate, TrackingInstant};
+pub use crate::jlink_log::JLinkLogReader;
+pub use crate::logic_capture::{LogicCapture, LogicCaptureReader};
+pub use crate::metrics::{IngestSummary, MetricsReport, ReaderMetrics, RunSummary, WatchSample};
+pub use crate::opts::{
+    AttrKeyLimitPolicy, BackwardsTimestampPolicy, ByteEncoding, PreStartPolicy, RtosMode,
+    TimestampComposition, UnnamedEventNames,
+};
+#[cfg(feature = "ingest")]
+pub use crate::opts::{DefmtOpts, ReflectorOpts};
+pub use crate::pcap::{PcapFilter, PcapReader, PcapStats};
+pub use crate::raw_capture::RawCaptureWriter;
+pub use crate::raw_frame::RawFrameTracker;
+pub use crate::reboot::{RebootDetector, RebootReason};
+#[cfg(feature = "rtt-dump")]
+pub use crate::rtt_dump::{MemoryImage, RttDumpChannel, RttDumpError};
+pub use crate::text::LineSplitter;
+pub use crate::time::{LatencySummary, LatencyTracker, Rate, TrackingInstant, WrappingInstant};
 
+pub mod attr;
+#[cfg(feature = "ingest")]
 pub mod client;
 pub mod config;
+#[cfg(feature = "ingest")]
+pub mod config_template;
 pub mod context_manager;
+#[cfg(feature = "ingest")]
+pub mod debuglink;
+#[cfg(feature = "ingest")]
 pub mod defmt_reader;
+pub mod elf_watch;
 pub mod error;
+pub mod esp;
 pub mod event_record;
+pub mod fault;
+pub mod framing;
+pub mod glob;
 pub mod interruptor;
+pub mod jlink_log;
+pub mod logic_capture;
+pub mod metrics;
 pub mod opts;
+pub mod pcap;
+pub mod promotion;
+pub mod raw_capture;
+pub mod raw_frame;
+pub mod reboot;
+pub mod rename;
+#[cfg(feature = "rtt-dump")]
+pub mod rtt_dump;
+pub mod text;
 pub mod time;
+#[cfg(feature = "ingest")]
 pub mod tracing;
@@ -0,0 +1,353 @@
+//! Reads the decoded UART bytes exported from logic analyzer software
+//! (Saleae Logic, sigrok/PulseView), along with each byte's capture time.
+
+use derive_more::Display;
+use serde_with::DeserializeFromStr;
+use std::{io, str::FromStr};
+use tracing::{debug, trace, warn};
+
+/// The export format of a logic analyzer capture
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, DeserializeFromStr)]
+pub enum LogicCapture {
+    /// Raw decoded bytes (e.g. `sigrok-cli -B uart=rx`), without timestamps
+    #[display(fmt = "binary")]
+    Binary,
+    /// Saleae Logic 1.x or 2.x async serial analyzer CSV export
+    #[display(fmt = "saleae")]
+    Saleae,
+    /// sigrok-cli or PulseView UART annotation text export
+    #[display(fmt = "sigrok")]
+    Sigrok,
+}
+
+impl FromStr for LogicCapture {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "binary" | "bin" => LogicCapture::Binary,
+            "saleae" | "saleae-csv" => LogicCapture::Saleae,
+            "sigrok" | "pulseview" => LogicCapture::Sigrok,
+            _ => return Err(format!("Unsupported logic analyzer capture format '{s}'")),
+        })
+    }
+}
+
+/// Column indices of a Saleae CSV export
+#[derive(Copy, Clone, Debug)]
+struct CsvColumns {
+    time: usize,
+    data: usize,
+    typ: Option<usize>,
+    errors: [Option<usize>; 2],
+}
+
+impl CsvColumns {
+    fn from_header(fields: &[String]) -> Option<Self> {
+        let find = |pred: &dyn Fn(&str) -> bool| {
+            fields
+                .iter()
+                .position(|f| pred(f.trim().to_lowercase().as_str()))
+        };
+        let time = find(&|f| f == "start_time" || f.starts_with("time"))?;
+        let data = find(&|f| f == "data" || f == "value")?;
+        let typ = find(&|f| f == "type");
+        let mut errors = fields
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.to_lowercase().contains("error"))
+            .map(|(idx, _)| idx);
+        Some(Self {
+            time,
+            data,
+            typ,
+            errors: [errors.next(), errors.next()],
+        })
+    }
+}
+
+/// Yields the decoded bytes of a logic analyzer capture export.
+///
+/// Text exports yield a single byte per read so that the capture time of
+/// each byte is available from [`LogicCaptureReader::capture_time_ns`].
+pub struct LogicCaptureReader<R> {
+    inner: R,
+    format: LogicCapture,
+    sample_rate: Option<u64>,
+    columns: Option<CsvColumns>,
+    line: String,
+    /// Capture time of the most recently read byte
+    time_ns: Option<u64>,
+    /// Pre-trigger captures have negative times, which are shifted to start at zero
+    origin_ns: Option<i128>,
+    missing_sample_rate_warned: bool,
+    bytes: u64,
+}
+
+impl<R: io::BufRead> LogicCaptureReader<R> {
+    /// `sample_rate` (Hz) converts the sample numbers of sigrok annotations to time
+    pub fn new(inner: R, format: LogicCapture, sample_rate: Option<u64>) -> Self {
+        debug!(%format, "Reading logic analyzer capture");
+        Self {
+            inner,
+            format,
+            sample_rate,
+            columns: None,
+            line: String::new(),
+            time_ns: None,
+            origin_ns: None,
+            missing_sample_rate_warned: false,
+            bytes: 0,
+        }
+    }
+
+    /// Capture time of the most recently read byte, in nanoseconds
+    pub fn capture_time_ns(&self) -> Option<u64> {
+        self.time_ns
+    }
+
+    /// Returns the next byte and its capture time
+    fn next_byte(&mut self) -> io::Result<Option<(u8, Option<i128>)>> {
+        loop {
+            self.line.clear();
+            if self.inner.read_line(&mut self.line)? == 0 {
+                debug!(bytes = self.bytes, "Finished reading capture");
+                return Ok(None);
+            }
+            let line = self.line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parsed = match self.format {
+                LogicCapture::Binary => unreachable!("Binary captures are read directly"),
+                LogicCapture::Saleae => self.parse_csv_line(line.to_owned())?,
+                LogicCapture::Sigrok => self.parse_sigrok_line(line.to_owned()),
+            };
+            if parsed.is_some() {
+                self.bytes += 1;
+                return Ok(parsed);
+            }
+        }
+    }
+
+    fn parse_csv_line(&mut self, line: String) -> io::Result<Option<(u8, Option<i128>)>> {
+        let fields = split_csv(&line);
+        let Some(cols) = self.columns else {
+            self.columns = Some(CsvColumns::from_header(&fields).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unrecognized Saleae CSV header '{line}'"),
+                )
+            })?);
+            return Ok(None);
+        };
+
+        if let Some(typ) = cols.typ.and_then(|idx| fields.get(idx)) {
+            if !typ.trim().eq_ignore_ascii_case("data") {
+                trace!(%typ, "Skipping non-data row");
+                return Ok(None);
+            }
+        }
+        let has_error = cols
+            .errors
+            .iter()
+            .flatten()
+            .filter_map(|idx| fields.get(*idx))
+            .any(|f| !f.trim().is_empty());
+        if has_error {
+            warn!(row = %line, "Skipping byte with a UART error");
+            return Ok(None);
+        }
+        let Some(byte) = fields.get(cols.data).and_then(|f| parse_byte(f)) else {
+            warn!(row = %line, "Skipping row with an unrecognized data value");
+            return Ok(None);
+        };
+        let time = fields
+            .get(cols.time)
+            .and_then(|t| t.trim().parse::<f64>().ok())
+            .map(|secs| (secs * 1e9).round() as i128);
+        Ok(Some((byte, time)))
+    }
+
+    /// Parses '[<start>-<end> ]<decoder>: [<class>: ]<hex byte>' annotation lines
+    fn parse_sigrok_line(&mut self, line: String) -> Option<(u8, Option<i128>)> {
+        let (prefix, value) = line.rsplit_once(':')?;
+        let value = value.trim();
+        if value.len() != 2 {
+            trace!(%line, "Skipping non-data annotation");
+            return None;
+        }
+        let byte = u8::from_str_radix(value, 16).ok()?;
+
+        let start_sample = prefix
+            .split_whitespace()
+            .next()
+            .and_then(|range| range.split_once('-'))
+            .and_then(|(start, _)| start.parse::<u64>().ok());
+        let time = match (start_sample, self.sample_rate) {
+            (Some(sample), Some(rate)) if rate != 0 => {
+                Some(sample as i128 * 1_000_000_000 / rate as i128)
+            }
+            (Some(_), _) => {
+                if !self.missing_sample_rate_warned {
+                    warn!("The capture has sample numbers, but no sample rate was provided. Capture times will be omitted.");
+                    self.missing_sample_rate_warned = true;
+                }
+                None
+            }
+            _ => None,
+        };
+        Some((byte, time))
+    }
+}
+
+impl<R: io::BufRead> io::Read for LogicCaptureReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.format == LogicCapture::Binary {
+            return self.inner.read(buf);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let Some((byte, time)) = self.next_byte()? else {
+            return Ok(0);
+        };
+        self.time_ns = time.map(|t| {
+            let origin = *self.origin_ns.get_or_insert(t.min(0));
+            (t - origin).max(0) as u64
+        });
+        buf[0] = byte;
+        Ok(1)
+    }
+}
+
+/// Splits a CSV row, handling double-quoted fields
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Parses a data value in any of the Saleae display radixes
+fn parse_byte(s: &str) -> Option<u8> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u8::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+        u8::from_str_radix(bin, 2).ok()
+    } else if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse::<u8>().ok()
+    } else {
+        // ASCII, possibly escaped and/or single-quoted
+        let s = s
+            .strip_prefix('\'')
+            .and_then(|s| s.strip_suffix('\''))
+            .unwrap_or(s);
+        match s {
+            "\\n" => Some(b'\n'),
+            "\\r" => Some(b'\r'),
+            "\\t" => Some(b'\t'),
+            "\\0" => Some(0),
+            "\\\\" => Some(b'\\'),
+            _ => {
+                if let Some(hex) = s.strip_prefix("\\x") {
+                    return u8::from_str_radix(hex, 16).ok();
+                }
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii() => Some(c as u8),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    fn read_all<R: io::BufRead>(r: &mut LogicCaptureReader<R>) -> Vec<(u8, Option<u64>)> {
+        let mut out = Vec::new();
+        let mut b = [0_u8; 16];
+        loop {
+            let n = r.read(&mut b).unwrap();
+            if n == 0 {
+                break;
+            }
+            assert_eq!(n, 1);
+            out.push((b[0], r.capture_time_ns()));
+        }
+        out
+    }
+
+    #[test]
+    fn saleae_logic2_csv() {
+        let csv = r#"name,type,start_time,duration,"data",error
+"Async Serial","data",-0.000010000,0.00008,0x41,
+"Async Serial","data",0.000120000,0.00008,0b01000010,
+"Async Serial","data",0.00025,0.00008,0x43,framing
+"Async Serial","data",1e-3,0.00008,",",
+"#;
+        let mut r = LogicCaptureReader::new(csv.as_bytes(), LogicCapture::Saleae, None);
+        assert_eq!(
+            read_all(&mut r),
+            vec![
+                (0x41, Some(0)),
+                (0x42, Some(130_000)),
+                (b',', Some(1_010_000))
+            ]
+        );
+    }
+
+    #[test]
+    fn saleae_logic1_csv() {
+        let csv = "Time [s],Value,Parity Error,Framing Error\n0.5,72,,\n0.75,'\\n',,\n";
+        let mut r = LogicCaptureReader::new(csv.as_bytes(), LogicCapture::Saleae, None);
+        assert_eq!(
+            read_all(&mut r),
+            vec![(72, Some(500_000_000)), (b'\n', Some(750_000_000))]
+        );
+
+        let mut r = LogicCaptureReader::new(&b"foo,bar\n1,2\n"[..], LogicCapture::Saleae, None);
+        assert!(r.read(&mut [0; 1]).is_err());
+    }
+
+    #[test]
+    fn sigrok_annotations() {
+        let txt = "100-200 uart-1: Start bit\n1000-2000 uart-1: 48\n3000-4000 UART: RX data: 0a\n";
+        let mut r = LogicCaptureReader::new(txt.as_bytes(), LogicCapture::Sigrok, Some(1_000_000));
+        assert_eq!(
+            read_all(&mut r),
+            vec![(0x48, Some(1_000_000)), (0x0A, Some(3_000_000))]
+        );
+
+        let txt = "uart-1: 48\n";
+        let mut r = LogicCaptureReader::new(txt.as_bytes(), LogicCapture::Sigrok, None);
+        assert_eq!(read_all(&mut r), vec![(0x48, None)]);
+    }
+
+    #[test]
+    fn binary_passthrough() {
+        let mut r = LogicCaptureReader::new(&[1_u8, 2, 3][..], LogicCapture::Binary, None);
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).unwrap();
+        assert_eq!(out, vec![1, 2, 3]);
+        assert_eq!(r.capture_time_ns(), None);
+    }
+}
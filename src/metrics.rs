@@ -0,0 +1,217 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Read loop throughput over one reporting window
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MetricsReport {
+    pub window: Duration,
+    /// Bytes read from the transport
+    pub bytes_read: u64,
+    /// defmt frames decoded
+    pub frames: u64,
+    /// Events handed to the ingest client, after filtering
+    pub events: u64,
+    /// Frames the defmt decoder had to resynchronize past
+    pub malformed_frames: u64,
+    /// Events received by modality that are not yet written, when known
+    pub ingest_pending: Option<u64>,
+}
+
+impl MetricsReport {
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.per_sec(self.bytes_read)
+    }
+
+    pub fn frames_per_sec(&self) -> f64 {
+        self.per_sec(self.frames)
+    }
+
+    pub fn events_per_sec(&self) -> f64 {
+        self.per_sec(self.events)
+    }
+
+    fn per_sec(&self, count: u64) -> f64 {
+        let secs = self.window.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            count as f64 / secs
+        }
+    }
+}
+
+/// Accumulates the read loop's counters, producing a report once per interval
+#[derive(Debug)]
+pub struct ReaderMetrics {
+    interval: Duration,
+    window_start: Instant,
+    current: MetricsReport,
+}
+
+impl ReaderMetrics {
+    pub fn new(interval: Duration) -> Self {
+        Self::new_at(interval, Instant::now())
+    }
+
+    fn new_at(interval: Duration, now: Instant) -> Self {
+        Self {
+            interval,
+            window_start: now,
+            current: Default::default(),
+        }
+    }
+
+    pub fn bytes_read(&mut self, bytes: usize) {
+        self.current.bytes_read += bytes as u64;
+    }
+
+    pub fn frame(&mut self) {
+        self.current.frames += 1;
+    }
+
+    pub fn malformed_frame(&mut self) {
+        self.current.malformed_frames += 1;
+    }
+
+    pub fn event(&mut self) {
+        self.current.events += 1;
+    }
+
+    /// The report of the window that just ended, once the interval has elapsed
+    pub fn poll(&mut self, now: Instant) -> Option<MetricsReport> {
+        let window = now.saturating_duration_since(self.window_start);
+        if window < self.interval {
+            return None;
+        }
+        self.window_start = now;
+        Some(MetricsReport {
+            window,
+            ..std::mem::take(&mut self.current)
+        })
+    }
+}
+
+/// The totals of a whole read loop, written to the summary file on exit for CI collection
+/// and trend tracking
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RunSummary {
+    /// 'finished', 'stopped' or 'failed'
+    pub outcome: String,
+    /// The error that ended the read loop, when it failed
+    pub error: Option<String>,
+    /// RFC 3339 start time
+    pub started_at: String,
+    pub duration_secs: f64,
+    /// Bytes read from the transport, after removing any framing
+    pub bytes_read: u64,
+    /// defmt frames decoded
+    pub frames: u64,
+    /// Events handed to the ingest client
+    pub events: u64,
+    /// Frames the defmt decoder had to resynchronize past
+    pub malformed_frames: u64,
+    /// Frames of excluded table indices or format strings
+    pub excluded_frames: u64,
+    /// Events dropped by the event filter
+    pub filtered_events: u64,
+    /// Timelines the events were sent on
+    pub timelines: usize,
+    /// The ingest client's status at the end of the run, when known
+    pub ingest: Option<IngestSummary>,
+}
+
+/// Event counts reported by modality's ingest status
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct IngestSummary {
+    pub events_received: u64,
+    pub events_written: u64,
+    pub events_pending: u64,
+}
+
+/// A value of target memory sampled by the collector (e.g. a heap high-water mark
+/// or a counter the firmware doesn't log)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchSample {
+    /// The symbol or address the value was read from
+    pub location: String,
+    pub address: u64,
+    pub value: u64,
+}
+
+impl WatchSample {
+    /// The largest watched value, in bytes
+    pub const MAX_SIZE: usize = 8;
+
+    /// Decodes a little-endian unsigned integer of up to [`Self::MAX_SIZE`] bytes
+    pub fn from_le_bytes(location: &str, address: u64, bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() || bytes.len() > Self::MAX_SIZE {
+            return None;
+        }
+        let mut value = [0_u8; Self::MAX_SIZE];
+        value[..bytes.len()].copy_from_slice(bytes);
+        Some(Self {
+            location: location.to_owned(),
+            address,
+            value: u64::from_le_bytes(value),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn windowed_reports() {
+        let start = Instant::now();
+        let mut metrics = ReaderMetrics::new_at(Duration::from_secs(2), start);
+        metrics.bytes_read(1000);
+        metrics.frame();
+        metrics.frame();
+        metrics.malformed_frame();
+        metrics.event();
+        assert_eq!(metrics.poll(start + Duration::from_secs(1)), None);
+
+        let report = metrics.poll(start + Duration::from_secs(2)).unwrap();
+        assert_eq!(
+            report,
+            MetricsReport {
+                window: Duration::from_secs(2),
+                bytes_read: 1000,
+                frames: 2,
+                events: 1,
+                malformed_frames: 1,
+                ingest_pending: None,
+            }
+        );
+        assert_eq!(report.bytes_per_sec(), 500.0);
+        assert_eq!(report.frames_per_sec(), 1.0);
+        assert_eq!(report.events_per_sec(), 0.5);
+
+        // Counters start over with the next window
+        metrics.event();
+        let report = metrics.poll(start + Duration::from_secs(5)).unwrap();
+        assert_eq!(report.window, Duration::from_secs(3));
+        assert_eq!(report.events, 1);
+        assert_eq!(report.frames, 0);
+        assert_eq!(MetricsReport::default().bytes_per_sec(), 0.0);
+    }
+
+    #[test]
+    fn watch_samples() {
+        let sample = WatchSample::from_le_bytes("HEAP_HWM", 0x2000_0000, &[0x34, 0x12]).unwrap();
+        assert_eq!(
+            sample,
+            WatchSample {
+                location: "HEAP_HWM".to_owned(),
+                address: 0x2000_0000,
+                value: 0x1234,
+            }
+        );
+        let sample = WatchSample::from_le_bytes("0x20000010", 0x2000_0010, &[0xFF; 8]).unwrap();
+        assert_eq!(sample.value, u64::MAX);
+        assert_eq!(WatchSample::from_le_bytes("X", 0, &[]), None);
+        assert_eq!(WatchSample::from_le_bytes("X", 0, &[0; 9]), None);
+    }
+}
@@ -1,10 +1,22 @@
-use crate::time::Rate;
-use clap::Parser;
 use derive_more::Display;
 use serde_with::DeserializeFromStr;
-use std::{path::PathBuf, str::FromStr};
-use url::Url;
+use std::str::FromStr;
+#[cfg(feature = "ingest")]
+use {
+    crate::{
+        config::{SourcePathRemap, StopTrigger},
+        framing::{FrameCrc, Framing},
+        glob::Glob,
+        rename::AttrRename,
+        time::Rate,
+    },
+    auxon_sdk::reflector_config::AttrKeyEqValuePair,
+    clap::Parser,
+    std::path::PathBuf,
+    url::Url,
+};
 
+#[cfg(feature = "ingest")]
 #[derive(Parser, Debug, Clone, Default)]
 pub struct ReflectorOpts {
     /// Use configuration from file
@@ -62,8 +74,38 @@ pub struct ReflectorOpts {
     /// Use the provided clock ID instead of generating a random UUID
     #[clap(long, name = "clock-id", help_heading = "REFLECTOR CONFIGURATION")]
     pub clock_id: Option<String>,
+
+    /// Apply the '[metadata.profile.<name>]' table of the configuration file over the
+    /// rest of the metadata, instead of the file's 'active-profiles'.
+    /// Can be provided multiple times, later profiles take precedence.
+    #[clap(
+        long = "profile",
+        name = "profile",
+        help_heading = "REFLECTOR CONFIGURATION"
+    )]
+    pub profiles: Vec<String>,
+
+    /// Add an attribute to every timeline, e.g. "ci_run=42" or "commit='3f2a1c9'".
+    /// Takes precedence over the configuration file's 'additional-timeline-attributes'.
+    /// Can be provided multiple times.
+    #[clap(
+        long = "timeline-attr",
+        name = "timeline-attr",
+        help_heading = "REFLECTOR CONFIGURATION"
+    )]
+    pub additional_timeline_attributes: Vec<AttrKeyEqValuePair>,
+
+    /// Override an attribute on every timeline, e.g. "platform='RTICv2'".
+    /// Can be provided multiple times.
+    #[clap(
+        long = "override-timeline-attr",
+        name = "override-timeline-attr",
+        help_heading = "REFLECTOR CONFIGURATION"
+    )]
+    pub override_timeline_attributes: Vec<AttrKeyEqValuePair>,
 }
 
+#[cfg(feature = "ingest")]
 #[derive(Parser, Debug, Clone, Default)]
 pub struct DefmtOpts {
     /// Use the provided rate as the time base for converting ticks to nanoseconds.
@@ -75,13 +117,434 @@ pub struct DefmtOpts {
     #[clap(long, help_heading = "DEFMT CONFIGURATION")]
     pub disable_interactions: bool,
 
+    /// Don't linearize the causality across context switches. There are no synthetic
+    /// 'AUXON_CONTEXT_RETURN' events or context switch interactions, only the explicit
+    /// ones (spawns and message correlations).
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub disable_linear_causality: bool,
+
     /// Use the provided init task name instead of the default ('main')
     #[clap(long, help_heading = "DEFMT CONFIGURATION")]
     pub init_task_name: Option<String>,
 
-    /// The RTOS mode to use (none, rtic1)
+    /// The RTOS mode to use (none, rtic1, rtic2, cmsis-rtos2, bare-metal, custom)
     #[clap(long, name = "rtos-mode", help_heading = "DEFMT CONFIGURATION")]
     pub rtos_mode: Option<RtosMode>,
+
+    /// The policy for events received before the RTOS start event
+    /// (disable-rtos-mode, hold, drop, pre-start-timeline, resync)
+    #[clap(long, name = "pre-start-policy", help_heading = "DEFMT CONFIGURATION")]
+    pub pre_start_policy: Option<PreStartPolicy>,
+
+    /// Fold the matching ISRs into the timeline of the context they interrupted, when in RTOS mode.
+    /// Their events get an 'event.isr' attribute instead of their own timeline.
+    /// Supports '*' and '?' wildcards, can be provided multiple times.
+    #[clap(
+        long = "fold-isr",
+        name = "fold-isr",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub fold_isrs: Vec<Glob>,
+
+    /// Reload the defmt table and location info when the ELF file changes, e.g. when the
+    /// firmware is rebuilt. Also starts a new run when '--segment-runs' is enabled
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub elf_reload: bool,
+
+    /// The separate debug file (e.g. from 'objcopy --only-keep-debug') to read the location
+    /// info from, when the ELF file has been stripped. By default, the debug file named by the
+    /// ELF file's '.gnu_debuglink' section is used
+    #[clap(long, name = "debug-file", help_heading = "DEFMT CONFIGURATION")]
+    pub debug_file: Option<PathBuf>,
+
+    /// The boot index to record on every timeline (timeline.boot_index), defaults to 0
+    #[clap(long, name = "boot-index", help_heading = "DEFMT CONFIGURATION")]
+    pub boot_index: Option<u64>,
+
+    /// Start a new run (run_id, clock_id and timelines, incrementing the boot index) when a
+    /// target reboot is detected, instead of welding the boots into a single trace
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub segment_runs: bool,
+
+    /// The name of the event the firmware logs on boot, marking a reboot when
+    /// '--segment-runs' is enabled
+    #[clap(long, name = "reboot-event", help_heading = "DEFMT CONFIGURATION")]
+    pub reboot_event: Option<String>,
+
+    /// Consider a timestamp going backwards to at most this raw value a reboot when
+    /// '--segment-runs' is enabled. Only applies to 64-bit and unit timestamps without
+    /// '--timestamp-width', narrower ones roll over
+    #[clap(
+        long,
+        name = "reboot-timestamp-threshold",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub reboot_timestamp_threshold: Option<u64>,
+
+    /// The core the events come from, recorded on every timeline (timeline.core).
+    /// Contexts are scoped per core, so identically-named tasks on different cores get
+    /// their own timelines.
+    #[clap(long, name = "core-id", help_heading = "DEFMT CONFIGURATION")]
+    pub core_id: Option<u64>,
+
+    /// Track the run time of each context, when in RTOS mode.
+    /// Context exit events get the activation's duration, and an 'AUXON_CONTEXT_STATS' event
+    /// with the totals is added to each context timeline at the end of the stream.
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub context_stats: bool,
+
+    /// The maximum context stack depth, when in RTOS mode (defaults to 32).
+    /// Deeper nesting is treated as unbalanced context markers and the stack is reset to
+    /// the root context.
+    #[clap(long, name = "max-context-depth", help_heading = "DEFMT CONFIGURATION")]
+    pub max_context_depth: Option<usize>,
+
+    /// Use the provided name for the timeline of events whose context is unknown,
+    /// instead of the default ('UNKNOWN_CONTEXT')
+    #[clap(
+        long,
+        name = "unknown-context-name",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub unknown_context_name: Option<String>,
+
+    /// Split the unknown context by source: events naming an ISR ('irq' or 'isr' parameter)
+    /// get their own unknown context, as does each RTT up channel
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub unknown_context_per_source: bool,
+
+    /// The name of the marker event whose 'phase' attribute sets the current test phase (event.phase)
+    #[clap(
+        long,
+        name = "phase-marker-event",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub phase_marker_event: Option<String>,
+
+    /// Promote the matching internal attribute keys into the public namespace,
+    /// e.g. 'event.internal.defmt.table_index' becomes 'event.table_index'.
+    /// Supports '*' and '?' wildcards, can be provided multiple times.
+    #[clap(
+        long = "promote-internal-attribute",
+        name = "promote-internal-attribute",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub promote_internal_attributes: Vec<Glob>,
+
+    /// The link-layer framing wrapping the defmt data (none, cobs, slip)
+    #[clap(long, name = "framing", help_heading = "DEFMT CONFIGURATION")]
+    pub framing: Option<Framing>,
+
+    /// The CRC appended to each link-layer frame's payload (none, crc16, crc32)
+    #[clap(
+        long,
+        name = "frame-crc",
+        requires = "framing",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub frame_crc: Option<FrameCrc>,
+
+    /// The maximum number of array or slice argument elements recorded as indexed attributes
+    /// (defaults to 32). The length is always recorded.
+    #[clap(
+        long,
+        name = "max-array-elements",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub max_array_elements: Option<usize>,
+
+    /// The encoding of byte slice argument attributes (hex, base64)
+    #[clap(long, name = "byte-encoding", help_heading = "DEFMT CONFIGURATION")]
+    pub byte_encoding: Option<ByteEncoding>,
+
+    /// Use the parameters' display hints when recording their attributes.
+    /// Hex (':x'), binary (':b') and debug (':?') hints add a formatted string attribute
+    /// ('<key>.formatted'), and byte slices with an ASCII hint (':a') are recorded as a string.
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub display_hints: bool,
+
+    /// The level recorded on frames that don't have one, like those from 'defmt::println!',
+    /// instead of the default ('println'). Use one of the regular levels (e.g. 'info') to
+    /// treat them like leveled frames.
+    #[clap(long, name = "println-level", help_heading = "DEFMT CONFIGURATION")]
+    pub println_level: Option<String>,
+
+    /// How the timestamp is composed from the 'defmt::timestamp!' arguments
+    /// (single, high-low, low-high). 'high-low' reconstructs 64-bit ticks from a high word
+    /// (or overflow count) followed by a low word (or counter), e.g. '{=u32}{=u32}'.
+    #[clap(
+        long,
+        name = "timestamp-composition",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub timestamp_composition: Option<TimestampComposition>,
+
+    /// The width, in bits, of the target's timestamp counter, when it's narrower than the
+    /// 'defmt::timestamp!' argument type (e.g. a 24-bit timer logged as a u32).
+    /// Rollovers are tracked at this width. Defaults to the argument type's width.
+    #[clap(long, name = "timestamp-width", help_heading = "DEFMT CONFIGURATION")]
+    pub timestamp_width: Option<u32>,
+
+    /// Use the host receive time (nanoseconds since the Unix epoch) as the 'event.timestamp'
+    /// of events whose frame doesn't have a timestamp, like firmware without 'defmt::timestamp!'.
+    /// Host and device time aren't mixed, it's turned off once a frame has a device timestamp
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub host_time_fallback: bool,
+
+    /// What to do with events whose timestamp is earlier than the previous event's
+    /// (warn, clamp, rollover, drop)
+    #[clap(
+        long,
+        name = "backwards-timestamp-policy",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub backwards_timestamp_policy: Option<BackwardsTimestampPolicy>,
+
+    /// Flag gaps in the device time between consecutive events that are longer than this
+    /// (e.g. '10ms'), likely data loss or a target stall. The event after the gap gets
+    /// an 'event.timestamp_gap' attribute with the gap's duration.
+    #[clap(
+        long,
+        name = "timestamp-gap-threshold",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub timestamp_gap_threshold: Option<humantime::Duration>,
+
+    /// The delimiter between the event name and the rest of the format string,
+    /// instead of the default ('::')
+    #[clap(
+        long,
+        name = "event-name-delimiter",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub event_name_delimiter: Option<String>,
+
+    /// The separator between key/value pairs in the format string (e.g. '|' or ';'),
+    /// instead of the default (',')
+    #[clap(long, name = "attr-separator", help_heading = "DEFMT CONFIGURATION")]
+    pub attr_separator: Option<char>,
+
+    /// Use the literal text before the first parameter as the event name when the format
+    /// string doesn't have an explicit one (e.g. 'Motor started {=u32}' is named 'Motor started')
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub literal_event_names: bool,
+
+    /// The event name of frames without an explicit one (formatted, format-string).
+    /// 'format-string' names events after their log statement, without any argument values
+    #[clap(
+        long,
+        name = "unnamed-event-names",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub unnamed_event_names: Option<UnnamedEventNames>,
+
+    /// Collapse whitespace and drop control characters in event names
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub normalize_event_names: bool,
+
+    /// The maximum event name length, in characters. Truncated names are suffixed
+    /// with a hash of the full name to keep them distinct
+    #[clap(
+        long,
+        name = "max-event-name-length",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub max_event_name_length: Option<usize>,
+
+    /// The maximum number of distinct event attribute keys declared to Modality,
+    /// guarding against firmware that derives keys from variable data
+    #[clap(
+        long,
+        name = "max-attribute-keys",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub max_attribute_keys: Option<usize>,
+
+    /// What to do once the attribute key limit is reached (warn, drop)
+    #[clap(
+        long,
+        name = "attribute-key-limit-policy",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub attribute_key_limit_policy: Option<AttrKeyLimitPolicy>,
+
+    /// Interpret attribute key unit suffixes. Integer durations ('_ns', '_us', '_ms', '_s')
+    /// are recorded as nanosecond timestamps, and frequencies ('_hz') and percentages ('_pct')
+    /// as floats. The original value is kept under '<key>.raw'.
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub infer_units: bool,
+
+    /// Rename an attribute key before ingest, e.g. 'event.tid=event.task_id'.
+    /// Existing attributes are never overwritten. Can be provided multiple times.
+    #[clap(
+        long = "rename-attribute",
+        name = "rename-attribute",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub rename_attributes: Vec<AttrRename>,
+
+    /// Only keep events with a matching level (e.g. 'warn'), dropping the rest before ingest.
+    /// Supports '*' and '?' wildcards, can be provided multiple times.
+    #[clap(
+        long = "include-level",
+        name = "include-level",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub include_levels: Vec<Glob>,
+
+    /// Drop events with a matching level (e.g. 'trace') before ingest.
+    /// Supports '*' and '?' wildcards, can be provided multiple times.
+    #[clap(
+        long = "exclude-level",
+        name = "exclude-level",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub exclude_levels: Vec<Glob>,
+
+    /// Only keep events from a matching source module path (e.g. 'fw::net::*'), dropping the rest
+    /// before ingest. Supports '*' and '?' wildcards, can be provided multiple times.
+    #[clap(
+        long = "include-module",
+        name = "include-module",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub include_modules: Vec<Glob>,
+
+    /// Drop events from a matching source module path before ingest.
+    /// Supports '*' and '?' wildcards, can be provided multiple times.
+    #[clap(
+        long = "exclude-module",
+        name = "exclude-module",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub exclude_modules: Vec<Glob>,
+
+    /// Only keep events with a matching name, dropping the rest before ingest.
+    /// Supports '*' and '?' wildcards, can be provided multiple times.
+    #[clap(
+        long = "include-event",
+        name = "include-event",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub include_events: Vec<Glob>,
+
+    /// Drop events with a matching name before ingest.
+    /// Supports '*' and '?' wildcards, can be provided multiple times.
+    #[clap(
+        long = "exclude-event",
+        name = "exclude-event",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub exclude_events: Vec<Glob>,
+
+    /// Skip the frames of a defmt table index right after decoding, before any
+    /// attribute extraction. Can be provided multiple times.
+    #[clap(
+        long = "exclude-table-index",
+        name = "exclude-table-index",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub exclude_table_indices: Vec<u64>,
+
+    /// Skip the frames whose format string matches (e.g. 'poll::*') right after decoding,
+    /// before any attribute extraction. Supports '*' and '?' wildcards, can be provided
+    /// multiple times.
+    #[clap(
+        long = "exclude-format-string",
+        name = "exclude-format-string",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub exclude_format_strings: Vec<Glob>,
+
+    /// Stop collection at the first event matching the trigger, e.g. 'panic*' or
+    /// 'name=panic*,level=error,event.code=42'. Takes an event name pattern, or
+    /// comma-separated 'KEY=PATTERN' criteria where the keys are 'name', 'level',
+    /// 'module' or an attribute key. Can be provided multiple times.
+    #[clap(
+        long = "stop-on",
+        name = "stop-on",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub stop_on: Vec<StopTrigger>,
+
+    /// Omit the internal event attributes ('event.internal.defmt.*') from ingest, for
+    /// smaller high-volume traces. Promoted internal attributes are kept.
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub omit_internal_attributes: bool,
+
+    /// Omit the formatted string event attribute ('event.internal.defmt.formatted_string')
+    /// from ingest
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub omit_formatted_string: bool,
+
+    /// Keep all of the attributes, overriding the 'omit-internal-attributes' and
+    /// 'omit-formatted-string' configuration for a debug session
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub debug_attributes: bool,
+
+    /// Record the formatted string as the 'event.message' attribute, so the rendered log line
+    /// is visible without digging into the internal attributes
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub message_attribute: bool,
+
+    /// Record the encoded bytes (base64) of each frame and its byte offset in the defmt stream
+    /// as internal attributes, for debugging decode discrepancies
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub raw_frame_attributes: bool,
+
+    /// Periodically log the read throughput, decode rate, frames per second, malformed
+    /// frame count and ingest queue depth at this interval.
+    /// Accepts durations like "10s" or "1minute".
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub metrics_interval: Option<humantime::Duration>,
+
+    /// Also ingest the periodic metrics as 'AUXON_METRICS' events, requires
+    /// a metrics interval
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub metric_events: bool,
+
+    /// Write a copy of the raw defmt byte stream to this file while ingesting, so the run
+    /// can be replayed and re-imported later
+    #[clap(long, name = "save-raw", help_heading = "DEFMT CONFIGURATION")]
+    pub save_raw: Option<PathBuf>,
+
+    /// Rotate the raw capture file once it reaches this many bytes, keeping the previous
+    /// files as '<path>.1', '<path>.2', etc
+    #[clap(long, requires = "save-raw", help_heading = "DEFMT CONFIGURATION")]
+    pub save_raw_rotate_size: Option<u64>,
+
+    /// The number of rotated raw capture files to keep (defaults to 5)
+    #[clap(long, requires = "save-raw", help_heading = "DEFMT CONFIGURATION")]
+    pub save_raw_rotate_count: Option<usize>,
+
+    /// Write a JSON summary of the run (events decoded, malformed frames, bytes read,
+    /// timelines, ingest status counts and timing) to this file on exit
+    #[clap(long, help_heading = "DEFMT CONFIGURATION")]
+    pub summary_file: Option<PathBuf>,
+
+    /// Replace a source file path prefix before recording 'event.source.file' and
+    /// 'event.source.uri', e.g. '/home/ci/builds/fw/=' strips a CI builder's workspace
+    /// directory. The first matching prefix wins. Can be provided multiple times.
+    #[clap(
+        long = "remap-source-path",
+        name = "remap-source-path",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub source_path_remaps: Vec<SourcePathRemap>,
+
+    /// The commit substituted into the '{commit}' placeholder of the 'source-urls' templates,
+    /// e.g. the firmware build's git hash
+    #[clap(long, name = "source-commit", help_heading = "DEFMT CONFIGURATION")]
+    pub source_commit: Option<String>,
+
+    /// Add an attribute to every event, e.g. "test_case='thermal-cycle-3'".
+    /// Attributes already on the event take precedence. Can be provided multiple times.
+    #[clap(
+        long = "additional-event-attribute",
+        name = "additional-event-attribute",
+        help_heading = "DEFMT CONFIGURATION"
+    )]
+    pub additional_event_attributes: Vec<AttrKeyEqValuePair>,
 }
 
 #[derive(
@@ -93,6 +556,15 @@ pub enum RtosMode {
     None,
     #[display(fmt = "rtic1")]
     Rtic1,
+    #[display(fmt = "rtic2")]
+    Rtic2,
+    #[display(fmt = "cmsis-rtos2")]
+    CmsisRtos2,
+    #[display(fmt = "bare-metal")]
+    BareMetal,
+    /// `rtic1` semantics with the marker events from the `rtos-markers` configuration
+    #[display(fmt = "custom")]
+    Custom,
 }
 
 impl FromStr for RtosMode {
@@ -102,11 +574,199 @@ impl FromStr for RtosMode {
         Ok(match s.trim().to_lowercase().as_ref() {
             "none" => RtosMode::None,
             "rtic1" => RtosMode::Rtic1,
+            "rtic2" => RtosMode::Rtic2,
+            "cmsis-rtos2" | "rtx" => RtosMode::CmsisRtos2,
+            "bare-metal" => RtosMode::BareMetal,
+            "custom" => RtosMode::Custom,
             _ => return Err(format!("Unsupported RTOS mode '{s}'")),
         })
     }
 }
 
+/// What to do with events received before the RTOS start event (`AUXON_TRACE_START`),
+/// e.g. stray boot output when attaching before a reset
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, DeserializeFromStr,
+)]
+pub enum PreStartPolicy {
+    /// Disable RTOS mode and put everything on a single timeline
+    #[default]
+    #[display(fmt = "disable-rtos-mode")]
+    DisableRtosMode,
+    /// Hold the events and put them on the init task timeline once the start event arrives
+    #[display(fmt = "hold")]
+    Hold,
+    /// Drop the events
+    #[display(fmt = "drop")]
+    Drop,
+    /// Put the events on a dedicated pre-start timeline
+    #[display(fmt = "pre-start-timeline")]
+    Timeline,
+    /// Keep RTOS mode, starting on the unknown context and following the context
+    /// markers from there. For attaching to a target that's already running.
+    #[display(fmt = "resync")]
+    Resync,
+}
+
+impl FromStr for PreStartPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "disable-rtos-mode" => PreStartPolicy::DisableRtosMode,
+            "hold" => PreStartPolicy::Hold,
+            "drop" => PreStartPolicy::Drop,
+            "pre-start-timeline" => PreStartPolicy::Timeline,
+            "resync" => PreStartPolicy::Resync,
+            _ => return Err(format!("Unsupported pre-start policy '{s}'")),
+        })
+    }
+}
+
+/// What to do with events whose timestamp is earlier than the previous event's
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, DeserializeFromStr,
+)]
+pub enum BackwardsTimestampPolicy {
+    /// Log a warning and keep the timestamp
+    #[default]
+    #[display(fmt = "warn")]
+    Warn,
+    /// Use the previous event's timestamp
+    #[display(fmt = "clamp")]
+    Clamp,
+    /// Treat it as a counter rollover (or reset) right after the previous event, offsetting
+    /// this and all of the following timestamps so time keeps increasing
+    #[display(fmt = "rollover")]
+    Rollover,
+    /// Drop the event
+    #[display(fmt = "drop")]
+    Drop,
+}
+
+impl FromStr for BackwardsTimestampPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "warn" => BackwardsTimestampPolicy::Warn,
+            "clamp" => BackwardsTimestampPolicy::Clamp,
+            "rollover" => BackwardsTimestampPolicy::Rollover,
+            "drop" => BackwardsTimestampPolicy::Drop,
+            _ => return Err(format!("Unsupported backwards timestamp policy '{s}'")),
+        })
+    }
+}
+
+/// How the timestamp is composed from the `defmt::timestamp!` arguments
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, DeserializeFromStr,
+)]
+pub enum TimestampComposition {
+    /// A single argument
+    #[default]
+    #[display(fmt = "single")]
+    Single,
+    /// Two arguments, the high bits (or an overflow count) followed by the low bits
+    /// (or the counter), e.g. `{=u32}{=u32}`
+    #[display(fmt = "high-low")]
+    HighLow,
+    /// Two arguments, the low bits followed by the high bits
+    #[display(fmt = "low-high")]
+    LowHigh,
+}
+
+impl FromStr for TimestampComposition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "single" => TimestampComposition::Single,
+            "high-low" | "overflow-counter" => TimestampComposition::HighLow,
+            "low-high" => TimestampComposition::LowHigh,
+            _ => return Err(format!("Unsupported timestamp composition '{s}'")),
+        })
+    }
+}
+
+/// What to do once the event attribute key limit is reached
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, DeserializeFromStr,
+)]
+pub enum AttrKeyLimitPolicy {
+    /// Log a warning, keep declaring new keys
+    #[default]
+    #[display(fmt = "warn")]
+    Warn,
+    /// Log a warning, drop the attributes with new keys
+    #[display(fmt = "drop")]
+    Drop,
+}
+
+impl FromStr for AttrKeyLimitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "warn" => AttrKeyLimitPolicy::Warn,
+            "drop" => AttrKeyLimitPolicy::Drop,
+            _ => return Err(format!("Unsupported attribute key limit policy '{s}'")),
+        })
+    }
+}
+
+/// The event name of frames without an explicit one
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, DeserializeFromStr,
+)]
+pub enum UnnamedEventNames {
+    /// The formatted string, including any argument values
+    #[default]
+    #[display(fmt = "formatted")]
+    Formatted,
+    /// The table entry's format string, one event name per log statement
+    #[display(fmt = "format-string")]
+    FormatString,
+}
+
+impl FromStr for UnnamedEventNames {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "formatted" | "formatted-string" => UnnamedEventNames::Formatted,
+            "format-string" | "format" => UnnamedEventNames::FormatString,
+            _ => return Err(format!("Unsupported unnamed event names '{s}'")),
+        })
+    }
+}
+
+/// How byte slice arguments (`{=[u8]}`) are encoded into string attributes
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, DeserializeFromStr,
+)]
+pub enum ByteEncoding {
+    /// Lowercase hex, two characters per byte
+    #[default]
+    #[display(fmt = "hex")]
+    Hex,
+    /// Standard base64, with padding
+    #[display(fmt = "base64")]
+    Base64,
+}
+
+impl FromStr for ByteEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_ref() {
+            "hex" => ByteEncoding::Hex,
+            "base64" => ByteEncoding::Base64,
+            _ => return Err(format!("Unsupported byte encoding '{s}'")),
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -115,9 +775,125 @@ mod test {
     fn rtos_mode() {
         assert_eq!(RtosMode::from_str("none"), Ok(RtosMode::None));
         assert_eq!(RtosMode::from_str("rtic1"), Ok(RtosMode::Rtic1));
+        assert_eq!(RtosMode::from_str("rtic2"), Ok(RtosMode::Rtic2));
+        assert_eq!(RtosMode::from_str("rtx"), Ok(RtosMode::CmsisRtos2));
+        assert_eq!(RtosMode::from_str("bare-metal"), Ok(RtosMode::BareMetal));
+        assert_eq!(RtosMode::from_str("custom"), Ok(RtosMode::Custom));
+        assert_eq!(
+            RtosMode::from_str("rtic3"),
+            Err("Unsupported RTOS mode 'rtic3'".to_owned())
+        );
+    }
+
+    #[test]
+    fn pre_start_policy() {
+        assert_eq!(
+            PreStartPolicy::from_str("disable-rtos-mode"),
+            Ok(PreStartPolicy::DisableRtosMode)
+        );
+        assert_eq!(PreStartPolicy::from_str("hold"), Ok(PreStartPolicy::Hold));
+        assert_eq!(PreStartPolicy::from_str("drop"), Ok(PreStartPolicy::Drop));
+        assert_eq!(
+            PreStartPolicy::from_str("pre-start-timeline"),
+            Ok(PreStartPolicy::Timeline)
+        );
+        assert_eq!(
+            PreStartPolicy::from_str("resync"),
+            Ok(PreStartPolicy::Resync)
+        );
+        assert_eq!(
+            PreStartPolicy::from_str("keep"),
+            Err("Unsupported pre-start policy 'keep'".to_owned())
+        );
+    }
+
+    #[test]
+    fn byte_encoding() {
+        assert_eq!(ByteEncoding::from_str("hex"), Ok(ByteEncoding::Hex));
+        assert_eq!(ByteEncoding::from_str("Base64"), Ok(ByteEncoding::Base64));
+        assert_eq!(
+            ByteEncoding::from_str("base32"),
+            Err("Unsupported byte encoding 'base32'".to_owned())
+        );
+    }
+
+    #[test]
+    fn timestamp_composition() {
+        assert_eq!(
+            TimestampComposition::from_str("single"),
+            Ok(TimestampComposition::Single)
+        );
+        assert_eq!(
+            TimestampComposition::from_str("high-low"),
+            Ok(TimestampComposition::HighLow)
+        );
+        assert_eq!(
+            TimestampComposition::from_str("overflow-counter"),
+            Ok(TimestampComposition::HighLow)
+        );
+        assert_eq!(
+            TimestampComposition::from_str("low-high"),
+            Ok(TimestampComposition::LowHigh)
+        );
+        assert_eq!(
+            TimestampComposition::from_str("mid"),
+            Err("Unsupported timestamp composition 'mid'".to_owned())
+        );
+    }
+
+    #[test]
+    fn backwards_timestamp_policy() {
+        assert_eq!(
+            BackwardsTimestampPolicy::from_str("warn"),
+            Ok(BackwardsTimestampPolicy::Warn)
+        );
+        assert_eq!(
+            BackwardsTimestampPolicy::from_str("clamp"),
+            Ok(BackwardsTimestampPolicy::Clamp)
+        );
+        assert_eq!(
+            BackwardsTimestampPolicy::from_str("rollover"),
+            Ok(BackwardsTimestampPolicy::Rollover)
+        );
+        assert_eq!(
+            BackwardsTimestampPolicy::from_str("drop"),
+            Ok(BackwardsTimestampPolicy::Drop)
+        );
+        assert_eq!(
+            BackwardsTimestampPolicy::from_str("ignore"),
+            Err("Unsupported backwards timestamp policy 'ignore'".to_owned())
+        );
+    }
+
+    #[test]
+    fn attr_key_limit_policy() {
+        assert_eq!(
+            AttrKeyLimitPolicy::from_str("warn"),
+            Ok(AttrKeyLimitPolicy::Warn)
+        );
+        assert_eq!(
+            AttrKeyLimitPolicy::from_str("Drop"),
+            Ok(AttrKeyLimitPolicy::Drop)
+        );
+        assert_eq!(
+            AttrKeyLimitPolicy::from_str("stop"),
+            Err("Unsupported attribute key limit policy 'stop'".to_owned())
+        );
+    }
+
+    #[test]
+    fn unnamed_event_names() {
+        assert_eq!(
+            UnnamedEventNames::from_str("formatted"),
+            Ok(UnnamedEventNames::Formatted)
+        );
+        assert_eq!(
+            UnnamedEventNames::from_str("format-string"),
+            Ok(UnnamedEventNames::FormatString)
+        );
         assert_eq!(
-            RtosMode::from_str("rtic2"),
-            Err("Unsupported RTOS mode 'rtic2'".to_owned())
+            UnnamedEventNames::from_str("literal"),
+            Err("Unsupported unnamed event names 'literal'".to_owned())
         );
     }
 }
@@ -0,0 +1,690 @@
+//! Extracts defmt payloads from pcap and pcapng network captures (e.g. from tcpdump),
+//! so network-transported defmt traffic can be replayed.
+
+use derive_more::Display;
+use serde_with::DeserializeFromStr;
+use std::{collections::BTreeMap, io, str::FromStr};
+use tracing::{debug, trace, warn};
+
+/// Selects the packets carrying the defmt data
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, DeserializeFromStr)]
+pub enum PcapFilter {
+    /// UDP datagrams with the given source or destination port
+    #[display(fmt = "udp:{_0}")]
+    Udp(u16),
+    /// The TCP stream with the given source or destination port.
+    /// The first direction of the first matching connection to carry data is used.
+    #[display(fmt = "tcp:{_0}")]
+    Tcp(u16),
+}
+
+impl FromStr for PcapFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid pcap filter '{s}', expected 'udp:<PORT>' or 'tcp:<PORT>'");
+        let (proto, port) = s.trim().split_once(':').ok_or_else(err)?;
+        let port = port.trim().parse::<u16>().map_err(|_| err())?;
+        Ok(match proto.trim().to_lowercase().as_ref() {
+            "udp" => PcapFilter::Udp(port),
+            "tcp" => PcapFilter::Tcp(port),
+            _ => return Err(err()),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct PcapStats {
+    pub packets: u64,
+    /// Packets that matched the filter
+    pub matched: u64,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn u16(self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        match self {
+            Endian::Little => u16::from_le_bytes(b),
+            Endian::Big => u16::from_be_bytes(b),
+        }
+    }
+
+    fn u32(self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        match self {
+            Endian::Little => u32::from_le_bytes(b),
+            Endian::Big => u32::from_be_bytes(b),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Format {
+    Pcap {
+        endian: Endian,
+        link_type: u32,
+    },
+    PcapNg {
+        endian: Endian,
+        link_types: Vec<u32>,
+    },
+}
+
+type Endpoint = ([u8; 16], u16);
+
+/// One direction of a TCP connection
+#[derive(Debug)]
+struct TcpFlow {
+    src: Endpoint,
+    dst: Endpoint,
+    next_seq: u32,
+    out_of_order: BTreeMap<u32, Vec<u8>>,
+}
+
+/// Reads a pcap or pcapng capture, yielding the payloads matching the filter
+pub struct PcapReader<R> {
+    inner: R,
+    filter: PcapFilter,
+    format: Option<Format>,
+    tcp_flow: Option<TcpFlow>,
+    payload: Vec<u8>,
+    payload_pos: usize,
+    eof: bool,
+    stats: PcapStats,
+}
+
+impl<R: io::Read> PcapReader<R> {
+    /// Segments held while waiting for a missing one, before giving up on it
+    const MAX_OUT_OF_ORDER_SEGMENTS: usize = 1024;
+
+    const PCAPNG_SHB: u32 = 0x0A0D_0D0A;
+    const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+    const PCAPNG_IDB: u32 = 1;
+    const PCAPNG_SPB: u32 = 3;
+    const PCAPNG_EPB: u32 = 6;
+
+    pub fn new(inner: R, filter: PcapFilter) -> Self {
+        Self {
+            inner,
+            filter,
+            format: None,
+            tcp_flow: None,
+            payload: Vec::new(),
+            payload_pos: 0,
+            eof: false,
+            stats: Default::default(),
+        }
+    }
+
+    pub fn stats(&self) -> PcapStats {
+        self.stats
+    }
+
+    /// Returns false on a clean EOF before any bytes were read
+    fn read_exact_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.inner.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Truncated capture",
+                    ))
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn invalid(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+    }
+
+    fn read_vec(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut v = vec![0; len];
+        if !self.read_exact_or_eof(&mut v)? && len != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Truncated capture",
+            ));
+        }
+        Ok(v)
+    }
+
+    /// Reads up to the first packet, determining the capture format
+    fn read_header(&mut self) -> io::Result<Option<Format>> {
+        let mut magic = [0_u8; 4];
+        if !self.read_exact_or_eof(&mut magic)? {
+            return Ok(None);
+        }
+        let endian = match u32::from_le_bytes(magic) {
+            0xA1B2_C3D4 | 0xA1B2_3C4D => Some(Endian::Little),
+            0xD4C3_B2A1 | 0x4D3C_B2A1 => Some(Endian::Big),
+            Self::PCAPNG_SHB => None,
+            _ => return Err(Self::invalid("Not a pcap or pcapng capture")),
+        };
+        Ok(Some(match endian {
+            Some(endian) => {
+                // Version, thiszone, sigfigs, snaplen, then the link type
+                let hdr = self.read_vec(20)?;
+                let link_type = endian.u32(&hdr[16..]);
+                debug!(link_type, "Reading pcap capture");
+                Format::Pcap { endian, link_type }
+            }
+            None => {
+                let endian = self.read_section_header()?;
+                debug!("Reading pcapng capture");
+                Format::PcapNg {
+                    endian,
+                    link_types: Vec::new(),
+                }
+            }
+        }))
+    }
+
+    /// Reads the rest of a pcapng section header block, after the block type
+    fn read_section_header(&mut self) -> io::Result<Endian> {
+        let mut len_and_bom = [0_u8; 8];
+        if !self.read_exact_or_eof(&mut len_and_bom)? {
+            return Err(Self::invalid("Truncated pcapng section header"));
+        }
+        let endian = if Endian::Little.u32(&len_and_bom[4..]) == Self::PCAPNG_BYTE_ORDER_MAGIC {
+            Endian::Little
+        } else if Endian::Big.u32(&len_and_bom[4..]) == Self::PCAPNG_BYTE_ORDER_MAGIC {
+            Endian::Big
+        } else {
+            return Err(Self::invalid("Invalid pcapng byte-order magic"));
+        };
+        let len = endian.u32(&len_and_bom) as usize;
+        if len < 12 + 16 {
+            return Err(Self::invalid("Invalid pcapng section header length"));
+        }
+        self.read_vec(len - 12)?;
+        Ok(endian)
+    }
+
+    /// Returns the next packet's link type and data
+    fn next_packet(&mut self) -> io::Result<Option<(u32, Vec<u8>)>> {
+        if self.format.is_none() {
+            self.format = self.read_header()?;
+        }
+        match self.format.clone() {
+            None => Ok(None),
+            Some(Format::Pcap { endian, link_type }) => {
+                let mut hdr = [0_u8; 16];
+                if !self.read_exact_or_eof(&mut hdr)? {
+                    return Ok(None);
+                }
+                let incl_len = endian.u32(&hdr[8..]) as usize;
+                Ok(Some((link_type, self.read_vec(incl_len)?)))
+            }
+            Some(Format::PcapNg { endian, .. }) => loop {
+                let mut hdr = [0_u8; 4];
+                if !self.read_exact_or_eof(&mut hdr)? {
+                    return Ok(None);
+                }
+                let block_type = endian.u32(&hdr);
+                if block_type == Self::PCAPNG_SHB {
+                    // A new section, possibly with a different byte order
+                    let endian = self.read_section_header()?;
+                    self.format = Some(Format::PcapNg {
+                        endian,
+                        link_types: Vec::new(),
+                    });
+                    return self.next_packet();
+                }
+
+                let len_bytes = self.read_vec(4)?;
+                let len = endian.u32(&len_bytes) as usize;
+                if len < 12 {
+                    return Err(Self::invalid("Invalid pcapng block length"));
+                }
+                // Body plus the trailing length
+                let block = self.read_vec(len - 8)?;
+                let body = &block[..block.len() - 4];
+                match block_type {
+                    Self::PCAPNG_IDB if body.len() >= 2 => {
+                        if let Some(Format::PcapNg { link_types, .. }) = self.format.as_mut() {
+                            link_types.push(endian.u16(body) as u32);
+                        }
+                    }
+                    Self::PCAPNG_EPB if body.len() >= 20 => {
+                        let interface = endian.u32(body) as usize;
+                        let cap_len = (endian.u32(&body[12..]) as usize).min(body.len() - 20);
+                        let Some(link_type) = self.link_type(interface) else {
+                            return Err(Self::invalid("pcapng packet for an unknown interface"));
+                        };
+                        return Ok(Some((link_type, body[20..20 + cap_len].to_vec())));
+                    }
+                    Self::PCAPNG_SPB if body.len() >= 4 => {
+                        let orig_len = (endian.u32(body) as usize).min(body.len() - 4);
+                        let Some(link_type) = self.link_type(0) else {
+                            return Err(Self::invalid("pcapng packet for an unknown interface"));
+                        };
+                        return Ok(Some((link_type, body[4..4 + orig_len].to_vec())));
+                    }
+                    _ => trace!(block_type, "Skipping pcapng block"),
+                }
+            },
+        }
+    }
+
+    fn link_type(&self, interface: usize) -> Option<u32> {
+        match self.format.as_ref() {
+            Some(Format::PcapNg { link_types, .. }) => link_types.get(interface).copied(),
+            _ => None,
+        }
+    }
+
+    fn process_packet(&mut self, link_type: u32, data: &[u8]) {
+        self.stats.packets += 1;
+        let Some(ip) = ip_packet(link_type, data) else {
+            return;
+        };
+        match (self.filter, ip.protocol) {
+            (PcapFilter::Udp(port), IP_PROTO_UDP) if ip.payload.len() >= 8 => {
+                let src_port = u16::from_be_bytes([ip.payload[0], ip.payload[1]]);
+                let dst_port = u16::from_be_bytes([ip.payload[2], ip.payload[3]]);
+                if src_port != port && dst_port != port {
+                    return;
+                }
+                let len = (u16::from_be_bytes([ip.payload[4], ip.payload[5]]) as usize)
+                    .clamp(8, ip.payload.len());
+                self.stats.matched += 1;
+                self.payload.extend_from_slice(&ip.payload[8..len]);
+            }
+            (PcapFilter::Tcp(port), IP_PROTO_TCP) if ip.payload.len() >= 20 => {
+                let seg = ip.payload;
+                let src_port = u16::from_be_bytes([seg[0], seg[1]]);
+                let dst_port = u16::from_be_bytes([seg[2], seg[3]]);
+                if src_port != port && dst_port != port {
+                    return;
+                }
+                let seq = u32::from_be_bytes([seg[4], seg[5], seg[6], seg[7]]);
+                let data_offset = ((seg[12] >> 4) as usize * 4).clamp(20, seg.len());
+                let data = &seg[data_offset..];
+                if data.is_empty() {
+                    return;
+                }
+                self.stats.matched += 1;
+                self.tcp_segment((ip.src, src_port), (ip.dst, dst_port), seq, data);
+            }
+            _ => (),
+        }
+    }
+
+    fn tcp_segment(&mut self, src: Endpoint, dst: Endpoint, seq: u32, data: &[u8]) {
+        let flow = self.tcp_flow.get_or_insert_with(|| {
+            debug!(src_port = src.1, dst_port = dst.1, "Following TCP stream");
+            TcpFlow {
+                src,
+                dst,
+                next_seq: seq,
+                out_of_order: Default::default(),
+            }
+        });
+        if flow.src != src || flow.dst != dst {
+            trace!(
+                src_port = src.1,
+                dst_port = dst.1,
+                "Ignoring segment from another TCP stream"
+            );
+            return;
+        }
+
+        let rel = seq.wrapping_sub(flow.next_seq) as i32;
+        if rel > 0 {
+            flow.out_of_order.insert(seq, data.to_vec());
+            if flow.out_of_order.len() > Self::MAX_OUT_OF_ORDER_SEGMENTS {
+                warn!("Missing TCP segment, skipping the gap");
+                Self::skip_gap(flow);
+            }
+        } else {
+            append_segment(flow, seq, data, &mut self.payload);
+        }
+        Self::drain_in_order(flow, &mut self.payload);
+    }
+
+    /// Resumes at the earliest held segment
+    fn skip_gap(flow: &mut TcpFlow) {
+        let next = flow
+            .out_of_order
+            .keys()
+            .copied()
+            .min_by_key(|s| s.wrapping_sub(flow.next_seq));
+        if let Some(next) = next {
+            flow.next_seq = next;
+        }
+    }
+
+    fn drain_in_order(flow: &mut TcpFlow, out: &mut Vec<u8>) {
+        loop {
+            // Anything at or before the next expected sequence number can be applied
+            let ready = flow
+                .out_of_order
+                .keys()
+                .copied()
+                .find(|s| s.wrapping_sub(flow.next_seq) as i32 <= 0);
+            let Some(seq) = ready else {
+                break;
+            };
+            let data = flow.out_of_order.remove(&seq).unwrap_or_default();
+            append_segment(flow, seq, &data, out);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(flow) = self.tcp_flow.as_mut() {
+            while !flow.out_of_order.is_empty() {
+                warn!("Missing TCP segment at the end of the capture, skipping the gap");
+                Self::skip_gap(flow);
+                Self::drain_in_order(flow, &mut self.payload);
+            }
+        }
+        debug!(
+            packets = self.stats.packets,
+            matched = self.stats.matched,
+            "Finished reading capture"
+        );
+    }
+}
+
+/// Appends the part of the segment at or after the next expected sequence number
+fn append_segment(flow: &mut TcpFlow, seq: u32, data: &[u8], out: &mut Vec<u8>) {
+    let behind = flow.next_seq.wrapping_sub(seq) as usize;
+    if behind < data.len() {
+        out.extend_from_slice(&data[behind..]);
+        flow.next_seq = seq.wrapping_add(data.len() as u32);
+    }
+}
+
+impl<R: io::Read> io::Read for PcapReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.payload_pos == self.payload.len() {
+            self.payload.clear();
+            self.payload_pos = 0;
+            if self.eof {
+                return Ok(0);
+            }
+            match self.next_packet()? {
+                Some((link_type, data)) => self.process_packet(link_type, &data),
+                None => {
+                    self.eof = true;
+                    self.finish();
+                }
+            }
+        }
+
+        let n = buf.len().min(self.payload.len() - self.payload_pos);
+        buf[..n].copy_from_slice(&self.payload[self.payload_pos..self.payload_pos + n]);
+        self.payload_pos += n;
+        Ok(n)
+    }
+}
+
+const IP_PROTO_TCP: u8 = 6;
+const IP_PROTO_UDP: u8 = 17;
+
+struct IpPacket<'a> {
+    src: [u8; 16],
+    dst: [u8; 16],
+    protocol: u8,
+    payload: &'a [u8],
+}
+
+/// Strips the link layer and IP headers
+fn ip_packet(link_type: u32, data: &[u8]) -> Option<IpPacket<'_>> {
+    const LINKTYPE_NULL: u32 = 0;
+    const LINKTYPE_ETHERNET: u32 = 1;
+    const LINKTYPE_RAW: u32 = 101;
+    const LINKTYPE_LINUX_SLL: u32 = 113;
+    const LINKTYPE_IPV4: u32 = 228;
+    const LINKTYPE_IPV6: u32 = 229;
+    const LINKTYPE_LINUX_SLL2: u32 = 276;
+    const ETHERTYPE_VLAN: u16 = 0x8100;
+    const ETHERTYPE_QINQ: u16 = 0x88A8;
+
+    let l3 = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            loop {
+                let ethertype = u16::from_be_bytes([*data.get(offset)?, *data.get(offset + 1)?]);
+                if ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+                    offset += 4;
+                } else {
+                    break data.get(offset + 2..)?;
+                }
+            }
+        }
+        LINKTYPE_LINUX_SLL => data.get(16..)?,
+        LINKTYPE_LINUX_SLL2 => data.get(20..)?,
+        LINKTYPE_NULL => data.get(4..)?,
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => data,
+        _ => {
+            trace!(link_type, "Unsupported link type");
+            return None;
+        }
+    };
+
+    match l3.first()? >> 4 {
+        4 => ipv4_packet(l3),
+        6 => ipv6_packet(l3),
+        _ => None,
+    }
+}
+
+fn ipv4_packet(data: &[u8]) -> Option<IpPacket<'_>> {
+    let ihl = (*data.first()? & 0x0F) as usize * 4;
+    if ihl < 20 || data.len() < ihl {
+        return None;
+    }
+    let total_len = (u16::from_be_bytes([data[2], data[3]]) as usize).clamp(ihl, data.len());
+    let flags_and_offset = u16::from_be_bytes([data[6], data[7]]);
+    if flags_and_offset & 0x3FFF != 0 {
+        trace!("Skipping fragmented IPv4 packet");
+        return None;
+    }
+    let mut src = [0; 16];
+    let mut dst = [0; 16];
+    src[..4].copy_from_slice(&data[12..16]);
+    dst[..4].copy_from_slice(&data[16..20]);
+    Some(IpPacket {
+        src,
+        dst,
+        protocol: data[9],
+        payload: &data[ihl..total_len],
+    })
+}
+
+fn ipv6_packet(data: &[u8]) -> Option<IpPacket<'_>> {
+    const HOP_BY_HOP: u8 = 0;
+    const ROUTING: u8 = 43;
+    const DESTINATION_OPTIONS: u8 = 60;
+
+    if data.len() < 40 {
+        return None;
+    }
+    let payload_len = u16::from_be_bytes([data[4], data[5]]) as usize;
+    let end = (40 + payload_len).min(data.len());
+    let mut next_header = data[6];
+    let mut offset = 40;
+    while matches!(next_header, HOP_BY_HOP | ROUTING | DESTINATION_OPTIONS) {
+        next_header = *data.get(offset)?;
+        offset += (*data.get(offset + 1)? as usize + 1) * 8;
+    }
+    Some(IpPacket {
+        src: data[8..24].try_into().ok()?,
+        dst: data[24..40].try_into().ok()?,
+        protocol: next_header,
+        payload: data.get(offset..end)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    fn ipv4(protocol: u8, l4: &[u8]) -> Vec<u8> {
+        let mut p = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, protocol, 0, 0];
+        p.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        let len = (p.len() + l4.len()) as u16;
+        p[2..4].copy_from_slice(&len.to_be_bytes());
+        p.extend_from_slice(l4);
+        p
+    }
+
+    fn ethernet(ip: &[u8]) -> Vec<u8> {
+        let mut f = vec![0; 12];
+        f.extend_from_slice(&[0x08, 0x00]);
+        f.extend_from_slice(ip);
+        // Minimum frame padding
+        f.resize(f.len().max(60), 0);
+        f
+    }
+
+    fn udp(src: u16, dst: u16, data: &[u8]) -> Vec<u8> {
+        let mut d = src.to_be_bytes().to_vec();
+        d.extend_from_slice(&dst.to_be_bytes());
+        d.extend_from_slice(&((8 + data.len()) as u16).to_be_bytes());
+        d.extend_from_slice(&[0, 0]);
+        d.extend_from_slice(data);
+        d
+    }
+
+    fn tcp(src: u16, dst: u16, seq: u32, data: &[u8]) -> Vec<u8> {
+        let mut s = src.to_be_bytes().to_vec();
+        s.extend_from_slice(&dst.to_be_bytes());
+        s.extend_from_slice(&seq.to_be_bytes());
+        s.extend_from_slice(&[0, 0, 0, 0, 0x50, 0x18, 0xFF, 0xFF, 0, 0, 0, 0]);
+        s.extend_from_slice(data);
+        s
+    }
+
+    fn pcap(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut c = 0xA1B2_C3D4_u32.to_le_bytes().to_vec();
+        c.extend_from_slice(&[2, 0, 4, 0]);
+        c.extend_from_slice(&[0; 8]);
+        c.extend_from_slice(&65535_u32.to_le_bytes());
+        c.extend_from_slice(&1_u32.to_le_bytes());
+        for f in frames {
+            c.extend_from_slice(&[0; 8]);
+            c.extend_from_slice(&(f.len() as u32).to_le_bytes());
+            c.extend_from_slice(&(f.len() as u32).to_le_bytes());
+            c.extend_from_slice(f);
+        }
+        c
+    }
+
+    fn pcapng_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let mut body = body.to_vec();
+        body.resize(body.len().div_ceil(4) * 4, 0);
+        let len = (body.len() + 12) as u32;
+        let mut b = block_type.to_be_bytes().to_vec();
+        b.extend_from_slice(&len.to_be_bytes());
+        b.extend_from_slice(&body);
+        b.extend_from_slice(&len.to_be_bytes());
+        b
+    }
+
+    /// A big-endian pcapng capture with a raw IP interface
+    fn pcapng(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut shb = 0x1A2B_3C4D_u32.to_be_bytes().to_vec();
+        shb.extend_from_slice(&[0, 1, 0, 0]);
+        shb.extend_from_slice(&u64::MAX.to_be_bytes());
+        let mut c = pcapng_block(0x0A0D_0D0A, &shb);
+        let mut idb = 101_u16.to_be_bytes().to_vec();
+        idb.extend_from_slice(&[0, 0, 0, 0, 0xFF, 0xFF]);
+        c.extend_from_slice(&pcapng_block(1, &idb));
+        // Some block we don't care about
+        c.extend_from_slice(&pcapng_block(5, &[1, 2, 3, 4]));
+        for p in packets {
+            let mut epb = 0_u32.to_be_bytes().to_vec();
+            epb.extend_from_slice(&[0; 8]);
+            epb.extend_from_slice(&(p.len() as u32).to_be_bytes());
+            epb.extend_from_slice(&(p.len() as u32).to_be_bytes());
+            epb.extend_from_slice(p);
+            c.extend_from_slice(&pcapng_block(6, &epb));
+        }
+        c
+    }
+
+    fn read_all<R: Read>(r: &mut R) -> Vec<u8> {
+        let mut out = Vec::new();
+        r.read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn filter() {
+        assert_eq!(PcapFilter::from_str("udp:5000"), Ok(PcapFilter::Udp(5000)));
+        assert_eq!(
+            PcapFilter::from_str("TCP:19021"),
+            Ok(PcapFilter::Tcp(19021))
+        );
+        assert_eq!(PcapFilter::Tcp(19021).to_string(), "tcp:19021");
+        assert!(PcapFilter::from_str("udp").is_err());
+        assert!(PcapFilter::from_str("sctp:1").is_err());
+        assert!(PcapFilter::from_str("udp:70000").is_err());
+    }
+
+    #[test]
+    fn pcap_udp() {
+        let capture = pcap(&[
+            ethernet(&ipv4(IP_PROTO_UDP, &udp(40000, 5000, b"ab"))),
+            ethernet(&ipv4(IP_PROTO_UDP, &udp(40000, 5001, b"xx"))),
+            ethernet(&ipv4(IP_PROTO_TCP, &tcp(40000, 5000, 1, b"yy"))),
+            ethernet(&ipv4(IP_PROTO_UDP, &udp(5000, 40000, b"cd"))),
+        ]);
+        let mut r = PcapReader::new(capture.as_slice(), PcapFilter::Udp(5000));
+        assert_eq!(read_all(&mut r), b"abcd");
+        assert_eq!(
+            r.stats(),
+            PcapStats {
+                packets: 4,
+                matched: 2
+            }
+        );
+    }
+
+    #[test]
+    fn pcapng_tcp_reassembly() {
+        let seq = u32::MAX - 1;
+        let capture = pcapng(&[
+            ipv4(IP_PROTO_TCP, &tcp(19021, 40000, seq, b"ab")),
+            // Out of order, across the sequence number wrap
+            ipv4(IP_PROTO_TCP, &tcp(19021, 40000, seq.wrapping_add(4), b"ef")),
+            // Other direction and other connection are ignored
+            ipv4(IP_PROTO_TCP, &tcp(40000, 19021, 7, b"zz")),
+            ipv4(IP_PROTO_TCP, &tcp(19021, 40001, 7, b"zz")),
+            ipv4(IP_PROTO_TCP, &tcp(19021, 40000, seq.wrapping_add(2), b"cd")),
+            // Retransmission overlapping new data
+            ipv4(
+                IP_PROTO_TCP,
+                &tcp(19021, 40000, seq.wrapping_add(4), b"efgh"),
+            ),
+        ]);
+        let mut r = PcapReader::new(capture.as_slice(), PcapFilter::Tcp(19021));
+        assert_eq!(read_all(&mut r), b"abcdefgh");
+        assert_eq!(r.stats().packets, 6);
+    }
+
+    #[test]
+    fn invalid_capture() {
+        let mut r = PcapReader::new(&b"not a capture"[..], PcapFilter::Udp(1));
+        assert!(r.read(&mut [0; 8]).is_err());
+
+        let mut r = PcapReader::new(&b""[..], PcapFilter::Udp(1));
+        assert_eq!(read_all(&mut r), b"");
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{glob::Glob, AttrKey, EventAttributes, EventRecord, TimelineAttributes, TimelineMeta};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+/// Promotes matching internal attributes into the public namespace,
+/// e.g. `event.internal.defmt.table_index` becomes `event.table_index`.
+///
+/// Existing public attributes are never overwritten by a promoted one.
+#[derive(Debug, Default)]
+pub struct AttrPromoter {
+    patterns: Vec<Glob>,
+    /// Promoted key lookups, keyed by the internal key
+    event_keys: HashMap<AttrKey, Option<AttrKey>>,
+}
+
+impl AttrPromoter {
+    pub fn new(patterns: Vec<Glob>) -> Self {
+        Self {
+            patterns,
+            event_keys: Default::default(),
+        }
+    }
+
+    pub fn promote_event_attrs(&mut self, attrs: &mut EventAttributes) {
+        if self.patterns.is_empty() {
+            return;
+        }
+
+        let Self {
+            patterns,
+            event_keys,
+        } = self;
+        let promotions: Vec<(AttrKey, AttrKey)> = attrs
+            .iter()
+            .filter_map(|(k, _)| {
+                let promoted = event_keys.entry(*k).or_insert_with(|| {
+                    promoted_key(
+                        patterns,
+                        k.as_str(),
+                        EventRecord::INTERNAL_ATTR_KEY_PREFIX,
+                        EventRecord::ATTR_KEY_PREFIX,
+                    )
+                    .map(|pk| AttrKey::new(&pk))
+                });
+                promoted.map(|pk| (*k, pk))
+            })
+            .collect();
+
+        for (internal_key, public_key) in promotions.into_iter() {
+            if attrs.contains_key(public_key.as_str()) {
+                debug!(
+                    attr_key = %internal_key,
+                    "Not promoting internal attribute, the public attribute already exists"
+                );
+                continue;
+            }
+            if let Some(val) = attrs.remove(internal_key.as_str()) {
+                attrs.insert(public_key, val);
+            }
+        }
+    }
+
+    pub fn promote_timeline_attrs(&self, attrs: &TimelineAttributes) -> TimelineAttributes {
+        let mut promoted_attrs = attrs.clone();
+        if self.patterns.is_empty() {
+            return promoted_attrs;
+        }
+
+        for (k, v) in attrs.iter() {
+            if let Some(pk) = promoted_key(
+                &self.patterns,
+                k,
+                TimelineMeta::INTERNAL_ATTR_KEY_PREFIX,
+                TimelineMeta::ATTR_KEY_PREFIX,
+            ) {
+                if promoted_attrs.contains_key(&pk) {
+                    warn!(
+                        attr_key = k,
+                        "Not promoting internal attribute, the public attribute already exists"
+                    );
+                    continue;
+                }
+                promoted_attrs.remove(k);
+                promoted_attrs.insert(pk, v.clone());
+            }
+        }
+
+        promoted_attrs
+    }
+}
+
+fn promoted_key(
+    patterns: &[Glob],
+    key: &str,
+    internal_prefix: &str,
+    public_prefix: &str,
+) -> Option<String> {
+    let suffix = key.strip_prefix(internal_prefix)?;
+    if patterns.iter().any(|p| p.is_match(key)) {
+        Some(format!("{public_prefix}{suffix}"))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use auxon_sdk::api::AttrVal;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn event_attrs() {
+        let mut promoter = AttrPromoter::new(vec![
+            Glob::new("event.internal.defmt.table_index"),
+            Glob::new("event.internal.defmt.timestamp*"),
+        ]);
+        let mut attrs: EventAttributes = vec![
+            (EventRecord::attr_key("timestamp"), AttrVal::Integer(1000)),
+            (EventRecord::internal_attr_key("timestamp"), 1_u64.into()),
+            (
+                EventRecord::internal_attr_key("timestamp.raw"),
+                1_u64.into(),
+            ),
+            (EventRecord::internal_attr_key("table_index"), 2_u64.into()),
+            (EventRecord::internal_attr_key("nonce"), 3_u64.into()),
+        ]
+        .into_iter()
+        .collect();
+
+        // Twice to exercise the key cache
+        promoter.promote_event_attrs(&mut attrs);
+        promoter.promote_event_attrs(&mut attrs);
+
+        assert_eq!(
+            attrs
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect::<Vec<_>>(),
+            vec![
+                ("event.internal.defmt.nonce".to_owned(), AttrVal::Integer(3)),
+                (
+                    "event.internal.defmt.timestamp".to_owned(),
+                    AttrVal::Integer(1)
+                ),
+                ("event.table_index".to_owned(), AttrVal::Integer(2)),
+                ("event.timestamp".to_owned(), AttrVal::Integer(1000)),
+                ("event.timestamp.raw".to_owned(), AttrVal::Integer(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn timeline_attrs() {
+        let promoter = AttrPromoter::new(vec![Glob::new("timeline.internal.defmt.rtos_mode")]);
+        let attrs: TimelineAttributes = vec![
+            (
+                TimelineMeta::internal_attr_key("rtos_mode"),
+                AttrVal::from("rtic1"),
+            ),
+            (
+                TimelineMeta::internal_attr_key("plugin.version"),
+                AttrVal::from("0.1.0"),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let attrs = promoter.promote_timeline_attrs(&attrs);
+        assert_eq!(
+            attrs.get("timeline.rtos_mode"),
+            Some(&AttrVal::from("rtic1"))
+        );
+        assert!(!attrs.contains_key("timeline.internal.defmt.rtos_mode"));
+        assert!(attrs.contains_key("timeline.internal.defmt.plugin.version"));
+    }
+}
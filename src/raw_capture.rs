@@ -0,0 +1,154 @@
+//! A copy of the defmt byte stream written to disk while ingesting, so a run
+//! can be replayed and re-imported later.
+
+use crate::Error;
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Writes the defmt byte stream to a file, optionally rotating it once it reaches
+/// the rotation size.
+///
+/// Rotated files get a numeric suffix, `capture.bin.1` being the most recent one.
+#[derive(Debug)]
+pub struct RawCaptureWriter {
+    path: PathBuf,
+    rotate_size: Option<u64>,
+    rotate_count: usize,
+    file: BufWriter<fs::File>,
+    written: u64,
+}
+
+impl RawCaptureWriter {
+    /// The number of rotated files kept by default
+    pub const DEFAULT_ROTATE_COUNT: usize = 5;
+
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        rotate_size: Option<u64>,
+        rotate_count: usize,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref().to_owned();
+        let file = create_file(&path)?;
+        Ok(Self {
+            path,
+            rotate_size,
+            rotate_count,
+            file,
+            written: 0,
+        })
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if let Some(size) = self.rotate_size {
+            if self.written != 0 && self.written + bytes.len() as u64 > size {
+                self.rotate()?;
+            }
+        }
+        self.file
+            .write_all(bytes)
+            .map_err(|e| Error::RawCapture(self.path.clone(), e))?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.file
+            .flush()
+            .map_err(|e| Error::RawCapture(self.path.clone(), e))
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        let rename = |from: &Path, to: &Path| match fs::rename(from, to) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(Error::RawCapture(from.to_owned(), e))
+            }
+            _ => Ok(()),
+        };
+        if self.rotate_count != 0 {
+            for idx in (1..self.rotate_count).rev() {
+                rename(
+                    &rotated_path(&self.path, idx),
+                    &rotated_path(&self.path, idx + 1),
+                )?;
+            }
+            rename(&self.path, &rotated_path(&self.path, 1))?;
+        }
+        self.file = create_file(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// The path of one of several sources sharing a configured output path, e.g.
+/// `capture.bin` becomes `capture.ch1.bin` for the `ch1` source
+pub fn source_capture_path(path: &Path, source: &str) -> PathBuf {
+    let mut file_name = path.file_stem().map(OsString::from).unwrap_or_default();
+    file_name.push(format!(".{source}"));
+    if let Some(ext) = path.extension() {
+        file_name.push(".");
+        file_name.push(ext);
+    }
+    path.with_file_name(file_name)
+}
+
+fn create_file(path: &Path) -> Result<BufWriter<fs::File>, Error> {
+    fs::File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| Error::RawCapture(path.to_owned(), e))
+}
+
+fn rotated_path(path: &Path, idx: usize) -> PathBuf {
+    let mut p = OsString::from(path.as_os_str());
+    p.push(format!(".{idx}"));
+    p.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.bin");
+        let mut capture = RawCaptureWriter::create(&path, Some(4), 2).unwrap();
+        capture.write(&[0, 1, 2]).unwrap();
+        capture.write(&[3]).unwrap();
+        capture.write(&[4, 5]).unwrap();
+        capture.write(&[6, 7, 8]).unwrap();
+        // Writes larger than the rotation size aren't split
+        capture.write(&[9; 6]).unwrap();
+        capture.flush().unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), vec![9; 6]);
+        assert_eq!(fs::read(rotated_path(&path, 1)).unwrap(), vec![6, 7, 8]);
+        assert_eq!(fs::read(rotated_path(&path, 2)).unwrap(), vec![4, 5]);
+        // Only the last 2 rotated files are kept
+        assert!(!rotated_path(&path, 3).exists());
+
+        let path = dir.path().join("unrotated.bin");
+        let mut capture = RawCaptureWriter::create(&path, None, 0).unwrap();
+        capture.write(&[0; 8]).unwrap();
+        capture.write(&[1; 8]).unwrap();
+        capture.flush().unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), 16);
+    }
+
+    #[test]
+    fn source_paths() {
+        assert_eq!(
+            source_capture_path(Path::new("/tmp/capture.bin"), "ch1"),
+            PathBuf::from("/tmp/capture.ch1.bin")
+        );
+        assert_eq!(
+            source_capture_path(Path::new("capture"), "board-a"),
+            PathBuf::from("capture.board-a")
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use defmt_decoder::{Encoding, Table};
+
+/// Shadows a defmt stream decoder to recover the encoded bytes of each decoded
+/// frame, along with its byte offset in the stream, for debugging decode discrepancies.
+///
+/// rzCOBS frames are delimited by a zero byte, raw frames are measured
+/// by decoding them against the table a second time.
+#[derive(Debug)]
+pub struct RawFrameTracker {
+    encoding: Encoding,
+    buf: Vec<u8>,
+    /// Stream offset of the first buffered byte
+    offset: u64,
+}
+
+impl RawFrameTracker {
+    pub fn new(table: &Table) -> Self {
+        Self {
+            encoding: table.encoding(),
+            buf: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    pub fn received(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Takes the stream offset and encoded bytes of the frame the decoder just consumed,
+    /// decoded successfully or not
+    pub fn next_frame(&mut self, table: &Table) -> Option<(u64, Vec<u8>)> {
+        let len = match self.encoding {
+            Encoding::Rzcobs => self.buf.iter().position(|b| *b == 0)? + 1,
+            Encoding::Raw => match table.decode(&self.buf) {
+                Ok((_, consumed)) => consumed,
+                // The stream decoder can't recover from malformed raw frames either
+                Err(_) => self.buf.len(),
+            },
+        };
+        let offset = self.offset;
+        self.offset += len as u64;
+        Some((offset, self.buf.drain(..len).collect()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use defmt_decoder::{TableEntry, Tag};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn raw_frames() {
+        let entries = vec![
+            TableEntry::new_without_symbol(Tag::Info, "Hello".to_owned()),
+            TableEntry::new_without_symbol(Tag::Info, "x={=u8}".to_owned()),
+        ];
+        let table = Table::new_test_table(None, entries);
+        let mut tracker = RawFrameTracker::new(&table);
+        tracker.received(&[0, 0, 1, 0]);
+        tracker.received(&[7]);
+        assert_eq!(tracker.next_frame(&table), Some((0, vec![0, 0])));
+        assert_eq!(tracker.next_frame(&table), Some((2, vec![1, 0, 7])));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{EventRecord, PluginConfig};
+use derive_more::Display;
+use tracing::{debug, warn};
+
+/// Why a target reboot was detected
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display)]
+pub enum RebootReason {
+    #[display(fmt = "boot event")]
+    BootEvent,
+    #[display(fmt = "timestamp reset")]
+    TimestampReset,
+    /// The ELF file was rebuilt, which goes along with reflashing the target
+    #[display(fmt = "ELF file reload")]
+    ElfReload,
+}
+
+/// Detects target reboots within a single stream, so a session spanning
+/// multiple boots can be segmented into separate runs.
+///
+/// A reboot is either the configured boot event, after any other event,
+/// or a timestamp going backwards to near zero. Timestamps with a configured
+/// width wrap around, so they're never considered a reset.
+#[derive(Debug)]
+pub struct RebootDetector {
+    boot_event: Option<String>,
+    timestamp_threshold: Option<u64>,
+    last_timestamp: Option<u64>,
+    seen_events: bool,
+}
+
+impl RebootDetector {
+    pub fn new(cfg: &PluginConfig) -> Self {
+        let timestamp_threshold = match (cfg.reboot_timestamp_threshold, cfg.timestamp_width) {
+            (Some(_), Some(width)) => {
+                warn!(
+                    width,
+                    "Ignoring the reboot timestamp threshold, timestamps with a configured width wrap around"
+                );
+                None
+            }
+            (threshold, _) => threshold,
+        };
+        Self {
+            boot_event: cfg.reboot_event.clone(),
+            timestamp_threshold,
+            last_timestamp: None,
+            seen_events: false,
+        }
+    }
+
+    pub fn detect(&mut self, record: &EventRecord) -> Option<RebootReason> {
+        let seen_events = std::mem::replace(&mut self.seen_events, true);
+
+        let mut reason = None;
+        if let Some(boot_event) = self.boot_event.as_deref() {
+            if seen_events && record.event_name() == Some(boot_event) {
+                reason = Some(RebootReason::BootEvent);
+            }
+        }
+
+        // Narrow timestamps roll over, which looks just like a reset
+        if let Some(ts) = record
+            .timestamp()
+            .filter(|ts| !ts.supports_rollover_tracking())
+        {
+            let ts = ts.as_u64();
+            if let (Some(threshold), Some(last)) = (self.timestamp_threshold, self.last_timestamp) {
+                if ts < last && ts <= threshold && reason.is_none() {
+                    debug!(timestamp = ts, last_timestamp = last, "Timestamp reset");
+                    reason = Some(RebootReason::TimestampReset);
+                }
+            }
+            self.last_timestamp = Some(ts);
+        }
+
+        reason
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Timestamp;
+    use auxon_sdk::api::AttrVal;
+    use pretty_assertions::assert_eq;
+
+    fn record(name: &str, ts: Option<u64>) -> EventRecord {
+        EventRecord::from_iter(
+            ts.map(Timestamp::Micros),
+            [(EventRecord::attr_key("name"), AttrVal::from(name))],
+        )
+    }
+
+    #[test]
+    fn boot_event() {
+        let mut detector = RebootDetector::new(&PluginConfig {
+            reboot_event: "booted".to_owned().into(),
+            ..Default::default()
+        });
+        assert_eq!(detector.detect(&record("booted", None)), None);
+        assert_eq!(detector.detect(&record("hello", None)), None);
+        assert_eq!(
+            detector.detect(&record("booted", None)),
+            Some(RebootReason::BootEvent)
+        );
+    }
+
+    #[test]
+    fn timestamp_reset() {
+        let mut detector = RebootDetector::new(&PluginConfig {
+            reboot_timestamp_threshold: 1_000.into(),
+            ..Default::default()
+        });
+        assert_eq!(detector.detect(&record("a", Some(500))), None);
+        assert_eq!(detector.detect(&record("b", Some(90_000))), None);
+        // Backwards, but not near zero
+        assert_eq!(detector.detect(&record("c", Some(80_000))), None);
+        assert_eq!(
+            detector.detect(&record("d", Some(20))),
+            Some(RebootReason::TimestampReset)
+        );
+        assert_eq!(detector.detect(&record("e", Some(40))), None);
+    }
+
+    #[test]
+    fn wrapping_timestamps_are_not_a_reset() {
+        let mut detector = RebootDetector::new(&PluginConfig {
+            reboot_timestamp_threshold: 1_000.into(),
+            timestamp_width: 24.into(),
+            ..Default::default()
+        });
+        assert_eq!(detector.detect(&record("a", Some(500))), None);
+        assert_eq!(detector.detect(&record("b", Some(0xFF_FF00))), None);
+        // Wrapped
+        assert_eq!(detector.detect(&record("c", Some(20))), None);
+        assert_eq!(detector.detect(&record("d", Some(40))), None);
+    }
+}
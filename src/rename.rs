@@ -0,0 +1,176 @@
+use crate::{AttrKey, EventAttributes, TimelineAttributes};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+use tracing::{debug, warn};
+
+/// Renames attribute keys to conform to external conventions,
+/// e.g. `event.tid` becomes `event.task_id`.
+///
+/// Existing attributes are never overwritten by a renamed one.
+#[derive(Debug, Default)]
+pub struct AttrRenamer {
+    renames: BTreeMap<String, String>,
+    /// Renamed key lookups, keyed by the original key
+    event_keys: HashMap<AttrKey, Option<AttrKey>>,
+}
+
+impl AttrRenamer {
+    pub fn new(renames: BTreeMap<String, String>) -> Self {
+        Self {
+            renames,
+            event_keys: Default::default(),
+        }
+    }
+
+    pub fn rename_event_attrs(&mut self, attrs: &mut EventAttributes) {
+        if self.renames.is_empty() {
+            return;
+        }
+
+        let Self {
+            renames,
+            event_keys,
+        } = self;
+        let pending: Vec<(AttrKey, AttrKey)> = attrs
+            .iter()
+            .filter_map(|(k, _)| {
+                let renamed = event_keys
+                    .entry(*k)
+                    .or_insert_with(|| renames.get(k.as_str()).map(|rk| AttrKey::new(rk)));
+                renamed.map(|rk| (*k, rk))
+            })
+            .collect();
+
+        for (key, renamed_key) in pending.into_iter() {
+            if attrs.contains_key(renamed_key.as_str()) {
+                debug!(
+                    attr_key = %key,
+                    renamed_attr_key = %renamed_key,
+                    "Not renaming attribute, the renamed attribute already exists"
+                );
+                continue;
+            }
+            if let Some(val) = attrs.remove(key.as_str()) {
+                attrs.insert(renamed_key, val);
+            }
+        }
+    }
+
+    pub fn rename_timeline_attrs(&self, attrs: &TimelineAttributes) -> TimelineAttributes {
+        let mut renamed_attrs = attrs.clone();
+        for (k, v) in attrs.iter() {
+            if let Some(rk) = self.renames.get(k) {
+                if renamed_attrs.contains_key(rk) {
+                    warn!(
+                        attr_key = k,
+                        renamed_attr_key = rk,
+                        "Not renaming attribute, the renamed attribute already exists"
+                    );
+                    continue;
+                }
+                renamed_attrs.remove(k);
+                renamed_attrs.insert(rk.clone(), v.clone());
+            }
+        }
+        renamed_attrs
+    }
+}
+
+/// An attribute key rename, parsed from 'FROM=TO' at the CLI
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AttrRename {
+    pub from: String,
+    pub to: String,
+}
+
+impl FromStr for AttrRename {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => Ok(Self {
+                from: from.trim().to_owned(),
+                to: to.trim().to_owned(),
+            }),
+            _ => Err(format!(
+                "Invalid attribute rename '{s}', expected 'FROM_KEY=TO_KEY'"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::EventRecord;
+    use auxon_sdk::api::AttrVal;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn event_attrs() {
+        let mut renamer = AttrRenamer::new(
+            vec![
+                ("event.tid".to_owned(), "event.task_id".to_owned()),
+                ("event.temp".to_owned(), "event.temperature_c".to_owned()),
+                ("event.id".to_owned(), "event.name".to_owned()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let mut attrs: EventAttributes = vec![
+            (EventRecord::attr_key("tid"), AttrVal::Integer(3)),
+            (EventRecord::attr_key("temp"), AttrVal::Integer(21)),
+            (EventRecord::attr_key("id"), AttrVal::Integer(1)),
+            (EventRecord::attr_key("name"), AttrVal::from("boot")),
+        ]
+        .into_iter()
+        .collect();
+
+        // Twice to exercise the key cache
+        renamer.rename_event_attrs(&mut attrs);
+        renamer.rename_event_attrs(&mut attrs);
+
+        assert_eq!(
+            attrs
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect::<Vec<_>>(),
+            vec![
+                ("event.id".to_owned(), AttrVal::Integer(1)),
+                ("event.name".to_owned(), AttrVal::from("boot")),
+                ("event.task_id".to_owned(), AttrVal::Integer(3)),
+                ("event.temperature_c".to_owned(), AttrVal::Integer(21)),
+            ]
+        );
+    }
+
+    #[test]
+    fn timeline_attrs() {
+        let renamer = AttrRenamer::new(
+            vec![("timeline.name".to_owned(), "timeline.task".to_owned())]
+                .into_iter()
+                .collect(),
+        );
+        let attrs: TimelineAttributes = vec![("timeline.name".to_owned(), AttrVal::from("main"))]
+            .into_iter()
+            .collect();
+        let attrs = renamer.rename_timeline_attrs(&attrs);
+        assert_eq!(attrs.get("timeline.task"), Some(&AttrVal::from("main")));
+        assert!(!attrs.contains_key("timeline.name"));
+    }
+
+    #[test]
+    fn parse_rename() {
+        assert_eq!(
+            AttrRename::from_str(" event.tid = event.task_id"),
+            Ok(AttrRename {
+                from: "event.tid".to_owned(),
+                to: "event.task_id".to_owned(),
+            })
+        );
+        assert!(AttrRename::from_str("event.tid").is_err());
+        assert!(AttrRename::from_str("event.tid=").is_err());
+    }
+}
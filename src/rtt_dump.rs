@@ -0,0 +1,303 @@
+//! Recovers the residual RTT up channel data from a post-mortem RAM dump
+//! or ELF core file of the target.
+
+use goblin::elf::{header::ET_CORE, program_header::PT_LOAD, Elf};
+use tracing::{debug, warn};
+
+const RTT_ID: &[u8] = b"SEGGER RTT\0";
+const RTT_ID_SIZE: u64 = 16;
+const RTT_BUFFER_DESC_SIZE: u64 = 24;
+const MAX_RTT_BUFFERS: u32 = 256;
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RttDumpError {
+    #[error("Failed to parse the ELF core file. {0}")]
+    CoreFile(#[source] goblin::error::Error),
+
+    #[error("Could not find the RTT control block in the memory dump")]
+    ControlBlockNotFound,
+
+    #[error("The RTT control block at 0x{0:X} is not contained in the memory dump")]
+    ControlBlockNotMapped(u64),
+
+    #[error("The data at 0x{0:X} is not a valid RTT control block")]
+    InvalidControlBlock(u64),
+
+    #[error("The base address of the RAM dump is unknown. Provide it, or the firmware ELF file with the '_SEGGER_RTT' symbol")]
+    UnknownBaseAddress,
+
+    #[error("The RTT up channel {0} is not present in the control block")]
+    UpChannelInvalid(usize),
+}
+
+/// The memory contents of the target, possibly in several segments
+#[derive(Clone, Debug)]
+pub struct MemoryImage {
+    segments: Vec<(u64, Vec<u8>)>,
+}
+
+impl MemoryImage {
+    pub fn new(segments: Vec<(u64, Vec<u8>)>) -> Self {
+        Self { segments }
+    }
+
+    /// A raw RAM dump starting at `base_address`, or an ELF core file.
+    ///
+    /// When the base address of a raw dump isn't known, it's inferred from the
+    /// control block address by locating the control block ID in the dump.
+    pub fn from_dump(
+        dump: Vec<u8>,
+        base_address: Option<u64>,
+        control_block_address: Option<u64>,
+    ) -> Result<Self, RttDumpError> {
+        if dump.starts_with(b"\x7FELF") {
+            let elf = Elf::parse(&dump).map_err(RttDumpError::CoreFile)?;
+            if elf.header.e_type == ET_CORE {
+                let segments = elf
+                    .program_headers
+                    .iter()
+                    .filter(|ph| ph.p_type == PT_LOAD && ph.p_filesz != 0)
+                    .filter_map(|ph| {
+                        let start = usize::try_from(ph.p_offset).ok()?;
+                        let end = start.checked_add(usize::try_from(ph.p_filesz).ok()?)?;
+                        Some((ph.p_vaddr, dump.get(start..end)?.to_vec()))
+                    })
+                    .collect::<Vec<_>>();
+                debug!(segments = segments.len(), "Read ELF core file");
+                return Ok(Self::new(segments));
+            }
+        }
+
+        let base_address = match (base_address, control_block_address) {
+            (Some(base), _) => base,
+            (None, Some(cb_addr)) => {
+                let offset = find_rtt_id(&dump).ok_or(RttDumpError::ControlBlockNotFound)?;
+                let base = cb_addr
+                    .checked_sub(offset as u64)
+                    .ok_or(RttDumpError::UnknownBaseAddress)?;
+                debug!(base_address = base, "Inferred the RAM dump base address");
+                base
+            }
+            (None, None) => return Err(RttDumpError::UnknownBaseAddress),
+        };
+        Ok(Self::new(vec![(base_address, dump)]))
+    }
+
+    pub fn read(&self, address: u64, len: usize) -> Option<&[u8]> {
+        self.segments.iter().find_map(|(base, data)| {
+            let start = usize::try_from(address.checked_sub(*base)?).ok()?;
+            data.get(start..start.checked_add(len)?)
+        })
+    }
+
+    fn read_u32(&self, address: u64) -> Option<u32> {
+        let b = self.read(address, 4)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn read_c_str(&self, address: u64) -> Option<String> {
+        let (base, data) = self
+            .segments
+            .iter()
+            .find(|(base, data)| address >= *base && address - *base < data.len() as u64)?;
+        let bytes = &data[(address - base) as usize..];
+        let bytes = &bytes[..bytes.len().min(MAX_NAME_LEN)];
+        let end = bytes.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+
+    /// Scans the memory for the control block ID
+    pub fn find_control_block(&self) -> Option<u64> {
+        self.segments
+            .iter()
+            .find_map(|(base, data)| find_rtt_id(data).map(|offset| base + offset as u64))
+    }
+}
+
+/// Address of the `_SEGGER_RTT` control block symbol in the firmware ELF file
+pub fn rtt_symbol(elf_contents: &[u8]) -> Option<u64> {
+    let elf = Elf::parse(elf_contents).ok()?;
+    elf.syms
+        .iter()
+        .find(|sym| elf.strtab.get_at(sym.st_name) == Some("_SEGGER_RTT"))
+        .map(|sym| sym.st_value)
+}
+
+fn find_rtt_id(data: &[u8]) -> Option<usize> {
+    data.windows(RTT_ID.len()).position(|w| w == RTT_ID)
+}
+
+/// An up channel's ring buffer, as it was when the dump was taken
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RttDumpChannel {
+    pub number: usize,
+    pub name: Option<String>,
+    pub buffer: Vec<u8>,
+    pub write_offset: usize,
+    pub read_offset: usize,
+}
+
+impl RttDumpChannel {
+    /// The data written by the target that the host didn't read yet
+    pub fn unread(&self) -> Vec<u8> {
+        let (wr, rd) = (self.write_offset, self.read_offset);
+        if wr >= rd {
+            self.buffer[rd..wr].to_vec()
+        } else {
+            [&self.buffer[rd..], &self.buffer[..wr]].concat()
+        }
+    }
+
+    /// The whole buffer, oldest data first. This includes data that was already
+    /// read by the host and may start in the middle of a defmt frame.
+    pub fn history(&self) -> Vec<u8> {
+        let wr = self.write_offset;
+        [&self.buffer[wr..], &self.buffer[..wr]].concat()
+    }
+}
+
+/// Reads the up channels of the RTT control block at `control_block_address`,
+/// or wherever its ID is found in the memory
+pub fn read_up_channels(
+    mem: &MemoryImage,
+    control_block_address: Option<u64>,
+) -> Result<Vec<RttDumpChannel>, RttDumpError> {
+    let cb = match control_block_address {
+        Some(addr) => addr,
+        None => mem
+            .find_control_block()
+            .ok_or(RttDumpError::ControlBlockNotFound)?,
+    };
+    let id = mem
+        .read(cb, RTT_ID.len())
+        .ok_or(RttDumpError::ControlBlockNotMapped(cb))?;
+    if id != RTT_ID {
+        return Err(RttDumpError::InvalidControlBlock(cb));
+    }
+    let max_up = mem
+        .read_u32(cb + RTT_ID_SIZE)
+        .filter(|n| *n <= MAX_RTT_BUFFERS)
+        .ok_or(RttDumpError::InvalidControlBlock(cb))?;
+    debug!(
+        address = cb,
+        up_channels = max_up,
+        "Found RTT control block"
+    );
+
+    let mut channels = Vec::new();
+    for number in 0..max_up as usize {
+        let desc = cb + RTT_ID_SIZE + 8 + number as u64 * RTT_BUFFER_DESC_SIZE;
+        let Some(fields) = mem.read(desc, RTT_BUFFER_DESC_SIZE as usize) else {
+            return Err(RttDumpError::InvalidControlBlock(cb));
+        };
+        let field = |idx: usize| {
+            let b = &fields[idx * 4..idx * 4 + 4];
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        };
+        let (name_ptr, buf_ptr, size) = (field(0), field(1), field(2) as usize);
+        let (write_offset, read_offset) = (field(3) as usize, field(4) as usize);
+        if buf_ptr == 0 || size == 0 {
+            continue;
+        }
+        if write_offset >= size || read_offset >= size {
+            warn!(
+                channel = number,
+                write_offset, read_offset, size, "Skipping corrupt RTT up channel"
+            );
+            continue;
+        }
+        let Some(buffer) = mem.read(buf_ptr.into(), size) else {
+            warn!(
+                channel = number,
+                address = buf_ptr,
+                "RTT up channel buffer is not contained in the memory dump"
+            );
+            continue;
+        };
+        let name = if name_ptr != 0 {
+            mem.read_c_str(name_ptr.into())
+        } else {
+            None
+        };
+        channels.push(RttDumpChannel {
+            number,
+            name,
+            buffer: buffer.to_vec(),
+            write_offset,
+            read_offset,
+        });
+    }
+    Ok(channels)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BASE: u64 = 0x2000_0000;
+
+    /// A RAM dump with a control block at BASE + 0x10 and two up channels
+    fn ram_dump() -> Vec<u8> {
+        let mut ram = vec![0_u8; 0x200];
+        let cb = 0x10;
+        ram[cb..cb + RTT_ID.len()].copy_from_slice(RTT_ID);
+        let mut put = |off: usize, v: u32| ram[off..off + 4].copy_from_slice(&v.to_le_bytes());
+        put(cb + 16, 2); // max up
+        put(cb + 20, 1); // max down
+        let up0 = cb + 24;
+        put(up0, BASE as u32 + 0x100); // name
+        put(up0 + 4, BASE as u32 + 0x110); // buffer
+        put(up0 + 8, 8); // size
+        put(up0 + 12, 2); // write offset
+        put(up0 + 16, 6); // read offset
+        let up1 = up0 + 24;
+        put(up1 + 4, BASE as u32 + 0x400); // unmapped buffer
+        put(up1 + 8, 8);
+        ram[0x100..0x107].copy_from_slice(b"defmt\0\0");
+        ram[0x110..0x118].copy_from_slice(b"abcdefgh");
+        ram
+    }
+
+    #[test]
+    fn raw_ram_dump() {
+        let mem = MemoryImage::from_dump(ram_dump(), Some(BASE), None).unwrap();
+        assert_eq!(mem.find_control_block(), Some(BASE + 0x10));
+        let channels = read_up_channels(&mem, None).unwrap();
+        assert_eq!(channels.len(), 1);
+        let ch = &channels[0];
+        assert_eq!(ch.number, 0);
+        assert_eq!(ch.name.as_deref(), Some("defmt"));
+        assert_eq!(ch.unread(), b"ghab");
+        assert_eq!(ch.history(), b"cdefghab");
+    }
+
+    #[test]
+    fn inferred_base_address() {
+        let mem = MemoryImage::from_dump(ram_dump(), None, Some(BASE + 0x10)).unwrap();
+        let channels = read_up_channels(&mem, Some(BASE + 0x10)).unwrap();
+        assert_eq!(channels[0].unread(), b"ghab");
+
+        assert!(matches!(
+            MemoryImage::from_dump(ram_dump(), None, None),
+            Err(RttDumpError::UnknownBaseAddress)
+        ));
+        let mem = MemoryImage::from_dump(ram_dump(), Some(BASE), None).unwrap();
+        assert!(matches!(
+            read_up_channels(&mem, Some(BASE + 0x20)),
+            Err(RttDumpError::InvalidControlBlock(_))
+        ));
+    }
+
+    #[test]
+    fn segmented_memory() {
+        let ram = ram_dump();
+        let mem = MemoryImage::new(vec![
+            (BASE, ram[..0x100].to_vec()),
+            (BASE + 0x100, ram[0x100..].to_vec()),
+        ]);
+        let channels = read_up_channels(&mem, None).unwrap();
+        assert_eq!(channels[0].unread(), b"ghab");
+        assert_eq!(mem.read(BASE + 0xFE, 4), None);
+    }
+}
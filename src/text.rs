@@ -0,0 +1,92 @@
+//! Plain text (non-defmt) log support, e.g. a legacy printf channel sharing the
+//! target with the defmt data.
+
+/// Lines longer than this are split
+const MAX_LINE_LEN: usize = 1024;
+
+/// Splits a byte stream into lines of text.
+///
+/// Carriage returns and ANSI escape sequences are stripped, and empty lines are skipped.
+#[derive(Debug, Default)]
+pub struct LineSplitter {
+    buf: Vec<u8>,
+}
+
+impl LineSplitter {
+    /// Appends the received bytes, returning the lines they complete
+    pub fn received(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for &b in bytes {
+            if b == b'\n' {
+                lines.extend(self.take_line());
+            } else {
+                self.buf.push(b);
+                if self.buf.len() >= MAX_LINE_LEN {
+                    lines.extend(self.take_line());
+                }
+            }
+        }
+        lines
+    }
+
+    /// The unterminated last line, at the end of the stream
+    pub fn finish(&mut self) -> Option<String> {
+        self.take_line()
+    }
+
+    fn take_line(&mut self) -> Option<String> {
+        let line = String::from_utf8_lossy(&self.buf).into_owned();
+        self.buf.clear();
+        let line = strip_ansi_escapes(&line);
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            None
+        } else {
+            Some(line.to_owned())
+        }
+    }
+}
+
+/// Removes the color and cursor escape sequences (`ESC [ ... final-byte`)
+fn strip_ansi_escapes(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'[').is_some() {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn lines() {
+        let mut lines = LineSplitter::default();
+        assert_eq!(lines.received(b"boot "), Vec::<String>::new());
+        assert_eq!(
+            lines.received(b"ok\r\n\r\n\x1b[32mI (42) wifi: connected\x1b[0m\nrest"),
+            vec!["boot ok".to_owned(), "I (42) wifi: connected".to_owned()]
+        );
+        assert_eq!(lines.finish(), Some("rest".to_owned()));
+        assert_eq!(lines.finish(), None);
+
+        let long = vec![b'a'; MAX_LINE_LEN + 1];
+        let split = lines.received(&long);
+        assert_eq!(split.len(), 1);
+        assert_eq!(split[0].len(), MAX_LINE_LEN);
+        assert_eq!(lines.finish(), Some("a".to_owned()));
+    }
+}
@@ -97,6 +97,103 @@ where
     }
 }
 
+/// Instant, in ticks, that tracks rollovers of a counter with a runtime width (1 to 63 bits).
+/// For counters that are narrower than the type they're logged with.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct WrappingInstant {
+    width: u32,
+    lower: u64,
+    upper: u64,
+}
+
+impl WrappingInstant {
+    pub fn new(width: u32) -> Option<Self> {
+        if (1..u64::BITS).contains(&width) {
+            Some(Self {
+                width,
+                lower: 0,
+                upper: 0,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn elapsed(&mut self, now: u64) -> InstantTicks {
+        let now = now & ((1 << self.width) - 1);
+
+        // Check for rollover on the lower
+        if now < self.lower {
+            self.upper += 1;
+        }
+
+        self.lower = now;
+
+        self.as_ticks()
+    }
+
+    pub fn as_ticks(&self) -> InstantTicks {
+        self.upper << self.width | self.lower
+    }
+}
+
+/// Tracks the transport latency between the target and the host.
+///
+/// The target and host clocks aren't correlated, so latencies are relative to the smallest
+/// observed offset between the two, i.e. the fastest transfer is taken as zero latency.
+/// Each event's latency is relative to the smallest offset observed so far, the summary
+/// is relative to the smallest offset of the whole run.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyTracker {
+    min_offset: Option<i128>,
+    /// Every observed offset, the summary is re-based on the final minimum
+    offsets: Vec<i128>,
+}
+
+/// Transport latency distribution, in nanoseconds
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub mean: u64,
+    pub max: u64,
+    pub p50: u64,
+    pub p99: u64,
+}
+
+impl LatencyTracker {
+    /// Records an event's target and host times, returning its latency
+    pub fn observe(&mut self, target_ns: InstantNanos, host_ns: InstantNanos) -> InstantNanos {
+        let offset = i128::from(host_ns) - i128::from(target_ns);
+        let min_offset = self.min_offset.map_or(offset, |m| m.min(offset));
+        self.min_offset = Some(min_offset);
+        self.offsets.push(offset);
+        u64::try_from(offset - min_offset).unwrap_or(u64::MAX)
+    }
+
+    pub fn summary(&self) -> Option<LatencySummary> {
+        let min_offset = self.min_offset?;
+        let mut latencies: Vec<u64> = self
+            .offsets
+            .iter()
+            .map(|offset| u64::try_from(offset - min_offset).unwrap_or(u64::MAX))
+            .collect();
+        latencies.sort_unstable();
+        let count = latencies.len() as u64;
+        let sum: u128 = latencies.iter().map(|l| u128::from(*l)).sum();
+        let percentile = |p: u64| {
+            let rank = (count * p).div_ceil(100).max(1);
+            latencies[(rank - 1) as usize]
+        };
+        Some(LatencySummary {
+            count,
+            mean: (sum / u128::from(count)) as u64,
+            max: latencies[latencies.len() - 1],
+            p50: percentile(50),
+            p99: percentile(99),
+        })
+    }
+}
+
 pub trait TicksExt: Ord + Copy + Clone + Add<Self, Output = Self> {
     const ZERO: Self;
     const MAX: Self;
@@ -170,6 +267,43 @@ mod test {
         assert_eq!(ns, 25);
     }
 
+    #[test]
+    fn transport_latency() {
+        let mut t = LatencyTracker::default();
+        assert_eq!(t.summary(), None);
+
+        // Host clock is 1000ns ahead, first transfer takes 50ns
+        assert_eq!(t.observe(100, 1150), 0);
+        assert_eq!(t.observe(200, 1300), 50);
+        // Fastest so far, takes 20ns
+        assert_eq!(t.observe(300, 1320), 0);
+        assert_eq!(t.observe(400, 1720), 300);
+
+        // Relative to the fastest transfer of the whole run: 30, 80, 0 and 300
+        let s = t.summary().unwrap();
+        assert_eq!(s.count, 4);
+        assert_eq!(s.mean, 102);
+        assert_eq!(s.max, 300);
+        assert_eq!(s.p50, 30);
+        assert_eq!(s.p99, 300);
+    }
+
+    #[test]
+    fn rollover_tracking_wrapping() {
+        assert_eq!(WrappingInstant::new(0), None);
+        assert_eq!(WrappingInstant::new(64), None);
+
+        let max24 = (1 << 24) - 1;
+        let mut instant = WrappingInstant::new(24).unwrap();
+        assert_eq!(instant.elapsed(max24 - 5), max24 - 5);
+
+        // 10 ticks after rollover
+        assert_eq!(instant.elapsed(10), max24 + 11);
+
+        // Bits above the width are ignored
+        assert_eq!(instant.elapsed((1 << 24) | 20), max24 + 21);
+    }
+
     #[test]
     fn rollover_tracking_u8() {
         // 5 ticks before rollover